@@ -0,0 +1,143 @@
+use std::{
+    fs,
+    io::{self, Write},
+    net::SocketAddr,
+    path::Path,
+    sync::Mutex,
+    time::Instant,
+};
+
+use axum::extract::{ConnectInfo, Request};
+use chrono::Local;
+use tracing::warn;
+
+// 存取紀錄的格式
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    // `%h %l %u %t "%r" %>s %b`
+    Common,
+    // Common 加上 `"%{Referer}i" "%{User-agent}i" %D`（處理時間，微秒）
+    Combined,
+}
+
+impl LogFormat {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "common" => Some(LogFormat::Common),
+            "combined" => Some(LogFormat::Combined),
+            _ => None,
+        }
+    }
+}
+
+// Apache 格式的存取紀錄（--access-log），寫入標準輸出或文件
+//
+// 每個回應在內容送完或用戶端中斷後寫入一行，位元組數是實際送出的數量。
+pub struct AccessLog {
+    format: LogFormat,
+    out: Mutex<Box<dyn Write + Send>>,
+}
+
+impl AccessLog {
+    // `-` 代表標準輸出，其他為附加寫入的文件
+    pub fn open(target: &Path, format: LogFormat) -> io::Result<Self> {
+        let out: Box<dyn Write + Send> = match target.to_str() {
+            Some("-") => Box::new(io::stdout()),
+            _ => Box::new(fs::OpenOptions::new().create(true).append(true).open(target)?),
+        };
+        Ok(AccessLog {
+            format,
+            out: Mutex::new(out),
+        })
+    }
+
+    fn write(&self, line: &str) {
+        let mut out = self.out.lock().unwrap();
+        if let Err(e) = out.write_all(line.as_bytes()).and_then(|_| out.flush()) {
+            warn!("無法寫入存取紀錄: {}", e);
+        }
+    }
+}
+
+// 請求開始時記下的欄位，回應送完後補上狀態碼與位元組數
+pub struct AccessEntry {
+    remote: String,
+    user: Option<String>,
+    time: String,
+    request_line: String,
+    referer: String,
+    user_agent: String,
+    started: Instant,
+}
+
+impl AccessEntry {
+    pub fn new(request: &Request) -> Self {
+        let header = |name| {
+            request
+                .headers()
+                .get(name)
+                .map(|v| escape(&String::from_utf8_lossy(v.as_bytes())))
+                .unwrap_or_else(|| "-".to_string())
+        };
+        let remote = request
+            .extensions()
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip().to_string())
+            .unwrap_or_else(|| "-".to_string());
+        let target = request.uri().path_and_query().map_or("/", |p| p.as_str());
+        AccessEntry {
+            remote,
+            user: None,
+            time: Local::now().format("%d/%b/%Y:%H:%M:%S %z").to_string(),
+            request_line: escape(&format!("{} {} {:?}", request.method(), target, request.version())),
+            referer: header("referer"),
+            user_agent: header("user-agent"),
+            started: Instant::now(),
+        }
+    }
+
+    // 通過驗證的用戶名稱
+    pub fn set_user(&mut self, name: &str) {
+        self.user = Some(escape(name));
+    }
+
+    pub fn finish(self, log: &AccessLog, status: u16, bytes: u64) {
+        let bytes = match bytes {
+            0 => "-".to_string(),
+            bytes => bytes.to_string(),
+        };
+        let mut line = format!(
+            "{} - {} [{}] \"{}\" {} {}",
+            self.remote,
+            self.user.as_deref().unwrap_or("-"),
+            self.time,
+            self.request_line,
+            status,
+            bytes
+        );
+        if log.format == LogFormat::Combined {
+            line.push_str(&format!(
+                " \"{}\" \"{}\" {}",
+                self.referer,
+                self.user_agent,
+                self.started.elapsed().as_micros()
+            ));
+        }
+        line.push('\n');
+        log.write(&line);
+    }
+}
+
+// 與 Apache 相同，跳脫引號、反斜線與控制字元，避免偽造紀錄行
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if c.is_control() => escaped.push_str(&format!("\\x{:02x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -0,0 +1,488 @@
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use axum::{
+    extract::{Path as UrlPath, State},
+    http::{header, StatusCode, Uri},
+    response::{IntoResponse, Redirect, Response},
+    routing::get,
+    Router,
+};
+use axum_server::tls_rustls::RustlsConfig;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use ring::{
+    rand::SystemRandom,
+    signature::{EcdsaKeyPair, KeyPair, ECDSA_P256_SHA256_FIXED_SIGNING},
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use tokio::net::TcpListener;
+use tracing::{error, info, warn};
+
+use crate::upgrade::Shutdown;
+
+// Let's Encrypt 正式環境
+pub const LETS_ENCRYPT: &str = "https://acme-v02.api.letsencrypt.org/directory";
+
+// 憑證剩餘有效期低於此值時更新
+const RENEW_BEFORE: Duration = Duration::from_secs(30 * 24 * 3600);
+
+// 檢查是否需要更新的間隔
+const CHECK_INTERVAL: Duration = Duration::from_secs(12 * 3600);
+
+// 輪詢驗證與簽發狀態的間隔與次數
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+const POLL_ATTEMPTS: u32 = 60;
+
+pub struct AcmeOptions {
+    pub domain: String,
+    pub directory: String,
+    pub email: Option<String>,
+    pub cache: PathBuf,
+}
+
+// 以 ACME HTTP-01 驗證自動取得並更新憑證
+//
+// 帳號金鑰、憑證與私鑰都存放在快取目錄，重新啟動時沿用，不會重複申請。
+pub struct Acme {
+    options: AcmeOptions,
+    // 驗證期間 token 對應的 key authorization，由 HTTP 監聽位址提供
+    challenges: RwLock<HashMap<String, String>>,
+    http: reqwest::Client,
+}
+
+impl Acme {
+    pub fn new(options: AcmeOptions) -> io::Result<Arc<Self>> {
+        fs::create_dir_all(&options.cache)?;
+        let http = reqwest::Client::builder()
+            .timeout(Duration::from_secs(30))
+            .build()
+            .map_err(io::Error::other)?;
+        Ok(Arc::new(Acme {
+            options,
+            challenges: RwLock::new(HashMap::new()),
+            http,
+        }))
+    }
+
+    pub fn cache_dir(&self) -> &Path {
+        &self.options.cache
+    }
+
+    pub fn cert_path(&self) -> PathBuf {
+        self.options.cache.join(format!("{}.crt.pem", self.options.domain))
+    }
+
+    pub fn key_path(&self) -> PathBuf {
+        self.options.cache.join(format!("{}.key.pem", self.options.domain))
+    }
+
+    // 快取中沒有憑證或即將到期時申請新的憑證
+    pub async fn ensure_certificate(&self) -> io::Result<bool> {
+        match remaining_validity(&self.cert_path()) {
+            Some(remaining) if remaining > RENEW_BEFORE => return Ok(false),
+            Some(_) => info!("{} 的憑證即將到期，重新申請", self.options.domain),
+            None => info!("正在為 {} 申請憑證", self.options.domain),
+        }
+        self.issue().await?;
+        info!("已取得 {} 的憑證", self.options.domain);
+        Ok(true)
+    }
+
+    // 在背景定期檢查並更新憑證，更新後立即套用到 HTTPS 監聽位址
+    pub fn spawn_renewal(self: Arc<Self>, tls: RustlsConfig) {
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(CHECK_INTERVAL).await;
+                match self.ensure_certificate().await {
+                    Ok(false) => {}
+                    Ok(true) => {
+                        if let Err(e) = tls.reload_from_pem_file(self.cert_path(), self.key_path()).await {
+                            error!("無法載入更新後的憑證: {}", e);
+                        }
+                    }
+                    Err(e) => error!("更新 {} 的憑證失敗，稍後重試: {}", self.options.domain, e),
+                }
+            }
+        });
+    }
+
+    async fn issue(&self) -> io::Result<()> {
+        let mut client = Client::connect(&self.http, &self.options).await?;
+
+        let (order_url, order) = client
+            .post(
+                &client.directory.new_order.clone(),
+                Some(json!({ "identifiers": [{ "type": "dns", "value": self.options.domain }] })),
+            )
+            .await?;
+        let order_url = order_url.ok_or_else(|| acme_error("建立訂單的回應缺少 Location"))?;
+        let order: Order = parse(&order)?;
+
+        let mut tokens = Vec::new();
+        let result = async {
+            for authorization in &order.authorizations {
+                self.authorize(&mut client, authorization, &mut tokens).await?;
+            }
+            self.finalize(&mut client, &order_url, &order.finalize).await
+        }
+        .await;
+
+        let mut challenges = self.challenges.write().unwrap();
+        for token in tokens {
+            challenges.remove(&token);
+        }
+        result
+    }
+
+    // 完成一個網域的 HTTP-01 驗證
+    async fn authorize(&self, client: &mut Client<'_>, url: &str, tokens: &mut Vec<String>) -> io::Result<()> {
+        let (_, body) = client.post(url, None).await?;
+        let authorization: Authorization = parse(&body)?;
+        if authorization.status == "valid" {
+            return Ok(());
+        }
+
+        let challenge = authorization
+            .challenges
+            .iter()
+            .find(|c| c.kind == "http-01")
+            .ok_or_else(|| acme_error("伺服器未提供 HTTP-01 驗證"))?;
+        let key_authorization = format!("{}.{}", challenge.token, client.thumbprint());
+        self.challenges
+            .write()
+            .unwrap()
+            .insert(challenge.token.clone(), key_authorization);
+        tokens.push(challenge.token.clone());
+
+        client.post(&challenge.url, Some(json!({}))).await?;
+
+        for _ in 0..POLL_ATTEMPTS {
+            tokio::time::sleep(POLL_INTERVAL).await;
+            let (_, body) = client.post(url, None).await?;
+            let authorization: Authorization = parse(&body)?;
+            match authorization.status.as_str() {
+                "valid" => return Ok(()),
+                "pending" | "processing" => {}
+                status => {
+                    let detail = authorization
+                        .challenges
+                        .iter()
+                        .find_map(|c| c.error.as_ref())
+                        .map(|e| e.to_string())
+                        .unwrap_or_default();
+                    return Err(acme_error(&format!("驗證失敗 ({}): {}", status, detail)));
+                }
+            }
+        }
+        Err(acme_error("等待驗證逾時"))
+    }
+
+    // 送出 CSR 並下載簽發的憑證
+    async fn finalize(&self, client: &mut Client<'_>, order_url: &str, finalize_url: &str) -> io::Result<()> {
+        let key = rcgen::KeyPair::generate().map_err(io::Error::other)?;
+        let csr = rcgen::CertificateParams::new(vec![self.options.domain.clone()])
+            .and_then(|params| params.serialize_request(&key))
+            .map_err(io::Error::other)?;
+        let csr = URL_SAFE_NO_PAD.encode(csr.der());
+
+        let (_, body) = client.post(finalize_url, Some(json!({ "csr": csr }))).await?;
+        let mut order: Order = parse(&body)?;
+        for _ in 0..POLL_ATTEMPTS {
+            match order.status.as_str() {
+                "valid" => break,
+                "pending" | "ready" | "processing" => {}
+                status => return Err(acme_error(&format!("簽發失敗 ({})", status))),
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+            let (_, body) = client.post(order_url, None).await?;
+            order = parse(&body)?;
+        }
+        let certificate_url = order
+            .certificate
+            .ok_or_else(|| acme_error("等待簽發逾時"))?;
+        let (_, chain) = client.post(&certificate_url, None).await?;
+
+        // 先寫私鑰再寫憑證，兩者不一致時下次啟動會重新申請
+        write_private(&self.key_path(), key.serialize_pem().as_bytes())?;
+        write_atomic(&self.cert_path(), &chain)
+    }
+
+    // HTTP-01 驗證回應
+    fn key_authorization(&self, token: &str) -> Option<String> {
+        self.challenges.read().unwrap().get(token).cloned()
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Directory {
+    new_nonce: String,
+    new_account: String,
+    new_order: String,
+}
+
+#[derive(Deserialize)]
+struct Order {
+    status: String,
+    #[serde(default)]
+    authorizations: Vec<String>,
+    finalize: String,
+    certificate: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Authorization {
+    status: String,
+    challenges: Vec<Challenge>,
+}
+
+#[derive(Deserialize)]
+struct Challenge {
+    #[serde(rename = "type")]
+    kind: String,
+    url: String,
+    token: String,
+    error: Option<Value>,
+}
+
+// ACME 伺服器的連線狀態：帳號金鑰、帳號網址與下一個請求使用的 nonce
+struct Client<'a> {
+    http: &'a reqwest::Client,
+    directory: Directory,
+    key: EcdsaKeyPair,
+    rng: SystemRandom,
+    account: Option<String>,
+    nonce: Option<String>,
+}
+
+impl<'a> Client<'a> {
+    // 讀取目錄並登入（或建立）帳號
+    async fn connect(http: &'a reqwest::Client, options: &AcmeOptions) -> io::Result<Client<'a>> {
+        let response = http
+            .get(&options.directory)
+            .send()
+            .await
+            .map_err(io::Error::other)?;
+        let directory: Directory = parse(&response.bytes().await.map_err(io::Error::other)?)?;
+
+        let rng = SystemRandom::new();
+        let key = account_key(&options.cache.join("account.key"), &rng)?;
+        let mut client = Client {
+            http,
+            directory,
+            key,
+            rng,
+            account: None,
+            nonce: None,
+        };
+
+        let mut payload = json!({ "termsOfServiceAgreed": true });
+        if let Some(email) = &options.email {
+            payload["contact"] = json!([format!("mailto:{}", email)]);
+        }
+        let (account, _) = client.post(&client.directory.new_account.clone(), Some(payload)).await?;
+        client.account = Some(account.ok_or_else(|| acme_error("建立帳號的回應缺少 Location"))?);
+        Ok(client)
+    }
+
+    // 帳號公鑰的 JWK
+    fn jwk(&self) -> Value {
+        // 未壓縮的 P-256 公鑰：0x04 || X || Y
+        let point = self.key.public_key().as_ref();
+        json!({
+            "crv": "P-256",
+            "kty": "EC",
+            "x": URL_SAFE_NO_PAD.encode(&point[1..33]),
+            "y": URL_SAFE_NO_PAD.encode(&point[33..65]),
+        })
+    }
+
+    // JWK 的 SHA-256 指紋（RFC 7638），欄位須依字母順序且不含空白
+    fn thumbprint(&self) -> String {
+        URL_SAFE_NO_PAD.encode(Sha256::digest(self.jwk().to_string().as_bytes()))
+    }
+
+    async fn nonce(&mut self) -> io::Result<String> {
+        if let Some(nonce) = self.nonce.take() {
+            return Ok(nonce);
+        }
+        let response = self
+            .http
+            .head(&self.directory.new_nonce)
+            .send()
+            .await
+            .map_err(io::Error::other)?;
+        replay_nonce(&response).ok_or_else(|| acme_error("無法取得 nonce"))
+    }
+
+    // 送出簽章的請求，`payload` 為 None 時是 POST-as-GET；回傳 Location 與回應內容
+    async fn post(&mut self, url: &str, payload: Option<Value>) -> io::Result<(Option<String>, Vec<u8>)> {
+        // nonce 過期時伺服器回傳 badNonce，以新的 nonce 重試一次
+        for attempt in 0..2 {
+            let mut protected = json!({ "alg": "ES256", "nonce": self.nonce().await?, "url": url });
+            match &self.account {
+                Some(account) => protected["kid"] = json!(account),
+                None => protected["jwk"] = self.jwk(),
+            }
+            let protected = URL_SAFE_NO_PAD.encode(protected.to_string());
+            let payload = payload
+                .as_ref()
+                .map(|p| URL_SAFE_NO_PAD.encode(p.to_string()))
+                .unwrap_or_default();
+            let signature = self
+                .key
+                .sign(&self.rng, format!("{}.{}", protected, payload).as_bytes())
+                .map_err(|_| acme_error("簽章失敗"))?;
+            let body = json!({
+                "protected": protected,
+                "payload": payload,
+                "signature": URL_SAFE_NO_PAD.encode(signature.as_ref()),
+            });
+
+            let response = self
+                .http
+                .post(url)
+                .header(header::CONTENT_TYPE.as_str(), "application/jose+json")
+                .body(body.to_string())
+                .send()
+                .await
+                .map_err(io::Error::other)?;
+            self.nonce = replay_nonce(&response);
+            let status = response.status();
+            let location = response
+                .headers()
+                .get(header::LOCATION.as_str())
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string);
+            let bytes = response.bytes().await.map_err(io::Error::other)?.to_vec();
+
+            if status.is_success() {
+                return Ok((location, bytes));
+            }
+            let problem: Value = serde_json::from_slice(&bytes).unwrap_or_default();
+            if attempt == 0 && problem["type"] == "urn:ietf:params:acme:error:badNonce" {
+                continue;
+            }
+            return Err(acme_error(&format!(
+                "{} 回應 {}: {}",
+                url,
+                status.as_u16(),
+                problem["detail"].as_str().unwrap_or_default()
+            )));
+        }
+        unreachable!("第二次嘗試一定會回傳")
+    }
+}
+
+fn replay_nonce(response: &reqwest::Response) -> Option<String> {
+    response
+        .headers()
+        .get("replay-nonce")
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string)
+}
+
+fn parse<'de, T: Deserialize<'de>>(bytes: &'de [u8]) -> io::Result<T> {
+    serde_json::from_slice(bytes).map_err(|e| acme_error(&format!("無法解析 ACME 回應: {}", e)))
+}
+
+fn acme_error(message: &str) -> io::Error {
+    io::Error::other(format!("ACME: {}", message))
+}
+
+// 讀取帳號金鑰，不存在時建立新的並保存
+fn account_key(path: &Path, rng: &SystemRandom) -> io::Result<EcdsaKeyPair> {
+    let pkcs8 = match fs::read(path) {
+        Ok(pkcs8) => pkcs8,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            let pkcs8 = EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, rng)
+                .map_err(|_| acme_error("無法產生帳號金鑰"))?;
+            write_private(path, pkcs8.as_ref())?;
+            pkcs8.as_ref().to_vec()
+        }
+        Err(e) => return Err(e),
+    };
+    EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, &pkcs8, rng)
+        .map_err(|e| acme_error(&format!("帳號金鑰 {} 無效: {}", path.display(), e)))
+}
+
+// 憑證的剩餘有效期，沒有憑證或無法解析時回傳 None
+fn remaining_validity(path: &Path) -> Option<Duration> {
+    let pem = fs::read(path).ok()?;
+    let (_, pem) = x509_parser::pem::parse_x509_pem(&pem).ok()?;
+    let certificate = pem.parse_x509().ok()?;
+    let not_after = certificate.validity().not_after.timestamp();
+    let remaining = not_after - chrono::Utc::now().timestamp();
+    Some(Duration::from_secs(remaining.max(0) as u64))
+}
+
+// 先寫入暫存檔再改名，讀取端不會看到寫到一半的內容
+fn write_atomic(path: &Path, content: &[u8]) -> io::Result<()> {
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, content)?;
+    fs::rename(&tmp, path)
+}
+
+// 私鑰只允許擁有者讀取
+fn write_private(path: &Path, content: &[u8]) -> io::Result<()> {
+    let tmp = path.with_extension("tmp");
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        let mut file = fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(&tmp)?;
+        io::Write::write_all(&mut file, content)?;
+    }
+    #[cfg(not(unix))]
+    fs::write(&tmp, content)?;
+    fs::rename(&tmp, path)
+}
+
+// HTTP 監聽位址：回應驗證請求，其餘請求轉址到 HTTPS
+pub async fn serve_http(
+    listener: TcpListener,
+    acme: Arc<Acme>,
+    https_port: u16,
+    shutdown: Shutdown,
+) -> io::Result<()> {
+    let app = Router::new()
+        .route("/.well-known/acme-challenge/:token", get(challenge))
+        .fallback(redirect_to_https)
+        .with_state((acme, https_port));
+    axum::serve(listener, app)
+        .with_graceful_shutdown(shutdown.wait())
+        .await
+}
+
+async fn challenge(
+    State((acme, _)): State<(Arc<Acme>, u16)>,
+    UrlPath(token): UrlPath<String>,
+) -> Response {
+    match acme.key_authorization(&token) {
+        Some(key_authorization) => key_authorization.into_response(),
+        None => {
+            warn!("收到未知的 ACME 驗證 token: {}", token);
+            StatusCode::NOT_FOUND.into_response()
+        }
+    }
+}
+
+async fn redirect_to_https(State((acme, https_port)): State<(Arc<Acme>, u16)>, uri: Uri) -> Redirect {
+    let path = uri.path_and_query().map(|p| p.as_str()).unwrap_or("/");
+    let url = match https_port {
+        443 => format!("https://{}{}", acme.options.domain, path),
+        port => format!("https://{}:{}{}", acme.options.domain, port, path),
+    };
+    Redirect::permanent(&url)
+}
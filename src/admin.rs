@@ -0,0 +1,405 @@
+use std::{io, path::Path as FsPath, sync::Arc};
+
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::IntoResponse,
+    routing::{delete, get, post},
+    Json, Router,
+};
+use serde::Deserialize;
+use serde_json::json;
+use tracing::info;
+
+use crate::{
+    blocking, check_restricted, duplicates,
+    holding::{Holding, Item},
+    is_dir, limits, surrogate,
+    upload::{self, Target},
+    webhook::WebhookEvent,
+    ServerConfig, ServerError,
+};
+
+// 快取預熱請求，`paths` 為相對於基礎目錄的 glob 樣式
+#[derive(Deserialize)]
+struct WarmRequest {
+    paths: Vec<String>,
+}
+
+// 共用的快取預熱結果，錯誤無法複製，因此包在 Arc 中
+pub type WarmResult = Arc<io::Result<(usize, u64)>>;
+
+// 快取清除請求，未指定時清除全部快取與路徑
+#[derive(Deserialize, Default)]
+struct FlushRequest {
+    #[serde(default)]
+    caches: Option<Vec<String>>,
+    #[serde(default)]
+    path: Option<String>,
+}
+
+// 清除路徑快取的請求，路徑相對於基礎目錄，`/` 代表全部
+#[derive(Deserialize)]
+struct PurgeRequest {
+    paths: Vec<String>,
+}
+
+// 建立 API token 的請求
+#[derive(Deserialize)]
+struct CreateTokenRequest {
+    id: String,
+    #[serde(default = "default_token_scopes")]
+    scopes: Vec<String>,
+    #[serde(default)]
+    path: String,
+}
+
+// 重複文件報告的查詢參數，小於 `min_size` 位元組的文件不列入
+#[derive(Deserialize)]
+struct DuplicatesQuery {
+    #[serde(default)]
+    min_size: u64,
+}
+
+fn default_token_scopes() -> Vec<String> {
+    vec!["read".to_string()]
+}
+
+// 管理 API 路由，只有設定 --admin-token 時才會掛載
+pub fn routes() -> Router<ServerConfig> {
+    Router::new()
+        .route("/_admin/cache/warm", post(warm_cache))
+        .route("/_admin/cache/flush", post(flush_cache))
+        .route("/_admin/purge", post(purge))
+        .route("/_admin/tokens", get(list_tokens).post(create_token))
+        .route("/_admin/tokens/:id", delete(revoke_token))
+        .route("/_admin/stats", get(transfer_stats))
+        .route("/_admin/duplicates", get(find_duplicates))
+        .route("/_admin/quarantine", get(list_pending))
+        .route("/_admin/quarantine/:id", delete(reject_upload))
+        .route("/_admin/quarantine/:id/approve", post(approve_upload))
+        .route("/_admin/trash", get(list_deleted))
+        .route("/_admin/trash/:id", delete(purge_deleted))
+        .route("/_admin/trash/:id/restore", post(restore_deleted))
+}
+
+// 驗證 Bearer token
+fn authorize(config: &ServerConfig, headers: &HeaderMap) -> Result<(), ServerError> {
+    let expected = config
+        .admin_token
+        .as_deref()
+        .ok_or_else(|| ServerError::NotFound("_admin".to_string()))?;
+
+    let provided = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+
+    match provided {
+        Some(token) if token == expected => Ok(()),
+        _ => Err(ServerError::Unauthorized("Bearer".to_string())),
+    }
+}
+
+// 將指定的文件讀入記憶體快取
+async fn warm_cache(
+    State(config): State<ServerConfig>,
+    headers: HeaderMap,
+    Json(request): Json<WarmRequest>,
+) -> Result<impl IntoResponse, ServerError> {
+    authorize(&config, &headers)?;
+
+    // 相同的預熱請求同時送達時（例如部署腳本在多台機器上重試）只讀取一次
+    let mut key = request.paths.clone();
+    key.sort();
+    key.dedup();
+
+    let cache_config = config.clone();
+    let result = config
+        .cache_warming
+        .run(key, move || async move {
+            let result = tokio::task::spawn_blocking(move || {
+                cache_config.file_cache.preload(
+                    FsPath::new(cache_config.base_path.as_str()),
+                    &request.paths,
+                    &|rel| check_restricted(rel, &cache_config).is_ok(),
+                )
+            })
+            .await
+            .unwrap_or_else(|e| Err(io::Error::other(e)));
+            Arc::new(result)
+        })
+        .await;
+
+    let (files, bytes) = match &*result {
+        Ok(loaded) => *loaded,
+        Err(e) if e.kind() == io::ErrorKind::InvalidInput => {
+            return Err(ServerError::BadRequest(e.to_string()));
+        }
+        Err(e) => return Err(ServerError::Filesystem(io::Error::new(e.kind(), e.to_string()))),
+    };
+
+    Ok(Json(json!({ "files": files, "bytes": bytes })))
+}
+
+// 依類型與路徑清除快取
+async fn flush_cache(
+    State(config): State<ServerConfig>,
+    headers: HeaderMap,
+    request: Option<Json<FlushRequest>>,
+) -> Result<impl IntoResponse, ServerError> {
+    authorize(&config, &headers)?;
+
+    let request = request.map(|Json(r)| r).unwrap_or_default();
+    let caches = request
+        .caches
+        .unwrap_or_else(|| vec!["files".to_string(), "metadata".to_string()]);
+    let prefix = request
+        .path
+        .map(|p| FsPath::new(config.base_path.as_str()).join(p.trim_start_matches('/')));
+
+    let mut flushed = serde_json::Map::new();
+    for cache in &caches {
+        let count = match cache.as_str() {
+            "files" => config.file_cache.flush(prefix.as_deref()),
+            "metadata" => config.name_index.flush(prefix.as_deref()),
+            other => return Err(ServerError::BadRequest(format!("未知的快取類型: {}", other))),
+        };
+        flushed.insert(cache.clone(), json!(count));
+    }
+
+    Ok(Json(json!({ "flushed": flushed })))
+}
+
+// 部署後清除路徑底下的所有快取，並通知 --purge-webhook 清除 CDN 上的內容
+async fn purge(
+    State(config): State<ServerConfig>,
+    headers: HeaderMap,
+    Json(request): Json<PurgeRequest>,
+) -> Result<impl IntoResponse, ServerError> {
+    authorize(&config, &headers)?;
+    if request.paths.is_empty() {
+        return Err(ServerError::BadRequest("未指定要清除的路徑".to_string()));
+    }
+
+    let base = FsPath::new(config.base_path.as_str());
+    let (mut files, mut metadata, mut not_found) = (0, 0, 0);
+    let mut purged = Vec::new();
+    for path in &request.paths {
+        let rel = path.trim_start_matches('/');
+        if rel.split('/').any(|c| c == "..") {
+            return Err(ServerError::BadRequest(format!("無效的路徑: {}", path)));
+        }
+        let prefix = base.join(rel);
+        files += config.file_cache.flush(Some(&prefix));
+        metadata += config.name_index.flush(Some(&prefix));
+        not_found += config.not_found.as_ref().map_or(0, |cache| cache.flush(Some(&prefix)));
+
+        // 目錄的快取標籤以 / 結尾，與回應中的 Surrogate-Key 一致
+        let rel = match !rel.is_empty() && !rel.ends_with('/') && is_dir(&prefix).await {
+            true => format!("{}/", rel),
+            false => rel.to_string(),
+        };
+        if let Some(hooks) = &config.purge_hooks {
+            hooks.notify(WebhookEvent::CachePurged { path: rel.clone() });
+        }
+        purged.push(json!({ "path": rel, "tag": surrogate::tag(&rel) }));
+    }
+
+    Ok(Json(json!({
+        "flushed": { "files": files, "metadata": metadata, "not_found": not_found },
+        "purged": purged,
+        "notified": config.purge_hooks.as_ref().map_or(0, |hooks| hooks.urls.len()),
+    })))
+}
+
+// 取得 token 儲存檔，未設定 --tokens 時視為不存在
+fn token_store(config: &ServerConfig) -> Result<&crate::tokens::TokenStore, ServerError> {
+    config
+        .tokens
+        .as_deref()
+        .ok_or_else(|| ServerError::NotFound("_admin/tokens".to_string()))
+}
+
+// 列出所有 token（不含 token 內容）
+async fn list_tokens(
+    State(config): State<ServerConfig>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, ServerError> {
+    authorize(&config, &headers)?;
+
+    let tokens = token_store(&config)?.list()?;
+    let tokens: Vec<_> = tokens
+        .iter()
+        .map(|t| json!({ "id": t.id, "scopes": t.scopes, "path": t.path, "created": t.created }))
+        .collect();
+    Ok(Json(json!({ "tokens": tokens })))
+}
+
+// 建立 token，回應中的 token 內容只會出現這一次
+async fn create_token(
+    State(config): State<ServerConfig>,
+    headers: HeaderMap,
+    Json(request): Json<CreateTokenRequest>,
+) -> Result<impl IntoResponse, ServerError> {
+    authorize(&config, &headers)?;
+
+    let token = match token_store(&config)?.create(&request.id, request.scopes, &request.path) {
+        Ok(token) => token,
+        Err(e) if e.kind() == io::ErrorKind::InvalidInput => {
+            return Err(ServerError::BadRequest(e.to_string()));
+        }
+        Err(e) => return Err(ServerError::Filesystem(e)),
+    };
+
+    Ok((StatusCode::CREATED, Json(json!({ "id": request.id, "token": token }))))
+}
+
+// 撤銷 token
+async fn revoke_token(
+    State(config): State<ServerConfig>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, ServerError> {
+    authorize(&config, &headers)?;
+
+    if !token_store(&config)?.revoke(&id)? {
+        return Err(ServerError::NotFound(format!("_admin/tokens/{}", id)));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// 傳輸統計：完成與中斷的回應數量及送出的位元組數，以及文件描述符與磁碟使用量
+async fn transfer_stats(
+    State(config): State<ServerConfig>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, ServerError> {
+    authorize(&config, &headers)?;
+    Ok(Json(json!({
+        "transfers": &*config.transfer_stats,
+        "open_files": limits::open_files(),
+        "disk": config.disk.status(),
+    })))
+}
+
+// 內容重複的文件與浪費的空間，禁止訪問的路徑不列入
+async fn find_duplicates(
+    State(config): State<ServerConfig>,
+    headers: HeaderMap,
+    Query(query): Query<DuplicatesQuery>,
+) -> Result<impl IntoResponse, ServerError> {
+    authorize(&config, &headers)?;
+
+    let report = tokio::task::spawn_blocking(move || {
+        duplicates::scan(FsPath::new(config.base_path.as_str()), query.min_size, &|rel| {
+            check_restricted(rel, &config).is_ok()
+        })
+    })
+    .await
+    .map_err(|e| ServerError::Internal(e.to_string()))??;
+    Ok(Json(report))
+}
+
+// 審核區，未設定 --moderate-uploads 時視為不存在
+fn quarantine(config: &ServerConfig) -> Result<Arc<Holding>, ServerError> {
+    config
+        .upload
+        .as_ref()
+        .and_then(|upload| upload.quarantine.clone())
+        .ok_or_else(|| ServerError::NotFound("_admin/quarantine".to_string()))
+}
+
+// 等待審核的上傳，新的在前
+async fn list_pending(
+    State(config): State<ServerConfig>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, ServerError> {
+    authorize(&config, &headers)?;
+    let quarantine = quarantine(&config)?;
+    let uploads = blocking(move || quarantine.list()).await??;
+    Ok(Json(json!({ "uploads": uploads })))
+}
+
+// 核准上傳，放到原本的目標位置；該位置已有文件時回應 409，不會取代
+async fn approve_upload(
+    State(config): State<ServerConfig>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, ServerError> {
+    authorize(&config, &headers)?;
+    let (item, target) = put_back(quarantine(&config)?, id, &config).await?;
+    if let Some(options) = &config.upload {
+        upload::run_hook(options, &target, item.size, &config);
+    }
+    info!("已核准上傳 /{} (編號 {})", item.path, item.id);
+    Ok(Json(json!({ "path": format!("/{}", config.url_path(&item.path)), "size": item.size })))
+}
+
+// 把保管區的項目放回原本的位置，並讓快取重新讀取
+async fn put_back(holding: Arc<Holding>, id: String, config: &ServerConfig) -> Result<(Item, Target), ServerError> {
+    let base = FsPath::new(config.base_path.as_str()).to_path_buf();
+    let item = blocking(move || holding.restore(&id, &base)).await??;
+    let file = FsPath::new(config.base_path.as_str()).join(&item.path);
+    let target = Target {
+        rel: item.path.clone(),
+        dir: file.parent().unwrap_or(FsPath::new("")).to_path_buf(),
+        file,
+    };
+    upload::invalidate(&target, config);
+    Ok((item, target))
+}
+
+// 拒絕上傳，直接刪除
+async fn reject_upload(
+    State(config): State<ServerConfig>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, ServerError> {
+    authorize(&config, &headers)?;
+    let quarantine = quarantine(&config)?;
+    let item = blocking(move || quarantine.discard(&id)).await??;
+    info!("已拒絕上傳 /{} (編號 {})", item.path, item.id);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// 垃圾桶，未設定 --trash-days 時視為不存在
+fn trash(config: &ServerConfig) -> Result<Arc<Holding>, ServerError> {
+    config.trash.clone().ok_or_else(|| ServerError::NotFound("_admin/trash".to_string()))
+}
+
+// 垃圾桶中的項目（原本的路徑、刪除時間與刪除的用戶），新的在前
+async fn list_deleted(
+    State(config): State<ServerConfig>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, ServerError> {
+    authorize(&config, &headers)?;
+    let trash = trash(&config)?;
+    let retention = trash.retention().map(|retention| retention.as_secs() / 86400);
+    let items = blocking(move || trash.list()).await??;
+    Ok(Json(json!({ "items": items, "retention_days": retention })))
+}
+
+// 不等保留期限，立即永久刪除
+async fn purge_deleted(
+    State(config): State<ServerConfig>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, ServerError> {
+    authorize(&config, &headers)?;
+    let trash = trash(&config)?;
+    let item = blocking(move || trash.discard(&id)).await??;
+    info!("已從 .trash 永久刪除 /{} (編號 {})", item.path, item.id);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// 還原 WebDAV 刪除的文件或目錄；原本的位置已有文件時回應 409，不會取代
+async fn restore_deleted(
+    State(config): State<ServerConfig>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, ServerError> {
+    authorize(&config, &headers)?;
+    let (item, _) = put_back(trash(&config)?, id, &config).await?;
+    info!("已從 .trash 還原 /{} (編號 {})", item.path, item.id);
+    Ok(Json(json!({ "path": format!("/{}", config.url_path(&item.path)), "size": item.size })))
+}
@@ -0,0 +1,132 @@
+use std::{
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Local};
+use flate2::{write::GzEncoder, Compression};
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use zip::{write::SimpleFileOptions, CompressionMethod, ZipWriter};
+
+use crate::tree::walk_files;
+
+// RFC 5987 的 attr-char 之外都要編碼
+const FILENAME: &AsciiSet = &NON_ALPHANUMERIC.remove(b'.').remove(b'-').remove(b'_');
+
+// 目錄下載的封存格式
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ArchiveFormat {
+    Zip,
+    TarGz,
+}
+
+impl ArchiveFormat {
+    pub const NAMES: &'static [&'static str] = &["zip", "tar.gz"];
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "zip" => Some(ArchiveFormat::Zip),
+            "tar.gz" => Some(ArchiveFormat::TarGz),
+            _ => None,
+        }
+    }
+
+    // 目錄網址的查詢字串選擇格式，例如 `/releases/?zip`
+    pub fn from_query(query: Option<&str>) -> Option<Self> {
+        query.and_then(Self::parse)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ArchiveFormat::Zip => "zip",
+            ArchiveFormat::TarGz => "tar.gz",
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            ArchiveFormat::Zip => "application/zip",
+            ArchiveFormat::TarGz => "application/gzip",
+        }
+    }
+
+    // 下載時的 Content-Disposition，檔名取目錄名稱
+    pub fn disposition(self, dir: &str) -> String {
+        let name = dir.trim_end_matches('/').rsplit('/').next().unwrap_or("");
+        let name = if name.is_empty() { "archive" } else { name };
+        let file_name = format!("{}.{}", name, self.name());
+        let ascii: String = file_name
+            .chars()
+            .map(|c| if c.is_ascii_graphic() && c != '"' && c != '\\' { c } else { '_' })
+            .collect();
+        format!(
+            "attachment; filename=\"{}\"; filename*=UTF-8''{}",
+            ascii,
+            utf8_percent_encode(&file_name, FILENAME)
+        )
+    }
+}
+
+// 收集要放入封存檔的文件，名稱相對於 `dir`，`allow` 以相對於基礎目錄的路徑判斷
+pub fn collect(
+    dir: &Path,
+    rel_dir: &str,
+    allow: &dyn Fn(&str) -> bool,
+) -> io::Result<Vec<(String, PathBuf, fs::Metadata)>> {
+    let rel_dir = rel_dir.trim_matches('/');
+    let mut files = Vec::new();
+    walk_files(
+        dir,
+        "",
+        &|rel| match rel_dir.is_empty() {
+            true => allow(rel),
+            false => allow(&format!("{}/{}", rel_dir, rel)),
+        },
+        &mut files,
+    )?;
+    files.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(files)
+}
+
+// 依序寫出封存檔，不需要事先知道總長度，也不需要對輸出 seek
+pub fn write<W: Write>(
+    format: ArchiveFormat,
+    files: &[(String, PathBuf, fs::Metadata)],
+    out: W,
+) -> io::Result<W> {
+    match format {
+        ArchiveFormat::Zip => {
+            let mut zip = ZipWriter::new_stream(out);
+            for (name, path, metadata) in files {
+                let mut options = SimpleFileOptions::default()
+                    .compression_method(CompressionMethod::Deflated)
+                    .large_file(metadata.len() >= u32::MAX as u64);
+                if let Some(time) = metadata
+                    .modified()
+                    .ok()
+                    .map(|t| DateTime::<Local>::from(t).naive_local())
+                    .and_then(|t| zip::DateTime::try_from(t).ok())
+                {
+                    options = options.last_modified_time(time);
+                }
+                #[cfg(unix)]
+                {
+                    use std::os::unix::fs::PermissionsExt;
+                    options = options.unix_permissions(metadata.permissions().mode());
+                }
+                zip.start_file(name.as_str(), options).map_err(io::Error::other)?;
+                io::copy(&mut fs::File::open(path)?, &mut zip)?;
+            }
+            Ok(zip.finish().map_err(io::Error::other)?.into_inner())
+        }
+        ArchiveFormat::TarGz => {
+            let mut tar = tar::Builder::new(GzEncoder::new(out, Compression::default()));
+            tar.follow_symlinks(false);
+            for (name, path, _) in files {
+                tar.append_path_with_name(path, name)?;
+            }
+            tar.into_inner()?.finish()
+        }
+    }
+}
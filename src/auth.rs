@@ -0,0 +1,604 @@
+use std::{
+    collections::HashMap,
+    fs, io,
+    net::{IpAddr, SocketAddr},
+    path::{Component, Path},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{header, HeaderMap},
+    middleware::Next,
+    response::Response,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use sha2::{Digest, Sha256};
+
+use crate::{csrf, session, ServerConfig, ServerError};
+
+// 帳號檔驗證結果的保留時間
+const USERS_FILE_VERIFIED_TTL: Duration = Duration::from_secs(3600);
+
+// 用戶可擁有的權限
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Permission {
+    Read,
+    List,
+    Write,
+    Delete,
+}
+
+impl Permission {
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "read" => Some(Permission::Read),
+            "list" => Some(Permission::List),
+            "write" => Some(Permission::Write),
+            "delete" => Some(Permission::Delete),
+            _ => None,
+        }
+    }
+}
+
+// 已登入的用戶
+#[derive(Debug)]
+pub struct User {
+    pub name: String,
+    password_hash: String,
+    // 用戶可見的子目錄，相對於基礎路徑，空字串代表整個目錄樹
+    pub root: String,
+    pub permissions: Vec<Permission>,
+    pub groups: Vec<String>,
+    // API token 的路徑範圍，設定時不套用存取規則，只能存取此前綴底下的內容
+    pub scope: Option<String>,
+}
+
+impl User {
+    // 由外部驗證後端（LDAP 等）建立的用戶，看得到整個目錄樹
+    pub fn external(name: String, permissions: Vec<Permission>) -> Self {
+        User {
+            name,
+            password_hash: String::new(),
+            root: String::new(),
+            permissions,
+            groups: Vec::new(),
+            scope: None,
+        }
+    }
+
+    // 以 API token 登入的用戶
+    pub fn token(name: String, permissions: Vec<Permission>, scope: String) -> Self {
+        User {
+            name,
+            password_hash: String::new(),
+            root: String::new(),
+            permissions,
+            groups: Vec::new(),
+            scope: Some(scope),
+        }
+    }
+
+    pub fn can(&self, permission: Permission) -> bool {
+        self.permissions.contains(&permission)
+    }
+
+    // 判斷用戶對目錄樹中某個路徑是否有指定權限
+    //
+    // `path` 為相對於基礎路徑的完整路徑（已包含用戶根目錄）。有存取規則符合時以規則為準，
+    // 否則使用帳號檔中的預設權限。
+    pub fn can_access(&self, rules: Option<&AccessRules>, path: &str, permission: Permission) -> bool {
+        if let Some(scope) = &self.scope {
+            return has_path_prefix(path.trim_matches('/'), scope) && self.can(permission);
+        }
+
+        match rules.and_then(|rules| rules.permissions_for(self, path)) {
+            Some(permissions) => permissions.contains(&permission),
+            None => self.can(permission),
+        }
+    }
+}
+
+// 用戶帳號檔
+//
+// 每行格式為 `name:bcrypt-hash:root:permissions[:groups]`，權限以逗號分隔
+// （read、list、write、delete），群組亦以逗號分隔。密碼雜湊可用
+// `htpasswd -nbB name password` 產生，`#` 開頭的行為註解。
+pub struct UserStore {
+    users: HashMap<String, Arc<User>>,
+    // 避免每個請求都重新計算 bcrypt
+    verified: VerifiedCache,
+}
+
+impl UserStore {
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let invalid = |line: usize, reason: &str| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} 第 {} 行: {}", path.display(), line, reason),
+            )
+        };
+
+        let mut users = HashMap::new();
+        for (index, line) in fs::read_to_string(path)?.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let fields: Vec<&str> = line.splitn(5, ':').collect();
+            if fields.len() < 4 {
+                return Err(invalid(index + 1, "格式應為 name:hash:root:permissions[:groups]"));
+            }
+
+            let root = fields[2].trim_matches('/');
+            // 用戶根目錄只能是基礎路徑底下的一般子目錄
+            if Path::new(root)
+                .components()
+                .any(|c| !matches!(c, Component::Normal(_)))
+            {
+                return Err(invalid(index + 1, "根目錄必須是相對的子目錄"));
+            }
+
+            let mut permissions = Vec::new();
+            for name in split_list(fields[3]) {
+                let permission = Permission::parse(name)
+                    .ok_or_else(|| invalid(index + 1, &format!("未知的權限 {}", name)))?;
+                permissions.push(permission);
+            }
+
+            let groups = fields
+                .get(4)
+                .map(|groups| split_list(groups).map(str::to_string).collect())
+                .unwrap_or_default();
+
+            users.insert(
+                fields[0].to_string(),
+                Arc::new(User {
+                    name: fields[0].to_string(),
+                    password_hash: fields[1].to_string(),
+                    root: root.to_string(),
+                    permissions,
+                    groups,
+                    scope: None,
+                }),
+            );
+        }
+
+        Ok(UserStore {
+            users,
+            verified: VerifiedCache::new(USERS_FILE_VERIFIED_TTL),
+        })
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<User>> {
+        self.users.get(name).cloned()
+    }
+
+    // 以帳號檔驗證用戶名稱與密碼
+    pub fn authenticate(&self, name: &str, password: &str) -> Option<Arc<User>> {
+        if let Some(user) = self.verified.get(name, password) {
+            return Some(user);
+        }
+
+        let user = self.users.get(name)?;
+
+        if !bcrypt::verify(password, &user.password_hash).unwrap_or(false) {
+            return None;
+        }
+
+        self.verified.insert(name, password, user.clone());
+        Some(user.clone())
+    }
+}
+
+// 各驗證後端共用的驗證結果快取，避免每個請求都重新驗證
+//
+// 以用戶名稱與密碼的摘要為鍵，不保存明文密碼。
+pub struct VerifiedCache {
+    ttl: Duration,
+    entries: Mutex<HashMap<[u8; 32], VerifiedEntry>>,
+}
+
+// 驗證成功的用戶與驗證時間
+type VerifiedEntry = (Arc<User>, Instant);
+
+impl VerifiedCache {
+    pub fn new(ttl: Duration) -> Self {
+        VerifiedCache {
+            ttl,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn key(name: &str, password: &str) -> [u8; 32] {
+        Sha256::digest(format!("{}:{}", name, password)).into()
+    }
+
+    pub fn get(&self, name: &str, password: &str) -> Option<Arc<User>> {
+        let mut entries = self.entries.lock().unwrap();
+        let key = Self::key(name, password);
+        match entries.get(&key) {
+            Some((user, at)) if at.elapsed() < self.ttl => Some(user.clone()),
+            Some(_) => {
+                entries.remove(&key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    pub fn insert(&self, name: &str, password: &str, user: Arc<User>) {
+        self.entries
+            .lock()
+            .unwrap()
+            .insert(Self::key(name, password), (user, Instant::now()));
+    }
+}
+
+// 從 Basic 驗證標頭取出用戶名稱與密碼
+pub fn basic_credentials(headers: &HeaderMap) -> Option<(String, String)> {
+    let value = headers.get(header::AUTHORIZATION)?.to_str().ok()?;
+    let encoded = value.strip_prefix("Basic ")?;
+    let decoded = String::from_utf8(STANDARD.decode(encoded).ok()?).ok()?;
+    let (name, password) = decoded.split_once(':')?;
+    Some((name.to_string(), password.to_string()))
+}
+
+// 拆分以逗號分隔的清單
+fn split_list(s: &str) -> impl Iterator<Item = &str> {
+    s.split(',').map(str::trim).filter(|s| !s.is_empty())
+}
+
+// 以路徑段為單位比對前綴，`docs` 不會符合 `docsets`，空前綴符合所有路徑
+fn has_path_prefix(path: &str, prefix: &str) -> bool {
+    prefix.is_empty()
+        || path == prefix
+        || path
+            .strip_prefix(prefix)
+            .is_some_and(|rest| rest.starts_with('/'))
+}
+
+// 單條路徑存取規則
+struct AccessRule {
+    // 用戶名稱、`@群組` 或代表所有用戶的 `*`
+    principal: String,
+    prefix: String,
+    permissions: Vec<Permission>,
+}
+
+impl AccessRule {
+    fn applies_to(&self, user: &User) -> bool {
+        match self.principal.strip_prefix('@') {
+            Some(group) => user.groups.iter().any(|g| g == group),
+            None => self.principal == "*" || self.principal == user.name,
+        }
+    }
+
+    fn matches(&self, path: &str) -> bool {
+        has_path_prefix(path, &self.prefix)
+    }
+}
+
+// 路徑權限規則檔
+//
+// 每行格式為 `principal prefix permissions`，例如 `@staff /reports read,list`。
+// 對同一個請求，只採用符合用戶且前綴最長的規則，同樣長度的多條規則權限取聯集。
+pub struct AccessRules {
+    rules: Vec<AccessRule>,
+}
+
+impl AccessRules {
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let mut rules = AccessRules { rules: Vec::new() };
+        for (index, line) in fs::read_to_string(path)?.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let result = match fields[..] {
+                [principal, prefix, permissions] => rules.push(principal, prefix, permissions),
+                _ => Err("格式應為 principal prefix permissions".to_string()),
+            };
+            result.map_err(|reason| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{} 第 {} 行: {}", path.display(), index + 1, reason),
+                )
+            })?;
+        }
+
+        Ok(rules)
+    }
+
+    // 設定檔中以表格寫成的規則，每項為 (principal, prefix, permissions)
+    pub fn from_entries<'a>(
+        entries: impl IntoIterator<Item = (&'a str, &'a str, &'a str)>,
+    ) -> Result<Self, String> {
+        let mut rules = AccessRules { rules: Vec::new() };
+        for (index, (principal, prefix, permissions)) in entries.into_iter().enumerate() {
+            rules
+                .push(principal, prefix, permissions)
+                .map_err(|reason| format!("第 {} 條規則: {}", index + 1, reason))?;
+        }
+        Ok(rules)
+    }
+
+    fn push(&mut self, principal: &str, prefix: &str, permissions: &str) -> Result<(), String> {
+        let permissions = split_list(permissions)
+            .map(|name| Permission::parse(name).ok_or_else(|| format!("未知的權限 {}", name)))
+            .collect::<Result<Vec<_>, _>>()?;
+        self.rules.push(AccessRule {
+            principal: principal.to_string(),
+            prefix: prefix.trim_matches('/').to_string(),
+            permissions,
+        });
+        Ok(())
+    }
+
+    // 取得用戶對路徑的權限，沒有任何規則符合時回傳 None
+    fn permissions_for(&self, user: &User, path: &str) -> Option<Vec<Permission>> {
+        let path = path.trim_matches('/');
+        let matching: Vec<&AccessRule> = self
+            .rules
+            .iter()
+            .filter(|rule| rule.applies_to(user) && rule.matches(path))
+            .collect();
+
+        let longest = matching.iter().map(|rule| rule.prefix.len()).max()?;
+        Some(
+            matching
+                .iter()
+                .filter(|rule| rule.prefix.len() == longest)
+                .flat_map(|rule| rule.permissions.iter().copied())
+                .collect(),
+        )
+    }
+}
+
+// 以用戶名稱與密碼驗證，依序嘗試帳號檔、LDAP 與 PAM，任一後端驗證成功即可
+//
+// 同一來源 IP，或同一來源對同一用戶連續失敗過多時，在鎖定期間直接拒絕。
+pub async fn authenticate_password(
+    config: &ServerConfig,
+    ip: Option<IpAddr>,
+    name: String,
+    password: String,
+) -> Result<Option<Arc<User>>, ServerError> {
+    if let Some(wait) = config.throttle.check(ip, Some(&name)) {
+        return Err(ServerError::TooManyRequests(wait));
+    }
+
+    let user = verify_password(config, name.clone(), password).await?;
+    match &user {
+        Some(_) => config.throttle.record_success(ip, Some(&name)),
+        None => {
+            // 只有帳號檔能確認用戶是否存在，LDAP 與 PAM 的失敗只計入來源 IP
+            let known = config.users.as_ref().is_some_and(|users| users.get(&name).is_some());
+            config.throttle.record_failure(ip, Some(&name), known)
+        }
+    }
+    Ok(user)
+}
+
+async fn verify_password(
+    config: &ServerConfig,
+    name: String,
+    password: String,
+) -> Result<Option<Arc<User>>, ServerError> {
+    // bcrypt 驗證相當耗費 CPU，移到阻塞執行緒處理
+    if let Some(users) = config.users.clone() {
+        let (name, password) = (name.clone(), password.clone());
+        let user = tokio::task::spawn_blocking(move || users.authenticate(&name, &password))
+            .await
+            .map_err(|e| ServerError::Internal(e.to_string()))?;
+        if user.is_some() {
+            return Ok(user);
+        }
+    }
+
+    if let Some(ldap) = &config.ldap {
+        if let Some(user) = ldap.authenticate(&name, &password).await {
+            return Ok(Some(user));
+        }
+    }
+
+    #[cfg(feature = "pam")]
+    if let Some(pam) = config.pam.clone() {
+        return tokio::task::spawn_blocking(move || pam.authenticate(&name, &password))
+            .await
+            .map_err(|e| ServerError::Internal(e.to_string()));
+    }
+
+    Ok(None)
+}
+
+// 依登入階段中的用戶名稱取得用戶
+//
+// 帳號檔中的用戶以帳號檔為準（已刪除的帳號立即失效），外部後端的用戶則重建預設權限。
+fn session_user(config: &ServerConfig, name: &str) -> Option<Arc<User>> {
+    if let Some(user) = config.users.as_ref().and_then(|users| users.get(name)) {
+        return Some(user);
+    }
+
+    #[cfg(feature = "pam")]
+    let external = config.ldap.is_some() || config.pam.is_some();
+    #[cfg(not(feature = "pam"))]
+    let external = config.ldap.is_some();
+
+    external.then(|| {
+        Arc::new(User::external(
+            name.to_string(),
+            vec![Permission::Read, Permission::List],
+        ))
+    })
+}
+
+// 要求請求通過驗證，並將用戶放入請求擴充中
+//
+// 依序檢查 API token、登入階段 cookie 與 Basic 驗證。瀏覽器請求在啟用登入頁時
+// 會被導向登入頁，而不是跳出 Basic 驗證視窗。修改類請求另外進行 CSRF 檢查。
+pub async fn require_user(
+    State(config): State<ServerConfig>,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, ServerError> {
+    if !config.requires_auth() {
+        return Ok(next.run(request).await);
+    }
+
+    // 瀏覽器會自動帶上 cookie 與快取的 Basic 帳密，其他網站發起的修改請求一律拒絕
+    let mutating = !request.method().is_safe();
+    if mutating && csrf::is_cross_site(request.headers()) {
+        return Err(ServerError::Forbidden("不接受跨站請求".to_string()));
+    }
+
+    let mut user = config
+        .tokens
+        .as_ref()
+        .and_then(|tokens| tokens.authenticate(request.headers()));
+
+    if user.is_none() {
+        if let Some(sessions) = &config.sessions {
+            user = sessions
+                .verify(request.headers())
+                .and_then(|name| session_user(&config, &name));
+
+            // 以登入階段 cookie 驗證的修改請求還需要附上 CSRF token
+            if user.is_some() && mutating && !csrf::verify(request.headers(), request.uri(), None) {
+                return Err(ServerError::Forbidden("CSRF 驗證失敗".to_string()));
+            }
+        }
+    }
+
+    if let (None, Some((name, password))) = (&user, basic_credentials(request.headers())) {
+        let ip = request
+            .extensions()
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|info| info.0.ip());
+        user = authenticate_password(&config, ip, name, password).await?;
+    }
+
+    match user {
+        Some(user) => {
+            request.extensions_mut().insert(user.clone());
+            // 存取紀錄從回應取得用戶名稱
+            let mut response = next.run(request).await;
+            response.extensions_mut().insert(user);
+            Ok(response)
+        }
+        None if config.sessions.is_some() && session::wants_html(request.headers()) => {
+            Ok(session::redirect_to_login(request.uri()))
+        }
+        None => Err(ServerError::Unauthorized(
+            "Basic realm=\"Static File Server\", charset=\"UTF-8\"".to_string(),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user(name: &str, groups: &[&str], permissions: Vec<Permission>) -> User {
+        User {
+            name: name.to_string(),
+            password_hash: String::new(),
+            root: String::new(),
+            permissions,
+            groups: groups.iter().map(|g| g.to_string()).collect(),
+            scope: None,
+        }
+    }
+
+    fn rules() -> AccessRules {
+        AccessRules::from_entries([
+            ("*", "/", "read,list"),
+            ("@staff", "/reports", "read,list,write"),
+            ("alice", "/reports/private", ""),
+            ("bob", "/reports/private", "read"),
+            ("@staff", "/reports/private", "list"),
+        ])
+        .unwrap()
+    }
+
+    #[test]
+    fn the_longest_matching_prefix_wins() {
+        let rules = rules();
+        let alice = user("alice", &["staff"], vec![]);
+        assert!(alice.can_access(Some(&rules), "docs/a.txt", Permission::Read));
+        assert!(!alice.can_access(Some(&rules), "docs/a.txt", Permission::Write));
+        assert!(alice.can_access(Some(&rules), "reports/q1.pdf", Permission::Write));
+        // 更長的前綴取代較短的規則，而不是與之合併
+        assert!(!alice.can_access(Some(&rules), "reports/private/x", Permission::Write));
+        assert!(!alice.can_access(Some(&rules), "reports/private/x", Permission::Read));
+        assert!(alice.can_access(Some(&rules), "reports/private", Permission::List));
+    }
+
+    #[test]
+    fn rules_with_the_same_prefix_are_combined() {
+        let rules = rules();
+        let bob = user("bob", &["staff"], vec![]);
+        assert!(bob.can_access(Some(&rules), "reports/private/x", Permission::Read));
+        assert!(bob.can_access(Some(&rules), "reports/private/x", Permission::List));
+        assert!(!bob.can_access(Some(&rules), "reports/private/x", Permission::Write));
+    }
+
+    #[test]
+    fn prefixes_match_whole_path_segments() {
+        let rules = rules();
+        let carol = user("carol", &["staff"], vec![]);
+        assert!(carol.can_access(Some(&rules), "/reports/", Permission::Write));
+        assert!(!carol.can_access(Some(&rules), "reportsx/a", Permission::Write));
+        assert!(!carol.can_access(Some(&rules), "reports-old/a", Permission::Write));
+    }
+
+    #[test]
+    fn default_permissions_apply_without_a_matching_rule() {
+        let rules = AccessRules::from_entries([("@staff", "/reports", "read")]).unwrap();
+        let dave = user("dave", &[], vec![Permission::Read, Permission::Write]);
+        assert!(dave.can_access(Some(&rules), "reports/a", Permission::Write));
+        assert!(dave.can_access(None, "reports/a", Permission::Write));
+        assert!(!dave.can_access(None, "reports/a", Permission::Delete));
+    }
+
+    #[test]
+    fn path_prefixes_compare_whole_segments() {
+        assert!(has_path_prefix("docs", "docs"));
+        assert!(has_path_prefix("docs/a/b", "docs"));
+        assert!(has_path_prefix("docs/a", "docs/a"));
+        assert!(!has_path_prefix("docsets/a", "docs"));
+        assert!(!has_path_prefix("doc", "docs"));
+        assert!(!has_path_prefix("", "docs"));
+        // 空前綴代表整個目錄樹
+        assert!(has_path_prefix("", ""));
+        assert!(has_path_prefix("anything/at/all", ""));
+    }
+
+    #[test]
+    fn token_scopes_replace_access_rules() {
+        let rules = rules();
+        let token = User::token("ci".to_string(), vec![Permission::Read, Permission::Write], "builds".to_string());
+        assert!(token.can_access(Some(&rules), "builds/1.0/app.zip", Permission::Write));
+        assert!(token.can_access(None, "/builds/", Permission::Read));
+        // 範圍外即使規則允許所有用戶讀取也不行
+        assert!(!token.can_access(Some(&rules), "docs/a.txt", Permission::Read));
+        assert!(!token.can_access(Some(&rules), "builds-old/a", Permission::Read));
+        assert!(!token.can_access(Some(&rules), "", Permission::List));
+        // 範圍內也只有 token 本身的權限
+        assert!(!token.can_access(Some(&rules), "builds/a", Permission::Delete));
+
+        let everything = User::token("ops".to_string(), vec![Permission::List], String::new());
+        assert!(everything.can_access(None, "any/path", Permission::List));
+        assert!(!everything.can_access(None, "any/path", Permission::Read));
+    }
+
+    #[test]
+    fn unknown_permissions_are_rejected() {
+        let error = AccessRules::from_entries([("*", "/", "read,execute")]).err().unwrap();
+        assert!(error.contains("execute"), "{}", error);
+    }
+}
@@ -0,0 +1,194 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use axum::{
+    body::{Body, Bytes, HttpBody},
+    extract::{Request, State},
+    middleware::Next,
+    response::Response,
+};
+use http_body::{Frame, SizeHint};
+use percent_encoding::percent_decode_str;
+use tokio::time::Sleep;
+
+use crate::{cache_control::Pattern, restrict::normalize, ServerConfig};
+
+// 一個頻寬等級：同一等級中同時進行的所有傳輸共用的速率上限
+//
+// 以權杖桶計算，閒置後最多可以一次送出一秒的量；送出的量超過可用額度時記為欠額，
+// 之後的傳輸依欠額等待，所有傳輸加總的速率因此不會超過上限。
+struct Class {
+    // 每秒位元組數
+    rate: f64,
+    bucket: Mutex<Bucket>,
+}
+
+struct Bucket {
+    available: f64,
+    updated: Instant,
+}
+
+impl Class {
+    fn new(rate: u64) -> Self {
+        Class {
+            rate: rate as f64,
+            bucket: Mutex::new(Bucket {
+                available: rate as f64,
+                updated: Instant::now(),
+            }),
+        }
+    }
+
+    // 扣除送出的位元組數，回傳下一個區塊送出前需要等待的時間
+    fn take(&self, bytes: usize) -> Duration {
+        let mut bucket = self.bucket.lock().unwrap();
+        let now = Instant::now();
+        let refill = now.duration_since(bucket.updated).as_secs_f64() * self.rate;
+        bucket.available = (bucket.available + refill).min(self.rate) - bytes as f64;
+        bucket.updated = now;
+        match bucket.available < 0.0 {
+            true => Duration::from_secs_f64(-bucket.available / self.rate),
+            false => Duration::ZERO,
+        }
+    }
+}
+
+// 依路徑指定的頻寬等級（--bandwidth）
+//
+// 每條規則寫成 `樣式=速率`，例如 `/iso/**=5MB/s` 或 `/docs/**=unlimited`，樣式的寫法與
+// --cache-control 相同。依指定的順序比對，第一條符合的規則決定請求所屬的等級，都不符合的請求不限速。
+// 速率是符合該規則的所有回應加總的上限，大型映像檔再多也不會佔滿頻寬，拖慢同一台伺服器上的小文件。
+#[derive(Default)]
+pub struct BandwidthRules {
+    rules: Vec<(Pattern, Option<Arc<Class>>)>,
+}
+
+impl BandwidthRules {
+    pub fn new<'a>(specs: impl IntoIterator<Item = &'a String>) -> Result<Self, String> {
+        let mut rules = Vec::new();
+        for spec in specs {
+            let invalid = |reason: &str| format!("無效的 --bandwidth 規則 {}: {}", spec, reason);
+            let (pattern, rate) = spec.split_once('=').ok_or_else(|| invalid("格式為 樣式=速率"))?;
+            let (pattern, rate) = (pattern.trim(), rate.trim());
+            if pattern.is_empty() {
+                return Err(invalid("缺少樣式"));
+            }
+            let pattern = Pattern::new(pattern).map_err(|e| invalid(&e.to_string()))?;
+            let class = match rate.eq_ignore_ascii_case("unlimited") {
+                true => None,
+                false => Some(Arc::new(Class::new(parse_rate(rate).ok_or_else(|| {
+                    invalid("速率應為每秒位元組數，例如 800KB/s、5MB/s 或 unlimited")
+                })?))),
+            };
+            rules.push((pattern, class));
+        }
+        Ok(BandwidthRules { rules })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    // 路徑所屬的等級，不限速時為 None
+    fn class(&self, path: &str) -> Option<Arc<Class>> {
+        let components: Vec<&str> = path.split('/').filter(|c| !c.is_empty()).collect();
+        self.rules
+            .iter()
+            .find(|(pattern, _)| pattern.matches(&components))
+            .and_then(|(_, class)| class.clone())
+    }
+}
+
+// `5MB/s`、`800K`、`1.5 MiB/s` 或位元組數；單位以 1024 進位，`/s` 可以省略
+fn parse_rate(rate: &str) -> Option<u64> {
+    let rate = rate.trim_end_matches("/s").trim();
+    let rate = rate.strip_suffix("iB").or_else(|| rate.strip_suffix('B')).unwrap_or(rate);
+    let split = rate.find(|c: char| c.is_ascii_alphabetic()).unwrap_or(rate.len());
+    let (number, unit) = rate.split_at(split);
+    let multiplier = match unit.to_ascii_uppercase().as_str() {
+        "" => 1.0,
+        "K" => 1024.0,
+        "M" => 1024.0 * 1024.0,
+        "G" => 1024.0 * 1024.0 * 1024.0,
+        _ => return None,
+    };
+    let bytes = number.trim().parse::<f64>().ok()? * multiplier;
+    (bytes >= 1.0 && bytes.is_finite()).then_some(bytes as u64)
+}
+
+// 依頻寬等級放慢送出回應主體的速度
+struct LimitedBody {
+    inner: Body,
+    class: Arc<Class>,
+    // 上一個區塊送出後需要等待的時間
+    pause: Option<Pin<Box<Sleep>>>,
+}
+
+impl HttpBody for LimitedBody {
+    type Data = Bytes;
+    type Error = axum::Error;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, axum::Error>>> {
+        if let Some(pause) = self.pause.as_mut() {
+            if pause.as_mut().poll(cx).is_pending() {
+                return Poll::Pending;
+            }
+            self.pause = None;
+        }
+        let poll = Pin::new(&mut self.inner).poll_frame(cx);
+        if let Poll::Ready(Some(Ok(frame))) = &poll {
+            if let Some(data) = frame.data_ref() {
+                let wait = self.class.take(data.len());
+                if !wait.is_zero() {
+                    self.pause = Some(Box::pin(tokio::time::sleep(wait)));
+                }
+            }
+        }
+        poll
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+// 將回應主體交給所屬的頻寬等級限速
+//
+// 在傳輸統計之內、壓縮之外，限制的是實際送出的位元組數。
+pub async fn limit(State(config): State<ServerConfig>, request: Request, next: Next) -> Response {
+    if config.bandwidth.is_empty() {
+        return next.run(request).await;
+    }
+    let decoded = percent_decode_str(request.uri().path()).decode_utf8_lossy();
+    let path = normalize(&decoded);
+    // 以網址前綴劃分的網站，樣式從網站的根目錄比對
+    let path = match config.mount.is_empty() {
+        true => path.as_str(),
+        false => match path.strip_prefix(config.mount.as_str()) {
+            Some(rest) if rest.is_empty() || rest.starts_with('/') => rest,
+            _ => path.as_str(),
+        },
+    };
+    let Some(class) = config.bandwidth.class(path) else {
+        return next.run(request).await;
+    };
+    next.run(request).await.map(|body| {
+        Body::new(LimitedBody {
+            inner: body,
+            class,
+            pause: None,
+        })
+    })
+}
@@ -0,0 +1,100 @@
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::RwLock,
+    time::SystemTime,
+};
+
+use axum::body::Bytes;
+use globset::{Glob, GlobSetBuilder};
+
+use crate::tree::walk_files;
+
+// 快取中的文件內容，附帶讀取當下的修改時間與大小用於驗證
+struct CachedFile {
+    content: Bytes,
+    modified: Option<SystemTime>,
+    len: u64,
+}
+
+// 記憶體文件快取
+//
+// 查詢時比對文件目前的修改時間與大小，內容已變動的項目視為未命中並移除。
+#[derive(Default)]
+pub struct FileCache {
+    files: RwLock<HashMap<PathBuf, CachedFile>>,
+}
+
+impl FileCache {
+    // 取得快取的文件內容
+    pub fn get(&self, path: &Path, metadata: &fs::Metadata) -> Option<Bytes> {
+        let files = self.files.read().unwrap();
+        let cached = files.get(path)?;
+
+        if cached.len == metadata.len() && cached.modified == metadata.modified().ok() {
+            return Some(cached.content.clone());
+        }
+
+        drop(files);
+        self.files.write().unwrap().remove(path);
+        None
+    }
+
+    // 讀取文件並放入快取
+    pub fn load(&self, path: &Path) -> io::Result<u64> {
+        let metadata = fs::metadata(path)?;
+        let content = Bytes::from(fs::read(path)?);
+        let len = content.len() as u64;
+
+        self.files.write().unwrap().insert(
+            path.to_path_buf(),
+            CachedFile {
+                content,
+                modified: metadata.modified().ok(),
+                len: metadata.len(),
+            },
+        );
+        Ok(len)
+    }
+
+    // 清除快取，可指定只清除某個路徑底下的項目，回傳清除的數量
+    pub fn flush(&self, prefix: Option<&Path>) -> usize {
+        let mut files = self.files.write().unwrap();
+        let before = files.len();
+        match prefix {
+            Some(prefix) => files.retain(|path, _| !path.starts_with(prefix)),
+            None => files.clear(),
+        }
+        before - files.len()
+    }
+
+    // 預先載入符合 glob 樣式的文件，回傳載入的文件數與總位元組數
+    pub fn preload(
+        &self,
+        base: &Path,
+        patterns: &[String],
+        allow: &dyn Fn(&str) -> bool,
+    ) -> io::Result<(usize, u64)> {
+        let invalid = |e: globset::Error| io::Error::new(io::ErrorKind::InvalidInput, e);
+        let mut builder = GlobSetBuilder::new();
+        for pattern in patterns {
+            builder.add(Glob::new(pattern).map_err(invalid)?);
+        }
+        let globs = builder.build().map_err(invalid)?;
+
+        let mut files = Vec::new();
+        walk_files(base, "", allow, &mut files)?;
+
+        let mut count = 0;
+        let mut bytes = 0;
+        for (rel, path, _) in files {
+            if globs.is_match(&rel) {
+                bytes += self.load(&path)?;
+                count += 1;
+            }
+        }
+
+        Ok((count, bytes))
+    }
+}
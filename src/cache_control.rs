@@ -0,0 +1,72 @@
+use axum::http::HeaderValue;
+use globset::{Glob, GlobBuilder, GlobMatcher};
+
+use crate::restrict::normalize;
+
+// 沒有任何規則符合時文件回應使用的值
+const DEFAULT: &str = "public, max-age=3600";
+
+// 路徑規則的樣式，--bandwidth 也使用相同的寫法
+pub enum Pattern {
+    // 不含 `/` 的樣式（`*.html`）比對文件名稱
+    Name(GlobMatcher),
+    // 含 `/` 的樣式（`/assets/*`）從根目錄比對，符合的目錄底下的文件也適用
+    Anchored(GlobMatcher),
+}
+
+impl Pattern {
+    pub fn new(pattern: &str) -> Result<Self, globset::Error> {
+        match pattern.contains('/') {
+            true => {
+                let glob = GlobBuilder::new(pattern.trim_matches('/')).literal_separator(true).build()?;
+                Ok(Pattern::Anchored(glob.compile_matcher()))
+            }
+            false => Ok(Pattern::Name(Glob::new(pattern)?.compile_matcher())),
+        }
+    }
+
+    // `components` 為正規化後路徑的各段
+    pub fn matches(&self, components: &[&str]) -> bool {
+        match self {
+            Pattern::Name(glob) => components.last().is_some_and(|name| glob.is_match(name)),
+            Pattern::Anchored(glob) => (1..=components.len()).any(|n| glob.is_match(components[..n].join("/"))),
+        }
+    }
+}
+
+// 文件回應的 Cache-Control 規則（--cache-control）
+//
+// 每條規則寫成 `樣式=值`，例如 `*.html=no-cache` 或 `/assets/*=max-age=31536000, immutable`，
+// 依指定的順序比對，第一條符合的規則生效。
+pub struct CacheRules {
+    rules: Vec<(Pattern, HeaderValue)>,
+}
+
+impl CacheRules {
+    pub fn new<'a>(specs: impl IntoIterator<Item = &'a String>) -> Result<Self, String> {
+        let mut rules = Vec::new();
+        for spec in specs {
+            let invalid = |reason: &str| format!("無效的 --cache-control 規則 {}: {}", spec, reason);
+            let (pattern, value) = spec.split_once('=').ok_or_else(|| invalid("格式為 樣式=值"))?;
+            let (pattern, value) = (pattern.trim(), value.trim());
+            if pattern.is_empty() {
+                return Err(invalid("缺少樣式"));
+            }
+            let value = HeaderValue::from_str(value).map_err(|_| invalid("值含有無效的字元"))?;
+            let pattern = Pattern::new(pattern).map_err(|e| invalid(&e.to_string()))?;
+            rules.push((pattern, value));
+        }
+        Ok(CacheRules { rules })
+    }
+
+    // 相對於基礎路徑的文件路徑對應的值
+    pub fn value(&self, path: &str) -> HeaderValue {
+        let path = normalize(path);
+        let components: Vec<&str> = path.split('/').filter(|c| !c.is_empty()).collect();
+        self.rules
+            .iter()
+            .find(|(pattern, _)| pattern.matches(&components))
+            .map(|(_, value)| value.clone())
+            .unwrap_or_else(|| HeaderValue::from_static(DEFAULT))
+    }
+}
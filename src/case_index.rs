@@ -0,0 +1,115 @@
+use std::{
+    collections::HashMap,
+    ffi::OsString,
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+// 所有目錄索引合計記住的名稱數量上限，超過時全部清除重新累積
+const MAX_NAMES: usize = 1_000_000;
+
+// 單一目錄的名稱索引：小寫名稱 -> 實際名稱
+type DirIndex = Arc<HashMap<String, OsString>>;
+
+// 不分大小寫路徑解析用的目錄名稱索引
+//
+// 每個目錄只在第一次查詢時讀取一次，之後以目錄的修改時間判斷是否需要重建。
+// 記住的名稱總數有上限，很大的目錄樹不會讓記憶體無限制地增加。
+#[derive(Default)]
+pub struct NameIndex {
+    cache: Mutex<Cache>,
+}
+
+#[derive(Default)]
+struct Cache {
+    dirs: HashMap<PathBuf, (Option<SystemTime>, DirIndex)>,
+    // 各目錄索引的名稱數量合計
+    names: usize,
+}
+
+impl NameIndex {
+    // 取得目錄索引，目錄內容有變動時重新建立
+    fn dir_index(&self, dir: &Path) -> Option<DirIndex> {
+        let modified = fs::metadata(dir).ok()?.modified().ok();
+
+        if let Some((cached_mtime, index)) = self.cache.lock().unwrap().dirs.get(dir) {
+            if *cached_mtime == modified {
+                return Some(index.clone());
+            }
+        }
+
+        let mut index = HashMap::new();
+        for entry in fs::read_dir(dir).ok()?.flatten() {
+            let name = entry.file_name();
+            // 若有多個名稱只差大小寫，保留排序最前的一個，讓結果可預期
+            index
+                .entry(name.to_string_lossy().to_lowercase())
+                .and_modify(|existing: &mut OsString| {
+                    if name < *existing {
+                        *existing = name.clone();
+                    }
+                })
+                .or_insert_with(|| name.clone());
+        }
+
+        let index = Arc::new(index);
+        let mut cache = self.cache.lock().unwrap();
+        if cache.names + index.len() > MAX_NAMES {
+            cache.dirs.clear();
+            cache.names = 0;
+        }
+        // 單一目錄就超過上限時只用這一次，不記住
+        if index.len() <= MAX_NAMES {
+            cache.names += index.len();
+            if let Some((_, replaced)) = cache.dirs.insert(dir.to_path_buf(), (modified, index.clone())) {
+                cache.names -= replaced.len();
+            }
+        }
+        Some(index)
+    }
+
+    // 清除目錄索引，可指定只清除某個路徑底下的目錄，回傳清除的數量
+    pub fn flush(&self, prefix: Option<&Path>) -> usize {
+        let mut cache = self.cache.lock().unwrap();
+        let before = cache.dirs.len();
+        match prefix {
+            Some(prefix) => cache.dirs.retain(|dir, _| !dir.starts_with(prefix)),
+            None => cache.dirs.clear(),
+        }
+        cache.names = cache.dirs.values().map(|(_, index)| index.len()).sum();
+        before - cache.dirs.len()
+    }
+
+    // 將請求路徑逐段對應到磁碟上的實際名稱，找不到時回傳 None
+    pub fn resolve(&self, base: &Path, path: &str) -> Option<String> {
+        let mut current = base.to_path_buf();
+        let mut resolved = Vec::new();
+
+        for component in path.split('/') {
+            if component.is_empty() || component == "." || component == ".." {
+                // 特殊路徑段交由後續的安全檢查處理
+                if !component.is_empty() {
+                    current.push(component);
+                }
+                resolved.push(component.to_string());
+                continue;
+            }
+
+            let exact = current.join(component);
+            if fs::symlink_metadata(&exact).is_ok() {
+                current = exact;
+                resolved.push(component.to_string());
+                continue;
+            }
+
+            let index = self.dir_index(&current)?;
+            let actual = index.get(&component.to_lowercase())?;
+            current.push(actual);
+            resolved.push(actual.to_string_lossy().to_string());
+        }
+
+        Some(resolved.join("/"))
+    }
+}
@@ -0,0 +1,64 @@
+use std::{any::Any, panic::AssertUnwindSafe};
+
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use futures_util::FutureExt;
+use tracing::error;
+
+use crate::{ServerConfig, ServerError};
+
+// 請求編號的標頭，用戶端或前端代理已帶有時沿用
+pub const REQUEST_ID: &str = "x-request-id";
+
+// 沿用的請求編號長度上限
+const MAX_ID_LEN: usize = 64;
+
+// 處理請求時的 panic 轉為 500 回應，不中斷連線
+//
+// 回應與記錄中帶有請求編號，方便對照用戶回報的錯誤與日誌。回應標頭送出後，
+// 產生回應內容時的 panic 無法再改為 500，只會中斷該次傳輸。
+// 以 --abort-on-panic 啟動時記錄後立即終止程序，保留發生當下的狀態供除錯。
+pub async fn catch(State(config): State<ServerConfig>, request: Request, next: Next) -> Response {
+    let id = request
+        .headers()
+        .get(REQUEST_ID)
+        .and_then(|v| v.to_str().ok())
+        .filter(|id| !id.is_empty() && id.len() <= MAX_ID_LEN)
+        .map(str::to_string);
+    let method = request.method().clone();
+    let path = request.uri().path().to_string();
+
+    let payload = match AssertUnwindSafe(next.run(request)).catch_unwind().await {
+        Ok(response) => return response,
+        Err(payload) => payload,
+    };
+    let id = id.unwrap_or_else(new_id);
+    error!("處理請求 {} 時發生 panic: {} {}: {}", id, method, path, message(&*payload));
+    if config.abort_on_panic {
+        error!("已指定 --abort-on-panic，終止程序");
+        std::process::abort();
+    }
+    if let Some(metrics) = &config.metrics {
+        metrics.record_panic();
+    }
+    ServerError::Panicked(id).into_response()
+}
+
+// 隨機產生的請求編號
+fn new_id() -> String {
+    let mut bytes = [0u8; 8];
+    let _ = getrandom::fill(&mut bytes);
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+// panic! 的訊息，通常是 &str 或 String
+fn message(payload: &(dyn Any + Send)) -> &str {
+    payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("（無法取得訊息）")
+}
@@ -0,0 +1,131 @@
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use sha2::{Digest, Sha256};
+
+use crate::{
+    deadline::Deadline,
+    manifest::{hash_file, Manifest},
+};
+
+// 記住的雜湊數量上限，超過時全部清除重新累積
+const MAX_CACHED: usize = 100_000;
+
+// 目錄網址的查詢字串要求的校驗和清單，例如 `/releases/?checksums=sha256`
+//
+// 目前只支援 sha256；指定其他演算法時回傳 `Some(Err(演算法名稱))`。
+pub fn from_query(query: Option<&str>) -> Option<Result<(), String>> {
+    query?.split(['&', ';']).find_map(|pair| match pair.split_once('=') {
+        Some(("checksums", "sha256")) => Some(Ok(())),
+        Some(("checksums", other)) => Some(Err(other.to_string())),
+        _ => None,
+    })
+}
+
+// 目錄中一個要列入清單的文件
+pub struct ChecksumFile {
+    pub name: String,
+    pub path: PathBuf,
+    pub len: u64,
+    pub modified: SystemTime,
+}
+
+// 目錄的 SHA256SUMS 清單（`?checksums=sha256`）
+//
+// 每個文件的雜湊依路徑、大小與修改時間記住，文件沒有改變時不再重新讀取；
+// 啟用 --manifest 時先使用文件清單中相同版本的雜湊。
+#[derive(Default)]
+pub struct Checksums {
+    hashes: Mutex<HashMap<PathBuf, (u64, SystemTime, String)>>,
+}
+
+impl Checksums {
+    // 依 `sha256sum` 的格式產生清單，`sha256sum -c SHA256SUMS` 可以直接檢查
+    pub fn document(
+        &self,
+        files: &[ChecksumFile],
+        manifest: Option<&Manifest>,
+        deadline: Deadline,
+    ) -> io::Result<String> {
+        let mut document = String::new();
+        for file in files {
+            deadline.check()?;
+            let hash = self.hash(file, manifest)?;
+            // 與 sha256sum 相同，名稱含有反斜線或換行時整行以反斜線開頭並跳脫名稱
+            if file.name.contains(['\\', '\n']) {
+                let name = file.name.replace('\\', "\\\\").replace('\n', "\\n");
+                document.push_str(&format!("\\{}  {}\n", hash, name));
+            } else {
+                document.push_str(&format!("{}  {}\n", hash, file.name));
+            }
+        }
+        Ok(document)
+    }
+
+    fn hash(&self, file: &ChecksumFile, manifest: Option<&Manifest>) -> io::Result<String> {
+        let mtime = file.modified.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        if let Some(entry) = manifest.and_then(|manifest| manifest.entry_for(&file.path)) {
+            if entry.size == file.len && entry.mtime == mtime {
+                return Ok(entry.sha256);
+            }
+        }
+        if let Some((len, modified, hash)) = self.hashes.lock().unwrap().get(&file.path) {
+            if *len == file.len && *modified == file.modified {
+                return Ok(hash.clone());
+            }
+        }
+        let hash = hash_file(&file.path)?;
+        // 讀取期間文件被改寫時不記住，下次重新計算
+        let unchanged = fs::metadata(&file.path)
+            .is_ok_and(|m| m.len() == file.len && m.modified().ok() == Some(file.modified));
+        if unchanged {
+            let mut hashes = self.hashes.lock().unwrap();
+            if hashes.len() >= MAX_CACHED {
+                hashes.clear();
+            }
+            hashes.insert(file.path.clone(), (file.len, file.modified, hash.clone()));
+        }
+        Ok(hash)
+    }
+}
+
+// 由名稱、大小與修改時間計算的弱 ETag，文件都沒有改變時不必重新產生清單
+pub fn etag(files: &[ChecksumFile]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(b"sha256sums\n");
+    for file in files {
+        let modified = file.modified.duration_since(UNIX_EPOCH).map(|d| d.as_nanos()).unwrap_or_default();
+        hasher.update(format!("{}\0{}\0{}\n", file.name, file.len, modified));
+    }
+    let hex: String = hasher.finalize()[..8].iter().map(|b| format!("{:02x}", b)).collect();
+    format!("W/\"{}\"", hex)
+}
+
+// 目錄中直接包含的一般文件，依名稱排序；符號連結與子目錄不列入
+pub fn collect(dir: &Path, allow: &dyn Fn(&str) -> bool) -> io::Result<Vec<ChecksumFile>> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        if !entry.file_type()?.is_file() {
+            continue;
+        }
+        let name = entry.file_name().to_string_lossy().to_string();
+        if !allow(&name) {
+            continue;
+        }
+        let metadata = entry.metadata()?;
+        files.push(ChecksumFile {
+            name,
+            path: entry.path(),
+            len: metadata.len(),
+            modified: metadata.modified().unwrap_or(UNIX_EPOCH),
+        });
+    }
+    files.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(files)
+}
@@ -0,0 +1,125 @@
+use std::{path::Path, time::Duration};
+
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    net::TcpStream,
+};
+
+use crate::ServerError;
+
+// 一次送出的內容大小
+const CHUNK_SIZE: usize = 64 * 1024;
+
+// 連線與掃描一個文件的時限
+const SCAN_TIMEOUT: Duration = Duration::from_secs(120);
+
+// 掃描結果
+#[derive(Debug, PartialEq)]
+pub enum Verdict {
+    Clean,
+    // 內容為 clamd 回報的病毒名稱
+    Infected(String),
+}
+
+// 以 clamd 掃描上傳的文件（--clamd）
+//
+// 位址以 `/` 開頭時為 Unix socket，否則為 `host:port`。文件以 INSTREAM 指令送出，clamd
+// 不必能讀取伺服器的目錄；clamd 無法連線或回報錯誤時拒絕上傳，不會讓未掃描的文件進入目錄。
+pub struct Clamd {
+    addr: String,
+}
+
+impl Clamd {
+    pub fn new(addr: &str) -> Self {
+        Clamd { addr: addr.to_string() }
+    }
+
+    pub async fn scan(&self, file: &Path) -> Result<Verdict, ServerError> {
+        let failed = |e: String| ServerError::Internal(format!("clamd {} 掃描失敗: {}", self.addr, e));
+        let scan = async {
+            let file = tokio::fs::File::open(file).await.map_err(|e| e.to_string())?;
+            if self.addr.starts_with('/') {
+                #[cfg(unix)]
+                {
+                    let stream = tokio::net::UnixStream::connect(&self.addr).await.map_err(|e| e.to_string())?;
+                    return instream(stream, file).await;
+                }
+                #[cfg(not(unix))]
+                return Err("此平台不支援 Unix socket".to_string());
+            }
+            let stream = TcpStream::connect(&self.addr).await.map_err(|e| e.to_string())?;
+            instream(stream, file).await
+        };
+        let reply = tokio::time::timeout(SCAN_TIMEOUT, scan)
+            .await
+            .map_err(|_| failed("逾時".to_string()))?
+            .map_err(failed)?;
+        parse_reply(&reply).map_err(failed)
+    }
+}
+
+// 送出 `zINSTREAM`：每段內容前加上 4 位元組的長度，以長度 0 結束，回傳 clamd 的回覆
+async fn instream<S, R>(mut stream: S, mut file: R) -> Result<String, String>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+    R: AsyncRead + Unpin,
+{
+    let io = |e: std::io::Error| e.to_string();
+    stream.write_all(b"zINSTREAM\0").await.map_err(io)?;
+    let mut buffer = vec![0; CHUNK_SIZE];
+    loop {
+        let n = file.read(&mut buffer).await.map_err(io)?;
+        stream.write_all(&(n as u32).to_be_bytes()).await.map_err(io)?;
+        if n == 0 {
+            break;
+        }
+        stream.write_all(&buffer[..n]).await.map_err(io)?;
+    }
+    let mut reply = Vec::new();
+    stream.read_to_end(&mut reply).await.map_err(io)?;
+    Ok(String::from_utf8_lossy(&reply).trim_end_matches(['\0', '\n']).to_string())
+}
+
+// 回覆格式為 `stream: OK`、`stream: <病毒名稱> FOUND` 或 `<原因> ERROR`
+fn parse_reply(reply: &str) -> Result<Verdict, String> {
+    let result = reply.strip_prefix("stream: ").unwrap_or(reply);
+    if result == "OK" {
+        return Ok(Verdict::Clean);
+    }
+    match result.strip_suffix(" FOUND") {
+        Some(name) => Ok(Verdict::Infected(name.to_string())),
+        None => Err(result.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replies_are_parsed() {
+        assert_eq!(parse_reply("stream: OK"), Ok(Verdict::Clean));
+        assert_eq!(
+            parse_reply("stream: Eicar-Test-Signature FOUND"),
+            Ok(Verdict::Infected("Eicar-Test-Signature".to_string()))
+        );
+        assert_eq!(
+            parse_reply("INSTREAM size limit exceeded. ERROR"),
+            Err("INSTREAM size limit exceeded. ERROR".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn content_is_sent_in_length_prefixed_chunks() {
+        let (client, mut server) = tokio::io::duplex(1024);
+        let clamd = tokio::spawn(async move {
+            let mut request = vec![0; 10 + 4 + 5 + 4];
+            server.read_exact(&mut request).await.unwrap();
+            server.write_all(b"stream: OK\0").await.unwrap();
+            request
+        });
+        let reply = instream(client, &b"hello"[..]).await.unwrap();
+        assert_eq!(reply, "stream: OK");
+        assert_eq!(clamd.await.unwrap(), b"zINSTREAM\0\0\0\0\x05hello\0\0\0\0");
+    }
+}
@@ -0,0 +1,60 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    hash::Hash,
+    sync::Mutex,
+};
+
+use futures_util::future::{BoxFuture, FutureExt, Shared};
+
+// 合併相同的進行中運算
+//
+// 多個請求同時要求同一個代價高的結果（目錄封存、雜湊、快取預熱）時，只有第一個請求
+// 會真正執行，其餘請求等待並共用同一個結果。運算完成後立即移除，之後的請求會重新計算，
+// 這裡只合併同時發生的請求，不是結果快取。
+//
+// 所有等待者都中斷連線時運算會暫停，下一個相同的請求會接續執行而不是重新開始。
+pub struct Coalescer<K, V: Clone> {
+    inflight: Mutex<HashMap<K, Shared<BoxFuture<'static, V>>>>,
+}
+
+impl<K, V> Default for Coalescer<K, V>
+where
+    V: Clone,
+{
+    fn default() -> Self {
+        Coalescer {
+            inflight: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl<K, V> Coalescer<K, V>
+where
+    K: Eq + Hash + Clone,
+    V: Clone + Send + Sync + 'static,
+{
+    // 執行 `compute`，或等待已在進行中的相同運算
+    pub async fn run<F, Fut>(&self, key: K, compute: F) -> V
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = V> + Send + 'static,
+    {
+        let shared = self
+            .inflight
+            .lock()
+            .unwrap()
+            .entry(key.clone())
+            .or_insert_with(|| compute().boxed().shared())
+            .clone();
+
+        let value = shared.clone().await;
+
+        // 第一個拿到結果的等待者負責移除；之後的等待者看到的已是新的運算或空位，不能移除
+        let mut inflight = self.inflight.lock().unwrap();
+        if inflight.get(&key).is_some_and(|current| current.ptr_eq(&shared)) {
+            inflight.remove(&key);
+        }
+        value
+    }
+}
@@ -0,0 +1,343 @@
+use std::io::{self, Write};
+
+use axum::{
+    body::{Body, Bytes},
+    extract::{Request, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use flate2::{write::GzEncoder, Compression};
+use futures_util::{stream, StreamExt};
+
+use crate::{ServerConfig, ServerError};
+
+// 回應壓縮的設定
+pub struct CompressOptions {
+    // 未指定時 gzip 使用 6、zstd 使用 3、Brotli 使用 4
+    pub level: Option<u32>,
+    // 已知長度小於此值的回應不壓縮
+    pub min_size: u64,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Encoding {
+    Brotli,
+    Zstd,
+    Gzip,
+}
+
+impl Encoding {
+    pub fn name(self) -> &'static str {
+        match self {
+            Encoding::Brotli => "br",
+            Encoding::Zstd => "zstd",
+            Encoding::Gzip => "gzip",
+        }
+    }
+
+    // 預先壓縮的同名文件所使用的副檔名
+    pub fn extension(self) -> &'static str {
+        match self {
+            Encoding::Brotli => ".br",
+            Encoding::Zstd => ".zst",
+            Encoding::Gzip => ".gz",
+        }
+    }
+}
+
+// 即時壓縮支援的編碼，依偏好排列
+const ON_THE_FLY: &[Encoding] = &[Encoding::Zstd, Encoding::Brotli, Encoding::Gzip];
+
+// 即時 Brotli 壓縮的內部緩衝與視窗大小（2^22，與 brotli 命令列工具相同）
+const BROTLI_BUFFER: usize = 4096;
+const BROTLI_WINDOW: u32 = 22;
+
+// 請求的 Accept-Encoding（RFC 9110 §12.5.3）
+//
+// 明確列出的編碼優先於 `*`，`q=0` 代表不接受；`x-gzip` 視同 `gzip`。沒有 Accept-Encoding 時
+// 只送未壓縮的內容。未壓縮的內容（identity）預設可接受，除非列出 `identity;q=0`，
+// 或列出 `*;q=0` 且沒有另外列出 identity。q 值格式錯誤的項目整項略過。
+pub struct AcceptEncoding {
+    // 是否有 Accept-Encoding 標頭；標頭存在但為空時只接受未壓縮的內容
+    present: bool,
+    // 小寫的編碼名稱與 q 值（以千分之一為單位）
+    items: Vec<(String, u16)>,
+}
+
+impl AcceptEncoding {
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        let values = headers.get_all(header::ACCEPT_ENCODING);
+        let items = values
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','))
+            .filter_map(|item| {
+                let mut parts = item.split(';').map(str::trim);
+                let coding = parts.next().filter(|c| !c.is_empty())?.to_ascii_lowercase();
+                let mut q = 1000;
+                for param in parts {
+                    let (name, value) = param.split_once('=')?;
+                    if name.trim().eq_ignore_ascii_case("q") {
+                        q = parse_qvalue(value.trim())?;
+                    }
+                }
+                let coding = if coding == "x-gzip" { "gzip".to_string() } else { coding };
+                Some((coding, q))
+            })
+            .collect();
+        AcceptEncoding {
+            present: values.iter().next().is_some(),
+            items,
+        }
+    }
+
+    // 明確列出的編碼的 q 值，同一編碼列出多次時取最高的
+    fn listed(&self, coding: &str) -> Option<u16> {
+        self.items.iter().filter(|(c, _)| c == coding).map(|&(_, q)| q).max()
+    }
+
+    fn quality(&self, coding: &str) -> u16 {
+        match self.listed(coding).or_else(|| self.listed("*")) {
+            Some(q) => q,
+            None if coding == "identity" => 1000,
+            None => 0,
+        }
+    }
+
+    // 是否可以送出未壓縮的內容
+    pub fn identity_acceptable(&self) -> bool {
+        self.quality("identity") > 0
+    }
+
+    // 從 `supported` 中選擇 q 值最高的編碼，q 值相同時取 `supported` 中較前面的；
+    // 用戶端較偏好未壓縮的內容時回傳 None
+    pub fn choose(&self, supported: &[Encoding]) -> Option<Encoding> {
+        if !self.present {
+            return None;
+        }
+        let mut best: Option<(Encoding, u16)> = None;
+        for &encoding in supported {
+            let q = self.quality(encoding.name());
+            if q > 0 && best.is_none_or(|(_, best_q)| q > best_q) {
+                best = Some((encoding, q));
+            }
+        }
+        // 沒有列出 identity 時未壓縮的內容只是可接受，不代表比列出的編碼更受偏好
+        let identity = self.listed("identity").or_else(|| self.listed("*")).unwrap_or(0);
+        best.filter(|&(_, q)| q >= identity).map(|(encoding, _)| encoding)
+    }
+}
+
+// RFC 9110 的 qvalue：`0` 到 `1`，小數最多三位，以千分之一為單位
+fn parse_qvalue(value: &str) -> Option<u16> {
+    let (integer, fraction) = value.split_once('.').unwrap_or((value, ""));
+    if fraction.len() > 3 || !fraction.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let thousandths = format!("{:0<3}", fraction).parse::<u16>().ok()?;
+    match integer {
+        "0" => Some(thousandths),
+        "1" if thousandths == 0 => Some(1000),
+        _ => None,
+    }
+}
+
+// 依 Accept-Encoding 從 `supported` 中選擇編碼，見 AcceptEncoding::choose
+pub fn negotiate(headers: &HeaderMap, supported: &[Encoding]) -> Option<Encoding> {
+    AcceptEncoding::from_headers(headers).choose(supported)
+}
+
+// 依 Accept-Encoding 選擇即時壓縮使用的編碼，與 compress_responses 的選擇相同
+pub fn on_the_fly(headers: &HeaderMap) -> Option<Encoding> {
+    negotiate(headers, ON_THE_FLY)
+}
+
+// 一次壓縮完整的內容
+pub fn encode(encoding: Encoding, level: Option<u32>, data: &[u8]) -> io::Result<Bytes> {
+    let mut encoder = Encoder::new(encoding, level)?;
+    let mut out = encoder.write(data)?.to_vec();
+    out.extend_from_slice(&encoder.finish()?);
+    Ok(Bytes::from(out))
+}
+
+// 值得壓縮的內容類型；圖片、影片與封存檔本身已經壓縮過
+fn compressible(content_type: &str) -> bool {
+    let mime = content_type.split(';').next().unwrap_or("").trim().to_ascii_lowercase();
+    mime.starts_with("text/")
+        || mime.ends_with("+json")
+        || mime.ends_with("+xml")
+        || matches!(
+            mime.as_str(),
+            "application/json"
+                | "application/javascript"
+                | "application/xml"
+                | "application/wasm"
+                | "image/svg+xml"
+        )
+}
+
+// 逐塊壓縮的編碼器
+enum Encoder {
+    Gzip(GzEncoder<Vec<u8>>),
+    Zstd(zstd::stream::write::Encoder<'static, Vec<u8>>),
+    Brotli(Box<brotli::CompressorWriter<Vec<u8>>>),
+}
+
+impl Encoder {
+    fn new(encoding: Encoding, level: Option<u32>) -> io::Result<Self> {
+        Ok(match encoding {
+            Encoding::Gzip => Encoder::Gzip(GzEncoder::new(
+                Vec::new(),
+                Compression::new(level.unwrap_or(6)),
+            )),
+            Encoding::Zstd => Encoder::Zstd(zstd::stream::write::Encoder::new(
+                Vec::new(),
+                level.unwrap_or(3) as i32,
+            )?),
+            Encoding::Brotli => Encoder::Brotli(Box::new(brotli::CompressorWriter::new(
+                Vec::new(),
+                BROTLI_BUFFER,
+                level.unwrap_or(4),
+                BROTLI_WINDOW,
+            ))),
+        })
+    }
+
+    // 寫入一塊內容，回傳目前已產生的壓縮資料（可能為空）
+    fn write(&mut self, chunk: &[u8]) -> io::Result<Bytes> {
+        let buf = match self {
+            Encoder::Gzip(encoder) => {
+                encoder.write_all(chunk)?;
+                encoder.get_mut()
+            }
+            Encoder::Zstd(encoder) => {
+                encoder.write_all(chunk)?;
+                encoder.get_mut()
+            }
+            Encoder::Brotli(encoder) => {
+                encoder.write_all(chunk)?;
+                encoder.get_mut()
+            }
+        };
+        Ok(Bytes::from(std::mem::take(buf)))
+    }
+
+    fn finish(self) -> io::Result<Bytes> {
+        match self {
+            Encoder::Gzip(encoder) => encoder.finish().map(Bytes::from),
+            Encoder::Zstd(encoder) => encoder.finish().map(Bytes::from),
+            // into_inner 會寫出結尾；寫入 Vec 不會失敗
+            Encoder::Brotli(encoder) => Ok(Bytes::from(encoder.into_inner())),
+        }
+    }
+}
+
+// 邊傳送邊壓縮回應主體，不需要先取得完整內容
+fn compress_body(body: Body, encoder: Encoder) -> Body {
+    let state = Some((body.into_data_stream(), encoder));
+    let stream = stream::unfold(state, |state| async move {
+        let (mut data, mut encoder) = state?;
+        loop {
+            match data.next().await {
+                Some(Ok(chunk)) => match encoder.write(&chunk) {
+                    Ok(out) if out.is_empty() => continue,
+                    Ok(out) => return Some((Ok(out), Some((data, encoder)))),
+                    Err(e) => return Some((Err(e), None)),
+                },
+                Some(Err(e)) => return Some((Err(io::Error::other(e)), None)),
+                None => return Some((encoder.finish(), None)),
+            }
+        }
+    });
+    Body::from_stream(stream)
+}
+
+// 依 Accept-Encoding 即時壓縮可壓縮的回應
+//
+// 只處理 200 回應：部分內容（206）的範圍是以未壓縮的內容計算。已有 Content-Encoding
+// 的回應（例如預先壓縮的清單）維持原樣。用戶端明確拒絕未壓縮的內容時，不論類型與大小
+// 都以可接受的編碼壓縮；沒有可接受的編碼時才回應 406。
+pub async fn compress_responses(
+    State(config): State<ServerConfig>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(options) = config.compress.clone() else {
+        return next.run(request).await;
+    };
+    let accept = AcceptEncoding::from_headers(request.headers());
+    let encoding = accept.choose(ON_THE_FLY);
+    let mut response = next.run(request).await;
+
+    let uncoded = response.status() == StatusCode::OK && !response.headers().contains_key(header::CONTENT_ENCODING);
+    let required = uncoded && !accept.identity_acceptable();
+    if required && encoding.is_none() {
+        let offered: Vec<&str> = ON_THE_FLY.iter().map(|encoding| encoding.name()).collect();
+        let mut response = ServerError::NotAcceptable(offered.join(", ")).into_response();
+        response
+            .headers_mut()
+            .append(header::VARY, HeaderValue::from_static("Accept-Encoding"));
+        return response;
+    }
+
+    let compressible = uncoded
+        && response
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(compressible);
+    if !compressible && !required {
+        return response;
+    }
+
+    let length = response
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok());
+    if length.is_some_and(|len| len < options.min_size) && !required {
+        return response;
+    }
+
+    // 是否壓縮取決於 Accept-Encoding，快取必須分開存放
+    let varies = response
+        .headers()
+        .get_all(header::VARY)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .any(|v| v.trim().eq_ignore_ascii_case("accept-encoding"));
+    if !varies {
+        response
+            .headers_mut()
+            .append(header::VARY, HeaderValue::from_static("Accept-Encoding"));
+    }
+
+    let Some(encoding) = encoding else {
+        return response;
+    };
+    let encoder = match Encoder::new(encoding, options.level) {
+        Ok(encoder) => encoder,
+        Err(e) => {
+            tracing::warn!("無法建立 {} 壓縮器: {}", encoding.name(), e);
+            return response;
+        }
+    };
+
+    let headers = response.headers_mut();
+    headers.insert(header::CONTENT_ENCODING, HeaderValue::from_static(encoding.name()));
+    headers.remove(header::CONTENT_LENGTH);
+    // 範圍是以未壓縮的內容計算，壓縮後的回應不提供範圍請求
+    headers.remove(header::ACCEPT_RANGES);
+    // 壓縮後的內容與原內容位元組不同，改為弱 ETag，If-None-Match 仍可比對
+    if let Some(etag) = headers.get(header::ETAG).and_then(|v| v.to_str().ok()) {
+        if !etag.starts_with("W/") {
+            if let Ok(weak) = HeaderValue::from_str(&format!("W/{}", etag)) {
+                headers.insert(header::ETAG, weak);
+            }
+        }
+    }
+
+    response.map(|body| compress_body(body, encoder))
+}
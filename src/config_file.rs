@@ -0,0 +1,420 @@
+use std::{
+    collections::HashMap,
+    ffi::OsString,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use clap::{ArgAction, Command};
+use globset::Glob;
+use toml::{Table, Value};
+
+use crate::{auth::AccessRules, headers::HeaderRule, vhost::VirtualHost};
+
+// TOML 設定檔
+//
+// 最上層的鍵即命令列參數的長名稱（`port = 8080`、`compress = true`），轉換為參數後放在
+// 命令列參數之前，因此命令列可以覆寫設定檔的值；可重複的參數（如 `webhook`）則是兩邊合併。
+// 無法用單一參數表達的結構寫成表格，例如 `[[acl]]` 存取規則、`[[header]]` 自訂標頭與 `[[vhost]]` 網站。
+// 設定可以用 `include` 分散在多個文件中。
+pub struct ConfigFile {
+    args: Vec<OsString>,
+    // 設定檔中的 `[[acl]]` 規則，命令列指定 `--acl` 時以命令列為準
+    pub acl: Option<AccessRules>,
+    // 設定檔中的 `[[header]]` 自訂標頭，與命令列的 `--add-header` 合併
+    pub headers: Vec<HeaderRule>,
+    // 依 Host 分派的網站，最上層的設定是未符合任何網站時的預設網站
+    pub vhosts: Vec<VirtualHost>,
+    // 讀取過的設定檔與 `include` 樣式所在的目錄，啟用沙箱後仍需可讀取
+    pub sources: Vec<PathBuf>,
+}
+
+impl ConfigFile {
+    // 從原始的命令列參數中找出 `--config <file>` 或 `--config=<file>`
+    pub fn path_in(args: &[OsString]) -> Option<PathBuf> {
+        let mut args = args.iter().skip(1);
+        while let Some(arg) = args.next() {
+            let arg = arg.to_string_lossy();
+            if arg == "--" {
+                break;
+            }
+            if arg == "--config" {
+                return args.next().map(PathBuf::from);
+            }
+            if let Some(path) = arg.strip_prefix("--config=") {
+                return Some(PathBuf::from(path));
+            }
+        }
+        None
+    }
+
+    // 讀取設定檔與其 `include` 的文件
+    //
+    // `include` 是相對於所在文件的路徑或樣式（字串或陣列，萬用字元只能用在文件名稱），
+    // 符合樣式的文件依名稱排序，在所在文件之後依序讀取，被引入的文件也可以再引入其他文件。
+    // 合併方式：可重複的參數與 `[[acl]]`、`[[header]]`、`[[vhost]]` 等表格依讀取順序串接；
+    // 其他參數只能在一個文件中設定，多個文件設定不同的值時視為錯誤，避免結果取決於讀取順序。
+    pub fn load(command: &Command, path: &Path) -> io::Result<Self> {
+        let mut loader = Loader {
+            command,
+            config: ConfigFile {
+                args: Vec::new(),
+                acl: None,
+                headers: Vec::new(),
+                vhosts: Vec::new(),
+                sources: Vec::new(),
+            },
+            acl: None,
+            settings: HashMap::new(),
+            visited: Vec::new(),
+        };
+        loader.load(path)?;
+
+        let mut config = loader.config;
+        let invalid = |reason: String| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), reason));
+        if let Some(entries) = &loader.acl {
+            let entries = entries.iter().map(|(principal, prefix, permissions)| {
+                (principal.as_str(), prefix.as_str(), permissions.as_str())
+            });
+            config.acl = Some(AccessRules::from_entries(entries).map_err(|e| invalid(format!("[[acl]] {}", e)))?);
+        }
+        if config.vhosts.iter().filter(|vhost| vhost.default).count() > 1 {
+            return Err(invalid("只能有一個網站設定 default = true".to_string()));
+        }
+        let mut prefixes: Vec<&str> = config.vhosts.iter().filter_map(|vhost| vhost.prefix.as_deref()).collect();
+        prefixes.sort();
+        if let Some(pair) = prefixes.windows(2).find(|pair| pair[0] == pair[1]) {
+            return Err(invalid(format!("網站前綴 {} 重複", pair[0])));
+        }
+        Ok(config)
+    }
+
+    // 將設定檔的參數插在程式名稱之後、命令列參數之前
+    pub fn merge(&self, args: Vec<OsString>) -> Vec<OsString> {
+        let mut args = args.into_iter();
+        args.next()
+            .into_iter()
+            .chain(self.args.iter().cloned())
+            .chain(args)
+            .collect()
+    }
+}
+
+// 讀取多個設定檔時的狀態
+struct Loader<'a> {
+    command: &'a Command,
+    config: ConfigFile,
+    // 各文件的 `[[acl]]` 規則，全部讀取後才建立
+    acl: Option<Vec<(String, String, String)>>,
+    // 單一值參數的值與設定它的文件，用來找出不同文件之間的衝突
+    settings: HashMap<String, (String, PathBuf)>,
+    // 已讀取的文件，避免重複引入或循環引入
+    visited: Vec<PathBuf>,
+}
+
+impl Loader<'_> {
+    fn load(&mut self, path: &Path) -> io::Result<()> {
+        let invalid = |reason: String| {
+            io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), reason))
+        };
+        let canonical = fs::canonicalize(path).map_err(|e| invalid(e.to_string()))?;
+        if self.visited.contains(&canonical) {
+            return Err(invalid("重複引入".to_string()));
+        }
+        self.visited.push(canonical);
+        self.config.sources.push(path.to_path_buf());
+
+        let table: Table = fs::read_to_string(path)?
+            .parse()
+            .map_err(|e: toml::de::Error| invalid(e.message().to_string()))?;
+
+        let mut includes = Vec::new();
+        for (key, value) in &table {
+            if key == "include" {
+                includes = self.include_paths(path, value).map_err(|e| invalid(format!("include {}", e)))?;
+                continue;
+            }
+            if key == "acl" {
+                let entries = parse_acl(value).map_err(|e| invalid(format!("[[acl]] {}", e)))?;
+                self.acl.get_or_insert_with(Vec::new).extend(entries);
+                continue;
+            }
+            if key == "header" {
+                let headers = parse_headers(value).map_err(|e| invalid(format!("[[header]] {}", e)))?;
+                self.config.headers.extend(headers);
+                continue;
+            }
+            // `[[tenant]]` 與 `[[vhost]]` 相同，兩者可以並用
+            if key == "vhost" || key == "tenant" {
+                let vhosts = parse_vhosts(value).map_err(|e| invalid(format!("[[{}]] {}", key, e)))?;
+                self.config.vhosts.extend(vhosts);
+                continue;
+            }
+            let arg = self
+                .command
+                .get_arguments()
+                .find(|arg| arg.get_long() == Some(key.as_str()) && key != "config")
+                .ok_or_else(|| invalid(format!("未知的設定 {}", key)))?;
+
+            let flag = format!("--{}", key);
+            match (arg.get_action(), value) {
+                (ArgAction::SetTrue, Value::Boolean(enabled)) => {
+                    self.settings_conflict(key, &enabled.to_string(), path).map_err(invalid)?;
+                    if *enabled {
+                        self.config.args.push(flag.into());
+                    }
+                }
+                (ArgAction::SetTrue, _) => return Err(invalid(format!("{} 應為 true 或 false", key))),
+                (ArgAction::Append, Value::Array(items)) => {
+                    for item in items {
+                        let item = scalar(item).ok_or_else(|| invalid(format!("{} 的項目應為字串或數字", key)))?;
+                        self.config.args.push(format!("{}={}", flag, item).into());
+                    }
+                }
+                (ArgAction::Append, value) => {
+                    let value = scalar(value).ok_or_else(|| invalid(format!("{} 應為字串或數字", key)))?;
+                    self.config.args.push(format!("{}={}", flag, value).into());
+                }
+                // 以逗號分隔的清單參數也可以寫成陣列
+                (_, Value::Array(items)) => {
+                    let items = items
+                        .iter()
+                        .map(scalar)
+                        .collect::<Option<Vec<_>>>()
+                        .ok_or_else(|| invalid(format!("{} 的項目應為字串或數字", key)))?;
+                    let value = items.join(",");
+                    self.settings_conflict(key, &value, path).map_err(invalid)?;
+                    self.config.args.push(format!("{}={}", flag, value).into());
+                }
+                (_, value) => {
+                    let value = scalar(value).ok_or_else(|| invalid(format!("{} 應為字串或數字", key)))?;
+                    self.settings_conflict(key, &value, path).map_err(invalid)?;
+                    self.config.args.push(format!("{}={}", flag, value).into());
+                }
+            }
+        }
+
+        for include in includes {
+            self.load(&include)?;
+        }
+        Ok(())
+    }
+
+    // 記錄單一值參數，已在其他文件設定為不同的值時回傳錯誤
+    fn settings_conflict(&mut self, key: &str, value: &str, path: &Path) -> Result<(), String> {
+        match self.settings.get(key) {
+            Some((previous, source)) if previous != value => Err(format!(
+                "{} 已在 {} 設定為 {}，不能再設定為 {}",
+                key,
+                source.display(),
+                previous,
+                value
+            )),
+            Some(_) => Ok(()),
+            None => {
+                self.settings.insert(key.to_string(), (value.to_string(), path.to_path_buf()));
+                Ok(())
+            }
+        }
+    }
+
+    // `include` 的路徑，樣式符合的文件依名稱排序
+    fn include_paths(&mut self, path: &Path, value: &Value) -> Result<Vec<PathBuf>, String> {
+        let patterns = match value {
+            Value::String(s) => vec![s.clone()],
+            Value::Array(items) => items
+                .iter()
+                .map(|item| item.as_str().map(str::to_string))
+                .collect::<Option<Vec<_>>>()
+                .ok_or("的項目應為字串")?,
+            _ => return Err("應為字串或陣列".to_string()),
+        };
+        let dir = path.parent().unwrap_or(Path::new(""));
+        let mut paths = Vec::new();
+        for pattern in patterns {
+            let pattern = dir.join(&pattern);
+            let name = pattern.file_name().and_then(|name| name.to_str()).ok_or("缺少文件名稱")?;
+            if !name.contains(['*', '?', '[', '{']) {
+                paths.push(pattern);
+                continue;
+            }
+            let parent = pattern.parent().unwrap_or(Path::new(""));
+            if parent.to_string_lossy().contains(['*', '?', '[', '{']) {
+                return Err(format!("{}：萬用字元只能用在文件名稱", pattern.display()));
+            }
+            let glob = Glob::new(name).map_err(|e| e.to_string())?.compile_matcher();
+            let mut matched: Vec<PathBuf> = fs::read_dir(parent)
+                .map_err(|e| format!("{}: {}", parent.display(), e))?
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| entry.file_type().is_ok_and(|t| t.is_file()))
+                .filter(|entry| glob.is_match(entry.file_name()))
+                .map(|entry| entry.path())
+                .collect();
+            matched.sort();
+            paths.extend(matched);
+            // 之後新增到目錄中的文件，升級後的新程序也要能讀取
+            self.config.sources.push(parent.to_path_buf());
+        }
+        Ok(paths)
+    }
+}
+
+fn scalar(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Integer(n) => Some(n.to_string()),
+        Value::Float(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+// `[[acl]]` 的每一項為 `principal`、`prefix` 與 `permissions`（字串或陣列）
+fn parse_acl(value: &Value) -> Result<Vec<(String, String, String)>, String> {
+    let entries = value.as_array().ok_or("應為表格陣列")?;
+    let mut rules = Vec::new();
+    for (index, entry) in entries.iter().enumerate() {
+        let field = |name: &str| -> Result<String, String> {
+            match entry.get(name) {
+                Some(Value::String(s)) => Ok(s.clone()),
+                Some(Value::Array(items)) => items
+                    .iter()
+                    .map(|item| item.as_str().map(str::to_string))
+                    .collect::<Option<Vec<_>>>()
+                    .map(|items| items.join(","))
+                    .ok_or_else(|| format!("第 {} 條規則的 {} 應為字串", index + 1, name)),
+                Some(_) => Err(format!("第 {} 條規則的 {} 應為字串", index + 1, name)),
+                None => Err(format!("第 {} 條規則缺少 {}", index + 1, name)),
+            }
+        };
+        rules.push((field("principal")?, field("prefix")?, field("permissions")?));
+    }
+    // 先在所屬文件中檢查，錯誤訊息的規則編號才對得上
+    AccessRules::from_entries(
+        rules
+            .iter()
+            .map(|(principal, prefix, permissions)| (principal.as_str(), prefix.as_str(), permissions.as_str())),
+    )?;
+    Ok(rules)
+}
+
+// `[[header]]` 的每一項為 `name` 與 `value`，可另外以 `prefix` 限定路徑
+fn parse_headers(value: &Value) -> Result<Vec<HeaderRule>, String> {
+    let entries = value.as_array().ok_or("應為表格陣列")?;
+    let mut rules = Vec::new();
+    for (index, entry) in entries.iter().enumerate() {
+        let table = entry.as_table().ok_or_else(|| format!("第 {} 個標頭應為表格", index + 1))?;
+        if let Some(key) = table.keys().find(|key| !["name", "value", "prefix"].contains(&key.as_str())) {
+            return Err(format!("第 {} 個標頭有未知的設定 {}", index + 1, key));
+        }
+        let field = |name: &str| -> Result<Option<&str>, String> {
+            match table.get(name) {
+                Some(Value::String(s)) => Ok(Some(s)),
+                Some(_) => Err(format!("第 {} 個標頭的 {} 應為字串", index + 1, name)),
+                None => Ok(None),
+            }
+        };
+        let name = field("name")?.ok_or_else(|| format!("第 {} 個標頭缺少 name", index + 1))?;
+        let value = field("value")?.ok_or_else(|| format!("第 {} 個標頭缺少 value", index + 1))?;
+        let prefix = field("prefix")?.unwrap_or_default();
+        rules.push(HeaderRule::new(prefix, name, value).map_err(|e| format!("第 {} 個標頭: {}", index + 1, e))?);
+    }
+    Ok(rules)
+}
+
+// 字串或字串陣列，陣列以逗號合併
+fn list(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Array(items) => items
+            .iter()
+            .map(|item| item.as_str().map(str::to_string))
+            .collect::<Option<Vec<_>>>()
+            .map(|items| items.join(",")),
+        _ => None,
+    }
+}
+
+// `[[vhost]]` 的每一項為 `host`（字串或陣列）或 `prefix`，以及 `base`；可另外指定 `index`、
+// `restricted-files`、`users`、`max-upload-size`、`quota`、`rate-limit` 與 `default`
+fn parse_vhosts(value: &Value) -> Result<Vec<VirtualHost>, String> {
+    let entries = value.as_array().ok_or("應為表格陣列")?;
+    let mut vhosts = Vec::new();
+    for (index, entry) in entries.iter().enumerate() {
+        let table = entry.as_table().ok_or_else(|| format!("第 {} 個網站應為表格", index + 1))?;
+        let field = |name: &str| -> Result<Option<String>, String> {
+            table
+                .get(name)
+                .map(|value| list(value).ok_or_else(|| format!("第 {} 個網站的 {} 應為字串", index + 1, name)))
+                .transpose()
+        };
+        let number = |name: &str| -> Result<Option<u64>, String> {
+            match table.get(name) {
+                Some(Value::Integer(n)) if *n > 0 => Ok(Some(*n as u64)),
+                Some(_) => Err(format!("第 {} 個網站的 {} 應為正整數", index + 1, name)),
+                None => Ok(None),
+            }
+        };
+        const KEYS: &[&str] = &[
+            "host",
+            "prefix",
+            "base",
+            "index",
+            "restricted-files",
+            "users",
+            "max-upload-size",
+            "quota",
+            "rate-limit",
+            "default",
+        ];
+        if let Some(key) = table.keys().find(|key| !KEYS.contains(&key.as_str())) {
+            return Err(format!("第 {} 個網站有未知的設定 {}", index + 1, key));
+        }
+
+        let names: Vec<String> = field("host")?
+            .unwrap_or_default()
+            .split(',')
+            .map(|name| name.trim().trim_end_matches('.').to_ascii_lowercase())
+            .filter(|name| !name.is_empty())
+            .collect();
+        let prefix = field("prefix")?.map(|prefix| prefix.trim_matches('/').to_string());
+        match (&prefix, names.is_empty()) {
+            (Some(_), false) => return Err(format!("第 {} 個網站只能指定 host 或 prefix 其中之一", index + 1)),
+            (None, true) => return Err(format!("第 {} 個網站缺少 host 或 prefix", index + 1)),
+            // 前綴是網址的第一層路徑，只允許不需編碼的字元
+            (Some(prefix), _)
+                if prefix.is_empty()
+                    || prefix.starts_with('_')
+                    || !prefix.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+                    || prefix.starts_with('.') =>
+            {
+                return Err(format!("第 {} 個網站的 prefix {} 無效：只能包含英數字、-、_ 與 .，且不能以 _ 或 . 開頭", index + 1, prefix));
+            }
+            _ => {}
+        }
+        let base = field("base")?.ok_or_else(|| format!("第 {} 個網站缺少 base", index + 1))?;
+        let default = match table.get("default") {
+            Some(Value::Boolean(default)) => *default,
+            Some(_) => return Err(format!("第 {} 個網站的 default 應為 true 或 false", index + 1)),
+            None => false,
+        };
+        if default && prefix.is_some() {
+            return Err(format!("第 {} 個網站以 prefix 劃分，不能設定 default", index + 1));
+        }
+
+        vhosts.push(VirtualHost {
+            names,
+            prefix,
+            base: PathBuf::from(base),
+            index: field("index")?,
+            restricted_files: field("restricted-files")?,
+            users: field("users")?.map(PathBuf::from),
+            max_upload_size: number("max-upload-size")?,
+            quota: number("quota")?,
+            rate_limit: number("rate-limit")?
+                .map(|n| u32::try_from(n).map_err(|_| format!("第 {} 個網站的 rate-limit 過大", index + 1)))
+                .transpose()?,
+            default,
+        });
+    }
+    Ok(vhosts)
+}
@@ -0,0 +1,119 @@
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+use crate::ServerConfig;
+
+// 實際回應中讓網頁程式可以讀取的標頭，其餘只有瀏覽器看得到
+const EXPOSE_HEADERS: &str = "Content-Length, Content-Range, Accept-Ranges, ETag, Last-Modified";
+
+// 允許的來源
+pub enum Origins {
+    Any,
+    List(Vec<String>),
+}
+
+// 跨來源資源共用設定（--cors、--cors-methods、--cors-headers、--cors-max-age、--cors-credentials）
+pub struct CorsOptions {
+    pub origins: Origins,
+    pub methods: HeaderValue,
+    pub headers: HeaderValue,
+    pub max_age: u64,
+    pub credentials: bool,
+}
+
+impl CorsOptions {
+    // 以逗號分隔的來源，`*` 代表任何來源；來源需為 `scheme://host[:port]`
+    pub fn parse_origins(list: &str) -> Result<Origins, String> {
+        let origins: Vec<String> = list
+            .split(',')
+            .map(|origin| origin.trim().trim_end_matches('/').to_ascii_lowercase())
+            .filter(|origin| !origin.is_empty())
+            .collect();
+        if origins.iter().any(|origin| origin == "*") {
+            return Ok(Origins::Any);
+        }
+        if let Some(origin) = origins.iter().find(|origin| {
+            !(origin.starts_with("http://") || origin.starts_with("https://")) || origin.matches('/').count() != 2
+        }) {
+            return Err(format!("無效的 CORS 來源 {}，格式為 https://example.com", origin));
+        }
+        if origins.is_empty() {
+            return Err("--cors 至少需要一個來源".to_string());
+        }
+        Ok(Origins::List(origins))
+    }
+
+    // 允許帶憑證時必須列出來源：`*` 加上憑證等於讓任何網站讀取已登入用戶的內容
+    pub fn check_credentials(origins: &Origins, credentials: bool) -> Result<(), String> {
+        match (origins, credentials) {
+            (Origins::Any, true) => Err("--cors-credentials 不能搭配 --cors '*'，請列出允許的來源".to_string()),
+            _ => Ok(()),
+        }
+    }
+
+    // 請求的來源允許時回傳 Access-Control-Allow-Origin 的值
+    fn allow_origin(&self, origin: &HeaderValue) -> Option<HeaderValue> {
+        match &self.origins {
+            Origins::Any => Some(HeaderValue::from_static("*")),
+            Origins::List(origins) => {
+                let origin_str = origin.to_str().ok()?.to_ascii_lowercase();
+                origins.contains(&origin_str).then(|| origin.clone())
+            }
+        }
+    }
+
+    // 回應內容隨 Origin 而不同時，快取需要分開存放
+    fn varies(&self) -> bool {
+        matches!(self.origins, Origins::List(_))
+    }
+}
+
+// 回應預檢請求，並在實際回應加上 CORS 標頭
+//
+// 預檢（帶有 Access-Control-Request-Method 的 OPTIONS）不帶憑證，在驗證與方法限制之前直接回應 204。
+// 來源不在允許清單中時不加任何 CORS 標頭，由瀏覽器拒絕。
+pub async fn handle(State(config): State<ServerConfig>, request: Request, next: Next) -> Response {
+    let Some(cors) = &config.cors else {
+        return next.run(request).await;
+    };
+    let origin = request.headers().get(header::ORIGIN).cloned();
+    let allowed = origin.as_ref().and_then(|origin| cors.allow_origin(origin));
+
+    let preflight = request.method() == Method::OPTIONS
+        && request.headers().contains_key(header::ACCESS_CONTROL_REQUEST_METHOD);
+    let mut response = match (preflight, &allowed) {
+        (true, Some(_)) => {
+            let mut response = StatusCode::NO_CONTENT.into_response();
+            let headers = response.headers_mut();
+            headers.insert(header::ACCESS_CONTROL_ALLOW_METHODS, cors.methods.clone());
+            headers.insert(header::ACCESS_CONTROL_ALLOW_HEADERS, cors.headers.clone());
+            headers.insert(header::ACCESS_CONTROL_MAX_AGE, HeaderValue::from(cors.max_age));
+            response
+        }
+        _ => {
+            let mut response = next.run(request).await;
+            if allowed.is_some() {
+                response
+                    .headers_mut()
+                    .insert(header::ACCESS_CONTROL_EXPOSE_HEADERS, HeaderValue::from_static(EXPOSE_HEADERS));
+            }
+            response
+        }
+    };
+
+    let headers = response.headers_mut();
+    if let Some(allowed) = allowed {
+        headers.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, allowed);
+        if cors.credentials {
+            headers.insert(header::ACCESS_CONTROL_ALLOW_CREDENTIALS, HeaderValue::from_static("true"));
+        }
+    }
+    if cors.varies() {
+        headers.append(header::VARY, HeaderValue::from_static("Origin"));
+    }
+    response
+}
@@ -0,0 +1,146 @@
+use axum::http::{header, HeaderMap, Uri};
+
+use crate::session::cookie_value;
+
+// 存放 CSRF token 的 cookie，不設 HttpOnly 讓頁面腳本可以讀取後放入請求標頭
+pub const COOKIE_NAME: &str = "sfs_csrf";
+
+// 以腳本送出請求時攜帶 token 的標頭
+const HEADER_NAME: &str = "x-csrf-token";
+
+// 表單欄位與查詢參數的名稱
+pub const FIELD_NAME: &str = "csrf_token";
+
+// 產生新的 CSRF token
+pub fn new_token() -> Option<String> {
+    let mut bytes = [0u8; 32];
+    getrandom::fill(&mut bytes).ok()?;
+    Some(bytes.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+// 請求中已有的 CSRF token
+pub fn current_token(headers: &HeaderMap) -> Option<&str> {
+    cookie_value(headers, COOKIE_NAME).filter(|t| t.len() == 64)
+}
+
+// 設定 CSRF token 的 Set-Cookie 標頭值，透過 HTTPS 提供服務時加上 Secure
+pub fn cookie(token: &str, secure: bool) -> String {
+    let secure = if secure { "; Secure" } else { "" };
+    format!("{}={}; Path=/; SameSite=Strict{}", COOKIE_NAME, token, secure)
+}
+
+// 雙重提交驗證：表單欄位、標頭或查詢參數中的 token 必須與 cookie 相同
+//
+// 其他網站無法讀取本站的 cookie，因此無法在偽造的請求中帶上正確的 token。
+pub fn verify(headers: &HeaderMap, uri: &Uri, field: Option<&str>) -> bool {
+    let Some(expected) = current_token(headers) else {
+        return false;
+    };
+
+    let submitted = field
+        .or_else(|| headers.get(HEADER_NAME).and_then(|v| v.to_str().ok()))
+        .or_else(|| {
+            uri.query()?
+                .split('&')
+                .filter_map(|pair| pair.split_once('='))
+                .find(|(key, _)| *key == FIELD_NAME)
+                .map(|(_, value)| value)
+        });
+
+    match submitted {
+        // 固定時間比較，避免以回應時間猜測 token
+        Some(submitted) if submitted.len() == expected.len() => submitted
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0u8, |acc, (a, b)| acc | (a ^ b))
+            == 0,
+        _ => false,
+    }
+}
+
+// 請求是否由其他網站發起
+//
+// 優先使用瀏覽器送出的 Sec-Fetch-Site，舊瀏覽器則比對 Origin 與 Host。
+// 兩者都沒有時視為非瀏覽器用戶端（curl、腳本），不予阻擋。
+pub fn is_cross_site(headers: &HeaderMap) -> bool {
+    if let Some(site) = headers.get("sec-fetch-site").and_then(|v| v.to_str().ok()) {
+        return !matches!(site, "same-origin" | "none");
+    }
+
+    let Some(origin) = headers.get(header::ORIGIN).and_then(|v| v.to_str().ok()) else {
+        return false;
+    };
+    let host = headers.get(header::HOST).and_then(|v| v.to_str().ok());
+    let origin_host = origin
+        .split_once("://")
+        .map(|(_, rest)| rest)
+        .unwrap_or(origin);
+    Some(origin_host) != host
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::HeaderValue;
+
+    use super::*;
+
+    fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.insert(*name, HeaderValue::from_str(value).unwrap());
+        }
+        headers
+    }
+
+    #[test]
+    fn the_submitted_token_must_match_the_cookie() {
+        let token = new_token().unwrap();
+        let other = new_token().unwrap();
+        assert_ne!(token, other);
+        let cookie = format!("{}={}", COOKIE_NAME, token);
+        let uri: Uri = "/upload".parse().unwrap();
+
+        assert!(verify(&headers(&[("cookie", &cookie)]), &uri, Some(&token)));
+        assert!(verify(&headers(&[("cookie", &cookie), (HEADER_NAME, &token)]), &uri, None));
+        let query: Uri = format!("/upload?x=1&{}={}", FIELD_NAME, token).parse().unwrap();
+        assert!(verify(&headers(&[("cookie", &cookie)]), &query, None));
+
+        assert!(!verify(&headers(&[("cookie", &cookie)]), &uri, Some(&other)));
+        assert!(!verify(&headers(&[("cookie", &cookie)]), &uri, Some(&token[..32])));
+        assert!(!verify(&headers(&[("cookie", &cookie)]), &uri, None));
+        // 沒有 cookie 時不論送出什麼都不接受
+        assert!(!verify(&headers(&[(HEADER_NAME, &token)]), &uri, Some(&token)));
+    }
+
+    #[test]
+    fn short_cookies_are_not_tokens() {
+        let cookie = format!("{}=abc", COOKIE_NAME);
+        assert_eq!(current_token(&headers(&[("cookie", &cookie)])), None);
+        let uri: Uri = "/upload".parse().unwrap();
+        assert!(!verify(&headers(&[("cookie", &cookie)]), &uri, Some("abc")));
+    }
+
+    #[test]
+    fn cross_site_requests_are_detected() {
+        assert!(is_cross_site(&headers(&[("sec-fetch-site", "cross-site")])));
+        assert!(is_cross_site(&headers(&[("sec-fetch-site", "same-site")])));
+        assert!(!is_cross_site(&headers(&[("sec-fetch-site", "same-origin")])));
+        assert!(!is_cross_site(&headers(&[("sec-fetch-site", "none")])));
+        // Sec-Fetch-Site 優先於 Origin
+        let fetch = [("sec-fetch-site", "same-origin"), ("origin", "https://evil.example"), ("host", "files.example")];
+        assert!(!is_cross_site(&headers(&fetch)));
+
+        let host = ("host", "files.example");
+        assert!(!is_cross_site(&headers(&[("origin", "https://files.example"), host])));
+        assert!(is_cross_site(&headers(&[("origin", "https://evil.example"), host])));
+        assert!(is_cross_site(&headers(&[("origin", "null"), host])));
+        // 沒有瀏覽器標頭的用戶端不阻擋
+        assert!(!is_cross_site(&headers(&[host])));
+    }
+
+    #[test]
+    fn cookies_are_secure_over_https() {
+        assert_eq!(cookie("t", false), "sfs_csrf=t; Path=/; SameSite=Strict");
+        assert_eq!(cookie("t", true), "sfs_csrf=t; Path=/; SameSite=Strict; Secure");
+    }
+}
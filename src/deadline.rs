@@ -0,0 +1,71 @@
+use std::{
+    convert::Infallible,
+    io,
+    time::{Duration, Instant},
+};
+
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Request, State},
+    http::request::Parts,
+    middleware::Next,
+    response::Response,
+};
+
+use crate::{ServerConfig, ServerError};
+
+// 請求必須完成處理的時間點，未設定 --request-timeout 時沒有期限
+//
+// 處理函式在期限到時直接被取消；在阻塞執行緒中進行的磁碟工作（產生列表、封存檔）
+// 無法從外部中斷，由工作本身定期檢查並提早結束。
+#[derive(Clone, Copy, Default)]
+pub struct Deadline(Option<Instant>);
+
+impl Deadline {
+    pub fn after(timeout: Duration) -> Self {
+        Deadline(Some(Instant::now() + timeout))
+    }
+
+    // 距離期限的時間，沒有期限時為 None
+    pub fn remaining(&self) -> Option<Duration> {
+        self.0.map(|at| at.saturating_duration_since(Instant::now()))
+    }
+
+    pub fn expired(&self) -> bool {
+        self.0.is_some_and(|at| Instant::now() >= at)
+    }
+
+    // 已過期時回傳 `TimedOut` 錯誤，讓阻塞中的工作以 `?` 結束
+    pub fn check(&self) -> io::Result<()> {
+        match self.expired() {
+            true => Err(io::Error::new(io::ErrorKind::TimedOut, "請求處理逾時")),
+            false => Ok(()),
+        }
+    }
+}
+
+// 從請求取得期限，沒有經過 `enforce` 的請求沒有期限
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Deadline {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _: &S) -> Result<Self, Self::Rejection> {
+        Ok(parts.extensions.get::<Deadline>().copied().unwrap_or_default())
+    }
+}
+
+// 為請求設定期限，處理函式超過期限仍未產生回應時回應 503
+pub async fn enforce(
+    State(config): State<ServerConfig>,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, ServerError> {
+    let Some(timeout) = config.request_timeout else {
+        return Ok(next.run(request).await);
+    };
+    request.extensions_mut().insert(Deadline::after(timeout));
+    let path = request.uri().path().to_string();
+    tokio::time::timeout(timeout, next.run(request))
+        .await
+        .map_err(|_| ServerError::Timeout(path))
+}
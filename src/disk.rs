@@ -0,0 +1,163 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use axum::{
+    extract::{Request, State},
+    http::Method,
+    middleware::Next,
+    response::Response,
+};
+use chrono::Utc;
+use serde::Serialize;
+use tracing::{info, warn};
+
+use crate::{
+    webhook::{WebhookEvent, Webhooks},
+    ServerConfig, ServerError,
+};
+
+// 檢查間隔
+const CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+// 基礎目錄所在磁碟區的空間與 inode 狀態
+#[derive(Serialize, Clone)]
+pub struct DiskStatus {
+    pub total_bytes: u64,
+    pub free_bytes: u64,
+    pub total_inodes: u64,
+    pub free_inodes: u64,
+    pub low: bool,
+    pub checked: String,
+}
+
+// 定期檢查磁碟剩餘空間與 inode，低於門檻時記錄警告並發送通知
+//
+// 上傳等寫入功能以 `is_low` 判斷是否拒絕寫入，避免磁碟被寫滿。
+pub struct DiskMonitor {
+    base: PathBuf,
+    min_free_bytes: Option<u64>,
+    min_free_inodes: Option<u64>,
+    status: RwLock<Option<DiskStatus>>,
+}
+
+impl DiskMonitor {
+    pub fn new(base: &Path, min_free_bytes: Option<u64>, min_free_inodes: Option<u64>) -> Self {
+        DiskMonitor {
+            base: base.to_path_buf(),
+            min_free_bytes,
+            min_free_inodes,
+            status: RwLock::new(None),
+        }
+    }
+
+    // 最近一次的檢查結果
+    pub fn status(&self) -> Option<DiskStatus> {
+        self.status.read().unwrap().clone()
+    }
+
+    // 剩餘空間或 inode 是否低於門檻
+    pub fn is_low(&self) -> bool {
+        self.status.read().unwrap().as_ref().is_some_and(|s| s.low)
+    }
+
+    // 立即檢查一次，回傳是否剛進入低於門檻的狀態
+    pub fn check(&self) -> io::Result<bool> {
+        let (total_bytes, free_bytes, total_inodes, free_inodes) = statvfs(&self.base)?;
+        let low = self.min_free_bytes.is_some_and(|min| free_bytes < min)
+            || self.min_free_inodes.is_some_and(|min| total_inodes > 0 && free_inodes < min);
+
+        let was_low = self.is_low();
+        *self.status.write().unwrap() = Some(DiskStatus {
+            total_bytes,
+            free_bytes,
+            total_inodes,
+            free_inodes,
+            low,
+            checked: Utc::now().to_rfc3339(),
+        });
+
+        if low && !was_low {
+            warn!(
+                "磁碟空間不足: 剩餘 {} 位元組、{} 個 inode，暫停接受寫入",
+                free_bytes, free_inodes
+            );
+        } else if !low && was_low {
+            info!("磁碟空間已恢復: 剩餘 {} 位元組、{} 個 inode", free_bytes, free_inodes);
+        }
+        Ok(low && !was_low)
+    }
+
+    // 在背景定期檢查
+    pub fn spawn(self: Arc<Self>, webhooks: Option<Arc<Webhooks>>) {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(CHECK_INTERVAL);
+            loop {
+                interval.tick().await;
+                let monitor = self.clone();
+                match tokio::task::spawn_blocking(move || monitor.check()).await {
+                    Ok(Ok(true)) => {
+                        if let (Some(webhooks), Some(status)) = (&webhooks, self.status()) {
+                            webhooks.notify(WebhookEvent::DiskLow {
+                                path: self.base.to_string_lossy().to_string(),
+                                bytes: status.free_bytes,
+                            });
+                        }
+                    }
+                    Ok(Ok(false)) => {}
+                    Ok(Err(e)) => warn!("無法取得磁碟空間: {}", e),
+                    Err(e) => warn!("無法取得磁碟空間: {}", e),
+                }
+            }
+        });
+    }
+}
+
+// 會寫入基礎目錄的請求；`/_` 開頭的內部路由（登入、管理 API）不受影響
+pub fn writes_files(request: &Request) -> bool {
+    let method = request.method();
+    let write = matches!(method.as_str(), "PUT" | "PATCH" | "MKCOL" | "COPY" | "MOVE");
+    write || (method == Method::POST && !request.uri().path().starts_with("/_"))
+}
+
+// 磁碟空間低於門檻時拒絕寫入請求
+pub async fn refuse_writes_when_low(
+    State(config): State<ServerConfig>,
+    request: Request,
+    next: Next,
+) -> Result<Response, ServerError> {
+    if writes_files(&request) && config.disk.is_low() {
+        return Err(ServerError::InsufficientStorage);
+    }
+    Ok(next.run(request).await)
+}
+
+// 回傳（總位元組、可用位元組、總 inode、可用 inode）
+#[cfg(unix)]
+#[allow(clippy::unnecessary_cast)]
+fn statvfs(path: &Path) -> io::Result<(u64, u64, u64, u64)> {
+    use std::{ffi::CString, os::unix::ffi::OsStrExt};
+
+    let c_path = CString::new(path.as_os_str().as_bytes())?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    // 欄位型別依平台而異，統一轉成 u64；可用空間使用非 root 用戶可用的 f_bavail
+    let block = stat.f_frsize as u64;
+    Ok((
+        stat.f_blocks as u64 * block,
+        stat.f_bavail as u64 * block,
+        stat.f_files as u64,
+        stat.f_favail as u64,
+    ))
+}
+
+#[cfg(not(unix))]
+fn statvfs(_path: &Path) -> io::Result<(u64, u64, u64, u64)> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "此平台不支援磁碟空間檢查"))
+}
@@ -0,0 +1,90 @@
+use std::{collections::HashMap, fs, io, path::Path};
+
+use serde::Serialize;
+
+use crate::{manifest::hash_file, tree::walk_files};
+
+// 內容相同的一組文件
+#[derive(Serialize)]
+pub struct DuplicateGroup {
+    pub sha256: String,
+    // 單一文件的大小
+    pub size: u64,
+    // 只保留一份時可省下的空間
+    pub wasted: u64,
+    pub paths: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct DuplicateReport {
+    // 掃描的文件數
+    pub files: usize,
+    pub wasted: u64,
+    // 依浪費的空間由大到小排列
+    pub groups: Vec<DuplicateGroup>,
+}
+
+// 掃描目錄樹，找出內容相同的文件
+//
+// 只有大小相同的文件才需要計算雜湊；同一個文件的硬連結不占額外空間，視為一份。
+// 小於 `min_size` 的文件不列入，空文件一律略過。
+pub fn scan(base: &Path, min_size: u64, allow: &dyn Fn(&str) -> bool) -> io::Result<DuplicateReport> {
+    let mut files = Vec::new();
+    walk_files(base, "", allow, &mut files)?;
+    let count = files.len();
+
+    let mut by_size: HashMap<u64, Vec<(String, fs::Metadata)>> = HashMap::new();
+    for (rel, _, metadata) in files {
+        if metadata.len() > 0 && metadata.len() >= min_size {
+            by_size.entry(metadata.len()).or_default().push((rel, metadata));
+        }
+    }
+
+    let mut groups = Vec::new();
+    for (size, candidates) in by_size.into_iter().filter(|(_, c)| c.len() > 1) {
+        let mut by_hash: HashMap<String, Vec<String>> = HashMap::new();
+        let mut seen = Vec::new();
+        for (rel, metadata) in candidates {
+            if let Some(inode) = inode(&metadata) {
+                if seen.contains(&inode) {
+                    continue;
+                }
+                seen.push(inode);
+            }
+            let hash = match hash_file(&base.join(&rel)) {
+                Ok(hash) => hash,
+                // 掃描期間被刪除的文件直接略過
+                Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e),
+            };
+            by_hash.entry(hash).or_default().push(rel);
+        }
+        for (sha256, mut paths) in by_hash.into_iter().filter(|(_, p)| p.len() > 1) {
+            paths.sort();
+            groups.push(DuplicateGroup {
+                sha256,
+                size,
+                wasted: size * (paths.len() as u64 - 1),
+                paths,
+            });
+        }
+    }
+    groups.sort_by(|a, b| b.wasted.cmp(&a.wasted).then_with(|| a.paths.cmp(&b.paths)));
+
+    Ok(DuplicateReport {
+        files: count,
+        wasted: groups.iter().map(|g| g.wasted).sum(),
+        groups,
+    })
+}
+
+#[cfg(unix)]
+fn inode(metadata: &fs::Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn inode(_: &fs::Metadata) -> Option<(u64, u64)> {
+    None
+}
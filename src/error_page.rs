@@ -0,0 +1,80 @@
+use std::{collections::HashMap, io, path::Path};
+
+use axum::{
+    body::Body,
+    extract::{Request, State},
+    http::{header, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+use tracing::warn;
+
+use crate::{problem::Problem, ServerConfig};
+
+// 自訂的錯誤頁面，對應到基礎路徑底下的 HTML 文件
+#[derive(Default)]
+pub struct ErrorPages {
+    pages: HashMap<u16, String>,
+    // `*=` 指定的頁面，用於沒有個別設定的狀態碼
+    fallback: Option<String>,
+}
+
+impl ErrorPages {
+    // 加入一筆 `<狀態碼>=<路徑>` 或 `*=<路徑>` 設定，路徑相對於基礎路徑
+    pub fn add(&mut self, spec: &str) -> io::Result<()> {
+        let invalid = |reason: String| io::Error::new(io::ErrorKind::InvalidInput, reason);
+        let (status, path) = spec
+            .split_once('=')
+            .ok_or_else(|| invalid(format!("錯誤頁面設定 {} 應為 <狀態碼>=<路徑>", spec)))?;
+        let path = path.trim().trim_start_matches('/').to_string();
+        if path.is_empty() || path.split('/').any(|c| c == ".." || c == ".") {
+            return Err(invalid(format!("錯誤頁面路徑 {} 無效", path)));
+        }
+        match status.trim() {
+            "*" => self.fallback = Some(path),
+            status => {
+                let status = status
+                    .parse::<u16>()
+                    .ok()
+                    .filter(|s| (400..600).contains(s))
+                    .ok_or_else(|| invalid(format!("錯誤頁面的狀態碼 {} 應介於 400 與 599", status)))?;
+                self.pages.insert(status, path);
+            }
+        }
+        Ok(())
+    }
+
+    fn page(&self, status: u16) -> Option<&str> {
+        self.pages.get(&status).or(self.fallback.as_ref()).map(String::as_str)
+    }
+}
+
+// 把錯誤回應的內建 HTML 換成自訂頁面，保留狀態碼與其他標頭
+//
+// 只處理帶有 Problem 的錯誤回應；API 用戶端之後仍由 `problem::render` 改寫為 JSON。
+// 頁面文件不存在或無法讀取時沿用內建的輸出。
+pub async fn render(State(config): State<ServerConfig>, request: Request, next: Next) -> Response {
+    let response = next.run(request).await;
+    let Some(pages) = &config.error_pages else {
+        return response;
+    };
+    let Some(problem) = response.extensions().get::<Problem>() else {
+        return response;
+    };
+    let Some(page) = pages.page(problem.status.as_u16()) else {
+        return response;
+    };
+
+    let file = Path::new(config.base_path.as_str()).join(page);
+    let body = match tokio::fs::read(&file).await {
+        Ok(body) => body,
+        Err(e) => {
+            warn!("無法讀取錯誤頁面 {}: {}", file.display(), e);
+            return response;
+        }
+    };
+    let (mut parts, _) = response.into_parts();
+    parts.headers.insert(header::CONTENT_TYPE, HeaderValue::from_static("text/html; charset=utf-8"));
+    parts.headers.remove(header::CONTENT_LENGTH);
+    Response::from_parts(parts, Body::from(body))
+}
@@ -0,0 +1,133 @@
+use axum::{
+    extract::{Request, State},
+    http::{header::HeaderName, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+use percent_encoding::percent_decode_str;
+
+use crate::{restrict::normalize, ServerConfig};
+
+// 自訂回應標頭的一條規則
+pub struct HeaderRule {
+    // 正規化後的路徑前綴，空字串代表所有路徑
+    prefix: String,
+    name: HeaderName,
+    value: String,
+}
+
+impl HeaderRule {
+    pub fn new(prefix: &str, name: &str, value: &str) -> Result<Self, String> {
+        let name = HeaderName::from_bytes(name.trim().as_bytes()).map_err(|_| format!("無效的標頭名稱 {}", name))?;
+        // 佔位符換成任何值之前就必須是有效的標頭值
+        HeaderValue::from_str(value).map_err(|_| format!("標頭 {} 的值含有無效的字元", name))?;
+        Ok(HeaderRule {
+            prefix: normalize(prefix),
+            name,
+            value: value.to_string(),
+        })
+    }
+
+    // `--add-header` 的 `名稱: 值`，套用到所有路徑
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let (name, value) = line
+            .split_once(':')
+            .ok_or_else(|| format!("無效的 --add-header: {}，格式為 名稱: 值", line))?;
+        HeaderRule::new("", name, value.trim())
+    }
+
+    fn matches(&self, path: &str) -> bool {
+        self.prefix.is_empty() || path == self.prefix || path.starts_with(&format!("{}/", self.prefix))
+    }
+}
+
+// `--secure-headers` 的預設標頭，排在自訂標頭之前，可由同名的自訂標頭覆寫或以空值移除
+//
+// Strict-Transport-Security 只在以 TLS 提供服務時加上，避免在純 HTTP 的部署中誤設。
+pub fn secure_defaults(tls: bool, content_security_policy: Option<&str>) -> Result<Vec<HeaderRule>, String> {
+    let mut rules = vec![
+        HeaderRule::new("", "X-Content-Type-Options", "nosniff")?,
+        HeaderRule::new("", "X-Frame-Options", "SAMEORIGIN")?,
+        HeaderRule::new("", "Referrer-Policy", "strict-origin-when-cross-origin")?,
+    ];
+    if tls {
+        rules.push(HeaderRule::new("", "Strict-Transport-Security", "max-age=31536000")?);
+    }
+    if let Some(policy) = content_security_policy {
+        rules.push(HeaderRule::new("", "Content-Security-Policy", policy)?);
+    }
+    Ok(rules)
+}
+
+// 加在回應上的自訂標頭（`--add-header`、設定檔的 `[[header]]` 與 `--secure-headers`）
+//
+// 值可以使用 `{path}`、`{query}`、`{host}` 與 `{method}` 佔位符，代入請求的網址路徑（維持編碼）、
+// 查詢字串、Host 與方法。同一個標頭有多條規則符合時，前綴最長的規則生效，前綴相同時後面的規則生效；
+// 自訂標頭會取代處理函式產生的同名標頭，值為空字串時則移除該標頭。
+#[derive(Default)]
+pub struct CustomHeaders {
+    rules: Vec<HeaderRule>,
+}
+
+impl CustomHeaders {
+    pub fn new(mut rules: Vec<HeaderRule>) -> Self {
+        // 由短到長套用，較長的前綴覆寫較短的
+        rules.sort_by_key(|rule| rule.prefix.len());
+        CustomHeaders { rules }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+}
+
+// 請求中可代入佔位符的部分
+struct Placeholders {
+    path: String,
+    query: String,
+    host: String,
+    method: String,
+}
+
+impl Placeholders {
+    fn render(&self, value: &str) -> String {
+        value
+            .replace("{path}", &self.path)
+            .replace("{query}", &self.query)
+            .replace("{host}", &self.host)
+            .replace("{method}", &self.method)
+    }
+}
+
+pub async fn add(State(config): State<ServerConfig>, request: Request, next: Next) -> Response {
+    if config.custom_headers.is_empty() {
+        return next.run(request).await;
+    }
+    let decoded = percent_decode_str(request.uri().path()).decode_utf8_lossy();
+    let path = normalize(&decoded);
+    let placeholders = Placeholders {
+        path: request.uri().path().to_string(),
+        query: request.uri().query().unwrap_or_default().to_string(),
+        host: request
+            .headers()
+            .get(axum::http::header::HOST)
+            .and_then(|v| v.to_str().ok())
+            .or_else(|| request.uri().host())
+            .unwrap_or_default()
+            .to_string(),
+        method: request.method().to_string(),
+    };
+
+    let mut response = next.run(request).await;
+    for rule in config.custom_headers.rules.iter().filter(|rule| rule.matches(&path)) {
+        if rule.value.is_empty() {
+            response.headers_mut().remove(&rule.name);
+            continue;
+        }
+        // 代入後含有無效字元（例如 Host 中的控制字元）時略過
+        if let Ok(value) = HeaderValue::from_str(&placeholders.render(&rule.value)) {
+            response.headers_mut().insert(rule.name.clone(), value);
+        }
+    }
+    response
+}
@@ -0,0 +1,238 @@
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, Read},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS};
+use tracing::warn;
+
+// 只讀取 HTML 開頭的這麼多位元組，關鍵資源應該都在 <head> 中
+const SCAN_BYTES: u64 = 64 * 1024;
+
+// 每個頁面最多送出的提示數
+const MAX_LINKS: usize = 16;
+
+// 快取的頁面數上限，超過時整個清空
+const MAX_CACHED: usize = 1024;
+
+// 手動指定提示的附屬文件副檔名，例如 `index.html.links`
+pub const SIDECAR_SUFFIX: &str = ".links";
+
+// 放進 Link 標頭前需要編碼的字元
+const LINK_ENCODE: &AsciiSet = &CONTROLS.add(b' ').add(b'<').add(b'>').add(b'"');
+
+struct Cached {
+    modified: Option<SystemTime>,
+    sidecar_modified: Option<SystemTime>,
+    links: Option<Arc<String>>,
+}
+
+// HTML 頁面的預先載入提示（--preload-hints）
+//
+// 從頁面的 <head> 找出樣式表、腳本與已宣告的 preload，以 `Link: rel=preload` 標頭送出，
+// 瀏覽器不必等讀到 HTML 才開始下載。同目錄有 `<頁面>.links` 時改用其中的設定，每行一個
+// Link 值（`</app.css>; rel=preload; as=style`）或只寫網址。解析結果依修改時間快取。
+#[derive(Default)]
+pub struct PreloadHints {
+    cache: Mutex<HashMap<PathBuf, Cached>>,
+}
+
+impl PreloadHints {
+    // 頁面的 Link 標頭值，沒有任何提示時為 None
+    pub fn links(&self, page: &Path) -> Option<Arc<String>> {
+        let modified = fs::metadata(page).and_then(|m| m.modified()).ok();
+        let sidecar = sidecar_path(page);
+        let sidecar_modified = fs::metadata(&sidecar).and_then(|m| m.modified()).ok();
+        if let Some(cached) = self.cache.lock().unwrap().get(page) {
+            if cached.modified == modified && cached.sidecar_modified == sidecar_modified {
+                return cached.links.clone();
+            }
+        }
+
+        let links = match sidecar_modified {
+            Some(_) => read_sidecar(&sidecar),
+            None => scan_page(page),
+        };
+        let links = match links {
+            Ok(links) if links.is_empty() => None,
+            Ok(links) => Some(Arc::new(links.join(", "))),
+            Err(e) => {
+                warn!("無法讀取 {} 的預先載入提示: {}", page.display(), e);
+                None
+            }
+        };
+
+        let mut cache = self.cache.lock().unwrap();
+        if cache.len() >= MAX_CACHED {
+            cache.clear();
+        }
+        cache.insert(
+            page.to_path_buf(),
+            Cached {
+                modified,
+                sidecar_modified,
+                links: links.clone(),
+            },
+        );
+        links
+    }
+}
+
+fn sidecar_path(page: &Path) -> PathBuf {
+    let mut name = page.as_os_str().to_os_string();
+    name.push(SIDECAR_SUFFIX);
+    PathBuf::from(name)
+}
+
+fn read_sidecar(path: &Path) -> io::Result<Vec<String>> {
+    Ok(fs::read_to_string(path)?
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| match line.starts_with('<') {
+            true => Some(line.to_string()),
+            false => link(line, Some(destination(line)), None),
+        })
+        .take(MAX_LINKS)
+        .collect())
+}
+
+// 依副檔名推測 preload 的 `as`
+fn destination(url: &str) -> &'static str {
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+    match path.rsplit('.').next().map(str::to_ascii_lowercase).as_deref() {
+        Some("css") => "style",
+        Some("js" | "mjs") => "script",
+        Some("woff" | "woff2" | "ttf" | "otf") => "font",
+        Some("png" | "jpg" | "jpeg" | "gif" | "webp" | "avif" | "svg" | "ico") => "image",
+        _ => "fetch",
+    }
+}
+
+// 組成一個 Link 值；其他網站的資源需要 CORS 設定，不在此提示
+fn link(url: &str, as_: Option<&str>, rel: Option<&str>) -> Option<String> {
+    let url = url.trim();
+    let lower = url.to_ascii_lowercase();
+    if url.is_empty() || url.starts_with("//") || lower.starts_with("data:") || lower.contains("://") {
+        return None;
+    }
+    let url = utf8_percent_encode(url, LINK_ENCODE);
+    let mut value = format!("<{}>; rel={}", url, rel.unwrap_or("preload"));
+    if let Some(as_) = as_ {
+        value.push_str(&format!("; as={}", as_));
+        // 字型一律以 CORS 模式載入，提示也必須標示，否則會下載兩次
+        if as_ == "font" {
+            value.push_str("; crossorigin");
+        }
+    }
+    Some(value)
+}
+
+// 掃描 <head> 中的 <link> 與 <script>，遇到 </head> 或 <body> 即停止
+fn scan_page(page: &Path) -> io::Result<Vec<String>> {
+    let mut head = Vec::new();
+    fs::File::open(page)?.take(SCAN_BYTES).read_to_end(&mut head)?;
+    let html = String::from_utf8_lossy(&head);
+    let lower = html.to_ascii_lowercase();
+
+    let mut links = Vec::new();
+    let mut pos = 0;
+    while let Some(start) = lower[pos..].find('<').map(|i| pos + i) {
+        let rest = &lower[start + 1..];
+        if rest.starts_with("/head") || rest.starts_with("body") {
+            break;
+        }
+        let Some(end) = lower[start..].find('>').map(|i| start + i) else {
+            break;
+        };
+        pos = end + 1;
+        let tag = &html[start + 1..end];
+        let name = tag.split(|c: char| c.is_ascii_whitespace() || c == '/').next().unwrap_or("");
+        let attrs = attributes(&tag[name.len()..]);
+        let attr = |name: &str| attrs.iter().find(|(n, _)| n == name).map(|(_, v)| v.as_str());
+
+        let hint = match name.to_ascii_lowercase().as_str() {
+            "link" => {
+                let rel = attr("rel").unwrap_or("").to_ascii_lowercase();
+                let href = attr("href").unwrap_or("");
+                match rel.split_ascii_whitespace().find(|r| matches!(*r, "stylesheet" | "preload" | "modulepreload")) {
+                    Some("stylesheet") => link(href, Some("style"), None),
+                    Some("preload") => link(href, Some(attr("as").unwrap_or_else(|| destination(href))), None),
+                    Some("modulepreload") => link(href, None, Some("modulepreload")),
+                    _ => None,
+                }
+            }
+            "script" if attr("nomodule").is_none() => match attr("src") {
+                Some(src) if attr("type").is_some_and(|t| t.eq_ignore_ascii_case("module")) => {
+                    link(src, None, Some("modulepreload"))
+                }
+                Some(src) => link(src, Some("script"), None),
+                None => None,
+            },
+            _ => None,
+        };
+        if let Some(hint) = hint.filter(|hint| !links.contains(hint)) {
+            links.push(hint);
+            if links.len() >= MAX_LINKS {
+                break;
+            }
+        }
+    }
+    Ok(links)
+}
+
+// 解析標籤中的屬性，名稱轉為小寫；沒有值的屬性值為空字串
+fn attributes(s: &str) -> Vec<(String, String)> {
+    let s = s.trim_end_matches('/');
+    let mut attrs = Vec::new();
+    let mut chars = s.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        if c.is_ascii_whitespace() {
+            continue;
+        }
+        let mut name_end = start + c.len_utf8();
+        while let Some(&(i, c)) = chars.peek() {
+            if c.is_ascii_whitespace() || c == '=' {
+                break;
+            }
+            name_end = i + c.len_utf8();
+            chars.next();
+        }
+        let name = s[start..name_end].to_ascii_lowercase();
+        while chars.peek().is_some_and(|&(_, c)| c.is_ascii_whitespace()) {
+            chars.next();
+        }
+        if chars.peek().is_none_or(|&(_, c)| c != '=') {
+            attrs.push((name, String::new()));
+            continue;
+        }
+        chars.next();
+        while chars.peek().is_some_and(|&(_, c)| c.is_ascii_whitespace()) {
+            chars.next();
+        }
+        let value = match chars.peek().copied() {
+            Some((i, quote @ ('"' | '\''))) => {
+                chars.next();
+                let end = s[i + 1..].find(quote).map_or(s.len(), |j| i + 1 + j);
+                while chars.peek().is_some_and(|&(j, _)| j <= end) {
+                    chars.next();
+                }
+                &s[i + 1..end]
+            }
+            Some((i, _)) => {
+                let end = s[i..].find(|c: char| c.is_ascii_whitespace()).map_or(s.len(), |j| i + j);
+                while chars.peek().is_some_and(|&(j, _)| j < end) {
+                    chars.next();
+                }
+                &s[i..end]
+            }
+            None => "",
+        };
+        attrs.push((name, value.to_string()));
+    }
+    attrs
+}
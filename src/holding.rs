@@ -0,0 +1,276 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::{auth::User, ServerError};
+
+// 審核中的上傳（--moderate-uploads）所在的目錄名稱，位於基礎路徑的最上層
+pub const QUARANTINE_DIR: &str = ".sfs-quarantine";
+
+// WebDAV 刪除的文件（--trash-days）所在的目錄名稱，位於基礎路徑的最上層
+pub const TRASH_DIR: &str = ".trash";
+
+// 保管區保留的名稱，不論其他設定一律禁止訪問
+pub fn is_reserved(rel: &str) -> bool {
+    matches!(rel.split('/').next(), Some(QUARANTINE_DIR | TRASH_DIR))
+}
+
+// 用戶看到的相對路徑換成最上層基礎路徑中的相對路徑，保管區與還原都以最上層為準
+pub fn tree_rel(user: Option<&User>, rel: &str) -> String {
+    match user.map(|user| user.root.as_str()).filter(|root| !root.is_empty()) {
+        Some(root) => format!("{}/{}", root, rel),
+        None => rel.to_string(),
+    }
+}
+
+// 保管區中的一個項目
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Item {
+    pub id: String,
+    // 原本（或預定）在基礎路徑中的相對路徑
+    pub path: String,
+    // 移入保管區的時間（RFC 3339）
+    pub time: String,
+    // 上傳或刪除的用戶，未登入時為 None
+    pub user: Option<String>,
+    pub size: u64,
+}
+
+// 暫時移出目錄樹的文件
+//
+// 每個項目以隨機編號命名，內容為 `<id>`（文件或整個目錄），紀錄為 `<id>.json`。保管區與
+// 基礎路徑在同一個文件系統中，移入與放回都只是 rename；放回時不會取代已存在的文件。
+// 有保留期限時，超過期限的項目由 spawn_expiry 定期刪除。
+pub struct Holding {
+    dir: PathBuf,
+    retention: Option<Duration>,
+}
+
+impl Holding {
+    pub fn open(dir: &Path, retention: Option<Duration>) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+        Ok(Holding {
+            dir: dir.to_path_buf(),
+            retention,
+        })
+    }
+
+    pub fn retention(&self) -> Option<Duration> {
+        self.retention
+    }
+
+    // 把 `source` 移入保管區，`path` 是它原本（或預定）的相對路徑
+    pub fn keep(&self, source: &Path, path: &str, user: Option<&str>) -> io::Result<Item> {
+        let mut random = [0u8; 8];
+        getrandom::fill(&mut random).map_err(io::Error::other)?;
+        let item = Item {
+            id: random.iter().map(|b| format!("{:02x}", b)).collect(),
+            path: path.to_string(),
+            time: Utc::now().to_rfc3339(),
+            user: user.map(str::to_string),
+            size: size(source)?,
+        };
+        fs::write(self.record(&item.id), serde_json::to_vec(&item).map_err(io::Error::other)?)?;
+        fs::rename(source, self.dir.join(&item.id)).inspect_err(|_| {
+            let _ = fs::remove_file(self.record(&item.id));
+        })?;
+        Ok(item)
+    }
+
+    // 所有項目，新的在前
+    pub fn list(&self) -> io::Result<Vec<Item>> {
+        let mut items = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let name = entry?.file_name();
+            let Some(id) = name.to_str().and_then(|name| name.strip_suffix(".json")) else {
+                continue;
+            };
+            if let Ok(item) = self.load(id) {
+                items.push(item);
+            }
+        }
+        items.sort_by(|a, b| b.time.cmp(&a.time));
+        Ok(items)
+    }
+
+    // 放回基礎路徑中原本的位置，上層目錄不存在時建立；該位置已有文件時回應 409
+    pub fn restore(&self, id: &str, base: &Path) -> Result<Item, ServerError> {
+        let item = self.load(id)?;
+        let target = base.join(&item.path);
+        if fs::symlink_metadata(&target).is_ok() {
+            return Err(ServerError::Conflict(format!("/{} 已存在", item.path)));
+        }
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::rename(self.dir.join(id), &target)?;
+        let _ = fs::remove_file(self.record(id));
+        Ok(item)
+    }
+
+    // 永久刪除
+    pub fn discard(&self, id: &str) -> Result<Item, ServerError> {
+        let item = self.load(id)?;
+        let content = self.dir.join(id);
+        match fs::symlink_metadata(&content) {
+            Ok(metadata) if metadata.is_dir() => fs::remove_dir_all(&content)?,
+            Ok(_) => fs::remove_file(&content)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+        fs::remove_file(self.record(id))?;
+        Ok(item)
+    }
+
+    // 讀取紀錄；編號只能是 keep 產生的十六進位字串，不會指到保管區以外
+    fn load(&self, id: &str) -> Result<Item, ServerError> {
+        let not_found = || ServerError::NotFound(id.to_string());
+        if id.is_empty() || !id.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(not_found());
+        }
+        let data = fs::read(self.record(id)).map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => not_found(),
+            _ => ServerError::Filesystem(e),
+        })?;
+        serde_json::from_slice(&data).map_err(|e| ServerError::Internal(e.to_string()))
+    }
+
+    fn record(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", id))
+    }
+
+    // 刪除超過保留期限的項目，回傳刪除的數量
+    pub fn expire(&self) -> io::Result<usize> {
+        let Some(retention) = self.retention else {
+            return Ok(0);
+        };
+        let expired = |item: &Item| {
+            DateTime::parse_from_rfc3339(&item.time)
+                .is_ok_and(|time| Utc::now().signed_duration_since(time).to_std().is_ok_and(|age| age > retention))
+        };
+        let mut count = 0;
+        for item in self.list()?.iter().filter(|item| expired(item)) {
+            if self.discard(&item.id).is_ok() {
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+
+    // 每小時刪除一次超過保留期限的項目
+    pub fn spawn_expiry(self: Arc<Self>) {
+        if self.retention.is_none() {
+            return;
+        }
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(3600));
+            loop {
+                interval.tick().await;
+                let holding = self.clone();
+                match tokio::task::spawn_blocking(move || holding.expire()).await {
+                    Ok(Ok(0)) => {}
+                    Ok(Ok(count)) => info!("已刪除 {} 個超過保留期限的項目: {}", count, self.dir.display()),
+                    Ok(Err(e)) => warn!("無法清理 {}: {}", self.dir.display(), e),
+                    Err(e) => warn!("無法清理 {}: {}", self.dir.display(), e),
+                }
+            }
+        });
+    }
+}
+
+// 文件或整個目錄的大小，不跟隨符號連結
+fn size(path: &Path) -> io::Result<u64> {
+    let metadata = fs::symlink_metadata(path)?;
+    if !metadata.is_dir() {
+        return Ok(metadata.len());
+    }
+    let mut total = 0;
+    for entry in fs::read_dir(path)? {
+        total += size(&entry?.path())?;
+    }
+    Ok(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("sfs-holding-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn items_are_kept_and_restored_without_replacing_files() {
+        let base = temp_dir("restore");
+        let holding = Holding::open(&base.join(QUARANTINE_DIR), None).unwrap();
+        fs::write(base.join("upload"), "held").unwrap();
+
+        let item = holding.keep(&base.join("upload"), "docs/a.txt", Some("alice")).unwrap();
+        assert_eq!((item.size, item.user.as_deref()), (4, Some("alice")));
+        assert_eq!(holding.list().unwrap().len(), 1);
+
+        // 原本的位置已被占用時不取代
+        fs::create_dir_all(base.join("docs")).unwrap();
+        fs::write(base.join("docs/a.txt"), "other").unwrap();
+        assert!(matches!(holding.restore(&item.id, &base), Err(ServerError::Conflict(_))));
+        fs::remove_file(base.join("docs/a.txt")).unwrap();
+
+        holding.restore(&item.id, &base).unwrap();
+        assert_eq!(fs::read_to_string(base.join("docs/a.txt")).unwrap(), "held");
+        assert!(holding.list().unwrap().is_empty());
+        fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn ids_cannot_leave_the_holding_area() {
+        let base = temp_dir("ids");
+        let holding = Holding::open(&base.join(QUARANTINE_DIR), None).unwrap();
+        fs::write(base.join("x.json"), "{}").unwrap();
+        for id in ["", "../x", "..", "x"] {
+            assert!(matches!(holding.discard(id), Err(ServerError::NotFound(_))), "{}", id);
+        }
+        assert!(base.join("x.json").exists());
+        fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn expired_items_are_discarded() {
+        let base = temp_dir("expire");
+        let holding = Holding::open(&base.join(TRASH_DIR), Some(Duration::from_secs(3600))).unwrap();
+        fs::create_dir_all(base.join("old/sub")).unwrap();
+        fs::write(base.join("old/sub/a"), "12345").unwrap();
+        fs::write(base.join("new"), "x").unwrap();
+
+        let old = holding.keep(&base.join("old"), "old", None).unwrap();
+        assert_eq!(old.size, 5);
+        holding.keep(&base.join("new"), "new", None).unwrap();
+        // 把紀錄的時間改到保留期限之前
+        let mut record = holding.load(&old.id).unwrap();
+        record.time = (Utc::now() - chrono::Duration::hours(2)).to_rfc3339();
+        fs::write(holding.record(&old.id), serde_json::to_vec(&record).unwrap()).unwrap();
+
+        assert_eq!(holding.expire().unwrap(), 1);
+        let left: Vec<String> = holding.list().unwrap().into_iter().map(|item| item.path).collect();
+        assert_eq!(left, ["new"]);
+        assert!(!base.join(TRASH_DIR).join(&old.id).exists());
+        fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn reserved_names_are_top_level_only() {
+        assert!(is_reserved(".trash/0123"));
+        assert!(is_reserved(".sfs-quarantine"));
+        assert!(is_reserved(".sfs-quarantine/0123"));
+        assert!(!is_reserved("docs/.sfs-quarantine"));
+    }
+}
@@ -0,0 +1,147 @@
+use std::{
+    path::{Path, PathBuf},
+    process::Stdio,
+    sync::Arc,
+    time::Duration,
+};
+
+use tokio::{process::Command, sync::Semaphore};
+use tracing::{info, warn};
+
+// 同時執行的命令數上限，大量上傳時其餘的命令依序等待
+const MAX_RUNNING: usize = 4;
+
+// 記錄到日誌的錯誤輸出長度上限
+const MAX_STDERR: usize = 2048;
+
+// 上傳完成的文件
+pub struct Uploaded {
+    // 文件的絕對路徑
+    pub file: PathBuf,
+    // 網址路徑（未編碼），以 `/` 開頭
+    pub url: String,
+    pub bytes: u64,
+}
+
+// 上傳完成後執行的命令（--on-upload）
+//
+// 命令不經過 shell：依 shell 的引號規則拆成程式與參數後直接執行，`{path}`、`{url}` 與
+// `{bytes}` 在拆開之後才代換，用戶取的文件名稱不會變成額外的參數或命令。子程序沒有標準輸入，
+// 只取得 PATH 環境變數，超過時限即被終止；命令在背景執行，不影響上傳的回應。
+pub struct UploadHook {
+    program: String,
+    args: Vec<String>,
+    timeout: Duration,
+    running: Semaphore,
+}
+
+impl UploadHook {
+    pub fn parse(command: &str, timeout: Duration) -> Result<Self, String> {
+        let mut words = shell_words::split(command).map_err(|e| format!("無法解析 --on-upload 命令: {}", e))?;
+        if words.is_empty() {
+            return Err("--on-upload 命令不能是空的".to_string());
+        }
+        let program = words.remove(0);
+        Ok(UploadHook {
+            program,
+            args: words,
+            timeout,
+            running: Semaphore::new(MAX_RUNNING),
+        })
+    }
+
+    // 要執行的程式；沒有路徑時在 PATH 中尋找，找不到時回傳 None
+    pub fn program(&self) -> Option<PathBuf> {
+        if self.program.contains('/') {
+            return std::fs::canonicalize(&self.program).ok();
+        }
+        std::env::var_os("PATH")
+            .iter()
+            .flat_map(std::env::split_paths)
+            .map(|dir| dir.join(&self.program))
+            .find(|path| path.is_file())
+    }
+
+    fn args(&self, uploaded: &Uploaded) -> Vec<String> {
+        self.args
+            .iter()
+            .map(|arg| {
+                arg.replace("{path}", &uploaded.file.to_string_lossy())
+                    .replace("{url}", &uploaded.url)
+                    .replace("{bytes}", &uploaded.bytes.to_string())
+            })
+            .collect()
+    }
+
+    // 在背景執行命令，結果只記錄到日誌
+    pub fn spawn(self: &Arc<Self>, uploaded: Uploaded) {
+        let hook = self.clone();
+        tokio::spawn(async move { hook.run(uploaded).await });
+    }
+
+    async fn run(&self, uploaded: Uploaded) {
+        let Ok(_permit) = self.running.acquire().await else {
+            return;
+        };
+        let mut command = Command::new(&self.program);
+        command
+            .args(self.args(&uploaded))
+            .env_clear()
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        if let Some(path) = std::env::var_os("PATH") {
+            command.env("PATH", path);
+        }
+
+        let child = match command.spawn() {
+            Ok(child) => child,
+            Err(e) => return warn!("無法執行 --on-upload 命令 {}: {}", self.program, e),
+        };
+        // 超過時限時捨棄 wait_with_output，kill_on_drop 會終止子程序
+        match tokio::time::timeout(self.timeout, child.wait_with_output()).await {
+            Ok(Ok(output)) if output.status.success() => info!("--on-upload 已處理 {}", uploaded.url),
+            Ok(Ok(output)) => {
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                let stderr = stderr.trim();
+                let end = stderr.char_indices().nth(MAX_STDERR).map_or(stderr.len(), |(i, _)| i);
+                warn!("--on-upload 處理 {} 失敗 ({}): {}", uploaded.url, output.status, &stderr[..end]);
+            }
+            Ok(Err(e)) => warn!("--on-upload 處理 {} 失敗: {}", uploaded.url, e),
+            Err(_) => warn!("--on-upload 處理 {} 超過 {} 秒，已終止", uploaded.url, self.timeout.as_secs()),
+        }
+    }
+}
+
+// 以絕對路徑傳給命令，命令的工作目錄與伺服器不同時也能使用
+pub fn absolute(path: &Path) -> PathBuf {
+    std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn placeholders_are_replaced_after_splitting() {
+        let hook = UploadHook::parse("'./scan it.sh' --file {path} -- {url}", Duration::from_secs(1)).unwrap();
+        assert_eq!(hook.program, "./scan it.sh");
+        let uploaded = Uploaded {
+            file: PathBuf::from("/srv/a b; rm -rf x.txt"),
+            url: "/a b; rm -rf x.txt".to_string(),
+            bytes: 3,
+        };
+        // 名稱中的空白與分號仍在同一個參數中
+        assert_eq!(
+            hook.args(&uploaded),
+            ["--file", "/srv/a b; rm -rf x.txt", "--", "/a b; rm -rf x.txt"]
+        );
+    }
+
+    #[test]
+    fn empty_or_unbalanced_commands_are_refused() {
+        assert!(UploadHook::parse("  ", Duration::from_secs(1)).is_err());
+        assert!(UploadHook::parse("scan 'path", Duration::from_secs(1)).is_err());
+    }
+}
@@ -0,0 +1,25 @@
+use percent_encoding::utf8_percent_encode;
+
+use crate::listing::HREF;
+
+// HTML 文字與屬性值的跳脫；文件名稱、請求路徑等外部來源的文字放進 HTML 前都要經過這裡
+pub fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// 網址路徑（不含開頭的 /）放進 `href` 屬性的值：各段百分比編碼後再跳脫，
+// 名稱中的空白、`#`、`?` 不會讓連結斷掉
+pub fn href(path: &str) -> String {
+    escape(&format!("/{}", utf8_percent_encode(path, HREF)))
+}
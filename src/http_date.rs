@@ -0,0 +1,28 @@
+use std::time::SystemTime;
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+
+// RFC 7231 的 IMF-fixdate，一律以 UTC 表示，例如 `Sun, 06 Nov 1994 08:49:37 GMT`
+const IMF_FIXDATE: &str = "%a, %d %b %Y %H:%M:%S GMT";
+// 已淘汰但接收端仍須接受的兩種格式
+const RFC850: &str = "%A, %d-%b-%y %H:%M:%S GMT";
+const ASCTIME: &str = "%a %b %e %H:%M:%S %Y";
+
+// Last-Modified 等標頭使用的時間格式
+pub fn format(time: SystemTime) -> String {
+    DateTime::<Utc>::from(time).format(IMF_FIXDATE).to_string()
+}
+
+// 解析 HTTP-date，三種格式皆可；無法解析時回傳 None
+pub fn parse(s: &str) -> Option<DateTime<Utc>> {
+    let s = s.trim();
+    [IMF_FIXDATE, RFC850, ASCTIME]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(s, format).ok())
+        .map(|time| time.and_utc())
+}
+
+// 文件在 `since` 之後是否沒有修改；HTTP-date 只精確到秒，文件時間的小數部分捨去
+pub fn not_modified_since(modified: SystemTime, since: &DateTime<Utc>) -> bool {
+    DateTime::<Utc>::from(modified).timestamp() <= since.timestamp()
+}
@@ -0,0 +1,158 @@
+use std::{
+    collections::HashMap,
+    fs::Metadata,
+    path::{Path, PathBuf},
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    task::{Context, Poll},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use axum::body::Bytes;
+use futures_util::Stream;
+use sha2::{Digest, Sha256};
+use tracing::{debug, error};
+
+use crate::manifest::{hash_file, Manifest};
+
+// 記住的雜湊數量上限，超過時全部清除重新累積
+const MAX_REMEMBERED: usize = 100_000;
+
+// 送出內容的完整性檢查（--verify-reads），用來找出偶爾讀到錯誤內容的儲存裝置
+//
+// 完整送出的文件會一邊傳送一邊計算 SHA-256，送完後與已知的雜湊比較：有文件清單時以清單為準，
+// 否則另外從頭讀一次文件計算，兩次一致才記住。不一致時記錄錯誤並計入指標，
+// 但內容已經送出，無法更正。只檢查完整送出的文件，部分內容與 HEAD 不檢查。
+pub struct Integrity {
+    manifest: Option<Arc<Manifest>>,
+    // 文件路徑對應大小、修改時間與確認過的雜湊
+    verified: Mutex<HashMap<PathBuf, (u64, SystemTime, String)>>,
+    pub mismatches: AtomicU64,
+}
+
+impl Integrity {
+    pub fn new(manifest: Option<Arc<Manifest>>) -> Self {
+        Integrity {
+            manifest,
+            verified: Mutex::new(HashMap::new()),
+            mismatches: AtomicU64::new(0),
+        }
+    }
+
+    // 包裝文件內容的串流，送完 `metadata` 的長度後檢查
+    pub fn wrap<S>(self: &Arc<Self>, inner: S, path: &Path, metadata: &Metadata) -> VerifiedStream<S> {
+        VerifiedStream {
+            inner,
+            hasher: Some(Sha256::new()),
+            sent: 0,
+            file: FileVersion {
+                path: path.to_path_buf(),
+                len: metadata.len(),
+                modified: metadata.modified().unwrap_or(UNIX_EPOCH),
+            },
+            integrity: self.clone(),
+        }
+    }
+
+    // 同一版本文件已知的雜湊
+    fn known(&self, file: &FileVersion) -> Option<String> {
+        let mtime = file.modified.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        if let Some(entry) = self.manifest.as_ref().and_then(|manifest| manifest.entry_for(&file.path)) {
+            if entry.size == file.len && entry.mtime == mtime {
+                return Some(entry.sha256);
+            }
+        }
+        match self.verified.lock().unwrap().get(&file.path) {
+            Some((len, modified, hash)) if *len == file.len && *modified == file.modified => Some(hash.clone()),
+            _ => None,
+        }
+    }
+
+    fn check(self: Arc<Self>, file: FileVersion, sent: String) {
+        if let Some(known) = self.known(&file) {
+            self.compare(&file, &sent, &known, "已知的雜湊");
+            return;
+        }
+        // 沒有已知的雜湊時在背景重新讀取整個文件
+        tokio::task::spawn_blocking(move || {
+            let reread = match hash_file(&file.path) {
+                Ok(hash) => hash,
+                Err(e) => return debug!("無法重新讀取 {} 以檢查內容: {}", file.path.display(), e),
+            };
+            // 重新讀取前文件已經改變時無從比較
+            let unchanged = std::fs::metadata(&file.path)
+                .is_ok_and(|m| m.len() == file.len && m.modified().ok() == Some(file.modified));
+            if !unchanged {
+                return;
+            }
+            if self.compare(&file, &sent, &reread, "重新讀取的結果") {
+                let mut verified = self.verified.lock().unwrap();
+                if verified.len() >= MAX_REMEMBERED {
+                    verified.clear();
+                }
+                verified.insert(file.path, (file.len, file.modified, reread));
+            }
+        });
+    }
+
+    fn compare(&self, file: &FileVersion, sent: &str, expected: &str, source: &str) -> bool {
+        if sent == expected {
+            return true;
+        }
+        self.mismatches.fetch_add(1, Ordering::Relaxed);
+        error!(
+            "送出的 {} 內容與{}不符：送出 sha256={}，預期 sha256={}",
+            file.path.display(),
+            source,
+            sent,
+            expected
+        );
+        false
+    }
+}
+
+#[derive(Clone)]
+struct FileVersion {
+    path: PathBuf,
+    len: u64,
+    modified: SystemTime,
+}
+
+// 計算送出內容雜湊的串流包裝
+pub struct VerifiedStream<S> {
+    inner: S,
+    // 送完後取出，確保只檢查一次
+    hasher: Option<Sha256>,
+    sent: u64,
+    file: FileVersion,
+    integrity: Arc<Integrity>,
+}
+
+impl<S, E> Stream for VerifiedStream<S>
+where
+    S: Stream<Item = Result<Bytes, E>> + Unpin,
+{
+    type Item = Result<Bytes, E>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let poll = Pin::new(&mut self.inner).poll_next(cx);
+        if let Poll::Ready(Some(Ok(chunk))) = &poll {
+            self.sent += chunk.len() as u64;
+            if let Some(hasher) = self.hasher.as_mut() {
+                hasher.update(chunk);
+            }
+        }
+        // 與 TrackedStream 相同，送滿長度後不一定會再輪詢到串流結束
+        let finished = matches!(poll, Poll::Ready(None)) || self.sent >= self.file.len;
+        if finished && self.sent == self.file.len {
+            if let Some(hasher) = self.hasher.take() {
+                let sent = format!("{:x}", hasher.finalize());
+                self.integrity.clone().check(self.file.clone(), sent);
+            }
+        }
+        poll
+    }
+}
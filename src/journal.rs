@@ -0,0 +1,195 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use axum::{
+    extract::{Extension, Query, State},
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::{
+    auth::{Permission, User},
+    check_restricted,
+    watcher::FsWatcher,
+    ServerConfig, ServerError,
+};
+
+// 最多保留的變動紀錄數，超過時捨棄最舊的紀錄
+const CAPACITY: usize = 100_000;
+
+#[derive(Clone)]
+struct Change {
+    time: DateTime<Utc>,
+    path: String,
+    removed: bool,
+}
+
+struct Entries {
+    changes: VecDeque<Change>,
+    // 此時間之前的變動已被捨棄（或發生在伺服器啟動前），無法從紀錄得知
+    complete_since: DateTime<Utc>,
+}
+
+// 文件變動紀錄，供同步用戶端只取得上次輪詢後變動的路徑
+//
+// 紀錄只保存在記憶體中，伺服器重新啟動或紀錄被捨棄後，更早的 `since` 會標示為不完整，
+// 用戶端應重新掃描整個目錄樹（例如讀取 /_manifest.json）。
+pub struct ChangeJournal {
+    entries: Mutex<Entries>,
+}
+
+impl ChangeJournal {
+    // 開始記錄監看器回報的變動
+    pub fn start(base: &Path, watcher: &FsWatcher) -> Arc<Self> {
+        let journal = Arc::new(ChangeJournal {
+            entries: Mutex::new(Entries {
+                changes: VecDeque::new(),
+                complete_since: Utc::now(),
+            }),
+        });
+
+        // 監看器回報的路徑可能以原始或正規化後的基礎路徑開頭
+        let bases: Vec<PathBuf> = std::iter::once(base.to_path_buf())
+            .chain(base.canonicalize().ok())
+            .collect();
+        let mut changes = watcher.subscribe();
+        let recorder = journal.clone();
+        tokio::spawn(async move {
+            loop {
+                let paths = match changes.recv().await {
+                    Ok(paths) => paths,
+                    // 遺漏了部分變動，之前的紀錄不再完整
+                    Err(RecvError::Lagged(_)) => {
+                        recorder.entries.lock().unwrap().complete_since = Utc::now();
+                        continue;
+                    }
+                    Err(RecvError::Closed) => break,
+                };
+
+                let time = Utc::now();
+                let batch: Vec<Change> = paths
+                    .iter()
+                    .filter_map(|path| {
+                        let rel = bases.iter().find_map(|base| path.strip_prefix(base).ok())?;
+                        let rel = rel.to_string_lossy().replace('\\', "/");
+                        (!rel.is_empty()).then(|| Change {
+                            time,
+                            path: rel,
+                            removed: !path.exists(),
+                        })
+                    })
+                    .collect();
+                recorder.record(batch);
+            }
+        });
+
+        journal
+    }
+
+    fn record(&self, batch: Vec<Change>) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.changes.extend(batch);
+        while entries.changes.len() > CAPACITY {
+            if let Some(dropped) = entries.changes.pop_front() {
+                entries.complete_since = dropped.time;
+            }
+        }
+    }
+
+    // `since` 之後的變動，每個路徑只保留最後一次；第二個值表示紀錄是否涵蓋整段時間
+    fn since(&self, since: DateTime<Utc>) -> (Vec<Change>, bool) {
+        let entries = self.entries.lock().unwrap();
+        let mut latest: HashMap<&str, &Change> = HashMap::new();
+        for change in entries.changes.iter().filter(|c| c.time > since) {
+            latest.insert(&change.path, change);
+        }
+        let mut changes: Vec<Change> = latest.into_values().cloned().collect();
+        changes.sort_by(|a, b| a.time.cmp(&b.time).then_with(|| a.path.cmp(&b.path)));
+        (changes, since >= entries.complete_since)
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ChangesQuery {
+    since: String,
+}
+
+#[derive(Serialize)]
+struct ChangeItem {
+    path: String,
+    time: String,
+    removed: bool,
+}
+
+// 解析 RFC 3339 時間或 Unix 時間戳（秒，可含小數）
+fn parse_since(since: &str) -> Option<DateTime<Utc>> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(since) {
+        return Some(time.with_timezone(&Utc));
+    }
+    let seconds: f64 = since.parse().ok()?;
+    DateTime::from_timestamp_micros((seconds * 1_000_000.0) as i64)
+}
+
+// 列出指定時間之後變動的路徑
+//
+// 回應中的 `until` 是下一次輪詢應使用的 `since`；`complete` 為 false 時代表
+// 紀錄無法涵蓋整段時間，用戶端應重新掃描。
+pub async fn list_changes(
+    State(config): State<ServerConfig>,
+    user: Option<Extension<Arc<User>>>,
+    Query(query): Query<ChangesQuery>,
+) -> Result<impl IntoResponse, ServerError> {
+    let journal = config
+        .journal
+        .as_ref()
+        .ok_or_else(|| ServerError::NotFound("api/changes".to_string()))?;
+    let since = parse_since(query.since.trim())
+        .ok_or_else(|| ServerError::BadRequest(format!("無效的時間: {}", query.since)))?;
+
+    let until = Utc::now();
+    let (changes, complete) = journal.since(since);
+
+    // 只列出用戶看得到的路徑，並以用戶的根目錄為起點
+    let visible = |path: &str| -> Option<String> {
+        if check_restricted(path, &config).is_err() {
+            return None;
+        }
+        match &user {
+            Some(Extension(user)) => {
+                let rel = if user.root.is_empty() {
+                    path
+                } else {
+                    path.strip_prefix(user.root.as_str())?.strip_prefix('/')?
+                };
+                user.can_access(config.access_rules.as_deref(), path, Permission::Read)
+                    .then(|| rel.to_string())
+            }
+            None => Some(path.to_string()),
+        }
+    };
+
+    let changes: Vec<ChangeItem> = changes
+        .into_iter()
+        .filter(|change| change.time <= until)
+        .filter_map(|change| {
+            Some(ChangeItem {
+                path: visible(&change.path)?,
+                time: change.time.to_rfc3339(),
+                removed: change.removed,
+            })
+        })
+        .collect();
+
+    Ok(Json(serde_json::json!({
+        "since": since.to_rfc3339(),
+        "until": until.to_rfc3339(),
+        "complete": complete,
+        "changes": changes,
+    })))
+}
@@ -0,0 +1,178 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+};
+
+use globset::{Glob, GlobMatcher};
+use tokio::sync::broadcast::error::RecvError;
+use tracing::info;
+
+use crate::{listing::version_cmp, restrict::RestrictRules, watcher::FsWatcher};
+
+// 選出最新項目的方式
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum LatestOrder {
+    // 修改時間最晚的項目
+    Modified,
+    // 名稱中的版本號最大的項目，例如 app-1.10.0 比 app-1.9.2 新
+    Version,
+}
+
+// 一個虛擬的 latest 連結
+pub struct LatestSpec {
+    // 連結的路徑，相對於基礎目錄，例如 `releases/latest`
+    link: String,
+    // 候選項目所在的目錄與名稱樣式
+    dir: String,
+    pattern: GlobMatcher,
+    order: LatestOrder,
+}
+
+impl LatestSpec {
+    // 格式為 `<連結>=<樣式>[;version]`，萬用字元只能出現在樣式的最後一層，
+    // 例如 `releases/latest=releases/app-*.tar.gz;version`
+    pub fn parse(spec: &str) -> io::Result<Self> {
+        let invalid = |reason: String| io::Error::new(io::ErrorKind::InvalidInput, reason);
+        let (link, pattern) = spec
+            .split_once('=')
+            .ok_or_else(|| invalid(format!("latest 設定 {} 應為 <連結>=<樣式>", spec)))?;
+        let (pattern, order) = match pattern.rsplit_once(';') {
+            Some((pattern, "version")) => (pattern, LatestOrder::Version),
+            Some((pattern, "mtime")) => (pattern, LatestOrder::Modified),
+            Some((_, order)) => return Err(invalid(format!("未知的排序方式 {}，應為 mtime 或 version", order))),
+            None => (pattern, LatestOrder::Modified),
+        };
+
+        let link = link.trim().trim_matches('/').to_string();
+        let pattern = pattern.trim().trim_matches('/');
+        let (dir, name) = pattern.rsplit_once('/').unwrap_or(("", pattern));
+        if link.is_empty() || name.is_empty() {
+            return Err(invalid(format!("latest 設定 {} 缺少連結或樣式", spec)));
+        }
+        if dir.contains(['*', '?', '[', '{']) {
+            return Err(invalid(format!("樣式 {} 的萬用字元只能出現在最後一層", pattern)));
+        }
+        if link.split('/').any(|c| c == ".." || c == ".") || dir.split('/').any(|c| c == ".." || c == ".") {
+            return Err(invalid(format!("latest 設定 {} 不能包含 . 或 ..", spec)));
+        }
+        let pattern = Glob::new(name)
+            .map_err(|e| invalid(format!("無效的樣式 {}: {}", name, e)))?
+            .compile_matcher();
+
+        Ok(LatestSpec {
+            link,
+            dir: dir.to_string(),
+            pattern,
+            order,
+        })
+    }
+
+    fn target(&self, name: &str) -> String {
+        match self.dir.is_empty() {
+            true => name.to_string(),
+            false => format!("{}/{}", self.dir, name),
+        }
+    }
+}
+
+// 指向最新項目的虛擬連結
+//
+// 取代手動維護的 latest 符號連結：目錄內容變動時依監看器的通知重新選出目標，
+// 請求連結時轉址到目前的目標，不會指向已刪除的文件。
+pub struct LatestLinks {
+    base: PathBuf,
+    restricted: Arc<RestrictRules>,
+    links: Vec<(LatestSpec, RwLock<Option<String>>)>,
+}
+
+impl LatestLinks {
+    pub fn start(
+        base: &Path,
+        specs: Vec<LatestSpec>,
+        restricted: Arc<RestrictRules>,
+        watcher: &FsWatcher,
+    ) -> Arc<Self> {
+        let links = Arc::new(LatestLinks {
+            base: base.to_path_buf(),
+            restricted,
+            links: specs.into_iter().map(|spec| (spec, RwLock::new(None))).collect(),
+        });
+        for index in 0..links.links.len() {
+            links.refresh(index);
+        }
+
+        // 監看器回報的路徑可能以正規化後的基礎路徑開頭
+        let canonical_base = base.canonicalize().ok();
+        let mut changes = watcher.subscribe();
+        let updater = links.clone();
+        tokio::spawn(async move {
+            loop {
+                let paths = match changes.recv().await {
+                    Ok(paths) => paths,
+                    Err(RecvError::Lagged(_)) => Arc::new(Vec::new()),
+                    Err(RecvError::Closed) => break,
+                };
+                for (index, (spec, _)) in updater.links.iter().enumerate() {
+                    let dir = updater.base.join(&spec.dir);
+                    let canonical_dir = canonical_base.as_ref().map(|base| base.join(&spec.dir));
+                    let affected = paths.is_empty()
+                        || paths.iter().any(|path| {
+                            path.parent() == Some(dir.as_path())
+                                || canonical_dir.as_deref().is_some_and(|dir| path.parent() == Some(dir))
+                        });
+                    if affected {
+                        updater.refresh(index);
+                    }
+                }
+            }
+        });
+
+        links
+    }
+
+    // 重新選出一個連結的目標
+    fn refresh(&self, index: usize) {
+        let (spec, target) = &self.links[index];
+        let mut candidates: Vec<(String, Option<std::time::SystemTime>)> = fs::read_dir(self.base.join(&spec.dir))
+            .into_iter()
+            .flatten()
+            .flatten()
+            .filter_map(|entry| {
+                let name = entry.file_name().to_string_lossy().to_string();
+                let visible = spec.pattern.is_match(&name)
+                    && !self.restricted.is_restricted(&spec.target(&name), || entry.path().is_dir());
+                visible.then(|| (name, entry.metadata().and_then(|m| m.modified()).ok()))
+            })
+            .collect();
+        candidates.sort_by(|a, b| match spec.order {
+            LatestOrder::Modified => a.1.cmp(&b.1).then_with(|| version_cmp(&a.0, &b.0)),
+            LatestOrder::Version => version_cmp(&a.0, &b.0),
+        });
+
+        let newest = candidates.pop().map(|(name, _)| spec.target(&name));
+        let mut current = target.write().unwrap();
+        if *current != newest {
+            match &newest {
+                Some(newest) => info!("/{} 指向 /{}", spec.link, newest),
+                None => info!("/{} 沒有符合的項目", spec.link),
+            }
+            *current = newest;
+        }
+    }
+
+    // 請求路徑是連結本身或其底下的路徑時，回傳替換成目前目標後的路徑；
+    // 連結目前沒有目標時為 `Some(None)`
+    pub fn resolve(&self, path: &str) -> Option<Option<String>> {
+        let path = path.trim_start_matches('/');
+        self.links.iter().find_map(|(spec, target)| {
+            let rest = match path.strip_prefix(spec.link.as_str()) {
+                Some("") => "",
+                Some(rest) if rest.starts_with('/') => rest,
+                _ => return None,
+            };
+            let target = target.read().unwrap();
+            Some(target.as_ref().map(|target| format!("{}{}", target, rest)))
+        })
+    }
+}
@@ -0,0 +1,96 @@
+use std::{sync::Arc, time::Duration};
+
+use ldap3::{dn_escape, ldap_escape, LdapConnAsync, LdapConnSettings, Scope};
+use tracing::warn;
+
+use crate::auth::{Permission, User, VerifiedCache};
+
+// 成功驗證的結果保留時間，避免每個請求都連線到目錄伺服器
+const VERIFIED_TTL: Duration = Duration::from_secs(300);
+
+// 連線逾時
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+// LDAP / Active Directory 驗證後端
+//
+// 以 `{user_attr}={name},{base_dn}` 作為 DN 直接 bind 驗證密碼，
+// 設定了群組 DN 時還需要是該群組的成員。
+pub struct LdapBackend {
+    pub url: String,
+    pub base_dn: String,
+    pub user_attr: String,
+    pub group_dn: Option<String>,
+    verified: VerifiedCache,
+}
+
+impl LdapBackend {
+    pub fn new(url: String, base_dn: String, user_attr: String, group_dn: Option<String>) -> Self {
+        LdapBackend {
+            url,
+            base_dn,
+            user_attr,
+            group_dn,
+            verified: VerifiedCache::new(VERIFIED_TTL),
+        }
+    }
+
+    pub async fn authenticate(&self, name: &str, password: &str) -> Option<Arc<User>> {
+        // 空密碼在 LDAP 中會變成匿名 bind 而成功，必須拒絕
+        if name.is_empty() || password.is_empty() {
+            return None;
+        }
+
+        if let Some(user) = self.verified.get(name, password) {
+            return Some(user);
+        }
+
+        match self.bind(name, password).await {
+            Ok(true) => {
+                // 目錄用戶預設可瀏覽與下載，更細的控制交由路徑權限規則
+                let user = Arc::new(User::external(
+                    name.to_string(),
+                    vec![Permission::Read, Permission::List],
+                ));
+                self.verified.insert(name, password, user.clone());
+                Some(user)
+            }
+            Ok(false) => None,
+            Err(e) => {
+                warn!("LDAP 驗證失敗: {}", e);
+                None
+            }
+        }
+    }
+
+    // 以用戶身分 bind，並在需要時檢查群組成員資格
+    async fn bind(&self, name: &str, password: &str) -> ldap3::result::Result<bool> {
+        let settings = LdapConnSettings::new().set_conn_timeout(CONNECT_TIMEOUT);
+        let (conn, mut ldap) = LdapConnAsync::with_settings(settings, &self.url).await?;
+        ldap3::drive!(conn);
+
+        let user_dn = format!("{}={},{}", self.user_attr, dn_escape(name), self.base_dn);
+        if ldap.simple_bind(&user_dn, password).await?.success().is_err() {
+            let _ = ldap.unbind().await;
+            return Ok(false);
+        }
+
+        let allowed = match &self.group_dn {
+            Some(group_dn) => {
+                let filter = format!(
+                    "(|(member={dn})(uniqueMember={dn})(memberUid={name}))",
+                    dn = ldap_escape(user_dn.as_str()),
+                    name = ldap_escape(name),
+                );
+                let (entries, _) = ldap
+                    .search(group_dn, Scope::Base, &filter, vec!["dn"])
+                    .await?
+                    .success()?;
+                !entries.is_empty()
+            }
+            None => true,
+        };
+
+        ldap.unbind().await?;
+        Ok(allowed)
+    }
+}
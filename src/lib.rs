@@ -0,0 +1,3603 @@
+use std::sync::Arc;
+use axum::{
+    extract::{Extension, Path, RawQuery, State},
+    http::{header, HeaderMap, Method, StatusCode, Uri},
+    response::{Html, IntoResponse, Redirect},
+    middleware,
+    routing::get,
+    Router,
+    body::Body,
+};
+use chrono::prelude::*;
+use clap::{Arg, ArgGroup, Command};
+use futures_util::{FutureExt, StreamExt};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use percent_encoding::utf8_percent_encode;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::{
+    fs,
+    future::IntoFuture,
+    io,
+    net::SocketAddr,
+    path::{Path as FsPath, PathBuf},
+    time::{Duration, SystemTime},
+};
+use thiserror::Error;
+use tokio::io::AsyncSeekExt;
+use tracing::{error, info, warn};
+
+mod access_log;
+mod acme;
+mod admin;
+mod archive;
+mod auth;
+mod bandwidth;
+mod cache;
+mod cache_control;
+mod catch_panic;
+mod checksums;
+mod clamd;
+mod case_index;
+mod coalesce;
+mod compress;
+mod config_file;
+mod cors;
+mod csrf;
+mod deadline;
+mod disk;
+mod duplicates;
+mod error_page;
+mod headers;
+mod hook;
+mod html;
+mod hints;
+mod holding;
+mod http_date;
+mod integrity;
+mod journal;
+mod latest;
+mod ldap;
+mod limits;
+mod listing;
+mod listing_cache;
+mod listener;
+mod manifest;
+mod methods;
+mod metrics;
+mod mirror;
+mod mp4;
+mod negative;
+#[cfg(feature = "pam")]
+mod pam;
+mod privileges;
+mod problem;
+mod quota;
+mod range;
+mod ratelimit;
+mod resume;
+mod readonly;
+mod restrict;
+mod s3;
+mod sandbox;
+mod session;
+mod sfignore;
+mod shadow;
+mod snapshot;
+mod sniff;
+mod suggest;
+mod surrogate;
+#[cfg(feature = "test-util")]
+pub mod test_util;
+mod throttle;
+mod tls;
+mod tokens;
+mod transfer;
+mod tree;
+mod upgrade;
+mod upload;
+mod vhost;
+mod watcher;
+mod webdav;
+mod webhook;
+
+use access_log::{AccessLog, LogFormat};
+use acme::{Acme, AcmeOptions};
+use archive::ArchiveFormat;
+use auth::{AccessRules, Permission, User, UserStore};
+use bandwidth::BandwidthRules;
+use cache::FileCache;
+use cache_control::CacheRules;
+use case_index::NameIndex;
+use checksums::Checksums;
+use coalesce::Coalescer;
+use compress::{CompressOptions, Encoding};
+use config_file::ConfigFile;
+use cors::CorsOptions;
+use deadline::Deadline;
+use disk::DiskMonitor;
+use error_page::ErrorPages;
+use headers::{CustomHeaders, HeaderRule};
+use hints::PreloadHints;
+use integrity::Integrity;
+use journal::ChangeJournal;
+use latest::{LatestLinks, LatestSpec};
+use ldap::LdapBackend;
+use listener::ListenerRoutes;
+use listing::ListingFormat;
+use listing_cache::ListingCache;
+use manifest::Manifest;
+use methods::MethodPolicy;
+use metrics::{CountConnections, Metrics};
+use mirror::MirrorOptions;
+use mp4::Mp4Checker;
+use negative::NegativeCache;
+use problem::Problem;
+use quota::Quota;
+use range::ByteRange;
+use ratelimit::RateLimiter;
+use restrict::{HiddenPolicy, RestrictRules, SymlinkPolicy};
+use s3::S3Options;
+use resume::UploadJournal;
+use sandbox::Sandbox;
+#[cfg(feature = "pam")]
+use pam::PamBackend;
+use privileges::Account;
+use session::SessionKeys;
+use clamd::Clamd;
+use holding::{Holding, QUARANTINE_DIR, TRASH_DIR};
+use hook::UploadHook;
+use upload::{UploadOptions, UploadPolicy, UploadTypes};
+use sfignore::IgnoreFile;
+use shadow::Shadow;
+use snapshot::{SnapshotSpec, Snapshots};
+use surrogate::{KeyHeader, SurrogateOptions};
+use throttle::LoginThrottle;
+use tokens::TokenStore;
+use transfer::{ChunkWriter, TrackedStream, TransferStats};
+use vhost::{VirtualHost, VirtualHosts};
+use watcher::FsWatcher;
+use webhook::{WebhookEvent, Webhooks};
+
+// 自定義錯誤類型
+#[derive(Error, Debug)]
+enum ServerError {
+    #[error("文件系統錯誤: {0}")]
+    Filesystem(#[from] std::io::Error),
+    
+    #[error("路徑不安全: {0}")]
+    UnsafePath(String),
+    
+    #[error("未找到: {0}")]
+    NotFound(String),
+    
+    // 內容為 WWW-Authenticate 標頭的挑戰值
+    #[error("需要授權: {0}")]
+    Unauthorized(String),
+    
+    #[error("請求錯誤: {0}")]
+    BadRequest(String),
+    
+    #[error("禁止操作: {0}")]
+    Forbidden(String),
+    
+    #[error("磁碟空間不足")]
+    InsufficientStorage,
+    
+    // 內容為 Allow 標頭的值
+    #[error("不允許的方法，允許: {0}")]
+    MethodNotAllowed(String),
+    
+    // 內容為文件的完整長度
+    #[error("請求範圍無法滿足，文件長度: {0}")]
+    RangeNotSatisfiable(u64),
+    
+    #[error("衝突: {0}")]
+    Conflict(String),
+    
+    // 內容為大小上限
+    #[error("請求內容過大，上限: {0}")]
+    PayloadTooLarge(u64),
+    
+    // 內容為需要等待的時間
+    #[error("嘗試次數過多: {0:?}")]
+    TooManyRequests(Duration),
+    
+    // 內容為請求的路徑
+    #[error("處理請求逾時: {0}")]
+    Timeout(String),
+    
+    // 內容為伺服器可使用的編碼
+    #[error("用戶端不接受任何可用的編碼，可使用: {0}")]
+    NotAcceptable(String),
+    
+    // 內容為不允許上傳的文件名稱
+    #[error("不允許上傳此類型的文件: {0}")]
+    UnsupportedMediaType(String),
+    
+    // 內容為 clamd 回報的病毒名稱
+    #[error("上傳的文件含有病毒: {0}")]
+    Infected(String),
+    
+    #[error("伺服器錯誤: {0}")]
+    Internal(String),
+    
+    // 內容為請求編號，已由 catch_panic::catch 記錄詳細內容
+    #[error("處理請求 {0} 時發生 panic")]
+    Panicked(String),
+}
+
+impl ServerError {
+    // 文件描述符用盡是暫時性的負載問題，不是伺服器錯誤
+    fn out_of_descriptors(&self) -> bool {
+        #[cfg(unix)]
+        if let ServerError::Filesystem(e) = self {
+            return matches!(e.raw_os_error(), Some(libc::EMFILE) | Some(libc::ENFILE));
+        }
+        false
+    }
+    
+    fn out_of_space(&self) -> bool {
+        #[cfg(unix)]
+        if let ServerError::Filesystem(e) = self {
+            return e.raw_os_error() == Some(libc::ENOSPC);
+        }
+        matches!(self, ServerError::InsufficientStorage)
+    }
+    
+    fn status(&self) -> StatusCode {
+        if self.out_of_space() {
+            return StatusCode::INSUFFICIENT_STORAGE;
+        }
+        if self.out_of_descriptors() {
+            return StatusCode::SERVICE_UNAVAILABLE;
+        }
+        match self {
+            ServerError::NotFound(_) => StatusCode::NOT_FOUND,
+            ServerError::UnsafePath(_) | ServerError::Forbidden(_) => StatusCode::FORBIDDEN,
+            ServerError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            ServerError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            ServerError::TooManyRequests(_) => StatusCode::TOO_MANY_REQUESTS,
+            ServerError::RangeNotSatisfiable(_) => StatusCode::RANGE_NOT_SATISFIABLE,
+            ServerError::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            ServerError::Conflict(_) => StatusCode::CONFLICT,
+            ServerError::MethodNotAllowed(_) => StatusCode::METHOD_NOT_ALLOWED,
+            ServerError::Timeout(_) => StatusCode::SERVICE_UNAVAILABLE,
+            ServerError::NotAcceptable(_) => StatusCode::NOT_ACCEPTABLE,
+            ServerError::UnsupportedMediaType(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            ServerError::Infected(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+    
+    // 機器可讀的錯誤代碼，API 用戶端以此判斷錯誤種類，不需解析訊息文字
+    fn code(&self) -> &'static str {
+        if self.out_of_space() {
+            return "insufficient_storage";
+        }
+        if self.out_of_descriptors() {
+            return "server_busy";
+        }
+        match self {
+            ServerError::NotFound(_) => "not_found",
+            ServerError::UnsafePath(_) => "unsafe_path",
+            ServerError::Forbidden(_) => "forbidden",
+            ServerError::Unauthorized(_) => "unauthorized",
+            ServerError::BadRequest(_) => "bad_request",
+            ServerError::TooManyRequests(_) => "too_many_requests",
+            ServerError::RangeNotSatisfiable(_) => "range_not_satisfiable",
+            ServerError::PayloadTooLarge(_) => "payload_too_large",
+            ServerError::Conflict(_) => "conflict",
+            ServerError::MethodNotAllowed(_) => "method_not_allowed",
+            ServerError::Timeout(_) => "timeout",
+            ServerError::NotAcceptable(_) => "not_acceptable",
+            ServerError::UnsupportedMediaType(_) => "unsupported_media_type",
+            ServerError::Infected(_) => "infected",
+            _ => "internal_error",
+        }
+    }
+    
+    // 顯示給使用者的說明，不包含內部細節
+    fn detail(&self) -> String {
+        if self.out_of_space() {
+            return "磁碟空間不足，暫時無法寫入".to_string();
+        }
+        if self.out_of_descriptors() {
+            return "伺服器忙碌中，請稍後再試".to_string();
+        }
+        match self {
+            ServerError::NotFound(path) => format!("找不到路徑: {}", path),
+            ServerError::UnsafePath(path) => format!("禁止訪問: {}", path),
+            ServerError::Forbidden(reason) => format!("禁止操作: {}", reason),
+            ServerError::Unauthorized(_) => "需要授權".to_string(),
+            ServerError::BadRequest(reason) => format!("請求錯誤: {}", reason),
+            ServerError::TooManyRequests(wait) => {
+                format!("嘗試次數過多，請於 {} 秒後再試", wait.as_secs() + 1)
+            }
+            ServerError::RangeNotSatisfiable(_) => "請求的範圍超出文件長度".to_string(),
+            ServerError::PayloadTooLarge(limit) => format!("上傳內容超過 {} 位元組的上限", limit),
+            ServerError::Conflict(reason) => reason.clone(),
+            ServerError::MethodNotAllowed(_) => "此路徑不允許使用此方法".to_string(),
+            ServerError::Timeout(_) => "處理請求逾時，請稍後再試".to_string(),
+            ServerError::NotAcceptable(offered) => {
+                format!("用戶端不接受未壓縮的內容，也不接受可使用的編碼: {}", offered)
+            }
+            ServerError::UnsupportedMediaType(name) => format!("不允許上傳此類型的文件: {}", name),
+            ServerError::Infected(name) => format!("上傳的文件含有病毒: {}", name),
+            ServerError::Panicked(id) => format!("伺服器內部錯誤，請求編號 {}", id),
+            _ => "伺服器內部錯誤".to_string(),
+        }
+    }
+    
+    // 錯誤附帶的標頭
+    fn header(&self) -> Option<(header::HeaderName, String)> {
+        if self.out_of_descriptors() {
+            return Some((header::RETRY_AFTER, "1".to_string()));
+        }
+        match self {
+            ServerError::Unauthorized(challenge) => Some((header::WWW_AUTHENTICATE, challenge.clone())),
+            ServerError::TooManyRequests(wait) => {
+                Some((header::RETRY_AFTER, (wait.as_secs() + 1).to_string()))
+            }
+            ServerError::RangeNotSatisfiable(len) => {
+                Some((header::CONTENT_RANGE, format!("bytes */{}", len)))
+            }
+            ServerError::MethodNotAllowed(allow) => Some((header::ALLOW, allow.clone())),
+            ServerError::Panicked(id) => Some((header::HeaderName::from_static(catch_panic::REQUEST_ID), id.clone())),
+            _ => None,
+        }
+    }
+}
+
+// 轉換為 HTTP 響應：瀏覽器取得 HTML，API 用戶端經 problem::render 取得 problem+json
+impl IntoResponse for ServerError {
+    fn into_response(self) -> axum::response::Response {
+        let status = self.status();
+        if self.out_of_space() {
+            error!("磁碟空間已滿: {}", self);
+        } else if self.out_of_descriptors() {
+            error!("文件描述符已用盡: {}，請以 --max-open-files 提高上限", self);
+        } else if let ServerError::Timeout(path) = &self {
+            warn!("處理請求逾時: /{}", path.trim_start_matches('/'));
+        } else if status == StatusCode::INTERNAL_SERVER_ERROR && !matches!(self, ServerError::Panicked(_)) {
+            error!("伺服器錯誤: {:?}", self);
+        }
+        
+        let problem = Problem::new(status, self.code(), self.detail());
+        // 說明中可能含有請求的路徑，必須跳脫
+        let mut response = (status, Html(format!("<h1>{}</h1>", html::escape(&problem.detail)))).into_response();
+        if let Some((name, value)) = self.header() {
+            if let Ok(value) = header::HeaderValue::from_str(&value) {
+                response.headers_mut().insert(name, value);
+            }
+        }
+        response.extensions_mut().insert(problem);
+        response
+    }
+}
+
+// 服務器配置
+#[derive(Clone)]
+struct ServerConfig {
+    base_path: Arc<String>,
+    restricted: Arc<RestrictRules>,
+    hide_restricted: bool,
+    // 以 `.` 開頭的隱藏文件的處理方式（--hidden）
+    hidden: HiddenPolicy,
+    // 符號連結的處理方式（--symlinks）
+    symlinks: SymlinkPolicy,
+    // 列表中顯示符號連結指向的路徑（--show-symlink-targets）
+    show_symlink_targets: bool,
+    // 基礎目錄的 .sfignore，只影響列表、文件清單與封存檔
+    ignore: Arc<IgnoreFile>,
+    // 目前監聽位址提供的功能，每個位址的設定各自獨立
+    routes: ListenerRoutes,
+    // 以網址前綴劃分的網站（`[[vhost]]` 的 prefix，不含斜線），產生連結時加在路徑前；其他網站為空字串
+    mount: Arc<String>,
+    use_plain_html: bool,
+    case_insensitive: bool,
+    name_index: Arc<NameIndex>,
+    suggest: bool,
+    manifest: Option<Arc<Manifest>>,
+    // 檢查送出的文件內容與已知的雜湊是否一致（--verify-reads）
+    integrity: Option<Arc<Integrity>>,
+    journal: Option<Arc<ChangeJournal>>,
+    not_found: Option<Arc<NegativeCache>>,
+    snapshots: Option<Arc<Snapshots>>,
+    latest: Option<Arc<LatestLinks>>,
+    // 預設依版本號排序列表的目錄（--version-sort）
+    version_sort: Option<Arc<GlobSet>>,
+    // 取代內建錯誤訊息的頁面（--error-page），路徑相對於基礎路徑
+    error_pages: Option<Arc<ErrorPages>>,
+    // 目錄請求時依序尋找的索引文件
+    index_files: Arc<Vec<String>>,
+    file_cache: Arc<FileCache>,
+    // 目錄 SHA256SUMS 清單中各文件的雜湊（`?checksums=sha256`）
+    checksums: Arc<Checksums>,
+    cache_warming: Arc<Coalescer<Vec<String>, admin::WarmResult>>,
+    compress: Option<Arc<CompressOptions>>,
+    // 壓縮後的目錄列表，啟用 --compress 時才有
+    listing_cache: Option<Arc<ListingCache>>,
+    precompressed: bool,
+    sniff_mime: bool,
+    // HTML 頁面的預先載入提示（--preload-hints）
+    preload_hints: Option<Arc<PreloadHints>>,
+    // 文件回應的 Cache-Control 規則（--cache-control）
+    cache_control: Arc<CacheRules>,
+    // 給 CDN 的 Surrogate-Control 與快取標籤（--surrogate-control、--surrogate-keys）
+    surrogate: Option<Arc<SurrogateOptions>>,
+    // 自訂回應標頭（--add-header 與設定檔的 `[[header]]`）
+    custom_headers: Arc<CustomHeaders>,
+    // 跨來源請求的允許設定（--cors）
+    cors: Option<Arc<CorsOptions>>,
+    admin_token: Option<Arc<String>>,
+    webhooks: Option<Arc<Webhooks>>,
+    // /_admin/purge 後通知的 CDN 清除 webhook（--purge-webhook）
+    purge_hooks: Option<Arc<Webhooks>>,
+    users: Option<Arc<UserStore>>,
+    access_rules: Option<Arc<AccessRules>>,
+    method_policy: Option<Arc<MethodPolicy>>,
+    ldap: Option<Arc<LdapBackend>>,
+    #[cfg(feature = "pam")]
+    pam: Option<Arc<PamBackend>>,
+    tokens: Option<Arc<TokenStore>>,
+    sessions: Option<Arc<SessionKeys>>,
+    throttle: Arc<LoginThrottle>,
+    transfer_stats: Arc<TransferStats>,
+    // Apache 格式的存取紀錄（--access-log）
+    access_log: Option<Arc<AccessLog>>,
+    // Prometheus 指標（--metrics、--metrics-addr）
+    metrics: Option<Arc<Metrics>>,
+    mp4_check: Option<Arc<Mp4Checker>>,
+    disk: Arc<DiskMonitor>,
+    // 啟動時已確認基礎目錄不可寫入，寫入功能一律停用
+    read_only: bool,
+    // 允許以 PUT 與 multipart POST 上傳文件（--upload）
+    upload: Option<UploadOptions>,
+    // 可續傳上傳的紀錄（--upload-journal）
+    upload_journal: Option<Arc<UploadJournal>>,
+    // WebDAV 刪除或取代的文件先移到這裡（--trash-days）
+    trash: Option<Arc<Holding>>,
+    // 上傳的總容量上限（--quota）
+    quota: Option<Arc<Quota>>,
+    // 每個來源 IP 的請求速率上限（--rate-limit）
+    rate_limit: Option<Arc<RateLimiter>>,
+    // 依路徑指定的頻寬等級（--bandwidth）
+    bandwidth: Arc<BandwidthRules>,
+    // 處理請求時發生 panic 時終止程序，而不是回應 500（--abort-on-panic）
+    abort_on_panic: bool,
+    // 鏡像部分請求到另一個網址（--shadow）
+    shadow: Option<Arc<Shadow>>,
+    // 以 WebDAV 提供目錄樹（--webdav）
+    webdav: bool,
+    // 每個請求的處理期限
+    request_timeout: Option<Duration>,
+}
+
+impl ServerConfig {
+    // 是否設定了任何驗證後端
+    fn requires_auth(&self) -> bool {
+        #[cfg(feature = "pam")]
+        if self.pam.is_some() {
+            return true;
+        }
+        self.users.is_some() || self.ldap.is_some() || self.tokens.is_some()
+    }
+    
+    // 相對路徑對應的網址路徑（不含開頭的 /），加上網站的網址前綴
+    fn url_path(&self, rel: &str) -> String {
+        match (self.mount.is_empty(), rel.is_empty()) {
+            (true, _) => rel.to_string(),
+            (false, true) => self.mount.to_string(),
+            (false, false) => format!("{}/{}", self.mount, rel),
+        }
+    }
+    
+    // 不列入列表、文件清單與封存檔，但仍可直接下載的路徑：.sfignore 排除的路徑，
+    // 以及 `--hidden ignore` 時的隱藏文件
+    fn ignored(&self) -> impl Fn(&str) -> bool + Send + Sync + 'static {
+        let ignored = self.ignore.matcher();
+        let hidden = self.hidden == HiddenPolicy::Ignore;
+        move |path| ignored(path) || (hidden && restrict::is_hidden(path))
+    }
+    
+    // 依登入用戶的根目錄取得實際使用的配置
+    fn scoped_to(&self, user: &User) -> ServerConfig {
+        let mut config = self.clone();
+        if !user.root.is_empty() {
+            let base = FsPath::new(self.base_path.as_str()).join(&user.root);
+            config.base_path = Arc::new(base.to_string_lossy().to_string());
+            config.ignore = Arc::new(self.ignore.scoped(&user.root));
+        }
+        config
+    }
+}
+
+// 檢查是否符合禁止訪問的規則，上傳中的暫存文件與 `--hidden deny` 時的隱藏文件也不能訪問
+//
+// 只有目錄規則（`node_modules/`）符合最後一層名稱時才需要查看文件系統，
+// 這種情況很少，直接在目前的執行緒上 stat。
+fn check_restricted(path: &str, config: &ServerConfig) -> Result<(), ServerError> {
+    let uploading = path.split('/').any(|c| c.starts_with(upload::TEMP_PREFIX)) || holding::is_reserved(path);
+    let hidden = config.hidden == HiddenPolicy::Deny && restrict::is_hidden(path);
+    let is_dir = || FsPath::new(config.base_path.as_str()).join(path).is_dir();
+    if uploading || hidden || config.restricted.is_restricted(path, is_dir) {
+        return Err(ServerError::UnsafePath(path.to_string()));
+    }
+    Ok(())
+}
+
+// 列表、文件清單與封存檔收錄路徑的條件：未被禁止訪問，也未被 .sfignore 排除
+fn published(config: &ServerConfig) -> impl Fn(&str) -> bool + '_ {
+    let ignored = config.ignored();
+    move |rel| check_restricted(rel, config).is_ok() && !ignored(rel)
+}
+
+// 在阻塞執行緒上執行文件系統操作
+//
+// std::fs 的呼叫會佔住執行它的執行緒，緩慢的網路磁碟或很大的目錄會拖住同一個執行緒上的其他連線；
+// 處理請求時的 read_dir、metadata、canonicalize 與讀取都應經過這裡或使用 tokio::fs。
+async fn blocking<T: Send + 'static>(f: impl FnOnce() -> T + Send + 'static) -> Result<T, ServerError> {
+    tokio::task::spawn_blocking(f).await.map_err(|e| ServerError::Internal(e.to_string()))
+}
+
+// 在阻塞執行緒上執行 is_safe_path
+async fn safe_path(path: &str, config: &ServerConfig) -> Result<SafePath, ServerError> {
+    let (path, config) = (path.to_string(), config.clone());
+    blocking(move || is_safe_path(&path, &config)).await?
+}
+
+// 路徑是否為目錄，無法讀取時視為不是
+async fn is_dir(path: &FsPath) -> bool {
+    tokio::fs::metadata(path).await.is_ok_and(|m| m.is_dir())
+}
+
+// 通過 is_safe_path 檢查的路徑
+//
+// `rel` 是正規化（不分大小寫模式下並對應到實際名稱）後的相對路徑。權限與 token 範圍
+// 一律以它判斷，不能用原始網址：`a/../b` 與 `A/x` 實際存取的是 `b` 與 `a/x`。
+struct SafePath {
+    fs_path: PathBuf,
+    rel: String,
+}
+
+// 檢查路徑是否安全
+//
+// 先以字面處理 `.`、`..` 與多餘的斜線：離開基礎目錄的路徑不論是否存在一律回應 403，
+// 不會因為 404 與 403 的差異洩漏基礎目錄外的文件是否存在。之後只在路徑經過符號連結時
+// 才解析實際位置，一般的請求不需要對基礎目錄與文件做 canonicalize。
+fn is_safe_path(path: &str, config: &ServerConfig) -> Result<SafePath, ServerError> {
+    let Some(normalized) = restrict::normalize_within(path) else {
+        return Err(ServerError::UnsafePath(path.to_string()));
+    };
+    check_restricted(&normalized, config)?;
+    
+    // 不分大小寫模式下，先將路徑對應到磁碟上的實際名稱
+    let base = FsPath::new(config.base_path.as_str());
+    let resolved = if config.case_insensitive {
+        match config.name_index.resolve(base, &normalized) {
+            Some(resolved) => {
+                // 實際名稱也必須通過禁止規則，避免以大小寫差異繞過
+                check_restricted(&resolved, config)?;
+                resolved
+            }
+            None => normalized,
+        }
+    } else {
+        normalized
+    };
+    
+    let fs_path = base.join(&resolved);
+    if let Some(not_found) = &config.not_found {
+        if not_found.contains(&fs_path) {
+            return Err(ServerError::NotFound(path.to_string()));
+        }
+    }
+    let missing = |e: io::Error| match e.kind() {
+        io::ErrorKind::NotFound | io::ErrorKind::NotADirectory => {
+            if let Some(not_found) = &config.not_found {
+                not_found.insert(&fs_path);
+            }
+            ServerError::NotFound(path.to_string())
+        }
+        _ => ServerError::Filesystem(e),
+    };
+    
+    // `--symlinks follow` 時不論指向何處都跟隨；其他模式在經過符號連結時才需要檢查
+    let crosses_symlink = config.symlinks != SymlinkPolicy::Follow
+        && restrict::crosses_symlink(base, &resolved).map_err(ServerError::Filesystem)?;
+    if crosses_symlink {
+        if config.symlinks == SymlinkPolicy::Deny {
+            return Err(ServerError::UnsafePath(path.to_string()));
+        }
+        let canonical_base = fs::canonicalize(base).map_err(ServerError::Filesystem)?;
+        let canonical_path = fs::canonicalize(&fs_path).map_err(missing)?;
+        if !canonical_path.starts_with(canonical_base) {
+            return Err(ServerError::UnsafePath(path.to_string()));
+        }
+    } else {
+        fs::metadata(&fs_path).map_err(missing)?;
+    }
+    
+    Ok(SafePath { fs_path, rel: resolved })
+}
+
+// 產生附帶相近名稱建議的 404 響應
+async fn not_found_response(path: &str, config: &ServerConfig) -> axum::response::Response {
+    let trimmed = path.trim_end_matches('/');
+    let (parent, name) = trimmed.rsplit_once('/').unwrap_or(("", trimmed));
+    
+    // 只從可訪問的目錄中提供建議，並排除禁止訪問與被 .sfignore 排除的項目
+    let (parent, name, scan_config) = (parent.to_string(), name.to_string(), config.clone());
+    let suggestions: Vec<String> = blocking(move || {
+        let published = published(&scan_config);
+        match is_safe_path(&parent, &scan_config) {
+            Ok(dir) => suggest::close_matches(&dir.fs_path, &name)
+                .into_iter()
+                .map(|s| if parent.is_empty() { s } else { format!("{}/{}", parent, s) })
+                .filter(|s| published(s))
+                .collect(),
+            Err(_) => Vec::new(),
+        }
+    })
+    .await
+    .unwrap_or_default();
+    
+    let mut html = format!("<h1>找不到路徑: {}</h1>", html::escape(path));
+    if !suggestions.is_empty() {
+        html.push_str("\n<p>您要找的是不是：</p>\n<ul>\n");
+        for suggestion in &suggestions {
+            html.push_str(&format!(
+                "<li><a href=\"{}\">{}</a></li>\n",
+                html::href(&config.url_path(suggestion)),
+                html::escape(suggestion)
+            ));
+        }
+        html.push_str("</ul>");
+    }
+    
+    let problem = Problem::new(StatusCode::NOT_FOUND, "not_found", format!("找不到路徑: {}", path))
+        .with("suggestions", json!(suggestions));
+    let mut response = (StatusCode::NOT_FOUND, Html(html)).into_response();
+    response.extensions_mut().insert(problem);
+    response
+}
+
+// 下載整個目錄的封存檔：有預先產生的快照時直接提供，否則邊走訪邊產生
+//
+// 禁止訪問、被 .sfignore 排除與用戶沒有讀取權限的文件都不會放入封存檔。
+// 快照是磁碟上的完整文件，與一般文件相同支援 ETag 與範圍請求，中斷的下載可以續傳；
+// 邊走訪邊產生的封存檔每次內容可能不同，不支援範圍請求。
+#[allow(clippy::too_many_arguments)]
+async fn serve_archive(
+    dir: &str,
+    fs_path: &FsPath,
+    format: ArchiveFormat,
+    config: &ServerConfig,
+    user: Option<Arc<User>>,
+    headers: &HeaderMap,
+    deadline: Deadline,
+    head: bool,
+) -> Result<axum::response::Response, ServerError> {
+    // 快照以基礎路徑建立，有自己子目錄的用戶不適用
+    let snapshot = match config.snapshots.clone().filter(|_| user.as_ref().is_none_or(|user| user.root.is_empty())) {
+        Some(snapshots) => {
+            let dir = dir.to_string();
+            blocking(move || snapshots.get(&dir, format)).await?
+        }
+        None => None,
+    };
+    if let Some(path) = snapshot {
+        // 快照以改名的方式替換，開啟後讀取的都是同一個版本
+        let mut file = tokio::fs::File::open(&path).await.map_err(ServerError::Filesystem)?;
+        let metadata = file.metadata().await.map_err(ServerError::Filesystem)?;
+        let modified = metadata.modified().map_err(ServerError::Filesystem)?;
+        let modified_str = http_date::format(modified);
+        let etag = file_etag(&metadata);
+        
+        let not_modified = if headers.contains_key(header::IF_NONE_MATCH) {
+            etag_matches(headers, &etag)
+        } else {
+            unmodified_since(headers, modified)
+        };
+        if not_modified {
+            return Ok((
+                StatusCode::NOT_MODIFIED,
+                [(header::ETAG, etag), (header::LAST_MODIFIED, modified_str)],
+            )
+                .into_response());
+        }
+        
+        let len = metadata.len();
+        let (status, start, content_length) = match ByteRange::from_headers(headers, len, &[&etag, &modified_str]) {
+            ByteRange::Full => (StatusCode::OK, 0, len),
+            ByteRange::Partial { start, end } => (StatusCode::PARTIAL_CONTENT, start, end - start + 1),
+            ByteRange::Unsatisfiable => return Err(ServerError::RangeNotSatisfiable(len)),
+        };
+        let body = match head {
+            true => Body::empty(),
+            false => {
+                if start > 0 {
+                    file.seek(io::SeekFrom::Start(start)).await.map_err(ServerError::Filesystem)?;
+                }
+                Body::from_stream(transfer::file(file, content_length))
+            }
+        };
+        let mut response = axum::response::Response::builder()
+            .status(status)
+            .header(header::CONTENT_TYPE, format.content_type())
+            .header(header::CONTENT_LENGTH, content_length)
+            .header(header::CONTENT_DISPOSITION, format.disposition(dir))
+            .header(header::ACCEPT_RANGES, "bytes")
+            .header(header::ETAG, etag)
+            .header(header::LAST_MODIFIED, modified_str)
+            // 快照會依排程重新產生，每次都要確認
+            .header(header::CACHE_CONTROL, "no-cache");
+        if status == StatusCode::PARTIAL_CONTENT {
+            response = response.header(
+                header::CONTENT_RANGE,
+                format!("bytes {}-{}/{}", start, start + content_length - 1, len),
+            );
+        }
+        return response.body(body).map_err(|e| ServerError::Internal(e.to_string()));
+    }
+    let headers = [
+        (header::CONTENT_TYPE, format.content_type().to_string()),
+        (header::CONTENT_DISPOSITION, format.disposition(dir)),
+        (header::ACCEPT_RANGES, "none".to_string()),
+    ];
+    // 邊走訪邊產生的封存檔沒有預知的長度，HEAD 請求不必走訪
+    if head {
+        return Ok((headers, transfer::omitted()).into_response());
+    }
+    let (fs_path, rel_dir, config) = (fs_path.to_path_buf(), dir.to_string(), config.clone());
+    let body = transfer::generated(deadline, move |out| {
+        // 超過期限時不再往下走訪，寫入時以逾時錯誤結束
+        let published = published(&config);
+        let allow = |rel: &str| {
+            !deadline.expired()
+                && published(rel)
+                && user.as_ref().is_none_or(|user| {
+                    let tree_path = format!("{}/{}", user.root, rel);
+                    user.can_access(config.access_rules.as_deref(), &tree_path, Permission::Read)
+                })
+        };
+        let files = archive::collect(&fs_path, &rel_dir, &allow)?;
+        deadline.check()?;
+        archive::write(format, &files, out).map(|_| ())
+    });
+    Ok((headers, Body::from_stream(body)).into_response())
+}
+
+// 以 SHA256SUMS 格式回應目錄中各文件的 SHA-256
+//
+// 只包含目錄中直接的一般文件，與封存檔相同排除禁止訪問、被 .sfignore 排除與用戶沒有讀取權限的文件。
+async fn serve_checksums(
+    dir: &str,
+    fs_path: &FsPath,
+    config: &ServerConfig,
+    user: Option<Arc<User>>,
+    headers: &HeaderMap,
+    deadline: Deadline,
+    head: bool,
+) -> Result<axum::response::Response, ServerError> {
+    let (fs_path, rel_dir, scan_config) = (fs_path.to_path_buf(), dir.to_string(), config.clone());
+    let files = blocking(move || {
+        let published = published(&scan_config);
+        let allow = |name: &str| {
+            let rel = match rel_dir.is_empty() {
+                true => name.to_string(),
+                false => format!("{}/{}", rel_dir.trim_end_matches('/'), name),
+            };
+            published(&rel)
+                && user.as_ref().is_none_or(|user| {
+                    let tree_path = format!("{}/{}", user.root, rel);
+                    user.can_access(scan_config.access_rules.as_deref(), &tree_path, Permission::Read)
+                })
+        };
+        checksums::collect(&fs_path, &allow)
+    })
+    .await?
+    .map_err(ServerError::Filesystem)?;
+    let etag = checksums::etag(&files);
+    if etag_matches(headers, &etag) {
+        return Ok((StatusCode::NOT_MODIFIED, [(header::ETAG, etag)]).into_response());
+    }
+    let response_headers = [
+        (header::CONTENT_TYPE, "text/plain; charset=utf-8".to_string()),
+        (header::CONTENT_DISPOSITION, "inline; filename=\"SHA256SUMS\"".to_string()),
+        (header::ETAG, etag),
+        (header::CACHE_CONTROL, "no-cache".to_string()),
+    ];
+    if head {
+        return Ok((response_headers, transfer::omitted()).into_response());
+    }
+    let (checksums, manifest) = (config.checksums.clone(), config.manifest.clone());
+    let document = blocking(move || checksums.document(&files, manifest.as_deref(), deadline))
+        .await?
+        .map_err(|e| match e.kind() {
+            io::ErrorKind::TimedOut => ServerError::Timeout(dir.to_string()),
+            _ => ServerError::Filesystem(e),
+        })?;
+    Ok((response_headers, document).into_response())
+}
+
+// 目錄列表的排序欄位
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum SortColumn {
+    Name,
+    Modified,
+    Size,
+    // 名稱中的數字以數值比較，app-1.10.0 排在 app-1.9.0 之後
+    Version,
+}
+
+// 目錄列表排序方式，對應 Apache mod_autoindex 的 ?C=M;O=D 查詢參數
+#[derive(Clone, Copy)]
+struct ListingSort {
+    column: SortColumn,
+    descending: bool,
+}
+
+impl ListingSort {
+    // 未指定欄位時依 `column` 排序，`--version-sort` 的目錄預設依版本號排序
+    fn from_query(query: Option<&str>, column: SortColumn) -> Self {
+        let mut sort = ListingSort {
+            column,
+            descending: false,
+        };
+        
+        for pair in query.unwrap_or("").split([';', '&']) {
+            match pair.split_once('=') {
+                Some(("C", "N")) => sort.column = SortColumn::Name,
+                Some(("C", "M")) => sort.column = SortColumn::Modified,
+                Some(("C", "S")) => sort.column = SortColumn::Size,
+                Some(("C", "V")) => sort.column = SortColumn::Version,
+                Some(("O", "A")) => sort.descending = false,
+                Some(("O", "D")) => sort.descending = true,
+                _ => {}
+            }
+        }
+        
+        sort
+    }
+}
+
+// 生成目錄索引HTML
+fn generate_directory_html(
+    path: &str,
+    entries: Vec<fs::DirEntry>,
+    use_plain_html: bool,
+    sort: ListingSort,
+    // 上傳表單送出的網址，不顯示表單時為 None
+    upload_form: Option<&str>,
+    show_symlink_targets: bool,
+    html: &mut ChunkWriter,
+) -> io::Result<()> {
+    let mut dir_entries = Vec::new();
+    
+    for entry in entries {
+        let file_name = entry.file_name().to_string_lossy().to_string();
+        let file_type = entry.file_type()?;
+        let metadata = entry.metadata()?;
+        let target = match show_symlink_targets && file_type.is_symlink() {
+            true => listing::symlink_target(&entry.path()),
+            false => None,
+        };
+        dir_entries.push((file_name, file_type, metadata, target));
+    }
+    
+    if use_plain_html {
+        // 與 nginx/Apache autoindex 相同的版面，讓 wget、lftp 等鏡像工具可以正確解析
+        let dir = path.trim_matches('/');
+        let title = if dir.is_empty() { "/".to_string() } else { format!("/{}/", dir) };
+        
+        // 目錄永遠排在前面，其餘依查詢參數指定的欄位排序
+        dir_entries.sort_by(|a, b| {
+            let order = match sort.column {
+                SortColumn::Name => a.0.cmp(&b.0),
+                SortColumn::Modified => a.2.modified().ok().cmp(&b.2.modified().ok()),
+                SortColumn::Size => a.2.len().cmp(&b.2.len()),
+                SortColumn::Version => listing::version_cmp(&a.0, &b.0),
+            };
+            let order = if sort.descending { order.reverse() } else { order };
+            b.1.is_dir().cmp(&a.1.is_dir()).then(order)
+        });
+        
+        let title = html::escape(&title);
+        html.push_str(&format!("<html>\r\n<head><title>Index of {}</title></head>\r\n", title));
+        html.push_str(&format!("<body>\r\n<h1>Index of {}</h1><hr><pre>", title));
+        
+        // 欄位標題的排序連結，目前排序的欄位點擊後反轉順序
+        let sort_link = |column: SortColumn, code: char, label: &str| {
+            let order = if sort.column == column && !sort.descending { 'D' } else { 'A' };
+            format!("<a href=\"?C={};O={}\">{}</a>", code, order, label)
+        };
+        html.push_str(&sort_link(SortColumn::Name, 'N', "Name"));
+        html.push_str(&" ".repeat(50 - "Name".len() + 1));
+        html.push_str(&sort_link(SortColumn::Modified, 'M', "Last modified"));
+        html.push_str(&" ".repeat(17 - "Last modified".len() + 1 + 7 - "Size".len()));
+        html.push_str(&sort_link(SortColumn::Size, 'S', "Size"));
+        html.push_str("\r\n<a href=\"../\">../</a>\r\n");
+        
+        for (file_name, file_type, metadata, target) in dir_entries {
+            if html.is_closed() {
+                break;
+            }
+            let is_dir = file_type.is_dir();
+            let display_name = if is_dir {
+                format!("{}/", file_name)
+            } else {
+                file_name.clone()
+            };
+            
+            // 目錄連結保留結尾斜線，鏡像工具據此判斷是否遞迴
+            let href = if dir.is_empty() {
+                display_name.clone()
+            } else {
+                format!("{}/{}", dir, display_name)
+            };
+            
+            // 超過 50 個字元的名稱截斷，與 nginx 相同
+            let name_len = display_name.chars().count();
+            let shown_name = if name_len > 50 {
+                format!("{}..&gt;", html::escape(&display_name.chars().take(47).collect::<String>()))
+            } else {
+                html::escape(&display_name)
+            };
+            let padding = " ".repeat(50_usize.saturating_sub(name_len));
+            
+            let date_str = metadata.modified().ok().map(|t| {
+                let datetime: DateTime<Local> = t.into();
+                datetime.format("%d-%b-%Y %H:%M").to_string()
+            }).unwrap_or_else(|| "-".to_string());
+            
+            let size_str = if is_dir {
+                "-".to_string()
+            } else {
+                let sz = metadata.len();
+                if sz > 1024*1024*1024-1 {
+                    format!("{:>6}G", (sz + 1024*1024*1024/2)/(1024*1024*1024))
+                } else if sz > 1024*1024-1 {
+                    format!("{:>6}M", (sz + 1024*1024/2)/(1024*1024))
+                } else if sz > 9999 {
+                    format!("{:>6}K", (sz + 1024/2)/1024)
+                } else {
+                    sz.to_string()
+                }
+            };
+            
+            // 與 nginx 的版面相同，指向的路徑加在行尾，不影響鏡像工具解析
+            let target = target.map(|t| format!(" -&gt; {}", html::escape(&t))).unwrap_or_default();
+            html.push_str(&format!(
+                "<a href=\"{}\">{}</a>{} {} {:>7}{}\r\n",
+                html::href(&href),
+                shown_name,
+                padding,
+                date_str,
+                size_str,
+                target
+            ));
+        }
+        
+        html.push_str("</pre><hr></body>\r\n</html>\r\n");
+        Ok(())
+    } else {
+        // 美化版HTML
+        match sort.column {
+            SortColumn::Version => dir_entries.sort_by(|a, b| {
+                let order = listing::version_cmp(&a.0, &b.0);
+                if sort.descending { order.reverse() } else { order }
+            }),
+            _ => dir_entries.sort_by_key(|a| a.0.to_lowercase()),
+        }
+        
+        let title = html::escape(path);
+        html.push_str(&format!("<html>\n<head>\n<title>Index of /{}</title>\n", title));
+        html.push_str("<style>\n");
+        html.push_str("body { font-family: system-ui, -apple-system, sans-serif; padding: 2em; }\n");
+        html.push_str("table { border-collapse: collapse; width: 100%; }\n");
+        html.push_str("th, td { text-align: left; padding: 8px; }\n");
+        html.push_str("tr:nth-child(even) { background-color: #f2f2f2; }\n");
+        html.push_str("th { background-color: #4CAF50; color: white; }\n");
+        html.push_str("a { text-decoration: none; }\n");
+        html.push_str("a:hover { text-decoration: underline; }\n");
+        html.push_str("</style>\n</head>\n");
+        
+        html.push_str(&format!("<body>\n<h1>Index of /{}</h1>\n", title));
+        html.push_str("<table>\n<tr><th>Name</th><th>Last Modified</th><th>Size</th></tr>\n");
+        
+        // 返回上一層目錄的連結
+        html.push_str("<tr><td><a href=\"../\">../</a></td><td></td><td></td></tr>\n");
+        
+        for (file_name, file_type, metadata, target) in dir_entries {
+            if html.is_closed() {
+                break;
+            }
+            let is_dir = file_type.is_dir();
+            let href = if path.is_empty() {
+                file_name.clone()
+            } else {
+                format!("{}/{}", path, file_name)
+            };
+            
+            // 符號連結與 `ls -F` 相同以 `@` 標示
+            let display_name = if is_dir {
+                format!("{}/", file_name)
+            } else if file_type.is_symlink() {
+                format!("{}@", file_name)
+            } else {
+                file_name.clone()
+            };
+            let target = target.map(|t| format!(" &rarr; {}", html::escape(&t))).unwrap_or_default();
+            
+            let modified_time = metadata.modified().ok().map(|t| {
+                let datetime: DateTime<Local> = t.into();
+                datetime.format("%d-%b-%Y %H:%M").to_string()
+            }).unwrap_or_else(|| "-".to_string());
+            
+            let size = if is_dir {
+                "-"
+            } else {
+                let sz = metadata.len();
+                if sz > 1024*1024*1024 {
+                    &format!("{:.1} GB", sz as f64 / (1024.0*1024.0*1024.0))
+                } else if sz > 1024*1024 {
+                    &format!("{:.1} MB", sz as f64 / (1024.0*1024.0))
+                } else if sz > 1024 {
+                    &format!("{:.1} KB", sz as f64 / 1024.0)
+                } else {
+                    &format!("{} B", sz)
+                }
+            };
+            
+            html.push_str(&format!(
+                "<tr><td><a href=\"{}\">{}</a>{}</td><td>{}</td><td>{}</td></tr>\n",
+                html::href(&href),
+                html::escape(&display_name),
+                target,
+                modified_time,
+                size
+            ));
+        }
+        
+        html.push_str("</table>\n");
+        if let Some(action) = upload_form {
+            html.push_str(&format!(
+                "<form method=\"post\" action=\"{}\" enctype=\"multipart/form-data\">\n\
+                 <p><input type=\"file\" name=\"file\" multiple required> <button type=\"submit\">Upload</button></p>\n</form>\n",
+                html::escape(action)
+            ));
+        }
+        html.push_str("<hr>\n<p style=\"font-size: 0.8em; color: #666;\">Powered by Rust Static Server</p>\n");
+        html.push_str("</body>\n</html>");
+        
+        Ok(())
+    }
+}
+
+// 目錄列表的弱 ETag，由各項目的名稱、大小、修改時間與列表呈現方式計算
+fn listing_etag(
+    path: &str,
+    entries: &[fs::DirEntry],
+    format: ListingFormat,
+    use_plain_html: bool,
+    sort: ListingSort,
+    upload_form: Option<&str>,
+) -> String {
+    let mut items: Vec<String> = entries
+        .iter()
+        .map(|entry| {
+            let metadata = entry.metadata().ok();
+            let modified = metadata
+                .as_ref()
+                .and_then(|m| m.modified().ok())
+                .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+                .map(|d| d.as_nanos())
+                .unwrap_or_default();
+            let len = metadata.map(|m| m.len()).unwrap_or_default();
+            format!("{}\0{}\0{}", entry.file_name().to_string_lossy(), len, modified)
+        })
+        .collect();
+    items.sort();
+    
+    let mut hasher = Sha256::new();
+    hasher.update(format!(
+        "{}\0{:?}\0{}\0{:?}\0{}\0{:?}\n",
+        path, format, use_plain_html, sort.column, sort.descending, upload_form
+    ));
+    for item in &items {
+        hasher.update(item.as_bytes());
+        hasher.update(b"\n");
+    }
+    let digest = hasher.finalize();
+    let hex: String = digest[..8].iter().map(|b| format!("{:02x}", b)).collect();
+    format!("W/\"{}\"", hex)
+}
+
+// 用戶端接受的預先壓縮同名文件（`.br`、`.zst`、`.gz`），與原文件一樣須通過路徑安全檢查
+async fn precompressed_variant(path: &str, headers: &HeaderMap, config: &ServerConfig) -> Option<(PathBuf, Encoding)> {
+    let (path, config) = (path.to_string(), config.clone());
+    let available: Vec<(Encoding, PathBuf)> = blocking(move || {
+        [Encoding::Brotli, Encoding::Zstd, Encoding::Gzip]
+            .into_iter()
+            .filter_map(|encoding| {
+                let variant = is_safe_path(&format!("{}{}", path, encoding.extension()), &config).ok()?.fs_path;
+                variant.is_file().then_some((encoding, variant))
+            })
+            .collect()
+    })
+    .await
+    .ok()?;
+    if available.is_empty() {
+        return None;
+    }
+    
+    let encodings: Vec<Encoding> = available.iter().map(|(encoding, _)| *encoding).collect();
+    let chosen = compress::negotiate(headers, &encodings)?;
+    available.into_iter().find(|(encoding, _)| *encoding == chosen).map(|(encoding, path)| (path, encoding))
+}
+
+// 文件的 ETag，由大小與修改時間（奈秒）組成，不需要讀取內容
+fn file_etag(metadata: &fs::Metadata) -> String {
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|d| d.as_nanos())
+        .unwrap_or_default();
+    format!("\"{:x}-{:x}\"", metadata.len(), modified)
+}
+
+// 文件在 If-Modified-Since 的時間之後是否沒有修改，標頭無法解析時視為沒有此標頭
+//
+// 以時間而非字串比較，用戶端送回的格式稍有不同（或送出較新的時間）時仍能得到 304。
+fn unmodified_since(headers: &HeaderMap, modified: SystemTime) -> bool {
+    headers
+        .get(header::IF_MODIFIED_SINCE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(http_date::parse)
+        .is_some_and(|since| http_date::not_modified_since(modified, &since))
+}
+
+// If-None-Match 是否符合目前的 ETag（弱比較）
+fn etag_matches(headers: &HeaderMap, etag: &str) -> bool {
+    let Some(value) = headers.get(header::IF_NONE_MATCH).and_then(|v| v.to_str().ok()) else {
+        return false;
+    };
+    let opaque = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+    value.trim() == "*" || value.split(',').any(|tag| opaque(tag) == opaque(etag))
+}
+
+// 目錄中第一個存在且可訪問的索引文件
+async fn find_index(dir: &str, config: &ServerConfig) -> Option<(String, PathBuf)> {
+    let (dir, config) = (dir.to_string(), config.clone());
+    blocking(move || {
+        config.index_files.iter().find_map(|name| {
+            let rel = format!("{}/{}", dir.trim_end_matches('/'), name);
+            let rel = rel.trim_start_matches('/').to_string();
+            let path = is_safe_path(&rel, &config).ok()?;
+            path.fs_path.is_file().then_some((path.rel, path.fs_path))
+        })
+    })
+    .await
+    .ok()?
+}
+
+// `/__zip/<目錄>` 與 `<目錄>/?zip` 相同，分享給不熟悉查詢字串的人較直觀
+async fn serve_zip(
+    state: State<ServerConfig>,
+    deadline: Deadline,
+    user: Option<Extension<Arc<User>>>,
+    path: Option<Path<String>>,
+    uri: Uri,
+    method: Method,
+    headers: HeaderMap,
+) -> Result<axum::response::Response, ServerError> {
+    let path = path.unwrap_or_else(|| Path(String::new()));
+    let scoped = match &user {
+        Some(Extension(user)) => state.scoped_to(user),
+        None => state.0.clone(),
+    };
+    if !is_dir(&safe_path(&path, &scoped).await?.fs_path).await {
+        return Err(ServerError::NotFound(path.0));
+    }
+    serve_static(state, deadline, user, Some(path), RawQuery(Some("zip".to_string())), uri, method, headers)
+        .await
+        .map(IntoResponse::into_response)
+}
+
+// 處理靜態文件或目錄請求
+#[allow(clippy::too_many_arguments)]
+async fn serve_static(
+    State(config): State<ServerConfig>,
+    deadline: Deadline,
+    user: Option<Extension<Arc<User>>>,
+    path: Option<Path<String>>,
+    RawQuery(query): RawQuery,
+    uri: Uri,
+    method: Method,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, ServerError> {
+    let path_str = match path {
+        Some(Path(p)) => p,
+        None => "".to_string(),
+    };
+    // HEAD 回應與 GET 的狀態碼及標頭相同，只是不讀取也不產生內容
+    let head = method == Method::HEAD;
+    
+    // latest 連結以基礎路徑為準，有自己子目錄的用戶不適用
+    if let Some(latest) = config.latest.as_ref().filter(|_| user.as_ref().is_none_or(|u| u.root.is_empty())) {
+        match latest.resolve(&path_str) {
+            Some(Some(target)) => {
+                let location = match &query {
+                    Some(query) => format!("/{}?{}", utf8_percent_encode(&config.url_path(&target), listing::HREF), query),
+                    None => format!("/{}", utf8_percent_encode(&config.url_path(&target), listing::HREF)),
+                };
+                return Ok((
+                    StatusCode::FOUND,
+                    [(header::LOCATION, location), (header::CACHE_CONTROL, "no-cache".to_string())],
+                )
+                    .into_response());
+            }
+            Some(None) => return Err(ServerError::NotFound(path_str)),
+            None => {}
+        }
+    }
+    
+    // 已登入的用戶只能看到自己的子目錄
+    let config = match &user {
+        Some(Extension(user)) => config.scoped_to(user),
+        None => config,
+    };
+    let SafePath { fs_path, rel } = match safe_path(&path_str, &config).await {
+        Err(ServerError::NotFound(p)) if config.suggest => {
+            return Ok(not_found_response(&p, &config).await);
+        }
+        result => result?,
+    };
+    
+    // 目錄有索引文件時改為提供該文件；指定封存或列表格式時仍是整個目錄
+    let explicit = ArchiveFormat::from_query(query.as_deref()).is_some()
+        || ListingFormat::from_query(query.as_deref()).is_some()
+        || checksums::from_query(query.as_deref()).is_some();
+    let (rel, fs_path) = match !explicit && is_dir(&fs_path).await {
+        true => match find_index(&rel, &config).await {
+            // 索引頁中的相對連結以目錄為基準，缺少結尾斜線時先轉址
+            Some(_) if !path_str.is_empty() && !path_str.ends_with('/') => {
+                let location = match &query {
+                    Some(query) => format!("{}/?{}", uri.path(), query),
+                    None => format!("{}/", uri.path()),
+                };
+                return Ok(Redirect::permanent(&location).into_response());
+            }
+            Some(index) => index,
+            None => (rel, fs_path),
+        },
+        false => (rel, fs_path),
+    };
+    // 之後的權限檢查、封存檔、列表與快取規則一律使用正規化後的路徑，不再使用原始網址
+    let path_str = rel;
+    
+    let allowed = |permission: Permission| match &user {
+        Some(Extension(user)) => {
+            let tree_path = format!("{}/{}", user.root, path_str);
+            if user.can_access(config.access_rules.as_deref(), &tree_path, permission) {
+                Ok(())
+            } else {
+                info!("用戶 {} 缺少 {:?} 權限: /{}", user.name, permission, path_str);
+                Err(ServerError::UnsafePath(path_str.clone()))
+            }
+        }
+        None => Ok(()),
+    };
+    
+    let file_type = tokio::fs::metadata(&fs_path).await.ok().map(|metadata| metadata.file_type());
+    if file_type.is_some_and(|t| t.is_dir()) {
+        if !config.routes.listings {
+            return Err(ServerError::NotFound(path_str));
+        }
+        allowed(Permission::List)?;
+        if let Some(format) = ArchiveFormat::from_query(query.as_deref()) {
+            allowed(Permission::Read)?;
+            let user = user.map(|Extension(user)| user);
+            return serve_archive(&path_str, &fs_path, format, &config, user, &headers, deadline, head).await;
+        }
+        if let Some(algorithm) = checksums::from_query(query.as_deref()) {
+            algorithm.map_err(|name| ServerError::BadRequest(format!("不支援的雜湊演算法 {}，可使用 sha256", name)))?;
+            allowed(Permission::Read)?;
+            let user = user.map(|Extension(user)| user);
+            return serve_checksums(&path_str, &fs_path, &config, user, &headers, deadline, head).await;
+        }
+        let default_column = match &config.version_sort {
+            Some(dirs) if dirs.is_match(path_str.trim_matches('/')) => SortColumn::Version,
+            _ => SortColumn::Name,
+        };
+        let sort = ListingSort::from_query(query.as_deref(), default_column);
+        let format = ListingFormat::negotiate(query.as_deref(), &headers);
+        let use_plain_html = config.use_plain_html;
+        let show_targets = config.show_symlink_targets;
+        
+        // 可以上傳時在美化版列表加上上傳表單；以登入階段驗證時表單需附上 CSRF token
+        let can_upload = config.upload.is_some()
+            && user.as_ref().is_none_or(|Extension(user)| {
+                let tree_path = format!("{}/{}", user.root, path_str);
+                user.can_access(config.access_rules.as_deref(), &tree_path, Permission::Write)
+            });
+        let upload_form = can_upload.then(|| match csrf::current_token(&headers) {
+            Some(token) => format!("?{}={}", csrf::FIELD_NAME, token),
+            None => String::new(),
+        });
+        
+        // 讀取目錄與各項目的 metadata 在阻塞執行緒上進行，很大的目錄不會拖住其他連線
+        let (dir, rel_dir, scan_config, form) = (fs_path.clone(), path_str.clone(), config.clone(), upload_form.clone());
+        let (entries, etag) = blocking(move || {
+            let mut entries = fs::read_dir(&dir)?.collect::<Result<Vec<_>, _>>()?;
+            
+            let ignored = scan_config.ignored();
+            entries.retain(|entry| {
+                let name = entry.file_name().to_string_lossy().to_string();
+                !ignored(&if rel_dir.is_empty() { name } else { format!("{}/{}", rel_dir, name) })
+            });
+            
+            // 不列出禁止訪問的項目，避免洩漏其存在
+            if scan_config.hide_restricted {
+                entries.retain(|entry| {
+                    let name = entry.file_name().to_string_lossy().to_string();
+                    let rel = if rel_dir.is_empty() { name } else { format!("{}/{}", rel_dir, name) };
+                    check_restricted(&rel, &scan_config).is_ok()
+                });
+            }
+            let etag = listing_etag(&rel_dir, &entries, format, use_plain_html, sort, form.as_deref());
+            Ok::<_, io::Error>((entries, etag))
+        })
+        .await?
+        .map_err(ServerError::Filesystem)?;
+        if etag_matches(&headers, &etag) {
+            return Ok((StatusCode::NOT_MODIFIED, [(header::ETAG, etag)]).into_response());
+        }
+        
+        // 同一份列表已以用戶端接受的編碼壓縮過時直接送出，不再重新產生與壓縮
+        let variant = match (&config.listing_cache, &config.compress) {
+            (Some(cache), Some(options)) if !head => {
+                compress::on_the_fly(&headers).map(|encoding| (cache.clone(), encoding, options.level))
+            }
+            _ => None,
+        };
+        if let Some((cache, encoding, _)) = &variant {
+            if let Some(cached) = cache.get(&etag, *encoding) {
+                // 列表的 ETag 本來就是弱 ETag，壓縮後不必改寫
+                return Ok((
+                    [
+                        (header::CONTENT_TYPE, format.content_type()),
+                        (header::CONTENT_ENCODING, encoding.name()),
+                        (header::ETAG, etag.as_str()),
+                        (header::CACHE_CONTROL, "no-cache"),
+                        (header::VARY, "Accept, Accept-Encoding"),
+                    ],
+                    cached,
+                )
+                    .into_response());
+            }
+        }
+        
+        // 列表中的連結與標題使用網址路徑
+        let listing_path = config.url_path(&path_str);
+        let body = match head {
+            true => transfer::omitted(),
+            false => {
+                let listing = transfer::generated(deadline, move |out| match format {
+                    ListingFormat::Html => {
+                        let upload_form = upload_form.as_deref();
+                        generate_directory_html(&listing_path, entries, use_plain_html, sort, upload_form, show_targets, out)
+                    }
+                    ListingFormat::Json => listing::write_json(&listing_path, entries, sort, show_targets, out),
+                    ListingFormat::Text => listing::write_text(entries, sort, show_targets, out),
+                    ListingFormat::Markdown => listing::write_markdown(entries, sort, show_targets, out),
+                });
+                match variant {
+                    Some((cache, encoding, level)) => Body::from_stream(cache.capture(etag.clone(), encoding, level, listing)),
+                    None => Body::from_stream(listing),
+                }
+            }
+        };
+        // 格式可能取決於 Accept 標頭，快取須分開存放
+        Ok((
+            [
+                (header::CONTENT_TYPE, format.content_type()),
+                (header::ETAG, etag.as_str()),
+                (header::CACHE_CONTROL, "no-cache"),
+                (header::VARY, "Accept"),
+            ],
+            body,
+        )
+            .into_response())
+    } else if file_type.is_some_and(|t| t.is_file()) {
+        if !config.routes.files {
+            return Err(ServerError::NotFound(path_str));
+        }
+        allowed(Permission::Read)?;
+        
+        // 有預先壓縮的同名文件且用戶端接受該編碼時改送該文件，內容類型仍依原文件
+        let variant = match config.precompressed {
+            true => precompressed_variant(&path_str, &headers, &config).await,
+            false => None,
+        };
+        let (serve_path, content_encoding) = match &variant {
+            Some((path, encoding)) => (path.as_path(), Some(encoding.name())),
+            None => (fs_path.as_path(), None),
+        };
+        
+        // 長度以開啟後的文件為準，之後讀取的都是同一個文件
+        let mut file = tokio::fs::File::open(serve_path).await.map_err(ServerError::Filesystem)?;
+        let metadata = file.metadata().await.map_err(ServerError::Filesystem)?;
+        
+        let modified = metadata.modified().map_err(ServerError::Filesystem)?;
+        let modified_str = http_date::format(modified);
+        let etag = file_etag(&metadata);
+        let cache_control = config.cache_control.value(&path_str);
+        
+        // 有 If-None-Match 時只比較 ETag，否則比較 If-Modified-Since 的時間
+        let not_modified = if headers.contains_key(header::IF_NONE_MATCH) {
+            etag_matches(&headers, &etag)
+        } else {
+            unmodified_since(&headers, modified)
+        };
+        if not_modified {
+            return Ok((
+                StatusCode::NOT_MODIFIED,
+                [
+                    (header::ETAG, etag.as_str()),
+                    (header::LAST_MODIFIED, modified_str.as_str()),
+                    (header::CACHE_CONTROL, cache_control.to_str().unwrap_or_default()),
+                ],
+            )
+                .into_response());
+        }
+        
+        // 只記錄警告，不必等待檢查完成
+        if let (Some(checker), None) = (config.mp4_check.clone(), content_encoding) {
+            let (path, metadata) = (fs_path.clone(), metadata.clone());
+            tokio::task::spawn_blocking(move || checker.check(&path, &metadata));
+        }
+        
+        let mut content_type = mime_guess::from_path(&fs_path).first_or_octet_stream().to_string();
+        // 沒有副檔名的文件依內容開頭判斷類型
+        if config.sniff_mime && fs_path.extension().is_none() && content_encoding.is_none() {
+            if let Some(sniffed) = sniff::sniff(&mut file).await.map_err(ServerError::Filesystem)? {
+                content_type = sniffed;
+            }
+        }
+        
+        let file_len = metadata.len();
+        let (status, start, content_length) =
+            match ByteRange::from_headers(&headers, file_len, &[&etag, &modified_str]) {
+                ByteRange::Full => (StatusCode::OK, 0, file_len),
+                ByteRange::Partial { start, end } => (StatusCode::PARTIAL_CONTENT, start, end - start + 1),
+                ByteRange::Unsatisfiable => return Err(ServerError::RangeNotSatisfiable(file_len)),
+            };
+        
+        // 優先使用記憶體快取中的內容，否則邊讀邊送，不需要先把整個文件讀進記憶體
+        let content = match config.file_cache.get(serve_path, &metadata) {
+            _ if head => futures_util::stream::empty().boxed(),
+            Some(content) => {
+                let range = start as usize..(start + content_length) as usize;
+                transfer::chunked(content.slice(range)).boxed()
+            }
+            None => {
+                if start > 0 {
+                    file.seek(io::SeekFrom::Start(start)).await.map_err(ServerError::Filesystem)?;
+                }
+                transfer::file(file, content_length).boxed()
+            }
+        };
+        let content = match &config.integrity {
+            Some(integrity) if status == StatusCode::OK && !head => integrity.wrap(content, serve_path, &metadata).boxed(),
+            _ => content,
+        };
+        
+        // 以區塊送出，傳輸統計才能反映用戶端實際收到的位元組數；
+        // 大型下載需要在完整送出後發送通知，部分內容不算完成下載
+        let body = match &config.webhooks {
+            _ if head => Body::empty(),
+            Some(webhooks) if status == StatusCode::OK && webhooks.wants_download(content_length) => {
+                let webhooks = webhooks.clone();
+                let path = path_str.clone();
+                Body::from_stream(TrackedStream::new(content, content_length, move |bytes| {
+                    webhooks.notify(WebhookEvent::DownloadCompleted { path, bytes });
+                }))
+            }
+            _ => Body::from_stream(content),
+        };
+        
+        let mut response = axum::response::Response::builder()
+            .status(status)
+            .header(header::CONTENT_TYPE, content_type.as_str())
+            .header(header::CONTENT_LENGTH, content_length)
+            .header(header::ACCEPT_RANGES, "bytes")
+            .header(header::ETAG, etag.as_str())
+            .header(header::LAST_MODIFIED, modified_str)
+            .header(header::CACHE_CONTROL, cache_control);
+        if config.precompressed {
+            response = response.header(header::VARY, "Accept-Encoding");
+        }
+        if let Some(encoding) = content_encoding {
+            response = response.header(header::CONTENT_ENCODING, encoding);
+        }
+        if let Some(surrogate) = &config.surrogate {
+            for (name, value) in surrogate.headers(&path_str) {
+                response = response.header(name, value);
+            }
+        }
+        if let Some(hints) = config.preload_hints.clone().filter(|_| status == StatusCode::OK) {
+            if content_type.starts_with("text/html") {
+                let page = fs_path.clone();
+                if let Some(links) = blocking(move || hints.links(&page)).await? {
+                    response = response.header(header::LINK, links.as_str());
+                }
+            }
+        }
+        if status == StatusCode::PARTIAL_CONTENT {
+            response = response.header(
+                header::CONTENT_RANGE,
+                format!("bytes {}-{}/{}", start, start + content_length - 1, file_len),
+            );
+        }
+        let response = response
+            .body(body)
+            .map_err(|e| ServerError::Internal(e.to_string()))?;
+            
+        Ok(response)
+    } else if config.suggest {
+        Ok(not_found_response(&path_str, &config).await)
+    } else {
+        Err(ServerError::NotFound(path_str))
+    }
+}
+
+// 提供整個目錄樹的清單
+async fn serve_manifest(
+    State(config): State<ServerConfig>,
+    user: Option<Extension<Arc<User>>>,
+    headers: HeaderMap,
+) -> Result<axum::response::Response, ServerError> {
+    // 清單涵蓋整個目錄樹，只提供給可讀取全部內容的用戶
+    if let Some(Extension(user)) = &user {
+        if !user.root.is_empty()
+            || !user.can_access(config.access_rules.as_deref(), "", Permission::Read)
+        {
+            return Err(ServerError::UnsafePath("_manifest.json".to_string()));
+        }
+    }
+    
+    let manifest = config
+        .manifest
+        .as_ref()
+        .ok_or_else(|| ServerError::NotFound("_manifest.json".to_string()))?;
+    let rendered = manifest.rendered();
+    
+    if etag_matches(&headers, &rendered.etag) {
+        return Ok((StatusCode::NOT_MODIFIED, [(header::ETAG, rendered.etag.clone())]).into_response());
+    }
+    
+    // 清單已預先壓縮，支援 gzip 的用戶端直接取得壓縮版本
+    let gzip = compress::negotiate(&headers, &[Encoding::Gzip]).is_some();
+    let body = if gzip { rendered.gzip.clone() } else { rendered.json.clone() };
+    
+    let mut response = axum::response::Response::builder()
+        .header(header::CONTENT_TYPE, "application/json")
+        .header(header::ETAG, rendered.etag.as_str())
+        .header(header::CACHE_CONTROL, "no-cache")
+        .header(header::VARY, "Accept-Encoding");
+    if gzip {
+        response = response.header(header::CONTENT_ENCODING, "gzip");
+    }
+    response
+        .body(Body::from(body))
+        .map_err(|e| ServerError::Internal(e.to_string()))
+}
+
+// 文件變動時在背景重建清單
+fn spawn_manifest_refresh(manifest: Arc<Manifest>, config: ServerConfig, watcher: &FsWatcher) {
+    let mut changes = watcher.subscribe();
+    tokio::spawn(async move {
+        loop {
+            match changes.recv().await {
+                Ok(_) | Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => {}
+                Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+            }
+            
+            let manifest = manifest.clone();
+            let config = config.clone();
+            let result = tokio::task::spawn_blocking(move || {
+                manifest.rebuild(&published(&config))
+            })
+            .await;
+            
+            match result {
+                Ok(Ok(())) => info!("已更新文件清單"),
+                Ok(Err(e)) => error!("更新文件清單失敗: {}", e),
+                Err(e) => error!("更新文件清單失敗: {}", e),
+            }
+        }
+    });
+}
+
+// 依監聽位址提供的功能組合路由與中介層
+fn build_app(config: &ServerConfig, routes: ListenerRoutes) -> Router {
+    let mut config = config.clone();
+    config.routes = routes;
+    // 只鏡像提供文件的位址，管理 API 與指標的位址不送出
+    if !routes.files && !routes.listings {
+        config.shadow = None;
+    }
+    
+    let mut app = Router::new();
+    if routes.manifest && config.manifest.is_some() {
+        app = app.route("/_manifest.json", get(serve_manifest));
+    }
+    // 只在啟用時佔用 /api/changes，否則同名的文件照常提供
+    if routes.changes && config.journal.is_some() {
+        app = app.route("/api/changes", get(journal::list_changes));
+    }
+    // 以網址前綴劃分的網站只處理前綴底下的路徑，`path` 參數仍是相對於網站基礎路徑的路徑
+    let mount = match config.mount.is_empty() {
+        true => String::new(),
+        false => format!("/{}", config.mount),
+    };
+    if routes.files || routes.listings {
+        let (mut files, mut root) = (get(serve_static), get(serve_static));
+        if config.upload.is_some() {
+            files = files.put(upload::put).post(upload::post);
+            root = root.post(upload::post);
+        }
+        // PROPFIND、MKCOL 等不是標準方法，無法個別註冊，由 fallback 統一處理
+        if config.webdav {
+            files = files.fallback(webdav::handle);
+            root = root.fallback(webdav::handle);
+        }
+        app = app.route(&format!("{}/*path", mount), files).route(&format!("{}/", mount), root);
+        // 前綴本身轉址到目錄形式，列表中的相對連結才正確
+        if !mount.is_empty() {
+            let location = format!("{}/", mount);
+            app = app.route(&mount, get(move || async move { Redirect::permanent(&location) }));
+        }
+    }
+    if routes.listings {
+        app = app
+            .route(&format!("{}/__zip/*path", mount), get(serve_zip))
+            .route(&format!("{}/__zip/", mount), get(serve_zip))
+            .route(&format!("{}/__zip", mount), get(serve_zip));
+    }
+    // 沒有任何需要驗證的路由時（例如只提供管理 API 或指標的位址）不能加上 route_layer
+    let protected = routes.files
+        || routes.listings
+        || (routes.manifest && config.manifest.is_some())
+        || (routes.changes && config.journal.is_some());
+    if protected {
+        app = app.route_layer(middleware::from_fn_with_state(config.clone(), auth::require_user));
+    }
+    
+    if routes.admin && config.admin_token.is_some() {
+        app = app.merge(admin::routes());
+    }
+    if routes.login && config.sessions.is_some() {
+        app = app.merge(session::routes());
+    }
+    if routes.metrics && config.metrics.is_some() {
+        app = app.route("/metrics", get(metrics::export));
+    }
+    
+    app.layer(middleware::from_fn_with_state(config.clone(), deadline::enforce))
+        .layer(middleware::from_fn_with_state(config.clone(), disk::refuse_writes_when_low))
+        .layer(middleware::from_fn_with_state(config.clone(), readonly::refuse_writes))
+        .layer(middleware::from_fn_with_state(config.clone(), compress::compress_responses))
+        .layer(middleware::from_fn_with_state(config.clone(), shadow::mirror))
+        .layer(middleware::from_fn_with_state(config.clone(), methods::enforce))
+        .layer(middleware::from_fn_with_state(config.clone(), ratelimit::enforce))
+        .layer(middleware::from_fn_with_state(config.clone(), bandwidth::limit))
+        .layer(middleware::from_fn_with_state(config.clone(), transfer::track_transfer))
+        .layer(middleware::from_fn_with_state(config.clone(), catch_panic::catch))
+        .layer(middleware::from_fn_with_state(config.clone(), error_page::render))
+        .layer(middleware::from_fn(problem::render))
+        .layer(middleware::from_fn_with_state(config.clone(), cors::handle))
+        .layer(middleware::from_fn_with_state(config.clone(), headers::add))
+        .layer(middleware::from_fn_with_state(config.clone(), metrics::track))
+        .with_state(config)
+}
+
+// 命令列參數定義，設定檔的鍵也依此驗證
+fn cli() -> Command {
+    Command::new("Static File Server")
+        .version("0.2.0")
+        .author("Your Name <youremail@example.com>")
+        .about("A simple static file server with security features")
+        // 設定檔的值在前，命令列重複指定同一參數時以後者為準
+        .args_override_self(true)
+        .arg(
+            Arg::new("config")
+                .long("config")
+                .value_name("FILE")
+                .help("從 TOML 設定檔讀取參數，命令列參數優先")
+                .value_parser(clap::value_parser!(PathBuf)),
+        )
+        .arg(
+            Arg::new("host")
+                .short('H')
+                .long("host")
+                .value_name("HOST")
+                .help("設置伺服器監聽地址")
+                .value_parser(clap::value_parser!(String))
+                .default_value("127.0.0.1"),
+        )
+        .arg(
+            Arg::new("port")
+                .short('p')
+                .long("port")
+                .value_name("PORT")
+                .help("設置伺服器監聽端口")
+                .value_parser(clap::value_parser!(u16))
+                .default_value("3000"),
+        )
+        .arg(
+            Arg::new("base")
+                .short('b')
+                .long("base")
+                .value_name("BASE")
+                .help("設置基礎路徑")
+                .value_parser(clap::value_parser!(String))
+                .default_value("."),
+        )
+        .arg(
+            Arg::new("plain")
+                .short('P')
+                .long("plain")
+                .help("使用原始HTML樣式，無美化")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("restricted-files")
+                .short('r')
+                .long("restricted-files")
+                .value_name("PATTERNS")
+                .help("設置禁止訪問的路徑規則，用逗號分隔；不含 / 時比對每一層名稱（.git、*.pem），含 / 時從根目錄比對（secret/**），re: 開頭為正規表示式")
+                .value_parser(clap::value_parser!(String))
+                .default_value(".env,.env.*,.git,Cargo.toml,Cargo.lock"),
+        )
+        .arg(
+            Arg::new("hidden")
+                .long("hidden")
+                .value_name("POLICY")
+                .help("以 . 開頭的隱藏文件與目錄：deny 不列出也不提供，show 與一般文件相同，ignore 不列出但仍可直接下載；.well-known 不受影響")
+                .default_value("deny")
+                .value_parser(["deny", "show", "ignore"]),
+        )
+        .arg(
+            Arg::new("symlinks")
+                .long("symlinks")
+                .value_name("POLICY")
+                .help("符號連結：confine 只跟隨指向基礎路徑之內的連結，follow 一律跟隨，deny 對任何經過符號連結的路徑回應 403")
+                .default_value("confine")
+                .value_parser(["follow", "deny", "confine"]),
+        )
+        .arg(
+            Arg::new("show-symlink-targets")
+                .long("show-symlink-targets")
+                .help("列表中顯示符號連結指向的路徑")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("show-restricted")
+                .long("show-restricted")
+                .help("在目錄列表中仍顯示禁止訪問的項目（預設隱藏）")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("user")
+                .long("user")
+                .value_name("USER")
+                .help("以 root 綁定監聽位址後切換為此用戶（名稱或 UID）再開始服務")
+                .value_parser(clap::value_parser!(String)),
+        )
+        .arg(
+            Arg::new("group")
+                .long("group")
+                .value_name("GROUP")
+                .help("切換後使用的群組，預設為用戶的主要群組")
+                .requires("user")
+                .value_parser(clap::value_parser!(String)),
+        )
+        .arg(
+            Arg::new("assert-readonly")
+                .long("assert-readonly")
+                .help("啟動時確認程序無法寫入基礎路徑，否則拒絕啟動；並停用所有寫入功能")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("upload")
+                .long("upload")
+                .help("允許以 PUT /<路徑> 與 multipart POST 到目錄上傳文件，寫入暫存文件後再改名取代目標")
+                .conflicts_with("assert-readonly")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("max-upload-size")
+                .long("max-upload-size")
+                .value_name("BYTES")
+                .help("單一請求上傳內容的大小上限")
+                .default_value("1073741824")
+                .value_parser(clap::value_parser!(u64).range(1..)),
+        )
+        .arg(
+            Arg::new("upload-types")
+                .long("upload-types")
+                .value_name("LIST")
+                .help("只允許上傳這些副檔名（zip、.pdf）或 MIME 類型（image/*），以逗號分隔；類型依文件名稱判斷")
+                .requires("upload")
+                .value_parser(clap::value_parser!(String)),
+        )
+        .arg(
+            Arg::new("upload-policy")
+                .long("upload-policy")
+                .value_name("POLICY")
+                .help("既有文件：overwrite 允許上傳取代與 WebDAV 刪除、移動；append-only 一律不能取代或刪除，同名的上傳改存成 name-1.ext、name-2.ext")
+                .default_value("overwrite")
+                .value_parser(["overwrite", "append-only"]),
+        )
+        .arg(
+            Arg::new("moderate-uploads")
+                .long("moderate-uploads")
+                .help("上傳的文件先放在基礎路徑下的 .sfs-quarantine，列表中看不到，由管理 API 核准後才放到目標位置")
+                .requires("upload")
+                .requires("admin-token")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("on-upload")
+                .long("on-upload")
+                .value_name("COMMAND")
+                .help("每個上傳完成後在背景執行的命令，例如 \"./process.sh {path}\"；不經過 shell，可使用 {path}、{url} 與 {bytes}")
+                .requires("upload")
+                .value_parser(clap::value_parser!(String)),
+        )
+        .arg(
+            Arg::new("on-upload-timeout")
+                .long("on-upload-timeout")
+                .value_name("SECONDS")
+                .help("--on-upload 命令的執行時限，超過時終止")
+                .default_value("60")
+                .value_parser(clap::value_parser!(u64).range(1..)),
+        )
+        .arg(
+            Arg::new("clamd")
+                .long("clamd")
+                .value_name("ADDR")
+                .help("以 clamd 掃描上傳的文件，含有病毒時回應 422；位址為 host:port 或 Unix socket 路徑")
+                .requires("upload")
+                .value_parser(clap::value_parser!(String)),
+        )
+        .arg(
+            Arg::new("quota")
+                .long("quota")
+                .value_name("BYTES")
+                .help("基礎路徑的總容量上限，用完後上傳回應 507")
+                .requires("upload")
+                .value_parser(clap::value_parser!(u64).range(1..)),
+        )
+        .arg(
+            Arg::new("upload-journal")
+                .long("upload-journal")
+                .value_name("DIR")
+                .help("記錄未完成的上傳，讓用戶端以 Content-Range 分段上傳並在中斷或重新啟動後續傳")
+                .requires("upload")
+                .value_parser(clap::value_parser!(PathBuf)),
+        )
+        .arg(
+            Arg::new("webdav")
+                .long("webdav")
+                .help("提供 WebDAV class 1（PROPFIND 等），可在檔案總管或 davfs2 掛載；搭配 --upload 時可寫入")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("trash-days")
+                .long("trash-days")
+                .value_name("DAYS")
+                .help("WebDAV 刪除或取代的文件先移到基礎路徑下的 .trash，保留指定天數後才刪除，期間可由管理 API 還原")
+                .requires("webdav")
+                .requires("upload")
+                .value_parser(clap::value_parser!(u64).range(1..)),
+        )
+        .arg(
+            Arg::new("sandbox")
+                .long("sandbox")
+                .help("啟動完成後以 Landlock 限制程序只能存取基礎路徑與必要的文件（Linux）")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("index")
+                .long("index")
+                .value_name("LIST")
+                .help("目錄中有這些文件時（依序尋找，以逗號分隔）提供該文件而不是目錄列表，空字串停用")
+                .default_value("index.html")
+                .value_parser(clap::value_parser!(String)),
+        )
+        .arg(
+            Arg::new("case-insensitive")
+                .short('i')
+                .long("case-insensitive")
+                .help("解析請求路徑時忽略大小寫")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("suggest")
+                .long("suggest")
+                .help("找不到文件時列出相近的名稱")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("manifest")
+                .long("manifest")
+                .help("啟動時產生目錄樹清單，並於 /_manifest.json 提供")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("snapshot")
+                .long("snapshot")
+                .value_name("DIR=SCHEDULE")
+                .help("依 cron 排程（含秒數）預先產生目錄的封存檔，於 <目錄>/?zip 與 <目錄>/?tar.gz 提供，可重複指定")
+                .action(clap::ArgAction::Append)
+                .value_parser(clap::value_parser!(String)),
+        )
+        .arg(
+            Arg::new("snapshot-formats")
+                .long("snapshot-formats")
+                .value_name("LIST")
+                .help("快照的封存格式，以逗號分隔")
+                .default_value("zip,tar.gz")
+                .value_parser(clap::value_parser!(String)),
+        )
+        .arg(
+            Arg::new("snapshot-cache")
+                .long("snapshot-cache")
+                .value_name("DIR")
+                .help("存放快照的目錄，不能位於基礎路徑底下")
+                .default_value(".snapshots")
+                .value_parser(clap::value_parser!(String)),
+        )
+        .arg(
+            Arg::new("changes")
+                .long("changes")
+                .help("記錄文件變動，並於 /api/changes?since=<時間> 提供指定時間之後變動的路徑")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("not-found-cache")
+                .long("not-found-cache")
+                .value_name("SECONDS")
+                .help("記住不存在的路徑指定秒數，期間內直接回應 404；路徑出現時立即失效")
+                .value_parser(clap::value_parser!(u64).range(1..)),
+        )
+        .arg(
+            Arg::new("latest")
+                .long("latest")
+                .value_name("LINK=GLOB[;version]")
+                .help("虛擬連結，轉址到符合樣式的最新項目（依修改時間或版本號），可重複指定")
+                .action(clap::ArgAction::Append),
+        )
+        .arg(
+            Arg::new("version-sort")
+                .long("version-sort")
+                .value_name("DIRS")
+                .help("列表預設依版本號排序的目錄樣式，以逗號分隔，例如 releases,releases/*；也可用 ?C=V 指定"),
+        )
+        .arg(
+            Arg::new("error-page")
+                .long("error-page")
+                .value_name("STATUS=PATH")
+                .help("以基礎路徑底下的 HTML 文件取代錯誤訊息，例如 404=/errors/404.html；*= 用於其他狀態碼，可重複指定")
+                .action(clap::ArgAction::Append),
+        )
+        .arg(
+            Arg::new("access-log")
+                .long("access-log")
+                .value_name("FILE")
+                .help("以 Apache 格式記錄每個請求，- 代表標準輸出，其他為附加寫入的文件")
+                .value_parser(clap::value_parser!(PathBuf)),
+        )
+        .arg(
+            Arg::new("access-log-format")
+                .long("access-log-format")
+                .value_name("FORMAT")
+                .help("存取紀錄格式：common，或加上 Referer、User-Agent 與處理時間（微秒）的 combined")
+                .default_value("combined")
+                .value_parser(["common", "combined"]),
+        )
+        .arg(
+            Arg::new("rate-limit")
+                .long("rate-limit")
+                .value_name("REQUESTS")
+                .help("每個來源 IP 每秒最多的請求數，超過時回應 429")
+                .value_parser(clap::value_parser!(u32).range(1..)),
+        )
+        .arg(
+            Arg::new("bandwidth")
+                .long("bandwidth")
+                .value_name("PATTERN=RATE")
+                .help("依路徑指定頻寬等級，例如 '/iso/**=5MB/s' 或 '/docs/**=unlimited'；符合同一條規則的回應共用該速率，可重複指定，第一條符合的規則生效")
+                .value_parser(clap::value_parser!(String))
+                .action(clap::ArgAction::Append),
+        )
+        .arg(
+            Arg::new("shadow")
+                .long("shadow")
+                .value_name("URL")
+                .help("將 GET、HEAD 等請求的複本（方法、路徑與標頭）送到此網址，回應直接丟棄，用來以實際流量測試替換的後端")
+                .value_parser(clap::value_parser!(String)),
+        )
+        .arg(
+            Arg::new("shadow-percent")
+                .long("shadow-percent")
+                .value_name("PERCENT")
+                .help("鏡像的請求比例（百分比）")
+                .requires("shadow")
+                .default_value("100")
+                .value_parser(clap::value_parser!(u8).range(1..=100)),
+        )
+        .arg(
+            Arg::new("request-timeout")
+                .long("request-timeout")
+                .value_name("SECONDS")
+                .help("請求的處理期限，超過時回應 503，產生中的列表與封存檔也會停止")
+                .value_parser(clap::value_parser!(u64).range(1..)),
+        )
+        .arg(
+            Arg::new("compress")
+                .long("compress")
+                .help("依 Accept-Encoding 以 zstd、Brotli 或 gzip 即時壓縮文字、JSON 等可壓縮的回應")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("compress-level")
+                .long("compress-level")
+                .value_name("LEVEL")
+                .help("壓縮等級 1-9，數字越大壓縮率越高但越耗費 CPU（預設 gzip 6、zstd 3、Brotli 4）")
+                .requires("compress")
+                .value_parser(clap::value_parser!(u32).range(1..=9)),
+        )
+        .arg(
+            Arg::new("compress-min-size")
+                .long("compress-min-size")
+                .value_name("BYTES")
+                .help("小於此位元組數的回應不壓縮")
+                .default_value("1024")
+                .value_parser(clap::value_parser!(u64)),
+        )
+        .arg(
+            Arg::new("precompressed")
+                .long("precompressed")
+                .help("文件旁有 .br、.zst 或 .gz 的預先壓縮版本且用戶端接受該編碼時，直接傳送壓縮版本")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("sniff-mime")
+                .long("sniff-mime")
+                .help("沒有副檔名的文件依內容開頭的特徵判斷內容類型，而不是一律使用 application/octet-stream")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("preload-hints")
+                .long("preload-hints")
+                .help("HTML 頁面附上 Link: rel=preload 標頭，提示 <head> 中的樣式表與腳本；可用 <頁面>.links 指定")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("add-header")
+                .long("add-header")
+                .value_name("NAME: VALUE")
+                .help("所有回應附加的標頭，例如 'X-Frame-Options: DENY'，可使用 {path}、{query}、{host}、{method}；可重複指定，限定路徑請用設定檔的 [[header]]")
+                .value_parser(clap::value_parser!(String))
+                .action(clap::ArgAction::Append),
+        )
+        .arg(
+            Arg::new("secure-headers")
+                .long("secure-headers")
+                .help("所有回應加上 X-Content-Type-Options、X-Frame-Options、Referrer-Policy，以 TLS 提供服務時另加 Strict-Transport-Security；可用 --add-header 或 [[header]] 個別覆寫")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("content-security-policy")
+                .long("content-security-policy")
+                .value_name("POLICY")
+                .help("--secure-headers 一併送出的 Content-Security-Policy，例如 \"default-src 'self'\"")
+                .requires("secure-headers")
+                .value_parser(clap::value_parser!(String)),
+        )
+        .arg(
+            Arg::new("cors")
+                .long("cors")
+                .value_name("ORIGINS")
+                .help("允許跨來源讀取的來源，以逗號分隔，例如 https://app.example.com，* 代表任何來源；預檢的 OPTIONS 請求直接回應")
+                .value_parser(clap::value_parser!(String)),
+        )
+        .arg(
+            Arg::new("cors-methods")
+                .long("cors-methods")
+                .value_name("METHODS")
+                .help("預檢回應中允許的方法")
+                .requires("cors")
+                .default_value("GET, HEAD, OPTIONS")
+                .value_parser(clap::value_parser!(String)),
+        )
+        .arg(
+            Arg::new("cors-headers")
+                .long("cors-headers")
+                .value_name("HEADERS")
+                .help("預檢回應中允許的請求標頭")
+                .requires("cors")
+                .default_value("Authorization, Content-Type, Range, If-Match, If-None-Match, If-Modified-Since, If-Range")
+                .value_parser(clap::value_parser!(String)),
+        )
+        .arg(
+            Arg::new("cors-max-age")
+                .long("cors-max-age")
+                .value_name("SECONDS")
+                .help("瀏覽器可以快取預檢結果的秒數")
+                .requires("cors")
+                .default_value("600")
+                .value_parser(clap::value_parser!(u64)),
+        )
+        .arg(
+            Arg::new("cors-credentials")
+                .long("cors-credentials")
+                .help("允許跨來源請求帶上 Cookie 與 Authorization 等憑證；--cors 需列出來源，不能是 *")
+                .requires("cors")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("cache-control")
+                .long("cache-control")
+                .value_name("PATTERN=VALUE")
+                .help("文件回應的 Cache-Control 規則，例如 '*.html=no-cache' 或 '/assets/*=max-age=31536000, immutable'；可重複指定，第一條符合的規則生效，都不符合時為 public, max-age=3600")
+                .value_parser(clap::value_parser!(String))
+                .action(clap::ArgAction::Append),
+        )
+        .arg(
+            Arg::new("surrogate-control")
+                .long("surrogate-control")
+                .value_name("VALUE")
+                .help("文件回應附上只給 CDN 的 Surrogate-Control 標頭，例如 max-age=86400")
+                .value_parser(clap::value_parser!(String)),
+        )
+        .arg(
+            Arg::new("surrogate-keys")
+                .long("surrogate-keys")
+                .value_name("HEADER")
+                .help("文件回應附上依路徑上層目錄產生的快取標籤，供 CDN 依標籤清除：surrogate-key 或 cache-tag")
+                .value_parser(KeyHeader::NAMES.to_vec()),
+        )
+        .arg(
+            Arg::new("preload")
+                .long("preload")
+                .value_name("PATTERNS")
+                .help("啟動時將符合 glob 樣式的文件載入記憶體快取，用逗號分隔")
+                .value_parser(clap::value_parser!(String)),
+        )
+        .arg(
+            Arg::new("admin-token")
+                .long("admin-token")
+                .value_name("TOKEN")
+                .help("啟用 /_admin 管理 API，並以此 Bearer token 驗證")
+                .value_parser(clap::value_parser!(String)),
+        )
+        .arg(
+            Arg::new("tls-cert")
+                .long("tls-cert")
+                .value_name("FILE")
+                .help("PEM 格式的 TLS 憑證鏈，與 --tls-key 一起設定時以 HTTPS 提供服務（支援 HTTP/2）")
+                .requires("tls-key")
+                .value_parser(clap::value_parser!(PathBuf)),
+        )
+        .arg(
+            Arg::new("tls-key")
+                .long("tls-key")
+                .value_name("FILE")
+                .help("PEM 格式的 TLS 私鑰")
+                .requires("tls-cert")
+                .value_parser(clap::value_parser!(PathBuf)),
+        )
+        .arg(
+            Arg::new("public-routes")
+                .long("public-routes")
+                .value_name("LIST")
+                .help("公開位址提供的功能，用逗號分隔: files, listings, manifest, changes, admin, login, metrics（預設全部，指定 --admin-listen、--metrics-addr 時不含對應項目）")
+                .value_parser(clap::value_parser!(String)),
+        )
+        .arg(
+            Arg::new("admin-routes")
+                .long("admin-routes")
+                .value_name("LIST")
+                .help("管理位址提供的功能，格式與 --public-routes 相同（預設只有 admin）")
+                .requires("admin-listen")
+                .value_parser(clap::value_parser!(String)),
+        )
+        .arg(
+            Arg::new("acme")
+                .long("acme")
+                .value_name("DOMAIN")
+                .help("以 ACME HTTP-01 驗證自動取得並更新此網域的憑證，以 HTTPS 提供服務")
+                .conflicts_with("tls-cert")
+                .value_parser(clap::value_parser!(String)),
+        )
+        .arg(
+            Arg::new("acme-email")
+                .long("acme-email")
+                .value_name("EMAIL")
+                .help("ACME 帳號的聯絡信箱，用於接收憑證到期通知")
+                .requires("acme")
+                .value_parser(clap::value_parser!(String)),
+        )
+        .arg(
+            Arg::new("acme-cache")
+                .long("acme-cache")
+                .value_name("DIR")
+                .help("存放 ACME 帳號金鑰、憑證與私鑰的目錄，不能位於基礎路徑底下")
+                .default_value(".acme")
+                .value_parser(clap::value_parser!(String)),
+        )
+        .arg(
+            Arg::new("acme-directory")
+                .long("acme-directory")
+                .value_name("URL")
+                .help("ACME 伺服器的目錄網址，測試時可改用 Let's Encrypt 的 staging 環境")
+                .default_value(acme::LETS_ENCRYPT)
+                .value_parser(clap::value_parser!(String)),
+        )
+        .arg(
+            Arg::new("acme-http")
+                .long("acme-http")
+                .value_name("ADDR")
+                .help("回應 HTTP-01 驗證的位址，其餘請求轉址到 HTTPS")
+                .default_value("0.0.0.0:80")
+                .value_parser(clap::value_parser!(String)),
+        )
+        .arg(
+            Arg::new("admin-listen")
+                .long("admin-listen")
+                .value_name("ADDR")
+                .help("只在此位址（例如 127.0.0.1:9000）提供管理 API，並從公開位址移除")
+                .requires("admin-token")
+                .value_parser(clap::value_parser!(String)),
+        )
+        .arg(
+            Arg::new("metrics")
+                .long("metrics")
+                .help("在 /metrics 提供 Prometheus 指標：請求數、處理時間、送出的位元組數與連線數")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("metrics-addr")
+                .long("metrics-addr")
+                .value_name("ADDR")
+                .help("只在此位址（例如 127.0.0.1:9090）提供 /metrics，並從公開位址移除")
+                .value_parser(clap::value_parser!(String)),
+        )
+        .arg(
+            Arg::new("s3-listen")
+                .long("s3-listen")
+                .value_name("ADDR")
+                .help("在此位址提供唯讀的 S3 相容介面（path-style），可用 aws cli、rclone 等工具讀取")
+                .value_parser(clap::value_parser!(String)),
+        )
+        .arg(
+            Arg::new("s3-bucket")
+                .long("s3-bucket")
+                .value_name("NAME")
+                .help("S3 介面中代表基礎路徑的儲存貯體名稱")
+                .default_value("files")
+                .requires("s3-listen")
+                .value_parser(clap::value_parser!(String)),
+        )
+        .arg(
+            Arg::new("s3-credentials")
+                .long("s3-credentials")
+                .value_name("KEY:SECRET")
+                .help("S3 介面的存取金鑰 ID 與私密金鑰，設定後要求 AWS Signature Version 4 簽章")
+                .requires("s3-listen")
+                .value_parser(clap::value_parser!(String)),
+        )
+        .arg(
+            Arg::new("webhook")
+                .long("webhook")
+                .value_name("URL")
+                .help("事件發生時通知的 webhook 網址，可重複指定")
+                .value_parser(clap::value_parser!(String))
+                .action(clap::ArgAction::Append),
+        )
+        .arg(
+            Arg::new("webhook-template")
+                .long("webhook-template")
+                .value_name("TEMPLATE")
+                .help("webhook 內容樣板，可使用 {event}、{path}、{bytes}、{tag}、{timestamp}")
+                .value_parser(clap::value_parser!(String)),
+        )
+        .arg(
+            Arg::new("webhook-secret")
+                .long("webhook-secret")
+                .value_name("SECRET")
+                .help("以 HMAC-SHA256 簽署 webhook 內容的金鑰")
+                .value_parser(clap::value_parser!(String)),
+        )
+        .arg(
+            Arg::new("webhook-download-bytes")
+                .long("webhook-download-bytes")
+                .value_name("BYTES")
+                .help("下載完成且大小達到此位元組數時發送通知")
+                .value_parser(clap::value_parser!(u64)),
+        )
+        .arg(
+            Arg::new("verify-reads")
+                .long("verify-reads")
+                .help("除錯用：計算完整送出的文件內容的 SHA-256，與文件清單或重新讀取的結果比較，不一致時記錄錯誤")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("abort-on-panic")
+                .long("abort-on-panic")
+                .help("處理請求時發生 panic 時記錄後終止程序（可產生 core dump），預設為回應 500 並繼續服務")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("webhook-aborts")
+                .long("webhook-aborts")
+                .help("用戶端在回應送完前中斷連線時發送 transfer.aborted 通知")
+                .requires("webhook")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("purge-webhook")
+                .long("purge-webhook")
+                .value_name("URL")
+                .help("POST /_admin/purge 清除快取後通知的網址（例如 CDN 的清除 API），可重複指定")
+                .requires("admin-token")
+                .value_parser(clap::value_parser!(String))
+                .action(clap::ArgAction::Append),
+        )
+        .arg(
+            Arg::new("purge-webhook-template")
+                .long("purge-webhook-template")
+                .value_name("TEMPLATE")
+                .help("清除通知的內容樣板，可使用 {event}、{path}、{tag}、{timestamp}")
+                .requires("purge-webhook")
+                .value_parser(clap::value_parser!(String)),
+        )
+        .arg(
+            Arg::new("purge-webhook-header")
+                .long("purge-webhook-header")
+                .value_name("NAME: VALUE")
+                .help("清除通知附加的標頭，例如 CDN API 的授權，可重複指定")
+                .requires("purge-webhook")
+                .value_parser(clap::value_parser!(String))
+                .action(clap::ArgAction::Append),
+        )
+        .arg(
+            Arg::new("max-open-files")
+                .long("max-open-files")
+                .value_name("N")
+                .help("啟動時將文件描述符上限（RLIMIT_NOFILE）提高到此值，不超過系統的硬性上限")
+                .value_parser(clap::value_parser!(u64)),
+        )
+        .arg(
+            Arg::new("min-free-space")
+                .long("min-free-space")
+                .value_name("BYTES")
+                .help("基礎目錄所在磁碟剩餘空間低於此位元組數時發出警告並拒絕寫入")
+                .value_parser(clap::value_parser!(u64)),
+        )
+        .arg(
+            Arg::new("min-free-inodes")
+                .long("min-free-inodes")
+                .value_name("N")
+                .help("基礎目錄所在磁碟剩餘 inode 低於此數量時發出警告並拒絕寫入")
+                .value_parser(clap::value_parser!(u64)),
+        )
+        .arg(
+            Arg::new("mp4-check")
+                .long("mp4-check")
+                .help("提供 MP4 影片時檢查 moov atom 是否在文件結尾，無法漸進式播放時記錄警告")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("users")
+                .long("users")
+                .value_name("FILE")
+                .help("用戶帳號檔，啟用 Basic 驗證與每位用戶各自的根目錄")
+                .value_parser(clap::value_parser!(String)),
+        )
+        .arg(
+            Arg::new("acl")
+                .long("acl")
+                .value_name("FILE")
+                .help("路徑權限規則檔，依用戶或群組設定各路徑前綴的權限")
+                .value_parser(clap::value_parser!(String))
+                .requires("auth"),
+        )
+        .arg(
+            Arg::new("method-policy")
+                .long("method-policy")
+                .value_name("FILE")
+                .help("依路徑前綴限制可使用的 HTTP 方法的規則檔")
+                .value_parser(clap::value_parser!(String)),
+        )
+        .arg(
+            Arg::new("auth-ldap")
+                .long("auth-ldap")
+                .value_name("URL")
+                .help("以 LDAP / Active Directory bind 驗證用戶，例如 ldap://dc.corp")
+                .value_parser(clap::value_parser!(String))
+                .requires("ldap-base-dn"),
+        )
+        .arg(
+            Arg::new("ldap-base-dn")
+                .long("ldap-base-dn")
+                .value_name("DN")
+                .help("用戶所在的基礎 DN，例如 ou=people,dc=corp,dc=example")
+                .value_parser(clap::value_parser!(String)),
+        )
+        .arg(
+            Arg::new("ldap-user-attr")
+                .long("ldap-user-attr")
+                .value_name("ATTR")
+                .help("組成用戶 DN 的屬性名稱")
+                .value_parser(clap::value_parser!(String))
+                .default_value("uid"),
+        )
+        .arg(
+            Arg::new("ldap-group-dn")
+                .long("ldap-group-dn")
+                .value_name("DN")
+                .help("只允許此群組的成員登入")
+                .value_parser(clap::value_parser!(String)),
+        )
+        .arg(
+            Arg::new("auth-pam")
+                .long("auth-pam")
+                .help("以 PAM 驗證本機系統帳號（需以 pam 功能編譯）")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("pam-service")
+                .long("pam-service")
+                .value_name("SERVICE")
+                .help("使用的 PAM 服務名稱，對應 /etc/pam.d 下的設定檔")
+                .value_parser(clap::value_parser!(String))
+                .default_value("login"),
+        )
+        .arg(
+            Arg::new("tokens")
+                .long("tokens")
+                .value_name("FILE")
+                .help("API token 儲存檔，接受 Authorization: Bearer 驗證")
+                .value_parser(clap::value_parser!(String)),
+        )
+        .arg(
+            Arg::new("login-page")
+                .long("login-page")
+                .help("啟用 HTML 登入頁，瀏覽器以登入階段 cookie 驗證而不跳出 Basic 驗證視窗")
+                .requires("auth")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("session-secret")
+                .long("session-secret")
+                .value_name("SECRET")
+                .help("簽署登入階段 cookie 的金鑰，未指定時每次啟動隨機產生")
+                .requires("login-page")
+                .value_parser(clap::value_parser!(String)),
+        )
+        .arg(
+            Arg::new("auth-max-failures")
+                .long("auth-max-failures")
+                .value_name("N")
+                .help("同一來源對同一用戶連續驗證失敗幾次後暫時鎖定（整個 IP 為四倍次數），鎖定時間逐次加倍，0 為停用")
+                .default_value("5")
+                .value_parser(clap::value_parser!(u32)),
+        )
+        .group(
+            ArgGroup::new("auth")
+                .args(["users", "auth-ldap", "auth-pam", "tokens"])
+                .multiple(true),
+        )
+        .subcommand(
+            Command::new("check")
+                .about("檢查設定檔與其引用的文件，不啟動伺服器")
+                .arg(
+                    Arg::new("file")
+                        .required(true)
+                        .value_name("FILE")
+                        .help("TOML 設定檔")
+                        .value_parser(clap::value_parser!(PathBuf)),
+                ),
+        )
+        .subcommand(
+            Command::new("mirror")
+                .about("從另一個實例的 /_manifest.json 同步有變動的文件到本機目錄，並驗證 SHA-256")
+                .arg(Arg::new("url").required(true).value_name("URL").help("來源伺服器的網址"))
+                .arg(
+                    Arg::new("dir")
+                        .required(true)
+                        .value_name("DIR")
+                        .help("本機目錄")
+                        .value_parser(clap::value_parser!(PathBuf)),
+                )
+                .arg(
+                    Arg::new("token")
+                        .long("token")
+                        .value_name("TOKEN")
+                        .help("以 Authorization: Bearer 送出的 API token"),
+                )
+                .arg(
+                    Arg::new("delete")
+                        .long("delete")
+                        .help("刪除來源已不存在的本機文件")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("jobs")
+                        .long("jobs")
+                        .value_name("N")
+                        .help("同時下載的文件數")
+                        .default_value("4")
+                        .value_parser(clap::value_parser!(u32).range(1..=64)),
+                )
+                .arg(
+                    Arg::new("interval")
+                        .long("interval")
+                        .value_name("SECONDS")
+                        .help("持續同步，每隔指定秒數檢查清單；未指定時同步一次就結束")
+                        .value_parser(clap::value_parser!(u64).range(1..)),
+                ),
+        )
+        .subcommand(
+            Command::new("duplicates")
+                .about("找出目錄中內容相同的文件，依浪費的空間由大到小列出")
+                .arg(
+                    Arg::new("dir")
+                        .required(true)
+                        .value_name("DIR")
+                        .help("要掃描的目錄")
+                        .value_parser(clap::value_parser!(PathBuf)),
+                )
+                .arg(
+                    Arg::new("min-size")
+                        .long("min-size")
+                        .value_name("BYTES")
+                        .help("忽略小於指定大小的文件")
+                        .default_value("1")
+                        .value_parser(clap::value_parser!(u64)),
+                )
+                .arg(
+                    Arg::new("json")
+                        .long("json")
+                        .help("以 JSON 輸出，格式與 /_admin/duplicates 相同")
+                        .action(clap::ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("hash-password")
+                .about("從標準輸入讀取密碼並輸出用於用戶帳號檔的 bcrypt 雜湊"),
+        )
+        .subcommand(
+            Command::new("token")
+                .about("管理 API token")
+                .subcommand_required(true)
+                .arg(
+                    Arg::new("file")
+                        .short('f')
+                        .long("file")
+                        .value_name("FILE")
+                        .help("API token 儲存檔")
+                        .value_parser(clap::value_parser!(String))
+                        .required(true),
+                )
+                .subcommand(
+                    Command::new("create")
+                        .about("建立新的 token 並輸出其內容（只會顯示一次）")
+                        .arg(Arg::new("id").required(true).help("token 名稱"))
+                        .arg(
+                            Arg::new("scope")
+                                .long("scope")
+                                .value_name("SCOPES")
+                                .help("權限範圍，用逗號分隔")
+                                .value_parser(clap::builder::PossibleValuesParser::new(tokens::SCOPES))
+                                .value_delimiter(',')
+                                .default_value("read"),
+                        )
+                        .arg(
+                            Arg::new("path")
+                                .long("path")
+                                .value_name("PREFIX")
+                                .help("只允許存取此路徑前綴底下的內容")
+                                .default_value(""),
+                        ),
+                )
+                .subcommand(
+                    Command::new("revoke")
+                        .about("撤銷 token")
+                        .arg(Arg::new("id").required(true).help("token 名稱")),
+                )
+                .subcommand(Command::new("list").about("列出所有 token")),
+        )
+}
+
+// 以逗號分隔的禁止訪問模式
+fn restricted_patterns(list: &str) -> Vec<String> {
+    list.split(',').map(|s| s.trim().to_string()).collect()
+}
+
+// 以逗號分隔的索引文件名稱，空字串表示停用
+fn index_files(list: &str) -> Result<Vec<String>, String> {
+    let names: Vec<String> = list
+        .split(',')
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .collect();
+    if let Some(name) = names.iter().find(|name| name.contains('/') || *name == "..") {
+        return Err(format!("索引文件 {} 必須是目錄中的文件名稱", name));
+    }
+    Ok(names)
+}
+
+// 以逗號分隔的目錄樣式，相對於基礎路徑，空字串表示基礎路徑本身
+fn version_sort_dirs(list: &str) -> Result<GlobSet, globset::Error> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in list.split(',').map(|s| s.trim().trim_matches('/')) {
+        builder.add(Glob::new(pattern)?);
+    }
+    builder.build()
+}
+
+fn error_pages<'a>(specs: impl Iterator<Item = &'a String>) -> io::Result<ErrorPages> {
+    let mut pages = ErrorPages::default();
+    for spec in specs {
+        pages.add(spec)?;
+    }
+    Ok(pages)
+}
+
+// 虛擬主機沿用最上層的設定，只替換基礎路徑、索引文件與禁止規則
+//
+// 文件清單、變更紀錄、快照、latest 連結與找不到的快取都以最上層的基礎路徑建立，不套用到其他網站。
+fn vhost_config(config: &ServerConfig, vhost: &VirtualHost) -> Result<ServerConfig, Box<dyn std::error::Error>> {
+    if !vhost.base.is_dir() {
+        return Err(format!("網站 {} 的基礎路徑 {} 不是目錄", vhost.label(), vhost.base.display()).into());
+    }
+    let mut config = config.clone();
+    if let Some(prefix) = &vhost.prefix {
+        config.mount = Arc::new(prefix.clone());
+    }
+    config.base_path = Arc::new(vhost.base.to_string_lossy().to_string());
+    config.ignore = Arc::new(IgnoreFile::open(&vhost.base));
+    if let Some(list) = &vhost.index {
+        config.index_files = Arc::new(index_files(list)?);
+    }
+    if let Some(list) = &vhost.restricted_files {
+        config.restricted = Arc::new(RestrictRules::new(&restricted_patterns(list))?);
+    }
+    config.name_index = Arc::new(NameIndex::default());
+    if config.listing_cache.is_some() {
+        config.listing_cache = Some(Arc::new(ListingCache::default()));
+    }
+    config.manifest = None;
+    config.journal = None;
+    config.not_found = None;
+    config.snapshots = None;
+    config.latest = None;
+    
+    // 網站有自己的用戶時，最上層的其他驗證後端、存取規則與登入階段都不適用
+    if let Some(users) = &vhost.users {
+        config.users = Some(Arc::new(UserStore::load(users)?));
+        config.ldap = None;
+        config.tokens = None;
+        config.sessions = None;
+        config.access_rules = None;
+        #[cfg(feature = "pam")]
+        {
+            config.pam = None;
+        }
+    }
+    if let (Some(upload), Some(max_size)) = (&mut config.upload, vhost.max_upload_size) {
+        upload.max_size = max_size;
+    }
+    // 審核區放在各網站自己的基礎路徑中，核准後放回同一個網站
+    if let Some(upload) = config.upload.as_mut().filter(|upload| upload.quarantine.is_some()) {
+        upload.quarantine = Some(Arc::new(Holding::open(&vhost.base.join(QUARANTINE_DIR), None)?));
+    }
+    if let Some(trash) = &config.trash {
+        config.trash = Some(Arc::new(Holding::open(&vhost.base.join(TRASH_DIR), trash.retention())?));
+    }
+    // 容量與速率各網站分開計算，未指定時沿用最上層的值
+    let quota = vhost.quota.or(config.quota.as_ref().map(|quota| quota.limit()));
+    config.quota = quota
+        .filter(|_| config.upload.is_some())
+        .map(|limit| Arc::new(Quota::new(&vhost.base, limit)));
+    let rate = vhost.rate_limit.or(config.rate_limit.as_ref().map(|limiter| limiter.per_second()));
+    config.rate_limit = rate.map(|rate| Arc::new(RateLimiter::new(rate)));
+    Ok(config)
+}
+
+// `名稱: 值` 格式的標頭
+fn purge_webhook_header(line: &str) -> Result<(String, String), String> {
+    let invalid = || format!("無效的 --purge-webhook-header: {}，格式為 名稱: 值", line);
+    let (name, value) = line.split_once(':').ok_or_else(invalid)?;
+    let (name, value) = (name.trim(), value.trim());
+    header::HeaderName::from_bytes(name.as_bytes()).map_err(|_| invalid())?;
+    header::HeaderValue::from_str(value).map_err(|_| invalid())?;
+    Ok((name.to_string(), value.to_string()))
+}
+
+fn surrogate_options(matches: &clap::ArgMatches) -> Result<Option<SurrogateOptions>, String> {
+    let control = matches
+        .get_one::<String>("surrogate-control")
+        .map(|value| {
+            header::HeaderValue::from_str(value).map_err(|_| format!("無效的 --surrogate-control 值: {}", value))
+        })
+        .transpose()?;
+    let keys = matches.get_one::<String>("surrogate-keys").and_then(|name| KeyHeader::parse(name));
+    Ok((control.is_some() || keys.is_some()).then_some(SurrogateOptions { control, keys }))
+}
+
+// 載入設定引用的文件與規則，找出啟動時才會發現的錯誤
+fn check_settings(matches: &clap::ArgMatches, vhosts: &[VirtualHost]) -> Result<(), Box<dyn std::error::Error>> {
+    let base = matches.get_one::<String>("base").unwrap();
+    if !FsPath::new(base).is_dir() {
+        return Err(format!("基礎路徑 {} 不是目錄", base).into());
+    }
+    RestrictRules::new(&restricted_patterns(matches.get_one::<String>("restricted-files").unwrap()))?;
+    index_files(matches.get_one::<String>("index").unwrap())?;
+    if let Some(list) = matches.get_one::<String>("version-sort") {
+        version_sort_dirs(list)?;
+    }
+    CacheRules::new(matches.get_many::<String>("cache-control").into_iter().flatten())?;
+    BandwidthRules::new(matches.get_many::<String>("bandwidth").into_iter().flatten())?;
+    if let Some(list) = matches.get_one::<String>("upload-types") {
+        UploadTypes::parse(list)?;
+    }
+    if let Some(origins) = matches.get_one::<String>("cors") {
+        CorsOptions::check_credentials(&CorsOptions::parse_origins(origins)?, matches.get_flag("cors-credentials"))?;
+    }
+    for line in matches.get_many::<String>("add-header").into_iter().flatten() {
+        HeaderRule::parse(line)?;
+    }
+    if let Some(specs) = matches.get_many::<String>("error-page") {
+        error_pages(specs)?;
+    }
+    for vhost in vhosts {
+        if !vhost.base.is_dir() {
+            return Err(format!("網站 {} 的基礎路徑 {} 不是目錄", vhost.label(), vhost.base.display()).into());
+        }
+        if let Some(users) = &vhost.users {
+            UserStore::load(users)?;
+        }
+        if let Some(list) = &vhost.index {
+            index_files(list)?;
+        }
+        if let Some(list) = &vhost.restricted_files {
+            RestrictRules::new(&restricted_patterns(list))?;
+        }
+    }
+    
+    if let Some(file) = matches.get_one::<String>("users") {
+        UserStore::load(FsPath::new(file))?;
+    }
+    if let Some(file) = matches.get_one::<String>("acl") {
+        AccessRules::load(FsPath::new(file))?;
+    }
+    if let Some(file) = matches.get_one::<String>("method-policy") {
+        MethodPolicy::load(FsPath::new(file))?;
+    }
+    if let Some(file) = matches.get_one::<String>("tokens") {
+        TokenStore::open(FsPath::new(file))?;
+    }
+    for name in ["tls-cert", "tls-key"] {
+        if let Some(file) = matches.get_one::<PathBuf>(name) {
+            fs::metadata(file).map_err(|e| format!("無法讀取 {}: {}", file.display(), e))?;
+        }
+    }
+    for name in ["public-routes", "admin-routes"] {
+        if let Some(list) = matches.get_one::<String>(name) {
+            ListenerRoutes::parse(list)?;
+        }
+    }
+    for spec in matches.get_many::<String>("snapshot").into_iter().flatten() {
+        SnapshotSpec::parse(spec)?;
+    }
+    for spec in matches.get_many::<String>("latest").into_iter().flatten() {
+        LatestSpec::parse(spec)?;
+    }
+    for format in matches.get_one::<String>("snapshot-formats").unwrap().split(',').map(str::trim) {
+        if !format.is_empty() && ArchiveFormat::parse(format).is_none() {
+            return Err(format!("未知的封存格式 {}", format).into());
+        }
+    }
+    Ok(())
+}
+
+// 由命令列參數建立伺服器設定；設定檔的 ACL 與 `[[header]]` 會從 `config_file` 取出
+fn server_config(
+    matches: &clap::ArgMatches,
+    mut config_file: Option<&mut ConfigFile>,
+) -> Result<ServerConfig, Box<dyn std::error::Error>> {
+    let base_path = Arc::new(matches.get_one::<String>("base").unwrap().clone());
+    let restricted = Arc::new(RestrictRules::new(&restricted_patterns(
+        matches.get_one::<String>("restricted-files").unwrap(),
+    ))?);
+    let upload_types = matches
+        .get_one::<String>("upload-types")
+        .map(|list| UploadTypes::parse(list))
+        .transpose()?
+        .map(Arc::new);
+    let quarantine = match matches.get_flag("moderate-uploads") {
+        true => Some(Arc::new(Holding::open(&FsPath::new(base_path.as_str()).join(QUARANTINE_DIR), None)?)),
+        false => None,
+    };
+    let on_upload = match matches.get_one::<String>("on-upload") {
+        Some(command) => {
+            let timeout = Duration::from_secs(*matches.get_one::<u64>("on-upload-timeout").unwrap());
+            let hook = UploadHook::parse(command, timeout)?;
+            if hook.program().is_none() {
+                return Err(format!("找不到 --on-upload 的程式: {}", command).into());
+            }
+            Some(Arc::new(hook))
+        }
+        None => None,
+    };
+
+    let mut config = ServerConfig {
+        base_path: base_path.clone(),
+        ignore: Arc::new(IgnoreFile::open(FsPath::new(base_path.as_str()))),
+        restricted,
+        routes: ListenerRoutes::all(),
+        mount: Arc::new(String::new()),
+        hide_restricted: !matches.get_flag("show-restricted"),
+        hidden: HiddenPolicy::parse(matches.get_one::<String>("hidden").unwrap()).unwrap(),
+        symlinks: SymlinkPolicy::parse(matches.get_one::<String>("symlinks").unwrap()).unwrap(),
+        show_symlink_targets: matches.get_flag("show-symlink-targets"),
+        use_plain_html: matches.get_flag("plain"),
+        case_insensitive: matches.get_flag("case-insensitive"),
+        name_index: Arc::new(NameIndex::default()),
+        suggest: matches.get_flag("suggest"),
+        manifest: None,
+        integrity: None,
+        journal: None,
+        not_found: None,
+        snapshots: None,
+        latest: None,
+        version_sort: matches
+            .get_one::<String>("version-sort")
+            .map(|list| version_sort_dirs(list).map(Arc::new))
+            .transpose()?,
+        error_pages: matches
+            .get_many::<String>("error-page")
+            .map(|specs| error_pages(specs).map(Arc::new))
+            .transpose()?,
+        index_files: Arc::new(index_files(matches.get_one::<String>("index").unwrap())?),
+        file_cache: Arc::new(FileCache::default()),
+        checksums: Arc::default(),
+        cache_warming: Arc::new(Coalescer::default()),
+        precompressed: matches.get_flag("precompressed"),
+        sniff_mime: matches.get_flag("sniff-mime"),
+        preload_hints: matches
+            .get_flag("preload-hints")
+            .then(|| Arc::new(PreloadHints::default())),
+        cache_control: Arc::new(CacheRules::new(matches.get_many::<String>("cache-control").into_iter().flatten())?),
+        surrogate: surrogate_options(matches)?.map(Arc::new),
+        custom_headers: Arc::default(),
+        cors: match matches.get_one::<String>("cors") {
+            Some(origins) => {
+                let header = |name: &str| {
+                    header::HeaderValue::from_str(matches.get_one::<String>(name).unwrap())
+                        .map_err(|_| format!("--{} 含有無效的字元", name))
+                };
+                let origins = CorsOptions::parse_origins(origins)?;
+                let credentials = matches.get_flag("cors-credentials");
+                CorsOptions::check_credentials(&origins, credentials)?;
+                Some(Arc::new(CorsOptions {
+                    origins,
+                    methods: header("cors-methods")?,
+                    headers: header("cors-headers")?,
+                    max_age: *matches.get_one::<u64>("cors-max-age").unwrap(),
+                    credentials,
+                }))
+            }
+            None => None,
+        },
+        compress: matches.get_flag("compress").then(|| {
+            Arc::new(CompressOptions {
+                level: matches.get_one::<u32>("compress-level").copied(),
+                min_size: *matches.get_one::<u64>("compress-min-size").unwrap(),
+            })
+        }),
+        listing_cache: matches.get_flag("compress").then(|| Arc::new(ListingCache::default())),
+        admin_token: matches.get_one::<String>("admin-token").cloned().map(Arc::new),
+        webhooks: None,
+        purge_hooks: None,
+        users: None,
+        access_rules: None,
+        method_policy: None,
+        ldap: None,
+        #[cfg(feature = "pam")]
+        pam: None,
+        tokens: None,
+        sessions: None,
+        throttle: Arc::new(LoginThrottle::new(
+            *matches.get_one::<u32>("auth-max-failures").unwrap(),
+        )),
+        transfer_stats: Arc::new(TransferStats::default()),
+        access_log: match matches.get_one::<PathBuf>("access-log") {
+            Some(target) => {
+                let format = LogFormat::parse(matches.get_one::<String>("access-log-format").unwrap()).unwrap();
+                Some(Arc::new(AccessLog::open(target, format)?))
+            }
+            None => None,
+        },
+        metrics: (matches.get_flag("metrics") || matches.contains_id("metrics-addr"))
+            .then(|| Arc::new(Metrics::default())),
+        mp4_check: matches
+            .get_flag("mp4-check")
+            .then(|| Arc::new(Mp4Checker::default())),
+        read_only: matches.get_flag("assert-readonly"),
+        upload: matches.get_flag("upload").then(|| UploadOptions {
+            max_size: *matches.get_one::<u64>("max-upload-size").unwrap(),
+            types: upload_types,
+            policy: UploadPolicy::parse(matches.get_one::<String>("upload-policy").unwrap()).unwrap(),
+            on_upload,
+            clamd: matches.get_one::<String>("clamd").map(|addr| Arc::new(Clamd::new(addr))),
+            quarantine,
+        }),
+        upload_journal: matches
+            .get_one::<PathBuf>("upload-journal")
+            .map(|dir| UploadJournal::open(dir))
+            .transpose()?,
+        trash: matches
+            .get_one::<u64>("trash-days")
+            .map(|&days| Holding::open(&FsPath::new(base_path.as_str()).join(TRASH_DIR), Some(Duration::from_secs(days * 86400))))
+            .transpose()?
+            .map(Arc::new),
+        quota: matches
+            .get_one::<u64>("quota")
+            .map(|&limit| Arc::new(Quota::new(FsPath::new(base_path.as_str()), limit))),
+        rate_limit: matches.get_one::<u32>("rate-limit").map(|&rate| Arc::new(RateLimiter::new(rate))),
+        bandwidth: Arc::new(BandwidthRules::new(matches.get_many::<String>("bandwidth").into_iter().flatten())?),
+        shadow: match matches.get_one::<String>("shadow") {
+            Some(url) => {
+                if !url.starts_with("http://") && !url.starts_with("https://") {
+                    return Err(format!("--shadow 的網址必須以 http:// 或 https:// 開頭: {}", url).into());
+                }
+                let percent = *matches.get_one::<u8>("shadow-percent").unwrap();
+                Some(Arc::new(Shadow::new(url, percent)?))
+            }
+            None => None,
+        },
+        webdav: matches.get_flag("webdav"),
+        abort_on_panic: matches.get_flag("abort-on-panic"),
+        request_timeout: matches.get_one::<u64>("request-timeout").map(|&seconds| Duration::from_secs(seconds)),
+        disk: Arc::new(DiskMonitor::new(
+            FsPath::new(base_path.as_str()),
+            matches.get_one::<u64>("min-free-space").copied(),
+            matches.get_one::<u64>("min-free-inodes").copied(),
+        )),
+    };
+    
+    if matches.get_flag("login-page") {
+        let secret = matches.get_one::<String>("session-secret").map(String::as_str);
+        let tls = matches.contains_id("tls-cert") || matches.contains_id("acme");
+        config.sessions = Some(Arc::new(SessionKeys::new(secret, tls)?));
+    }
+    
+    if let Some(tokens_file) = matches.get_one::<String>("tokens") {
+        config.tokens = Some(Arc::new(TokenStore::open(FsPath::new(tokens_file))?));
+    }
+    
+    if let Some(users_file) = matches.get_one::<String>("users") {
+        config.users = Some(Arc::new(UserStore::load(FsPath::new(users_file))?));
+    }
+    
+    if let Some(url) = matches.get_one::<String>("auth-ldap") {
+        config.ldap = Some(Arc::new(LdapBackend::new(
+            url.clone(),
+            matches.get_one::<String>("ldap-base-dn").unwrap().clone(),
+            matches.get_one::<String>("ldap-user-attr").unwrap().clone(),
+            matches.get_one::<String>("ldap-group-dn").cloned(),
+        )));
+    }
+    
+    if matches.get_flag("auth-pam") {
+        #[cfg(feature = "pam")]
+        {
+            config.pam = Some(Arc::new(PamBackend::new(
+                matches.get_one::<String>("pam-service").unwrap(),
+            )?));
+        }
+        #[cfg(not(feature = "pam"))]
+        return Err("此版本編譯時未啟用 pam 功能，無法使用 --auth-pam".into());
+    }
+    
+    if let Some(acl_file) = matches.get_one::<String>("acl") {
+        config.access_rules = Some(Arc::new(AccessRules::load(FsPath::new(acl_file))?));
+    } else if let Some(rules) = config_file.as_mut().and_then(|file| file.acl.take()) {
+        config.access_rules = Some(Arc::new(rules));
+    }
+    
+    let mut header_rules = match matches.get_flag("secure-headers") {
+        true => {
+            let tls = matches.contains_id("tls-cert") || matches.contains_id("acme");
+            headers::secure_defaults(tls, matches.get_one::<String>("content-security-policy").map(String::as_str))?
+        }
+        false => Vec::new(),
+    };
+    for line in matches.get_many::<String>("add-header").into_iter().flatten() {
+        header_rules.push(HeaderRule::parse(line)?);
+    }
+    header_rules.extend(config_file.as_mut().map(|file| std::mem::take(&mut file.headers)).unwrap_or_default());
+    config.custom_headers = Arc::new(CustomHeaders::new(header_rules));
+    
+    if let Some(policy_file) = matches.get_one::<String>("method-policy") {
+        config.method_policy = Some(Arc::new(MethodPolicy::load(FsPath::new(policy_file))?));
+    }
+    
+    if let Some(urls) = matches.get_many::<String>("webhook") {
+        config.webhooks = Some(Arc::new(Webhooks {
+            client: reqwest::Client::builder()
+                .timeout(std::time::Duration::from_secs(10))
+                .build()?,
+            urls: urls.cloned().collect(),
+            template: matches.get_one::<String>("webhook-template").cloned(),
+            secret: matches.get_one::<String>("webhook-secret").cloned(),
+            download_threshold: matches.get_one::<u64>("webhook-download-bytes").copied(),
+            notify_aborts: matches.get_flag("webhook-aborts"),
+            headers: Vec::new(),
+        }));
+    }
+    if let Some(urls) = matches.get_many::<String>("purge-webhook") {
+        let headers = matches
+            .get_many::<String>("purge-webhook-header")
+            .into_iter()
+            .flatten()
+            .map(|line| purge_webhook_header(line))
+            .collect::<Result<Vec<_>, _>>()?;
+        config.purge_hooks = Some(Arc::new(Webhooks {
+            client: reqwest::Client::builder()
+                .timeout(std::time::Duration::from_secs(10))
+                .build()?,
+            urls: urls.cloned().collect(),
+            template: matches.get_one::<String>("purge-webhook-template").cloned(),
+            secret: matches.get_one::<String>("webhook-secret").cloned(),
+            download_threshold: None,
+            notify_aborts: false,
+            headers,
+        }));
+    }
+    
+    Ok(config)
+}
+
+// 依命令列參數執行伺服器或子命令，由 `sfs` 執行檔呼叫
+pub async fn run() -> Result<(), Box<dyn std::error::Error>> {
+    let command = cli();
+    // 設定檔的值先放入參數，命令列參數在後面，可覆寫設定檔
+    let args: Vec<std::ffi::OsString> = std::env::args_os().collect();
+    let mut config_file = match ConfigFile::path_in(&args) {
+        Some(path) => Some(ConfigFile::load(&command, &path)?),
+        None => None,
+    };
+    let args = match &config_file {
+        Some(file) => file.merge(args),
+        None => args,
+    };
+    let matches = command.clone().get_matches_from(args);
+    
+    if let Some(check_matches) = matches.subcommand_matches("check") {
+        let path = check_matches.get_one::<PathBuf>("file").unwrap();
+        let file = ConfigFile::load(&command, path)?;
+        let file_matches = command
+            .try_get_matches_from(file.merge(vec!["sfs".into()]))
+            .map_err(|e| format!("{}: {}", path.display(), e.to_string().trim_end()))?;
+        check_settings(&file_matches, &file.vhosts)?;
+        println!("{}: 設定正確", path.display());
+        return Ok(());
+    }
+    
+    if let Some(token_matches) = matches.subcommand_matches("token") {
+        let store = TokenStore::open(FsPath::new(token_matches.get_one::<String>("file").unwrap()))?;
+        match token_matches.subcommand() {
+            Some(("create", sub)) => {
+                let scopes = sub.get_many::<String>("scope").unwrap().cloned().collect();
+                let token = store.create(
+                    sub.get_one::<String>("id").unwrap(),
+                    scopes,
+                    sub.get_one::<String>("path").unwrap(),
+                )?;
+                println!("{}", token);
+            }
+            Some(("revoke", sub)) => {
+                let id = sub.get_one::<String>("id").unwrap();
+                if !store.revoke(id)? {
+                    return Err(format!("找不到 token: {}", id).into());
+                }
+            }
+            Some(("list", _)) => {
+                for token in store.list()? {
+                    println!("{}\t{}\t/{}\t{}", token.id, token.scopes.join(","), token.path, token.created);
+                }
+            }
+            _ => unreachable!("clap 已要求指定子命令"),
+        }
+        return Ok(());
+    }
+    
+    if let Some(mirror_matches) = matches.subcommand_matches("mirror") {
+        mirror::run(MirrorOptions {
+            url: mirror_matches.get_one::<String>("url").unwrap().clone(),
+            dir: mirror_matches.get_one::<PathBuf>("dir").unwrap().clone(),
+            token: mirror_matches.get_one::<String>("token").cloned(),
+            delete: mirror_matches.get_flag("delete"),
+            jobs: *mirror_matches.get_one::<u32>("jobs").unwrap() as usize,
+            interval: mirror_matches.get_one::<u64>("interval").map(|s| Duration::from_secs(*s)),
+        })
+        .await?;
+        return Ok(());
+    }
+    
+    if let Some(duplicates_matches) = matches.subcommand_matches("duplicates") {
+        let report = duplicates::scan(
+            duplicates_matches.get_one::<PathBuf>("dir").unwrap(),
+            *duplicates_matches.get_one::<u64>("min-size").unwrap(),
+            &|_| true,
+        )?;
+        if duplicates_matches.get_flag("json") {
+            println!("{}", serde_json::to_string_pretty(&report)?);
+            return Ok(());
+        }
+        for group in &report.groups {
+            println!("{} 份 × {} 位元組，浪費 {} 位元組（{}）", group.paths.len(), group.size, group.wasted, group.sha256);
+            for path in &group.paths {
+                println!("  {}", path);
+            }
+        }
+        println!(
+            "掃描 {} 個文件，{} 組重複，共浪費 {} 位元組",
+            report.files,
+            report.groups.len(),
+            report.wasted
+        );
+        return Ok(());
+    }
+    
+    if matches.subcommand_matches("hash-password").is_some() {
+        let mut password = String::new();
+        std::io::stdin().read_line(&mut password)?;
+        let password = password.trim_end_matches(['\r', '\n']);
+        println!("{}", bcrypt::hash(password, bcrypt::DEFAULT_COST)?);
+        return Ok(());
+    }
+
+    let host = matches.get_one::<String>("host").unwrap();
+    let port = matches.get_one::<u16>("port").unwrap();
+
+    let max_open_files = matches.get_one::<u64>("max-open-files").copied();
+    match limits::raise_nofile(max_open_files) {
+        Ok(_) => {}
+        Err(e) if max_open_files.is_some() => return Err(e.into()),
+        Err(_) => {}
+    }
+
+    // 先確認帳號存在，綁定位址後才切換
+    let account = match matches.get_one::<String>("user") {
+        Some(user) => Some(Account::lookup(user, matches.get_one::<String>("group").map(String::as_str))?),
+        None => None,
+    };
+
+    let mut config = server_config(&matches, config_file.as_mut())?;
+    
+    if let Some(preload) = matches.get_one::<String>("preload") {
+        let patterns = preload
+            .split(',')
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .collect::<Vec<String>>();
+        
+        let (count, bytes) = config.file_cache.preload(
+            FsPath::new(config.base_path.as_str()),
+            &patterns,
+            &|rel| check_restricted(rel, &config).is_ok(),
+        )?;
+        info!("已預先載入 {} 個文件 ({} 位元組)", count, bytes);
+    }
+    
+    config.disk.clone().spawn(config.webhooks.clone());
+    
+    // 需要時才啟動文件監看，監看器須存活至伺服器結束
+    let fs_watcher = if matches.get_flag("manifest")
+        || matches.get_flag("changes")
+        || matches.contains_id("not-found-cache")
+        || matches.contains_id("latest")
+    {
+        Some(FsWatcher::start(FsPath::new(config.base_path.as_str()))?)
+    } else {
+        None
+    };
+    
+    if let (true, Some(watcher)) = (matches.get_flag("changes"), &fs_watcher) {
+        config.journal = Some(ChangeJournal::start(FsPath::new(config.base_path.as_str()), watcher));
+    }
+    
+    if let (Some(specs), Some(watcher)) = (matches.get_many::<String>("latest"), &fs_watcher) {
+        let specs = specs.map(|s| LatestSpec::parse(s)).collect::<Result<Vec<_>, _>>()?;
+        config.latest = Some(LatestLinks::start(
+            FsPath::new(config.base_path.as_str()),
+            specs,
+            config.restricted.clone(),
+            watcher,
+        ));
+    }
+    
+    if let (Some(&seconds), Some(watcher)) = (matches.get_one::<u64>("not-found-cache"), &fs_watcher) {
+        config.not_found = Some(NegativeCache::start(
+            FsPath::new(config.base_path.as_str()),
+            watcher,
+            Duration::from_secs(seconds),
+            config.case_insensitive,
+        ));
+    }
+    
+    if matches.get_flag("manifest") {
+        info!("正在產生文件清單...");
+        let manifest = Arc::new(Manifest::build(
+            FsPath::new(config.base_path.as_str()),
+            &published(&config),
+        )?);
+        config.manifest = Some(manifest.clone());
+        
+        if let Some(watcher) = &fs_watcher {
+            spawn_manifest_refresh(manifest, config.clone(), watcher);
+        }
+    }
+    if matches.get_flag("verify-reads") {
+        config.integrity = Some(Arc::new(Integrity::new(config.manifest.clone())));
+    }
+
+    if let Some(specs) = matches.get_many::<String>("snapshot") {
+        let specs = specs.map(|s| SnapshotSpec::parse(s)).collect::<Result<Vec<_>, _>>()?;
+        for spec in &specs {
+            if !is_safe_path(&spec.dir, &config).is_ok_and(|p| p.fs_path.is_dir()) {
+                return Err(format!("快照目錄 /{} 不存在或禁止訪問", spec.dir).into());
+            }
+        }
+        let formats = matches
+            .get_one::<String>("snapshot-formats")
+            .unwrap()
+            .split(',')
+            .map(str::trim)
+            .filter(|f| !f.is_empty())
+            .map(|f| {
+                ArchiveFormat::parse(f).ok_or_else(|| {
+                    format!("未知的封存格式 {}，可使用: {}", f, ArchiveFormat::NAMES.join(", "))
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        
+        let cache = PathBuf::from(matches.get_one::<String>("snapshot-cache").unwrap());
+        fs::create_dir_all(&cache)?;
+        // 快照放在基礎路徑底下會被列入下一次的快照
+        if fs::canonicalize(&cache)?.starts_with(fs::canonicalize(config.base_path.as_str())?) {
+            return Err(format!("快照目錄 {} 不能位於基礎路徑底下", cache.display()).into());
+        }
+        config.snapshots = Some(Arc::new(Snapshots::new(cache, formats, specs)));
+    }
+
+    let addr = format!("{}:{}", host, port);
+    // 啟動前先載入憑證，設定錯誤時直接結束而不是在第一個連線時才失敗
+    let tls_config = match (
+        matches.get_one::<PathBuf>("tls-cert"),
+        matches.get_one::<PathBuf>("tls-key"),
+    ) {
+        (Some(cert), Some(key)) => Some(tls::load(cert, key).await?),
+        _ => None,
+    };
+    let acme = match matches.get_one::<String>("acme") {
+        Some(domain) => {
+            let cache = PathBuf::from(matches.get_one::<String>("acme-cache").unwrap());
+            fs::create_dir_all(&cache)?;
+            // 快取目錄含有私鑰，不能位於提供服務的目錄中
+            if fs::canonicalize(&cache)?.starts_with(fs::canonicalize(config.base_path.as_str())?) {
+                return Err(format!("ACME 快取目錄 {} 不能位於基礎路徑底下", cache.display()).into());
+            }
+            Some(Acme::new(AcmeOptions {
+                domain: domain.clone(),
+                directory: matches.get_one::<String>("acme-directory").unwrap().clone(),
+                email: matches.get_one::<String>("acme-email").cloned(),
+                cache,
+            })?)
+        }
+        None => None,
+    };
+    let scheme = if tls_config.is_some() || acme.is_some() { "https" } else { "http" };
+    info!("伺服器運行在 {}://{}", scheme, addr);
+
+    // 指定管理位址時，管理 API 預設只在該位址提供，公開位址上完全不存在
+    let admin_addr = matches.get_one::<String>("admin-listen");
+    let metrics_addr = matches.get_one::<String>("metrics-addr");
+    let public_routes = match matches.get_one::<String>("public-routes") {
+        Some(list) => ListenerRoutes::parse(list)?,
+        None => ListenerRoutes {
+            admin: admin_addr.is_none(),
+            metrics: metrics_addr.is_none(),
+            ..ListenerRoutes::all()
+        },
+    };
+    let admin_routes = match matches.get_one::<String>("admin-routes") {
+        Some(list) => ListenerRoutes::parse(list)?,
+        None => ListenerRoutes {
+            admin: true,
+            ..ListenerRoutes::default()
+        },
+    };
+    
+    let mut app = build_app(&config, public_routes);
+    let vhosts = config_file.as_mut().map(|file| std::mem::take(&mut file.vhosts)).unwrap_or_default();
+    let mut bases = vec![PathBuf::from(config.base_path.as_str())];
+    let tenant_users: Vec<PathBuf> = vhosts.iter().filter_map(|vhost| vhost.users.clone()).collect();
+    if !vhosts.is_empty() {
+        let mut hosts = Vec::new();
+        for vhost in vhosts {
+            // 有自己用戶或掛在路徑前綴下的網站不可位於其他網站的基礎路徑內，否則可經由上層網站存取
+            if vhost.prefix.is_some() || vhost.users.is_some() {
+                let base = std::fs::canonicalize(&vhost.base)?;
+                for other in &bases {
+                    if base.starts_with(std::fs::canonicalize(other)?) {
+                        return Err(format!(
+                            "網站 {} 的基礎路徑 {} 位於 {} 之內，無法隔離",
+                            vhost.label(),
+                            vhost.base.display(),
+                            other.display()
+                        )
+                        .into());
+                    }
+                }
+            }
+            bases.push(vhost.base.clone());
+            let site = vhost_config(&config, &vhost)?;
+            if let Some(trash) = &site.trash {
+                trash.clone().spawn_expiry();
+            }
+            let app = build_app(&site, public_routes);
+            info!("網站 {} 使用基礎路徑 {}", vhost.label(), vhost.base.display());
+            hosts.push((vhost, app));
+        }
+        app = VirtualHosts::new(hosts, app).into_router();
+    }
+    let admin_app = admin_addr.map(|_| build_app(&config, admin_routes));
+    let metrics_app = metrics_addr.map(|_| {
+        build_app(
+            &config,
+            ListenerRoutes {
+                metrics: true,
+                ..ListenerRoutes::default()
+            },
+        )
+    });
+    let s3_addr = matches.get_one::<String>("s3-listen");
+    let s3_app = match s3_addr {
+        Some(_) => {
+            let bucket = matches.get_one::<String>("s3-bucket").unwrap().clone();
+            if !S3Options::valid_bucket(&bucket) {
+                return Err(format!("無效的儲存貯體名稱 {}：需為 3 到 63 個小寫英數字、- 或 .", bucket).into());
+            }
+            let credentials = matches
+                .get_one::<String>("s3-credentials")
+                .map(|value| S3Options::parse_credentials(value))
+                .transpose()?;
+            // S3 介面不使用帳號驗證，設定了驗證時必須改以簽章保護
+            if config.requires_auth() && credentials.is_none() {
+                return Err("已設定驗證，--s3-listen 需要搭配 --s3-credentials".into());
+            }
+            Some(s3::app(&config, S3Options { bucket, credentials }))
+        }
+        None => None,
+    };
+
+    let listener = upgrade::listen(&addr, 0).await?;
+    let admin_listener = match (&admin_app, admin_addr) {
+        (Some(_), Some(admin_addr)) => Some(upgrade::listen(admin_addr, 1).await?),
+        _ => None,
+    };
+    let s3_listener = match s3_addr {
+        Some(s3_addr) => Some(upgrade::listen(s3_addr, 3).await?),
+        None => None,
+    };
+    let metrics_listener = match metrics_addr {
+        Some(metrics_addr) => Some(upgrade::listen(metrics_addr, 4).await?),
+        None => None,
+    };
+    
+    let acme_listener = match &acme {
+        Some(_) => Some(upgrade::listen(matches.get_one::<String>("acme-http").unwrap(), 2).await?),
+        None => None,
+    };
+    
+    // 收到 SIGUSR2 時把 socket 交給新的執行檔，目前的程序處理完進行中的請求後結束
+    let shutdown = upgrade::on_upgrade_signal(vec![
+        Some(&listener),
+        admin_listener.as_ref(),
+        acme_listener.as_ref(),
+        s3_listener.as_ref(),
+        metrics_listener.as_ref(),
+    ])?;
+    
+    // 監聽位址已綁定，之後不再需要 root 權限
+    if let Some(account) = &account {
+        let writable: Vec<&FsPath> = acme
+            .as_ref()
+            .map(|acme| acme.cache_dir())
+            .into_iter()
+            .chain(config.snapshots.as_ref().map(|snapshots| snapshots.cache_dir()))
+            .collect();
+        account.switch(&writable)?;
+        info!("已切換為用戶 {}", account.name);
+    }
+    
+    // 最小權限：提供服務的程序不應能修改內容
+    let writable = readonly::is_writable(FsPath::new(config.base_path.as_str()))?;
+    if config.read_only && writable {
+        return Err(format!(
+            "指定了 --assert-readonly，但基礎路徑 {} 可以寫入；請以唯讀方式掛載或改用沒有寫入權限的用戶執行",
+            config.base_path
+        )
+        .into());
+    }
+    let authenticated = config.users.is_some() || config.ldap.is_some() || config.tokens.is_some();
+    #[cfg(feature = "pam")]
+    let authenticated = authenticated || config.pam.is_some();
+    if writable && !authenticated {
+        warn!(
+            "基礎路徑 {} 可以寫入，且未要求驗證；建議以唯讀方式提供並加上 --assert-readonly",
+            config.base_path
+        );
+    }
+    
+    // 之後不再需要的文件一律無法開啟；設定檔等只在啟動時讀取的文件保留讀取權限，供升級後的新程序使用
+    if matches.get_flag("sandbox") {
+        #[cfg(feature = "pam")]
+        if config.pam.is_some() {
+            return Err("--sandbox 無法與 PAM 驗證同時使用".into());
+        }
+        let mut sandbox = Sandbox::default();
+        for base in &bases {
+            match config.upload {
+                Some(_) => sandbox.write(base),
+                None => sandbox.read(base),
+            };
+        }
+        if let Some(acme) = &acme {
+            sandbox.write(acme.cache_dir());
+        }
+        if let Some(snapshots) = &config.snapshots {
+            sandbox.write(snapshots.cache_dir());
+        }
+        if let Some(journal) = &config.upload_journal {
+            sandbox.write(journal.dir());
+        }
+        for name in ["users", "acl", "method-policy"] {
+            if let Some(file) = matches.get_one::<String>(name) {
+                sandbox.read(file);
+            }
+        }
+        for name in ["tls-cert", "tls-key"] {
+            if let Some(file) = matches.get_one::<PathBuf>(name) {
+                sandbox.read(file);
+            }
+        }
+        for file in &tenant_users {
+            sandbox.read(file);
+        }
+        for path in config_file.iter().flat_map(|file| &file.sources) {
+            sandbox.read(path);
+        }
+        // token 文件由 `sfs token create` 改寫，尚未建立時允許讀取所在目錄
+        if let Some(file) = matches.get_one::<String>("tokens") {
+            let file = fs::canonicalize(file).or_else(|_| {
+                let dir = FsPath::new(file).parent().filter(|dir| !dir.as_os_str().is_empty());
+                fs::canonicalize(dir.unwrap_or(FsPath::new(".")))
+            })?;
+            sandbox.read(file);
+        }
+        if let Some(dir) = std::env::current_exe()?.parent() {
+            sandbox.execute(dir);
+        }
+        // --on-upload 的子程序同樣受限，只能執行指定的程式
+        if let Some(program) = config.upload.as_ref().and_then(|options| options.on_upload.as_ref()?.program()) {
+            sandbox.execute(program);
+        }
+        sandbox.enforce()?;
+        info!("已限制文件存取範圍");
+    }
+    
+    if let Some(snapshots) = &config.snapshots {
+        snapshots.clone().spawn(config.clone());
+    }
+    if let Some(journal) = &config.upload_journal {
+        journal.clone().spawn_cleanup();
+    }
+    if let Some(trash) = &config.trash {
+        trash.clone().spawn_expiry();
+    }
+    
+    // HTTP 驗證位址須先開始服務，才能申請憑證
+    let mut tls_config = tls_config;
+    if let (Some(acme), Some(acme_listener)) = (acme, acme_listener) {
+        info!("ACME 驗證與 HTTPS 轉址運行在 http://{}", acme_listener.local_addr()?);
+        let http = acme::serve_http(acme_listener, acme.clone(), *port, shutdown.clone());
+        tokio::spawn(async move {
+            if let Err(e) = http.await {
+                error!("ACME 驗證位址停止服務: {}", e);
+            }
+        });
+        
+        acme.ensure_certificate().await?;
+        let loaded = tls::load(&acme.cert_path(), &acme.key_path()).await?;
+        acme.spawn_renewal(loaded.clone());
+        tls_config = Some(loaded);
+    }
+    
+    // 連線數只計算公開位址
+    let connections = |app: Router| {
+        CountConnections::new(app.into_make_service_with_connect_info::<SocketAddr>(), config.metrics.clone())
+    };
+    let public = match tls_config {
+        Some(tls_config) => tls::serve(listener, tls_config, connections(app), shutdown.clone()).boxed(),
+        None => axum::serve(listener, connections(app))
+            .with_graceful_shutdown(shutdown.clone().wait())
+            .into_future()
+            .boxed(),
+    };
+    
+    let admin = match (admin_app, admin_listener) {
+        (Some(admin_app), Some(admin_listener)) => {
+            info!("管理 API 運行在 http://{}", admin_listener.local_addr()?);
+            axum::serve(
+                admin_listener,
+                admin_app.into_make_service_with_connect_info::<SocketAddr>(),
+            )
+            .with_graceful_shutdown(shutdown.clone().wait())
+            .into_future()
+            .boxed()
+        }
+        _ => std::future::ready(Ok(())).boxed(),
+    };
+    let s3 = match (s3_app, s3_listener) {
+        (Some(s3_app), Some(s3_listener)) => {
+            info!("S3 相容介面運行在 http://{}", s3_listener.local_addr()?);
+            axum::serve(s3_listener, s3_app.into_make_service_with_connect_info::<SocketAddr>())
+                .with_graceful_shutdown(shutdown.clone().wait())
+                .into_future()
+                .boxed()
+        }
+        _ => std::future::ready(Ok(())).boxed(),
+    };
+    let metrics = match (metrics_app, metrics_listener) {
+        (Some(metrics_app), Some(metrics_listener)) => {
+            info!("Prometheus 指標運行在 http://{}/metrics", metrics_listener.local_addr()?);
+            axum::serve(
+                metrics_listener,
+                metrics_app.into_make_service_with_connect_info::<SocketAddr>(),
+            )
+            .with_graceful_shutdown(shutdown.wait())
+            .into_future()
+            .boxed()
+        }
+        _ => std::future::ready(Ok(())).boxed(),
+    };
+    tokio::try_join!(public, admin, s3, metrics)?;
+    
+    Ok(())
+}
\ No newline at end of file
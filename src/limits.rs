@@ -0,0 +1,86 @@
+use std::io;
+
+use serde::Serialize;
+use tracing::{info, warn};
+
+// 低於此值時啟動時提出警告，每個連線與正在傳送的文件各需要一個描述符
+const LOW_NOFILE: u64 = 1024;
+
+// 文件描述符的使用量與上限
+#[derive(Serialize)]
+pub struct OpenFiles {
+    pub used: Option<u64>,
+    pub soft_limit: Option<u64>,
+    pub hard_limit: Option<u64>,
+}
+
+#[cfg(unix)]
+fn get_nofile() -> io::Result<libc::rlimit> {
+    let mut limit = libc::rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+    if unsafe { libc::getrlimit(libc::RLIMIT_NOFILE, &mut limit) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(limit)
+}
+
+// 將 RLIMIT_NOFILE 的軟性上限提高到指定值（不超過硬性上限），回傳調整後的軟性上限
+#[cfg(unix)]
+pub fn raise_nofile(wanted: Option<u64>) -> io::Result<u64> {
+    let mut limit = get_nofile()?;
+
+    if let Some(wanted) = wanted {
+        let hard = limit.rlim_max as u64;
+        let target = wanted.min(hard);
+        if wanted > hard {
+            warn!("文件描述符上限 {} 超過硬性上限 {}，改用 {}", wanted, hard, target);
+        }
+        if target != limit.rlim_cur as u64 {
+            limit.rlim_cur = target as libc::rlim_t;
+            if unsafe { libc::setrlimit(libc::RLIMIT_NOFILE, &limit) } != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            info!("文件描述符上限已調整為 {}", target);
+        }
+    }
+
+    let soft = limit.rlim_cur as u64;
+    if soft < LOW_NOFILE {
+        warn!(
+            "文件描述符上限只有 {}，高負載時可能無法接受連線或開啟文件，可以 --max-open-files 提高",
+            soft
+        );
+    }
+    Ok(soft)
+}
+
+#[cfg(not(unix))]
+pub fn raise_nofile(_wanted: Option<u64>) -> io::Result<u64> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "此平台不支援調整文件描述符上限"))
+}
+
+// 目前開啟的文件描述符數量與上限
+pub fn open_files() -> OpenFiles {
+    // Linux 以外的平台沒有 /proc/self/fd，只回報上限
+    let used = std::fs::read_dir("/proc/self/fd")
+        .ok()
+        .map(|entries| entries.count() as u64);
+
+    // rlim_t 在部分平台不是 u64
+    #[cfg(unix)]
+    #[allow(clippy::unnecessary_cast)]
+    let (soft_limit, hard_limit) = match get_nofile() {
+        Ok(limit) => (Some(limit.rlim_cur as u64), Some(limit.rlim_max as u64)),
+        Err(_) => (None, None),
+    };
+    #[cfg(not(unix))]
+    let (soft_limit, hard_limit) = (None, None);
+
+    OpenFiles {
+        used,
+        soft_limit,
+        hard_limit,
+    }
+}
@@ -0,0 +1,63 @@
+use std::io;
+
+// 每個監聽位址提供的功能
+//
+// 以逗號分隔的名稱設定，例如公開位址只提供 `files`，管理位址提供 `files,listings,admin`。
+// 未啟用的功能在該位址上完全不存在（404），不只是拒絕存取。
+#[derive(Clone, Copy, Default, Debug)]
+pub struct ListenerRoutes {
+    // 文件內容
+    pub files: bool,
+    // 目錄列表
+    pub listings: bool,
+    // /_manifest.json
+    pub manifest: bool,
+    // /api/changes
+    pub changes: bool,
+    // /_admin 管理 API
+    pub admin: bool,
+    // /_login 登入頁面
+    pub login: bool,
+    // /metrics Prometheus 指標
+    pub metrics: bool,
+}
+
+impl ListenerRoutes {
+    pub const NAMES: &'static [&'static str] = &["files", "listings", "manifest", "changes", "admin", "login", "metrics"];
+
+    // 所有功能（實際是否提供仍取決於對應的功能是否啟用）
+    pub fn all() -> Self {
+        ListenerRoutes {
+            files: true,
+            listings: true,
+            manifest: true,
+            changes: true,
+            admin: true,
+            login: true,
+            metrics: true,
+        }
+    }
+
+    pub fn parse(list: &str) -> io::Result<Self> {
+        let mut routes = ListenerRoutes::default();
+        for name in list.split(',').map(str::trim).filter(|n| !n.is_empty()) {
+            let enabled = match name {
+                "files" => &mut routes.files,
+                "listings" => &mut routes.listings,
+                "manifest" => &mut routes.manifest,
+                "changes" => &mut routes.changes,
+                "admin" => &mut routes.admin,
+                "login" => &mut routes.login,
+                "metrics" => &mut routes.metrics,
+                _ => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("未知的路由 {}，可使用: {}", name, Self::NAMES.join(", ")),
+                    ));
+                }
+            };
+            *enabled = true;
+        }
+        Ok(routes)
+    }
+}
@@ -0,0 +1,297 @@
+use std::{cmp::Ordering, fs, io, path::Path, time::UNIX_EPOCH};
+
+use axum::http::{header, HeaderMap};
+use chrono::{DateTime, Utc};
+use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS};
+use serde::Serialize;
+
+use crate::{problem::wants_json, transfer::ChunkWriter, ListingSort, SortColumn};
+
+// 連結路徑中需要編碼的字元
+pub const HREF: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}');
+
+// 目錄列表的輸出格式
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ListingFormat {
+    Html,
+    Json,
+    // 每行一個項目，類似 `ls -l`，方便以 shell 工具處理
+    Text,
+    // Markdown 表格，可直接貼到發行說明或 wiki
+    Markdown,
+}
+
+impl ListingFormat {
+    // 查詢參數明確指定的格式，例如 `?format=json`
+    pub fn from_query(query: Option<&str>) -> Option<Self> {
+        query.unwrap_or("").split(['&', ';']).find_map(|pair| match pair.split_once('=') {
+            Some(("format", "json")) => Some(ListingFormat::Json),
+            Some(("format", "txt")) => Some(ListingFormat::Text),
+            Some(("format", "html")) => Some(ListingFormat::Html),
+            Some(("format", "md")) => Some(ListingFormat::Markdown),
+            _ => None,
+        })
+    }
+
+    // 查詢參數優先，其次依 Accept 標頭；瀏覽器一律取得 HTML
+    pub fn negotiate(query: Option<&str>, headers: &HeaderMap) -> Self {
+        if let Some(format) = Self::from_query(query) {
+            return format;
+        }
+        if wants_json(headers) {
+            return ListingFormat::Json;
+        }
+        let accept = headers.get(header::ACCEPT).and_then(|v| v.to_str().ok()).unwrap_or("");
+        let accepts = |media_type: &str| {
+            accept
+                .split(',')
+                .any(|item| item.split(';').next().unwrap_or("").trim() == media_type)
+        };
+        if accept.contains("text/html") {
+            ListingFormat::Html
+        } else if accepts("text/markdown") {
+            ListingFormat::Markdown
+        } else if accepts("text/plain") {
+            ListingFormat::Text
+        } else {
+            ListingFormat::Html
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            ListingFormat::Html => "text/html; charset=utf-8",
+            ListingFormat::Json => "application/json",
+            ListingFormat::Text => "text/plain; charset=utf-8",
+            ListingFormat::Markdown => "text/markdown; charset=utf-8",
+        }
+    }
+}
+
+// 依版本號比較名稱：連續的數字以數值比較，其餘部分逐字比較
+pub fn version_cmp(a: &str, b: &str) -> Ordering {
+    let (mut a, mut b) = (a, b);
+    loop {
+        let (Some(x), Some(y)) = (a.chars().next(), b.chars().next()) else {
+            return a.len().cmp(&b.len());
+        };
+        if x.is_ascii_digit() && y.is_ascii_digit() {
+            let a_end = a.find(|c: char| !c.is_ascii_digit()).unwrap_or(a.len());
+            let b_end = b.find(|c: char| !c.is_ascii_digit()).unwrap_or(b.len());
+            let (a_num, b_num) = (a[..a_end].trim_start_matches('0'), b[..b_end].trim_start_matches('0'));
+            let order = a_num.len().cmp(&b_num.len()).then_with(|| a_num.cmp(b_num));
+            if order != Ordering::Equal {
+                return order;
+            }
+            (a, b) = (&a[a_end..], &b[b_end..]);
+        } else {
+            if x != y {
+                return x.cmp(&y);
+            }
+            (a, b) = (&a[x.len_utf8()..], &b[y.len_utf8()..]);
+        }
+    }
+}
+
+// 符號連結指向的路徑（`--show-symlink-targets`），無法讀取時為 None
+pub fn symlink_target(path: &Path) -> Option<String> {
+    fs::read_link(path).ok().map(|target| target.to_string_lossy().into_owned())
+}
+
+// 列表的一個項目：名稱、類型、資訊與符號連結指向的路徑
+type Item = (String, fs::FileType, fs::Metadata, Option<String>);
+
+// 讀取項目資訊並排序，排序與 `?C=M;O=D` 等參數相同，目錄排在前面
+fn sorted(entries: Vec<fs::DirEntry>, sort: ListingSort, show_targets: bool) -> io::Result<Vec<Item>> {
+    let mut items = Vec::new();
+    for entry in entries {
+        let file_type = entry.file_type()?;
+        let target = match show_targets && file_type.is_symlink() {
+            true => symlink_target(&entry.path()),
+            false => None,
+        };
+        items.push((entry.file_name().to_string_lossy().to_string(), file_type, entry.metadata()?, target));
+    }
+    items.sort_by(|a, b| {
+        let order = match sort.column {
+            SortColumn::Name => a.0.cmp(&b.0),
+            SortColumn::Modified => a.2.modified().ok().cmp(&b.2.modified().ok()),
+            SortColumn::Size => a.2.len().cmp(&b.2.len()),
+            SortColumn::Version => version_cmp(&a.0, &b.0),
+        };
+        let order = if sort.descending { order.reverse() } else { order };
+        b.1.is_dir().cmp(&a.1.is_dir()).then(order)
+    });
+    Ok(items)
+}
+
+#[derive(Serialize)]
+struct JsonEntry<'a> {
+    name: &'a str,
+    // `file`、`dir` 或 `symlink`
+    #[serde(rename = "type")]
+    kind: &'static str,
+    // 目錄為 null
+    size: Option<u64>,
+    // Unix 時間（秒），與 /_manifest.json 相同
+    mtime: Option<u64>,
+    // 從根目錄開始、已編碼的網址路徑，目錄以 `/` 結尾
+    href: String,
+    // 符號連結指向的路徑，只在 --show-symlink-targets 時提供
+    #[serde(skip_serializing_if = "Option::is_none")]
+    target: Option<&'a str>,
+}
+
+// 以 JSON 陣列輸出目錄列表
+pub fn write_json(
+    path: &str,
+    entries: Vec<fs::DirEntry>,
+    sort: ListingSort,
+    show_targets: bool,
+    out: &mut ChunkWriter,
+) -> io::Result<()> {
+    let items = sorted(entries, sort, show_targets)?;
+    let dir = path.trim_matches('/');
+    out.push_str("[");
+    for (index, (name, file_type, metadata, target)) in items.iter().enumerate() {
+        if out.is_closed() {
+            break;
+        }
+        let is_dir = file_type.is_dir();
+        let rel = if dir.is_empty() { name.clone() } else { format!("{}/{}", dir, name) };
+        let entry = JsonEntry {
+            name,
+            kind: if is_dir {
+                "dir"
+            } else if file_type.is_symlink() {
+                "symlink"
+            } else {
+                "file"
+            },
+            size: (!is_dir).then_some(metadata.len()),
+            mtime: metadata
+                .modified()
+                .ok()
+                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                .map(|d| d.as_secs()),
+            href: format!("/{}{}", utf8_percent_encode(&rel, HREF), if is_dir { "/" } else { "" }),
+            target: target.as_deref(),
+        };
+        if index > 0 {
+            out.push_str(",");
+        }
+        out.push_str("\n");
+        serde_json::to_writer(&mut *out, &entry)?;
+    }
+    out.push_str("\n]\n");
+    Ok(())
+}
+
+// 以純文字輸出目錄列表：類型（`d`、`l` 或 `-`）、大小、UTC 修改時間與名稱，以空白分隔
+//
+// 前面的欄位寬度固定，名稱從第 40 個字元開始且目錄以 `/` 結尾，含空白的名稱也能以
+// `cut -c40-` 取出，`grep '/$'` 則可篩出目錄。
+pub fn write_text(
+    entries: Vec<fs::DirEntry>,
+    sort: ListingSort,
+    show_targets: bool,
+    out: &mut ChunkWriter,
+) -> io::Result<()> {
+    for (name, file_type, metadata, target) in sorted(entries, sort, show_targets)? {
+        if out.is_closed() {
+            break;
+        }
+        let is_dir = file_type.is_dir();
+        let kind = if is_dir {
+            'd'
+        } else if file_type.is_symlink() {
+            'l'
+        } else {
+            '-'
+        };
+        let size = if is_dir { "-".to_string() } else { metadata.len().to_string() };
+        let modified = metadata
+            .modified()
+            .map(|t| DateTime::<Utc>::from(t).format("%Y-%m-%dT%H:%M:%SZ").to_string())
+            .unwrap_or_else(|_| "-".to_string());
+        // 與 `ls -l` 相同，符號連結指向的路徑接在名稱後面
+        let target = target.map(|t| format!(" -> {}", t)).unwrap_or_default();
+        out.push_str(&format!("{} {:>15} {} {}{}{}\n", kind, size, modified, name, if is_dir { "/" } else { "" }, target));
+    }
+    Ok(())
+}
+
+// Markdown 連結目標中另外需要編碼的括號與表格分隔符號
+const MARKDOWN_HREF: &AsciiSet = &HREF.add(b'(').add(b')').add(b'|');
+
+// 以 Markdown 表格輸出目錄列表，連結相對於目錄本身
+//
+// 名稱中的 `|`、`[`、`*` 等字元以反斜線跳脫，避免破壞表格或被當成格式；時間為 UTC。
+pub fn write_markdown(
+    entries: Vec<fs::DirEntry>,
+    sort: ListingSort,
+    show_targets: bool,
+    out: &mut ChunkWriter,
+) -> io::Result<()> {
+    out.push_str("| Name | Last modified (UTC) | Size |\n| --- | --- | ---: |\n");
+    for (name, file_type, metadata, target) in sorted(entries, sort, show_targets)? {
+        if out.is_closed() {
+            break;
+        }
+        let is_dir = file_type.is_dir();
+        let suffix = if is_dir { "/" } else { "" };
+        let modified = metadata
+            .modified()
+            .map(|t| DateTime::<Utc>::from(t).format("%Y-%m-%d %H:%M").to_string())
+            .unwrap_or_else(|_| "-".to_string());
+        let size = if is_dir { "-".to_string() } else { human_size(metadata.len()) };
+        let target = target.map(|t| format!(" → {}", markdown_escape(&t))).unwrap_or_default();
+        out.push_str(&format!(
+            "| [{}{}]({}{}){} | {} | {} |\n",
+            markdown_escape(&name),
+            suffix,
+            utf8_percent_encode(&name, MARKDOWN_HREF),
+            suffix,
+            target,
+            modified,
+            size
+        ));
+    }
+    Ok(())
+}
+
+fn markdown_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '>' | '|' | '#' | '~') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+// 以 1024 為單位的大小，例如 `1.5 MB`
+fn human_size(size: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if size < 1024 {
+        return format!("{} B", size);
+    }
+    let mut value = size as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
@@ -0,0 +1,172 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    io,
+    sync::{Arc, Mutex},
+};
+
+use axum::body::Bytes;
+use futures_util::{stream, Stream, StreamExt};
+use tracing::warn;
+
+use crate::compress::{self, Encoding};
+
+// 保存的壓縮列表總大小上限，超過時先淘汰最早放入的項目
+const CAPACITY: usize = 16 * 1024 * 1024;
+
+// 未壓縮超過此大小的列表不保存，產生時也不再複製
+const MAX_LISTING: usize = 4 * 1024 * 1024;
+
+type Key = (String, Encoding);
+
+#[derive(Default)]
+struct Variants {
+    bodies: HashMap<Key, Bytes>,
+    // 放入的順序，最早的在前
+    order: VecDeque<Key>,
+    bytes: usize,
+}
+
+// 壓縮後的目錄列表（--compress）
+//
+// 以列表的 ETag 與編碼為鍵保存即時壓縮的結果，熱門目錄的列表不必每次重新產生與壓縮。
+// ETag 已涵蓋目錄內容、格式、排序與上傳表單，目錄變動後舊的項目不會再被查到，
+// 只是依放入順序淘汰。每個網站各有一份，相同的 ETag 不會取到其他網站的列表。
+pub struct ListingCache {
+    capacity: usize,
+    variants: Mutex<Variants>,
+}
+
+impl Default for ListingCache {
+    fn default() -> Self {
+        ListingCache::new(CAPACITY)
+    }
+}
+
+impl ListingCache {
+    pub fn new(capacity: usize) -> Self {
+        ListingCache {
+            capacity,
+            variants: Mutex::default(),
+        }
+    }
+
+    pub fn get(&self, etag: &str, encoding: Encoding) -> Option<Bytes> {
+        let key = (etag.to_string(), encoding);
+        self.variants.lock().unwrap().bodies.get(&key).cloned()
+    }
+
+    pub fn insert(&self, etag: &str, encoding: Encoding, body: Bytes) {
+        if body.len() > self.capacity {
+            return;
+        }
+        let key = (etag.to_string(), encoding);
+        let mut variants = self.variants.lock().unwrap();
+        if variants.bodies.contains_key(&key) {
+            return;
+        }
+        while variants.bytes + body.len() > self.capacity {
+            let Some(oldest) = variants.order.pop_front() else {
+                break;
+            };
+            if let Some(evicted) = variants.bodies.remove(&oldest) {
+                variants.bytes -= evicted.len();
+            }
+        }
+        variants.bytes += body.len();
+        variants.order.push_back(key.clone());
+        variants.bodies.insert(key, body);
+    }
+
+    // 原樣送出產生中的列表，同時保留一份；完整送出後在背景壓縮並保存
+    //
+    // 產生失敗、用戶端中途離開或列表太大時不保存。
+    pub fn capture<S>(
+        self: &Arc<Self>,
+        etag: String,
+        encoding: Encoding,
+        level: Option<u32>,
+        listing: S,
+    ) -> impl Stream<Item = io::Result<Bytes>>
+    where
+        S: Stream<Item = io::Result<Bytes>>,
+    {
+        let copy = Arc::new(Mutex::new(Some(Vec::new())));
+        let chunks = copy.clone();
+        let listing = listing.inspect(move |chunk| {
+            let mut copy = chunks.lock().unwrap();
+            match (chunk, copy.as_mut()) {
+                (Ok(chunk), Some(data)) if data.len() + chunk.len() <= MAX_LISTING => data.extend_from_slice(chunk),
+                _ => *copy = None,
+            }
+        });
+        let cache = self.clone();
+        let done = stream::once(async move {
+            let Some(data) = copy.lock().unwrap().take() else {
+                return;
+            };
+            tokio::task::spawn_blocking(move || match compress::encode(encoding, level, &data) {
+                Ok(body) => cache.insert(&etag, encoding, body),
+                Err(e) => warn!("無法以 {} 壓縮列表: {}", encoding.name(), e),
+            });
+        });
+        listing.chain(done.filter_map(|_| async { None }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn variants_are_kept_per_encoding_and_evicted_oldest_first() {
+        let cache = ListingCache::new(10);
+        cache.insert("\"a\"", Encoding::Gzip, Bytes::from_static(b"gzip-a"));
+        cache.insert("\"a\"", Encoding::Zstd, Bytes::from_static(b"zs"));
+        assert_eq!(cache.get("\"a\"", Encoding::Gzip).as_deref(), Some(&b"gzip-a"[..]));
+        assert_eq!(cache.get("\"a\"", Encoding::Brotli), None);
+
+        // 放不下時淘汰最早放入的 gzip 版本
+        cache.insert("\"b\"", Encoding::Gzip, Bytes::from_static(b"gzip-b"));
+        assert_eq!(cache.get("\"a\"", Encoding::Gzip), None);
+        assert_eq!(cache.get("\"a\"", Encoding::Zstd).as_deref(), Some(&b"zs"[..]));
+        assert_eq!(cache.get("\"b\"", Encoding::Gzip).as_deref(), Some(&b"gzip-b"[..]));
+
+        // 比上限還大的列表不保存
+        cache.insert("\"c\"", Encoding::Gzip, Bytes::from_static(b"much too large"));
+        assert_eq!(cache.get("\"c\"", Encoding::Gzip), None);
+        assert!(cache.get("\"b\"", Encoding::Gzip).is_some());
+    }
+
+    #[tokio::test]
+    async fn complete_listings_are_compressed_and_kept() {
+        let cache = Arc::new(ListingCache::default());
+        let chunks = vec![Ok(Bytes::from_static(b"<html>")), Ok(Bytes::from_static(b"</html>"))];
+        let sent: Vec<Bytes> = cache
+            .capture("\"a\"".to_string(), Encoding::Gzip, None, stream::iter(chunks))
+            .map(Result::unwrap)
+            .collect()
+            .await;
+        assert_eq!(sent.concat(), b"<html></html>");
+
+        let mut kept = None;
+        for _ in 0..100 {
+            kept = cache.get("\"a\"", Encoding::Gzip);
+            if kept.is_some() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        let mut html = String::new();
+        io::Read::read_to_string(&mut flate2::read::GzDecoder::new(&kept.unwrap()[..]), &mut html).unwrap();
+        assert_eq!(html, "<html></html>");
+
+        // 產生失敗的列表不保存
+        let chunks = vec![Ok(Bytes::from_static(b"<html>")), Err(io::Error::other("timeout"))];
+        let _: Vec<_> = cache
+            .capture("\"b\"".to_string(), Encoding::Gzip, None, stream::iter(chunks))
+            .collect()
+            .await;
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        assert_eq!(cache.get("\"b\"", Encoding::Gzip), None);
+    }
+}
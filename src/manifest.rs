@@ -0,0 +1,148 @@
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, RwLock},
+    time::UNIX_EPOCH,
+};
+
+use axum::body::Bytes;
+use chrono::Utc;
+use flate2::{write::GzEncoder, Compression};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::tree::walk_files;
+
+// 清單中的單一文件
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct ManifestEntry {
+    pub path: String,
+    pub size: u64,
+    pub mtime: u64,
+    pub sha256: String,
+}
+
+#[derive(Serialize)]
+struct ManifestDocument<'a> {
+    generated: String,
+    files: &'a [ManifestEntry],
+}
+
+// 產生好的清單內容，連同預先壓縮的版本與 ETag，鏡像用戶端頻繁輪詢時不需重新產生
+pub struct Rendered {
+    pub json: Bytes,
+    pub gzip: Bytes,
+    pub etag: String,
+}
+
+impl Rendered {
+    fn new(json: String) -> io::Result<Self> {
+        let etag = format!("\"{:x}\"", Sha256::digest(json.as_bytes()));
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::best());
+        io::Write::write_all(&mut encoder, json.as_bytes())?;
+        let gzip = encoder.finish()?;
+        Ok(Rendered {
+            json: Bytes::from(json),
+            gzip: Bytes::from(gzip),
+            etag,
+        })
+    }
+}
+
+// 整個目錄樹的文件清單（路徑、大小、雜湊），供鏡像腳本一次取得
+pub struct Manifest {
+    base: PathBuf,
+    entries: Mutex<HashMap<String, ManifestEntry>>,
+    rendered: RwLock<Arc<Rendered>>,
+}
+
+impl Manifest {
+    // 掃描基礎目錄並建立清單，`allow` 用來排除禁止訪問的路徑
+    pub fn build(base: &Path, allow: &dyn Fn(&str) -> bool) -> io::Result<Self> {
+        let manifest = Manifest {
+            base: base.to_path_buf(),
+            entries: Mutex::new(HashMap::new()),
+            rendered: RwLock::new(Arc::new(Rendered::new(String::new())?)),
+        };
+        manifest.rebuild(allow)?;
+        Ok(manifest)
+    }
+
+    // 重新掃描目錄樹，大小與修改時間未變的文件沿用先前的雜湊
+    pub fn rebuild(&self, allow: &dyn Fn(&str) -> bool) -> io::Result<()> {
+        let mut files = Vec::new();
+        walk_files(&self.base, "", allow, &mut files)?;
+
+        let previous = self.entries.lock().unwrap().clone();
+        let mut entries = HashMap::with_capacity(files.len());
+
+        for (rel, path, metadata) in files {
+            let size = metadata.len();
+            let mtime = metadata
+                .modified()
+                .ok()
+                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                .map(|d| d.as_secs())
+                .unwrap_or(0);
+
+            let sha256 = match previous.get(&rel) {
+                Some(old) if old.size == size && old.mtime == mtime => old.sha256.clone(),
+                _ => match hash_file(&path) {
+                    Ok(hash) => hash,
+                    // 掃描期間被刪除的文件直接略過
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                    Err(e) => return Err(e),
+                },
+            };
+
+            entries.insert(
+                rel.clone(),
+                ManifestEntry {
+                    path: rel,
+                    size,
+                    mtime,
+                    sha256,
+                },
+            );
+        }
+
+        // 內容沒有變動時保留原本的清單，讓 ETag 維持不變
+        if entries == previous && !self.rendered.read().unwrap().json.is_empty() {
+            return Ok(());
+        }
+
+        let mut sorted: Vec<ManifestEntry> = entries.values().cloned().collect();
+        sorted.sort_by(|a, b| a.path.cmp(&b.path));
+
+        let document = ManifestDocument {
+            generated: Utc::now().to_rfc3339(),
+            files: &sorted,
+        };
+        let json = serde_json::to_string(&document).map_err(io::Error::other)?;
+        let rendered = Rendered::new(json)?;
+
+        *self.entries.lock().unwrap() = entries;
+        *self.rendered.write().unwrap() = Arc::new(rendered);
+        Ok(())
+    }
+
+    // 基礎路徑下的文件在清單中的項目
+    pub fn entry_for(&self, path: &Path) -> Option<ManifestEntry> {
+        let rel = path.strip_prefix(&self.base).ok()?.to_str()?.replace('\\', "/");
+        self.entries.lock().unwrap().get(&rel).cloned()
+    }
+
+    // 取得目前的清單內容
+    pub fn rendered(&self) -> Arc<Rendered> {
+        self.rendered.read().unwrap().clone()
+    }
+}
+
+// 計算文件的 SHA-256
+pub fn hash_file(path: &Path) -> io::Result<String> {
+    let mut file = fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
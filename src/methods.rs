@@ -0,0 +1,152 @@
+use std::{fs, io, path::Path};
+
+use axum::{
+    extract::{Request, State},
+    http::Method,
+    middleware::Next,
+    response::Response,
+};
+use percent_encoding::percent_decode_str;
+
+use crate::{restrict::normalize, ServerConfig, ServerError};
+
+struct MethodRule {
+    prefix: String,
+    methods: Vec<Method>,
+}
+
+// 依路徑前綴限制可使用的 HTTP 方法
+//
+// 規則檔每行為 `prefix METHOD,METHOD,...`，`#` 開頭為註解，`*` 代表不限制。
+// 與路徑權限規則相同，最長的符合前綴優先；沒有任何規則符合的路徑不限制。
+pub struct MethodPolicy {
+    rules: Vec<MethodRule>,
+}
+
+impl MethodPolicy {
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let mut rules = Vec::new();
+        for (index, line) in fs::read_to_string(path)?.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let invalid = |reason: String| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{} 第 {} 行: {}", path.display(), index + 1, reason),
+                )
+            };
+
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() != 2 {
+                return Err(invalid("格式應為 prefix methods".to_string()));
+            }
+
+            let mut methods = if fields[1] == "*" {
+                Vec::new()
+            } else {
+                fields[1]
+                    .split(',')
+                    .map(|m| m.trim().to_ascii_uppercase())
+                    .filter(|m| !m.is_empty())
+                    .map(|m| Method::from_bytes(m.as_bytes()).map_err(|_| invalid(format!("無效的方法 {}", m))))
+                    .collect::<io::Result<Vec<_>>>()?
+            };
+            // 允許 GET 的路徑一定也允許 HEAD，`curl -I` 與連結檢查工具才能使用
+            if methods.contains(&Method::GET) && !methods.contains(&Method::HEAD) {
+                methods.push(Method::HEAD);
+            }
+
+            // `/incoming/**` 與 `/incoming` 同義
+            let prefix = fields[0].trim_end_matches("**").trim_matches('/').to_string();
+            rules.push(MethodRule { prefix, methods });
+        }
+
+        Ok(MethodPolicy { rules })
+    }
+
+    // 路徑允許的方法，`None` 代表不限制
+    //
+    // 不分大小寫模式下 `/UPLOADS/x` 與 `/uploads/x` 是同一個文件，比對前先把兩邊轉成小寫。
+    fn allowed(&self, path: &str, ignore_case: bool) -> Option<&[Method]> {
+        let fold = |s: &str| match ignore_case {
+            true => s.to_lowercase(),
+            false => s.to_string(),
+        };
+        let path = fold(path);
+        let rule = self
+            .rules
+            .iter()
+            .filter(|rule| {
+                let prefix = fold(&rule.prefix);
+                prefix.is_empty() || path == prefix || path.starts_with(&format!("{}/", prefix))
+            })
+            .max_by_key(|rule| rule.prefix.len())?;
+
+        if rule.methods.is_empty() {
+            None
+        } else {
+            Some(&rule.methods)
+        }
+    }
+}
+
+// 在任何處理函式之前檢查請求方法
+pub async fn enforce(
+    State(config): State<ServerConfig>,
+    request: Request,
+    next: Next,
+) -> Result<Response, ServerError> {
+    if let Some(policy) = &config.method_policy {
+        let decoded = percent_decode_str(request.uri().path()).decode_utf8_lossy();
+        let path = normalize(&decoded);
+
+        if let Some(methods) = policy.allowed(&path, config.case_insensitive) {
+            if !methods.contains(request.method()) {
+                let allow: Vec<&str> = methods.iter().map(Method::as_str).collect();
+                return Err(ServerError::MethodNotAllowed(allow.join(", ")));
+            }
+        }
+    }
+
+    Ok(next.run(request).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy() -> MethodPolicy {
+        let rule = |prefix: &str, methods: &[Method]| MethodRule {
+            prefix: prefix.to_string(),
+            methods: methods.to_vec(),
+        };
+        MethodPolicy {
+            rules: vec![
+                rule("", &[Method::GET, Method::HEAD]),
+                rule("uploads", &[Method::PUT]),
+                rule("uploads/open", &[]),
+            ],
+        }
+    }
+
+    #[test]
+    fn longest_prefix_wins() {
+        let policy = policy();
+        assert_eq!(policy.allowed("a.txt", false), Some(&[Method::GET, Method::HEAD][..]));
+        assert_eq!(policy.allowed("uploads/x", false), Some(&[Method::PUT][..]));
+        assert_eq!(policy.allowed("uploads/open/x", false), None);
+        // 以路徑段比對，`uploadsx` 不屬於 `uploads`
+        assert_eq!(policy.allowed("uploadsx", false), Some(&[Method::GET, Method::HEAD][..]));
+    }
+
+    #[test]
+    fn case_is_folded_when_paths_ignore_case() {
+        let policy = policy();
+        assert_eq!(policy.allowed("UPLOADS/x", false), Some(&[Method::GET, Method::HEAD][..]));
+        assert_eq!(policy.allowed("UPLOADS/x", true), Some(&[Method::PUT][..]));
+        assert_eq!(policy.allowed("Uploads/Open/x", true), None);
+    }
+}
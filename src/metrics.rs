@@ -0,0 +1,216 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    task::{Context, Poll},
+    time::Instant,
+};
+
+use axum::{
+    extract::{Request, State},
+    http::{header, Method},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use futures_util::future::{BoxFuture, FutureExt};
+use tower::Service;
+
+use crate::{ServerConfig, ServerError};
+
+// 請求處理時間的直方圖區間（秒）
+const BUCKETS: [f64; 11] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+// 以原樣作為標籤的方法，其餘一律記為 OTHER，避免任意方法名稱讓序列數無限增加
+const METHODS: &[&str] = &[
+    "GET", "HEAD", "POST", "PUT", "DELETE", "OPTIONS", "PATCH", "PROPFIND", "PROPPATCH", "MKCOL", "COPY", "MOVE",
+];
+
+// Prometheus 指標（--metrics、--metrics-addr）
+//
+// 記錄各方法與狀態碼的請求數、處理時間（到送出回應標頭為止）、處理中的請求數與開啟的連線數；
+// 送出的位元組數與完成、中斷的傳輸數取自傳輸統計。
+#[derive(Default)]
+pub struct Metrics {
+    requests: Mutex<BTreeMap<(&'static str, u16), u64>>,
+    // 每個區間各自的數量，輸出時再累加
+    buckets: [AtomicU64; BUCKETS.len()],
+    // 處理時間總和，單位為微秒
+    duration_micros: AtomicU64,
+    in_flight: AtomicU64,
+    connections: AtomicU64,
+    panics: AtomicU64,
+}
+
+impl Metrics {
+    fn observe(&self, method: &Method, status: u16, seconds: f64) {
+        let method = METHODS.iter().find(|m| **m == method.as_str()).copied().unwrap_or("OTHER");
+        *self.requests.lock().unwrap().entry((method, status)).or_default() += 1;
+        if let Some(bucket) = BUCKETS.iter().position(|le| seconds <= *le) {
+            self.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        }
+        self.duration_micros.fetch_add((seconds * 1e6) as u64, Ordering::Relaxed);
+    }
+
+    // 處理請求時發生的 panic
+    pub fn record_panic(&self) {
+        self.panics.fetch_add(1, Ordering::Relaxed);
+    }
+
+    // Prometheus 文字格式
+    fn render(&self, config: &ServerConfig) -> String {
+        let mut out = String::new();
+        let requests = self.requests.lock().unwrap().clone();
+
+        family(&mut out, "sfs_http_requests_total", "counter", "已處理的 HTTP 請求數");
+        for ((method, status), count) in &requests {
+            let _ = writeln!(out, "sfs_http_requests_total{{method=\"{}\",status=\"{}\"}} {}", method, status, count);
+        }
+
+        family(&mut out, "sfs_http_request_duration_seconds", "histogram", "送出回應標頭前的處理時間");
+        let mut cumulative = 0;
+        for (le, bucket) in BUCKETS.iter().zip(&self.buckets) {
+            cumulative += bucket.load(Ordering::Relaxed);
+            let _ = writeln!(out, "sfs_http_request_duration_seconds_bucket{{le=\"{}\"}} {}", le, cumulative);
+        }
+        let total: u64 = requests.values().sum();
+        let _ = writeln!(out, "sfs_http_request_duration_seconds_bucket{{le=\"+Inf\"}} {}", total);
+        let sum = self.duration_micros.load(Ordering::Relaxed) as f64 / 1e6;
+        let _ = writeln!(out, "sfs_http_request_duration_seconds_sum {}", sum);
+        let _ = writeln!(out, "sfs_http_request_duration_seconds_count {}", total);
+
+        family(&mut out, "sfs_http_requests_in_flight", "gauge", "處理中的請求數");
+        let _ = writeln!(out, "sfs_http_requests_in_flight {}", self.in_flight.load(Ordering::Relaxed));
+        family(&mut out, "sfs_http_connections", "gauge", "開啟中的連線數");
+        let _ = writeln!(out, "sfs_http_connections {}", self.connections.load(Ordering::Relaxed));
+        family(&mut out, "sfs_panics_total", "counter", "處理請求時發生並回應 500 的 panic 數");
+        let _ = writeln!(out, "sfs_panics_total {}", self.panics.load(Ordering::Relaxed));
+
+        if let Some(integrity) = &config.integrity {
+            family(&mut out, "sfs_integrity_mismatches_total", "counter", "送出內容與已知雜湊不符的次數（--verify-reads）");
+            let _ = writeln!(out, "sfs_integrity_mismatches_total {}", integrity.mismatches.load(Ordering::Relaxed));
+        }
+        let stats = &config.transfer_stats;
+        family(&mut out, "sfs_bytes_sent_total", "counter", "回應主體實際送出的位元組數");
+        let _ = writeln!(out, "sfs_bytes_sent_total {}", stats.bytes_sent.load(Ordering::Relaxed));
+        family(&mut out, "sfs_transfers_total", "counter", "結束的回應傳輸數，依是否完整送出區分");
+        let _ = writeln!(out, "sfs_transfers_total{{result=\"completed\"}} {}", stats.completed.load(Ordering::Relaxed));
+        let _ = writeln!(out, "sfs_transfers_total{{result=\"aborted\"}} {}", stats.aborted.load(Ordering::Relaxed));
+        out
+    }
+}
+
+fn family(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+// 處理中請求數的計數，回應標頭產生後或請求被取消時減回
+struct InFlight(Arc<Metrics>);
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.0.in_flight.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+// 記錄每個請求的方法、狀態碼與處理時間
+pub async fn track(State(config): State<ServerConfig>, request: Request, next: Next) -> Response {
+    let Some(metrics) = config.metrics.clone() else {
+        return next.run(request).await;
+    };
+    let method = request.method().clone();
+    let started = Instant::now();
+    metrics.in_flight.fetch_add(1, Ordering::Relaxed);
+    let guard = InFlight(metrics.clone());
+
+    let response = next.run(request).await;
+    drop(guard);
+    metrics.observe(&method, response.status().as_u16(), started.elapsed().as_secs_f64());
+    response
+}
+
+// GET /metrics
+pub async fn export(State(config): State<ServerConfig>) -> Result<Response, ServerError> {
+    let metrics = config
+        .metrics
+        .as_ref()
+        .ok_or_else(|| ServerError::NotFound("metrics".to_string()))?;
+    Ok((
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4; charset=utf-8")],
+        metrics.render(&config),
+    )
+        .into_response())
+}
+
+// 計算開啟中連線數的 make service 包裝，每個連線的服務在最後一份複本丟棄時減回
+#[derive(Clone)]
+pub struct CountConnections<M> {
+    inner: M,
+    metrics: Option<Arc<Metrics>>,
+}
+
+impl<M> CountConnections<M> {
+    pub fn new(inner: M, metrics: Option<Arc<Metrics>>) -> Self {
+        CountConnections { inner, metrics }
+    }
+}
+
+impl<M, T> Service<T> for CountConnections<M>
+where
+    M: Service<T>,
+    M::Future: Send + 'static,
+{
+    type Response = Connection<M::Response>;
+    type Error = M::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, target: T) -> Self::Future {
+        let guard = self.metrics.clone().map(|metrics| {
+            metrics.connections.fetch_add(1, Ordering::Relaxed);
+            Arc::new(ConnectionGuard(metrics))
+        });
+        self.inner
+            .call(target)
+            .map(|service| service.map(|inner| Connection { inner, _guard: guard }))
+            .boxed()
+    }
+}
+
+struct ConnectionGuard(Arc<Metrics>);
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        self.0.connections.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+// 單一連線的服務
+#[derive(Clone)]
+pub struct Connection<S> {
+    inner: S,
+    _guard: Option<Arc<ConnectionGuard>>,
+}
+
+impl<S, R> Service<R> for Connection<S>
+where
+    S: Service<R>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: R) -> Self::Future {
+        self.inner.call(request)
+    }
+}
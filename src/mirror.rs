@@ -0,0 +1,285 @@
+use std::{
+    collections::HashSet,
+    io::{self, Read},
+    path::{Component, Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use flate2::read::GzDecoder;
+use futures_util::{stream, StreamExt};
+use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS};
+use reqwest::{header, Client, StatusCode};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use tokio::io::AsyncWriteExt;
+use tracing::{info, warn};
+
+use crate::manifest::ManifestEntry;
+
+// 下載途中的暫存檔副檔名，完成並驗證雜湊後才改名
+const PARTIAL: &str = ".sfs-partial";
+
+// 網址路徑中需要編碼的字元
+const PATH_SEGMENT: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}');
+
+pub struct MirrorOptions {
+    // 來源伺服器的網址，例如 `https://primary.example.com`
+    pub url: String,
+    pub dir: PathBuf,
+    // 以 `Authorization: Bearer` 送出的 API token
+    pub token: Option<String>,
+    // 刪除來源已不存在的本機文件
+    pub delete: bool,
+    // 同時下載的文件數
+    pub jobs: usize,
+    // 持續同步的輪詢間隔，未指定時同步一次就結束
+    pub interval: Option<Duration>,
+}
+
+#[derive(Deserialize)]
+struct ManifestDocument {
+    files: Vec<ManifestEntry>,
+}
+
+// 從另一個實例的 /_manifest.json 同步文件到本機目錄
+pub async fn run(options: MirrorOptions) -> io::Result<()> {
+    let client = Client::builder()
+        .timeout(Duration::from_secs(300))
+        .build()
+        .map_err(io::Error::other)?;
+    let mirror = Mirror { client, options };
+    tokio::fs::create_dir_all(&mirror.options.dir).await?;
+
+    let mut etag: Option<String> = None;
+    loop {
+        match mirror.fetch_manifest(etag.as_deref()).await {
+            Ok(Some((files, new_etag))) => {
+                let failed = mirror.sync(&files).await?;
+                // 有文件失敗時不記住 ETag，下次輪詢即使清單未變也會重試
+                etag = if failed == 0 { new_etag } else { None };
+            }
+            Ok(None) => info!("清單未變動"),
+            Err(e) if mirror.options.interval.is_some() => warn!("無法取得清單: {}", e),
+            Err(e) => return Err(e),
+        }
+
+        match mirror.options.interval {
+            Some(interval) => tokio::time::sleep(interval).await,
+            None => return Ok(()),
+        }
+    }
+}
+
+struct Mirror {
+    client: Client,
+    options: MirrorOptions,
+}
+
+impl Mirror {
+    fn get(&self, path: &str) -> reqwest::RequestBuilder {
+        let url = format!(
+            "{}/{}",
+            self.options.url.trim_end_matches('/'),
+            utf8_percent_encode(path, PATH_SEGMENT)
+        );
+        let request = self.client.get(url);
+        match &self.options.token {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        }
+    }
+
+    // 取得清單；與上次的 ETag 相同時回傳 None
+    async fn fetch_manifest(&self, etag: Option<&str>) -> io::Result<Option<(Vec<ManifestEntry>, Option<String>)>> {
+        let mut request = self.get("_manifest.json").header(header::ACCEPT_ENCODING, "gzip");
+        if let Some(etag) = etag {
+            request = request.header(header::IF_NONE_MATCH, etag);
+        }
+        let response = request.send().await.map_err(io::Error::other)?;
+        if response.status() == StatusCode::NOT_MODIFIED {
+            return Ok(None);
+        }
+        if !response.status().is_success() {
+            return Err(io::Error::other(format!("取得清單失敗: {}", response.status())));
+        }
+
+        let etag = response
+            .headers()
+            .get(header::ETAG)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string);
+        let gzip = response
+            .headers()
+            .get(header::CONTENT_ENCODING)
+            .is_some_and(|v| v.as_bytes().eq_ignore_ascii_case(b"gzip"));
+        let body = response.bytes().await.map_err(io::Error::other)?;
+        let json = if gzip {
+            let mut json = Vec::new();
+            GzDecoder::new(&body[..]).read_to_end(&mut json)?;
+            json
+        } else {
+            body.to_vec()
+        };
+
+        let document: ManifestDocument = serde_json::from_slice(&json)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("無效的清單: {}", e)))?;
+        Ok(Some((document.files, etag)))
+    }
+
+    // 下載有變動的文件，回傳失敗的文件數
+    async fn sync(&self, files: &[ManifestEntry]) -> io::Result<usize> {
+        // 清單來自遠端，路徑只能是目錄底下的一般名稱
+        let files: Vec<&ManifestEntry> = files
+            .iter()
+            .filter(|entry| {
+                let safe = !entry.path.is_empty()
+                    && Path::new(&entry.path).components().all(|c| matches!(c, Component::Normal(_)));
+                if !safe {
+                    warn!("略過不安全的路徑: {}", entry.path);
+                }
+                safe
+            })
+            .collect();
+
+        let results: Vec<(&ManifestEntry, io::Result<bool>)> = stream::iter(files.iter().copied())
+            .map(|entry| async move { (entry, self.sync_file(entry).await) })
+            .buffer_unordered(self.options.jobs.max(1))
+            .collect()
+            .await;
+
+        let (mut downloaded, mut bytes, mut failed) = (0, 0, 0);
+        for (entry, result) in results {
+            match result {
+                Ok(true) => {
+                    downloaded += 1;
+                    bytes += entry.size;
+                }
+                Ok(false) => {}
+                Err(e) => {
+                    warn!("同步 {} 失敗: {}", entry.path, e);
+                    failed += 1;
+                }
+            }
+        }
+
+        let removed = match self.options.delete {
+            true => {
+                let keep: HashSet<String> = files.iter().map(|entry| entry.path.clone()).collect();
+                let dir = self.options.dir.clone();
+                tokio::task::spawn_blocking(move || remove_extra(&dir, &keep))
+                    .await
+                    .map_err(io::Error::other)??
+            }
+            false => 0,
+        };
+
+        info!(
+            "同步完成: 下載 {} 個文件 ({} 位元組)，刪除 {} 個，失敗 {} 個",
+            downloaded, bytes, removed, failed
+        );
+        Ok(failed)
+    }
+
+    // 同步單一文件，回傳是否有下載
+    async fn sync_file(&self, entry: &ManifestEntry) -> io::Result<bool> {
+        let path = self.options.dir.join(&entry.path);
+        let mtime = UNIX_EPOCH + Duration::from_secs(entry.mtime);
+
+        if let Ok(metadata) = tokio::fs::metadata(&path).await {
+            if metadata.is_file() && metadata.len() == entry.size {
+                if metadata.modified().ok() == Some(mtime) {
+                    return Ok(false);
+                }
+                // 修改時間不同但內容相同（例如複製後），只更新時間
+                if hash_file(&path).await? == entry.sha256 {
+                    set_modified(&path, mtime).await?;
+                    return Ok(false);
+                }
+            }
+        }
+
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        let mut partial = path.clone().into_os_string();
+        partial.push(PARTIAL);
+        let partial = PathBuf::from(partial);
+
+        let result = self.download(entry, &partial).await;
+        if let Err(e) = result {
+            let _ = tokio::fs::remove_file(&partial).await;
+            return Err(e);
+        }
+        set_modified(&partial, mtime).await?;
+        tokio::fs::rename(&partial, &path).await?;
+        Ok(true)
+    }
+
+    async fn download(&self, entry: &ManifestEntry, partial: &Path) -> io::Result<()> {
+        let mut response = self.get(&entry.path).send().await.map_err(io::Error::other)?;
+        if response.status() != StatusCode::OK {
+            return Err(io::Error::other(format!("下載失敗: {}", response.status())));
+        }
+
+        let mut file = tokio::fs::File::create(partial).await?;
+        let mut hasher = Sha256::new();
+        while let Some(chunk) = response.chunk().await.map_err(io::Error::other)? {
+            hasher.update(&chunk);
+            file.write_all(&chunk).await?;
+        }
+        file.sync_all().await?;
+
+        // 清單產生後來源文件可能又變動，雜湊不符時保留舊文件，下次同步再試
+        let hash = format!("{:x}", hasher.finalize());
+        if hash != entry.sha256 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("SHA-256 不符，預期 {}，實際 {}", entry.sha256, hash),
+            ));
+        }
+        Ok(())
+    }
+}
+
+async fn hash_file(path: &Path) -> io::Result<String> {
+    let path = path.to_path_buf();
+    tokio::task::spawn_blocking(move || {
+        let mut hasher = Sha256::new();
+        io::copy(&mut std::fs::File::open(path)?, &mut hasher)?;
+        Ok(format!("{:x}", hasher.finalize()))
+    })
+    .await
+    .map_err(io::Error::other)?
+}
+
+async fn set_modified(path: &Path, time: SystemTime) -> io::Result<()> {
+    let path = path.to_path_buf();
+    tokio::task::spawn_blocking(move || std::fs::File::options().write(true).open(path)?.set_modified(time))
+        .await
+        .map_err(io::Error::other)?
+}
+
+// 刪除清單中沒有的文件（包括中斷留下的暫存檔），回傳刪除的數量
+fn remove_extra(dir: &Path, keep: &HashSet<String>) -> io::Result<usize> {
+    let mut files = Vec::new();
+    crate::tree::walk_files(dir, "", &|_| true, &mut files)?;
+    let mut removed = 0;
+    for (rel, path, _) in files {
+        if !keep.contains(&rel) {
+            std::fs::remove_file(&path)?;
+            info!("已刪除 {}", rel);
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
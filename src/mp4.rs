@@ -0,0 +1,95 @@
+use std::{
+    collections::HashSet,
+    fs::{File, Metadata},
+    io::{self, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::SystemTime,
+};
+
+use tracing::warn;
+
+// 需要檢查的影片副檔名
+const EXTENSIONS: [&str; 3] = ["mp4", "m4v", "mov"];
+
+// 最多讀取的頂層 atom 數，避免損壞的文件造成長時間掃描
+const MAX_ATOMS: usize = 64;
+
+// 檢查 MP4 的 moov atom 是否位於 mdat 之後
+//
+// moov 在文件結尾時，瀏覽器必須先下載（或以 Range 跳到）文件尾端才能開始播放，
+// 漸進式播放會卡住。每個文件版本只警告一次。
+#[derive(Default)]
+pub struct Mp4Checker {
+    warned: Mutex<HashSet<(PathBuf, Option<SystemTime>)>>,
+}
+
+impl Mp4Checker {
+    pub fn check(&self, path: &Path, metadata: &Metadata) {
+        let is_video = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| EXTENSIONS.contains(&e.to_ascii_lowercase().as_str()));
+        if !is_video {
+            return;
+        }
+
+        let key = (path.to_path_buf(), metadata.modified().ok());
+        if self.warned.lock().unwrap().contains(&key) {
+            return;
+        }
+
+        if let Ok(true) = moov_after_mdat(path) {
+            warn!(
+                "{} 的 moov atom 位於文件結尾，瀏覽器無法邊下載邊播放，請以 `ffmpeg -i in.mp4 -c copy -movflags +faststart out.mp4` 重新封裝",
+                path.display()
+            );
+        }
+        // 無論結果如何都記下，未變動的文件不再重複讀取
+        self.warned.lock().unwrap().insert(key);
+    }
+}
+
+// 依序讀取頂層 atom，回傳 mdat 是否出現在 moov 之前
+fn moov_after_mdat(path: &Path) -> io::Result<bool> {
+    let mut file = File::open(path)?;
+    let len = file.metadata()?.len();
+    let mut offset = 0u64;
+    let mut seen_mdat = false;
+
+    for _ in 0..MAX_ATOMS {
+        if offset + 8 > len {
+            break;
+        }
+        file.seek(SeekFrom::Start(offset))?;
+        let mut header = [0u8; 8];
+        file.read_exact(&mut header)?;
+        let size = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as u64;
+        let kind = &header[4..8];
+
+        let size = match size {
+            // 大小為 0 代表延伸到文件結尾
+            0 => len - offset,
+            // 大小為 1 代表後面接著 64 位元的實際大小
+            1 => {
+                let mut large = [0u8; 8];
+                file.read_exact(&mut large)?;
+                u64::from_be_bytes(large)
+            }
+            size => size,
+        };
+
+        match kind {
+            b"moov" => return Ok(seen_mdat),
+            b"mdat" => seen_mdat = true,
+            _ => {}
+        }
+
+        if size < 8 {
+            break;
+        }
+        offset += size;
+    }
+
+    Ok(false)
+}
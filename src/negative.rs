@@ -0,0 +1,134 @@
+use std::{
+    collections::HashMap,
+    path::{Component, Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::watcher::FsWatcher;
+
+// 最多記住的不存在路徑數，超過時先清除過期項目，仍然太多則全部清空
+const CAPACITY: usize = 10_000;
+
+// 近期查詢過但不存在的路徑
+//
+// 掃描程式反覆請求不存在的路徑（wp-login.php 等）時，在有效期間內直接回應 404，
+// 不必每次都 canonicalize。監看器回報路徑（或其上層目錄）出現時立即移除對應項目，
+// 有效期間只是監看器遺漏事件時的上限。
+pub struct NegativeCache {
+    ttl: Duration,
+    case_insensitive: bool,
+    entries: Mutex<HashMap<PathBuf, Instant>>,
+}
+
+impl NegativeCache {
+    pub fn start(base: &Path, watcher: &FsWatcher, ttl: Duration, case_insensitive: bool) -> Arc<Self> {
+        let cache = Arc::new(NegativeCache {
+            ttl,
+            case_insensitive,
+            entries: Mutex::new(HashMap::new()),
+        });
+
+        // 監看器回報的路徑可能以正規化後的基礎路徑開頭，統一換回設定的基礎路徑
+        let base = base.to_path_buf();
+        let canonical_base = base.canonicalize().ok();
+        let mut changes = watcher.subscribe();
+        let invalidator = cache.clone();
+        tokio::spawn(async move {
+            loop {
+                let paths = match changes.recv().await {
+                    Ok(paths) => paths,
+                    // 遺漏了部分變動，無法得知哪些路徑已出現
+                    Err(RecvError::Lagged(_)) => {
+                        invalidator.entries.lock().unwrap().clear();
+                        continue;
+                    }
+                    Err(RecvError::Closed) => break,
+                };
+
+                let changed: Vec<PathBuf> = paths
+                    .iter()
+                    .map(|path| match canonical_base.as_deref().and_then(|c| path.strip_prefix(c).ok()) {
+                        Some(rel) if !path.starts_with(&base) => invalidator.key(&base.join(rel)),
+                        _ => invalidator.key(path),
+                    })
+                    .collect();
+                invalidator
+                    .entries
+                    .lock()
+                    .unwrap()
+                    .retain(|missing, _| !changed.iter().any(|path| missing.starts_with(path)));
+            }
+        });
+
+        cache
+    }
+
+    fn key(&self, path: &Path) -> PathBuf {
+        if self.case_insensitive {
+            PathBuf::from(path.to_string_lossy().to_lowercase())
+        } else {
+            path.to_path_buf()
+        }
+    }
+
+    // 路徑是否在有效期間內確認過不存在
+    pub fn contains(&self, path: &Path) -> bool {
+        let key = self.key(path);
+        let mut entries = self.entries.lock().unwrap();
+        match entries.get(&key) {
+            Some(expires) if *expires > Instant::now() => true,
+            Some(_) => {
+                entries.remove(&key);
+                false
+            }
+            None => false,
+        }
+    }
+
+    // 記住不存在的路徑
+    //
+    // 含 `..` 的路徑與監看器回報的路徑寫法不同，無法在出現時移除，因此不記錄。
+    pub fn insert(&self, path: &Path) {
+        if path.components().any(|c| c == Component::ParentDir) {
+            return;
+        }
+
+        let now = Instant::now();
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() >= CAPACITY {
+            entries.retain(|_, expires| *expires > now);
+            if entries.len() >= CAPACITY {
+                entries.clear();
+            }
+        }
+        entries.insert(self.key(path), now + self.ttl);
+    }
+
+    // 伺服器自己建立了路徑（上傳、MKCOL、COPY），不必等監看器回報
+    //
+    // 清除路徑本身、上層目錄與底下的項目。
+    pub fn forget(&self, path: &Path) {
+        let path = self.key(path);
+        self.entries
+            .lock()
+            .unwrap()
+            .retain(|missing, _| !path.starts_with(missing) && !missing.starts_with(&path));
+    }
+
+    // 清除記住的路徑，可指定只清除某個路徑底下的項目，回傳清除的數量
+    pub fn flush(&self, prefix: Option<&Path>) -> usize {
+        let mut entries = self.entries.lock().unwrap();
+        let before = entries.len();
+        match prefix {
+            Some(prefix) => {
+                let prefix = self.key(prefix);
+                entries.retain(|missing, _| !missing.starts_with(&prefix));
+            }
+            None => entries.clear(),
+        }
+        before - entries.len()
+    }
+}
@@ -0,0 +1,153 @@
+use std::{
+    ffi::{c_char, c_int, c_void, CString},
+    ptr,
+    sync::Arc,
+    time::Duration,
+};
+
+use crate::auth::{Permission, User, VerifiedCache};
+
+// 成功驗證的結果保留時間
+const VERIFIED_TTL: Duration = Duration::from_secs(300);
+
+const PAM_SUCCESS: c_int = 0;
+const PAM_CONV_ERR: c_int = 19;
+const PAM_BUF_ERR: c_int = 5;
+const PAM_PROMPT_ECHO_OFF: c_int = 1;
+const PAM_PROMPT_ECHO_ON: c_int = 2;
+
+#[repr(C)]
+struct PamMessage {
+    msg_style: c_int,
+    msg: *const c_char,
+}
+
+#[repr(C)]
+struct PamResponse {
+    resp: *mut c_char,
+    resp_retcode: c_int,
+}
+
+#[repr(C)]
+struct PamConv {
+    conv: extern "C" fn(
+        num_msg: c_int,
+        msg: *mut *const PamMessage,
+        resp: *mut *mut PamResponse,
+        appdata_ptr: *mut c_void,
+    ) -> c_int,
+    appdata_ptr: *mut c_void,
+}
+
+#[link(name = "pam")]
+extern "C" {
+    fn pam_start(
+        service_name: *const c_char,
+        user: *const c_char,
+        pam_conversation: *const PamConv,
+        pamh: *mut *mut c_void,
+    ) -> c_int;
+    fn pam_authenticate(pamh: *mut c_void, flags: c_int) -> c_int;
+    fn pam_acct_mgmt(pamh: *mut c_void, flags: c_int) -> c_int;
+    fn pam_end(pamh: *mut c_void, pam_status: c_int) -> c_int;
+}
+
+// PAM 對話函式：以密碼回答所有提示
+//
+// 回應陣列與字串由 PAM 負責以 free 釋放，因此必須以 malloc 系列配置。
+extern "C" fn converse(
+    num_msg: c_int,
+    msg: *mut *const PamMessage,
+    resp: *mut *mut PamResponse,
+    appdata_ptr: *mut c_void,
+) -> c_int {
+    if num_msg <= 0 || msg.is_null() || resp.is_null() || appdata_ptr.is_null() {
+        return PAM_CONV_ERR;
+    }
+
+    unsafe {
+        let password = appdata_ptr as *const c_char;
+        let responses =
+            libc::calloc(num_msg as usize, std::mem::size_of::<PamResponse>()) as *mut PamResponse;
+        if responses.is_null() {
+            return PAM_BUF_ERR;
+        }
+
+        for i in 0..num_msg as usize {
+            let message = *msg.add(i);
+            let style = (*message).msg_style;
+            if style == PAM_PROMPT_ECHO_OFF || style == PAM_PROMPT_ECHO_ON {
+                (*responses.add(i)).resp = libc::strdup(password);
+            }
+        }
+
+        *resp = responses;
+    }
+    PAM_SUCCESS
+}
+
+// 以 PAM 驗證本機系統帳號
+pub struct PamBackend {
+    service: CString,
+    verified: VerifiedCache,
+}
+
+impl PamBackend {
+    pub fn new(service: &str) -> Result<Self, std::ffi::NulError> {
+        Ok(PamBackend {
+            service: CString::new(service)?,
+            verified: VerifiedCache::new(VERIFIED_TTL),
+        })
+    }
+
+    // 驗證用戶名稱與密碼，此函式會阻塞，須在阻塞執行緒中呼叫
+    pub fn authenticate(&self, name: &str, password: &str) -> Option<Arc<User>> {
+        if name.is_empty() || password.is_empty() {
+            return None;
+        }
+
+        if let Some(user) = self.verified.get(name, password) {
+            return Some(user);
+        }
+
+        let c_name = CString::new(name).ok()?;
+        let c_password = CString::new(password).ok()?;
+        let conversation = PamConv {
+            conv: converse,
+            appdata_ptr: c_password.as_ptr() as *mut c_void,
+        };
+
+        let status = unsafe {
+            let mut handle = ptr::null_mut();
+            let mut status = pam_start(
+                self.service.as_ptr(),
+                c_name.as_ptr(),
+                &conversation,
+                &mut handle,
+            );
+            if status == PAM_SUCCESS {
+                status = pam_authenticate(handle, 0);
+            }
+            if status == PAM_SUCCESS {
+                // 確認帳號未被停用或過期
+                status = pam_acct_mgmt(handle, 0);
+            }
+            if !handle.is_null() {
+                pam_end(handle, status);
+            }
+            status
+        };
+
+        if status != PAM_SUCCESS {
+            return None;
+        }
+
+        // 系統帳號預設可瀏覽與下載，更細的控制交由路徑權限規則
+        let user = Arc::new(User::external(
+            name.to_string(),
+            vec![Permission::Read, Permission::List],
+        ));
+        self.verified.insert(name, password, user.clone());
+        Some(user)
+    }
+}
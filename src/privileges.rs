@@ -0,0 +1,102 @@
+use std::{io, path::Path};
+
+// 綁定特權埠之後要切換成的帳號
+pub struct Account {
+    pub name: String,
+    #[cfg(unix)]
+    uid: libc::uid_t,
+    #[cfg(unix)]
+    gid: libc::gid_t,
+}
+
+#[cfg(unix)]
+impl Account {
+    // 查詢用戶與群組，也接受數字 ID；未指定群組時使用用戶的主要群組
+    pub fn lookup(user: &str, group: Option<&str>) -> io::Result<Self> {
+        use std::ffi::{CStr, CString};
+
+        let not_found = |what: &str, name: &str| {
+            io::Error::new(io::ErrorKind::NotFound, format!("找不到{} {}", what, name))
+        };
+
+        let c_user = CString::new(user)?;
+        let passwd = match user.parse::<libc::uid_t>() {
+            Ok(uid) => unsafe { libc::getpwuid(uid) },
+            Err(_) => unsafe { libc::getpwnam(c_user.as_ptr()) },
+        };
+        if passwd.is_null() {
+            return Err(not_found("用戶", user));
+        }
+        let (name, uid, primary_gid) = unsafe {
+            (
+                CStr::from_ptr((*passwd).pw_name).to_string_lossy().to_string(),
+                (*passwd).pw_uid,
+                (*passwd).pw_gid,
+            )
+        };
+
+        let gid = match group {
+            None => primary_gid,
+            Some(group) => match group.parse::<libc::gid_t>() {
+                Ok(gid) => gid,
+                Err(_) => {
+                    let c_group = CString::new(group)?;
+                    let entry = unsafe { libc::getgrnam(c_group.as_ptr()) };
+                    if entry.is_null() {
+                        return Err(not_found("群組", group));
+                    }
+                    unsafe { (*entry).gr_gid }
+                }
+            },
+        };
+
+        Ok(Account { name, uid, gid })
+    }
+
+    // 放棄 root 權限：補充群組、群組、用戶依序設定，順序不可顛倒
+    //
+    // `writable` 中的目錄（例如 ACME 與快照的快取）先改為該帳號擁有，切換後才能繼續寫入；
+    // 只處理目錄本身與其中的文件。glibc 的 setuid 等函式會套用到程序的所有執行緒，
+    // tokio 已啟動的工作執行緒也一併切換。已經是該帳號時（例如升級後的新程序）不做任何事。
+    pub fn switch(&self, writable: &[&Path]) -> io::Result<()> {
+        use std::{ffi::CString, os::unix::fs::chown};
+
+        let already = unsafe { libc::geteuid() == self.uid && libc::getegid() == self.gid };
+        if already {
+            return Ok(());
+        }
+
+        for dir in writable {
+            chown(dir, Some(self.uid), Some(self.gid))?;
+            for entry in std::fs::read_dir(dir)? {
+                chown(entry?.path(), Some(self.uid), Some(self.gid))?;
+            }
+        }
+
+        let name = CString::new(self.name.as_str())?;
+        unsafe {
+            if libc::initgroups(name.as_ptr(), self.gid as _) != 0
+                || libc::setgid(self.gid) != 0
+                || libc::setuid(self.uid) != 0
+            {
+                return Err(io::Error::last_os_error());
+            }
+            // 確認無法再取回 root 權限
+            if self.uid != 0 && libc::setuid(0) == 0 {
+                return Err(io::Error::other("切換用戶後仍能取回 root 權限"));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(unix))]
+impl Account {
+    pub fn lookup(_user: &str, _group: Option<&str>) -> io::Result<Self> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "此平台不支援切換用戶"))
+    }
+
+    pub fn switch(&self, _writable: &[&Path]) -> io::Result<()> {
+        Ok(())
+    }
+}
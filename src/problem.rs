@@ -0,0 +1,89 @@
+use axum::{
+    body::Body,
+    extract::Request,
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    middleware::Next,
+    response::Response,
+};
+use serde_json::{json, Map, Value};
+
+// 錯誤的機器可讀描述（RFC 9457 problem details）
+//
+// 錯誤回應預設是給瀏覽器看的 HTML，同時把這份描述附在回應的擴充資料中，
+// 由 `render` 依請求的 Accept 改寫為 application/problem+json。
+#[derive(Clone, Debug)]
+pub struct Problem {
+    pub status: StatusCode,
+    // 穩定的錯誤代碼，例如 `not_found`、`range_not_satisfiable`
+    pub code: &'static str,
+    pub detail: String,
+    // 額外的欄位，例如 404 的 `suggestions`
+    extensions: Map<String, Value>,
+}
+
+impl Problem {
+    pub fn new(status: StatusCode, code: &'static str, detail: impl Into<String>) -> Self {
+        Problem {
+            status,
+            code,
+            detail: detail.into(),
+            extensions: Map::new(),
+        }
+    }
+
+    pub fn with(mut self, name: &str, value: Value) -> Self {
+        self.extensions.insert(name.to_string(), value);
+        self
+    }
+
+    fn to_json(&self, instance: &str) -> Value {
+        let mut body = json!({
+            "type": "about:blank",
+            "title": self.status.canonical_reason().unwrap_or("Error"),
+            "status": self.status.as_u16(),
+            "detail": self.detail,
+            "instance": instance,
+            "code": self.code,
+        });
+        if let Value::Object(fields) = &mut body {
+            for (name, value) in &self.extensions {
+                fields.entry(name.clone()).or_insert_with(|| value.clone());
+            }
+        }
+        body
+    }
+}
+
+// 請求是否來自 API 用戶端：明確接受 JSON 且不是瀏覽器頁面瀏覽
+pub fn wants_json(headers: &HeaderMap) -> bool {
+    let accept = headers
+        .get(header::ACCEPT)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("");
+    !accept.contains("text/html")
+        && accept
+            .split(',')
+            .map(|item| item.split(';').next().unwrap_or("").trim())
+            .any(|mime| mime == "application/json" || mime.ends_with("+json"))
+}
+
+// 把帶有 Problem 的錯誤回應改寫為 problem+json，保留其他標頭（WWW-Authenticate、Retry-After 等）
+pub async fn render(request: Request, next: Next) -> Response {
+    let json = wants_json(request.headers());
+    let instance = request.uri().path().to_string();
+    let response = next.run(request).await;
+    if !json {
+        return response;
+    }
+    let Some(problem) = response.extensions().get::<Problem>().cloned() else {
+        return response;
+    };
+
+    let (mut parts, _) = response.into_parts();
+    parts.headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/problem+json"),
+    );
+    parts.headers.remove(header::CONTENT_LENGTH);
+    Response::from_parts(parts, Body::from(problem.to_json(&instance).to_string()))
+}
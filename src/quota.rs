@@ -0,0 +1,82 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use crate::{tree::walk_files, ServerError};
+
+// 用量重新掃描的間隔，期間內以上傳的大小累加估計
+const RESCAN_INTERVAL: Duration = Duration::from_secs(60);
+
+struct Usage {
+    bytes: u64,
+    scanned: Instant,
+}
+
+// 上傳的總容量上限（--quota，或 `[[vhost]]` 的 quota）
+//
+// 用量是基礎路徑下所有文件的大小總和，定期重新掃描；兩次掃描之間上傳的大小直接累加，
+// 取代既有文件時會暫時高估，下次掃描後修正。
+pub struct Quota {
+    limit: u64,
+    base: PathBuf,
+    usage: Mutex<Option<Usage>>,
+}
+
+impl Quota {
+    pub fn new(base: &Path, limit: u64) -> Self {
+        Quota {
+            limit,
+            base: base.to_path_buf(),
+            usage: Mutex::new(None),
+        }
+    }
+
+    pub fn limit(&self) -> u64 {
+        self.limit
+    }
+
+    // 剩餘可上傳的位元組數，已用完時回應 507
+    pub async fn remaining(&self) -> Result<u64, ServerError> {
+        let cached = self
+            .usage
+            .lock()
+            .unwrap()
+            .as_ref()
+            .filter(|usage| usage.scanned.elapsed() < RESCAN_INTERVAL)
+            .map(|usage| usage.bytes);
+        let used = match cached {
+            Some(bytes) => bytes,
+            None => {
+                let base = self.base.clone();
+                let bytes = tokio::task::spawn_blocking(move || scan(&base))
+                    .await
+                    .map_err(|e| ServerError::Internal(e.to_string()))??;
+                *self.usage.lock().unwrap() = Some(Usage {
+                    bytes,
+                    scanned: Instant::now(),
+                });
+                bytes
+            }
+        };
+        match self.limit.saturating_sub(used) {
+            0 => Err(ServerError::InsufficientStorage),
+            remaining => Ok(remaining),
+        }
+    }
+
+    // 記錄完成的上傳
+    pub fn record(&self, bytes: u64) {
+        if let Some(usage) = self.usage.lock().unwrap().as_mut() {
+            usage.bytes += bytes;
+        }
+    }
+}
+
+fn scan(base: &Path) -> io::Result<u64> {
+    let mut files = Vec::new();
+    walk_files(base, "", &|_| true, &mut files)?;
+    Ok(files.iter().map(|(_, _, metadata)| metadata.len()).sum())
+}
@@ -0,0 +1,81 @@
+use axum::http::{header, HeaderMap};
+
+// 請求的內容範圍
+#[derive(Debug, PartialEq)]
+pub enum ByteRange {
+    // 沒有 Range 標頭，或標頭無法解析、不適用，回傳完整內容
+    Full,
+    // 包含兩端的位元組範圍
+    Partial { start: u64, end: u64 },
+    // 範圍完全落在內容之外
+    Unsatisfiable,
+}
+
+impl ByteRange {
+    // 依 Range 與 If-Range 標頭決定回傳的範圍
+    //
+    // 只支援單一範圍；多個範圍需要 multipart/byteranges 回應，這裡直接回傳完整內容，
+    // 規範允許伺服器忽略 Range 標頭。If-Range 與目前的驗證值不同時代表文件已變動，
+    // 也回傳完整內容，避免用戶端把新舊版本的片段拼在一起。
+    pub fn from_headers(headers: &HeaderMap, len: u64, validators: &[&str]) -> Self {
+        let Some(range) = headers.get(header::RANGE).and_then(|v| v.to_str().ok()) else {
+            return ByteRange::Full;
+        };
+
+        if let Some(if_range) = headers.get(header::IF_RANGE) {
+            let current = if_range
+                .to_str()
+                .is_ok_and(|v| validators.contains(&v.trim()));
+            if !current {
+                return ByteRange::Full;
+            }
+        }
+
+        parse(range, len)
+    }
+}
+
+fn parse(range: &str, len: u64) -> ByteRange {
+    let Some(spec) = range.trim().strip_prefix("bytes=") else {
+        return ByteRange::Full;
+    };
+    if spec.contains(',') {
+        return ByteRange::Full;
+    }
+    let Some((start, end)) = spec.trim().split_once('-') else {
+        return ByteRange::Full;
+    };
+    let (start, end) = (start.trim(), end.trim());
+
+    if start.is_empty() {
+        // `bytes=-500`：最後 500 個位元組
+        let Ok(suffix) = end.parse::<u64>() else {
+            return ByteRange::Full;
+        };
+        if suffix == 0 || len == 0 {
+            return ByteRange::Unsatisfiable;
+        }
+        return ByteRange::Partial {
+            start: len.saturating_sub(suffix),
+            end: len - 1,
+        };
+    }
+
+    let Ok(start) = start.parse::<u64>() else {
+        return ByteRange::Full;
+    };
+    let end = if end.is_empty() {
+        len.saturating_sub(1)
+    } else {
+        match end.parse::<u64>() {
+            Ok(end) if end >= start => end.min(len.saturating_sub(1)),
+            _ => return ByteRange::Full,
+        }
+    };
+
+    if start >= len {
+        ByteRange::Unsatisfiable
+    } else {
+        ByteRange::Partial { start, end }
+    }
+}
@@ -0,0 +1,85 @@
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    middleware::Next,
+    response::Response,
+};
+
+use crate::{ServerConfig, ServerError};
+
+// 紀錄數量超過此值時清理已回滿額度的來源
+const PRUNE_THRESHOLD: usize = 10_000;
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+// 每個來源 IP 的請求速率上限（--rate-limit，或 `[[vhost]]` 的 rate-limit）
+//
+// 以 token bucket 計算：每秒補充 `rate` 個請求的額度，最多累積一秒的量，
+// 短暫的突發（例如載入頁面時同時取得的資源）不會被拒絕。
+pub struct RateLimiter {
+    rate: f64,
+    buckets: Mutex<HashMap<IpAddr, Bucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(per_second: u32) -> Self {
+        RateLimiter {
+            rate: per_second as f64,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn per_second(&self) -> u32 {
+        self.rate as u32
+    }
+
+    // 取用一個請求的額度，用完時回傳需要等待的時間
+    fn acquire(&self, ip: IpAddr) -> Result<(), Duration> {
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() > PRUNE_THRESHOLD {
+            let rate = self.rate;
+            buckets.retain(|_, b| b.tokens + now.duration_since(b.updated).as_secs_f64() * rate < rate);
+        }
+        let bucket = buckets.entry(ip).or_insert(Bucket {
+            tokens: self.rate,
+            updated: now,
+        });
+        let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.rate).min(self.rate);
+        bucket.updated = now;
+        if bucket.tokens < 1.0 {
+            return Err(Duration::from_secs_f64((1.0 - bucket.tokens) / self.rate));
+        }
+        bucket.tokens -= 1.0;
+        Ok(())
+    }
+}
+
+// 超過速率上限的請求回應 429 與 Retry-After
+pub async fn enforce(
+    State(config): State<ServerConfig>,
+    request: Request,
+    next: Next,
+) -> Result<Response, ServerError> {
+    let Some(limiter) = &config.rate_limit else {
+        return Ok(next.run(request).await);
+    };
+    let ip = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip());
+    if let Some(ip) = ip {
+        limiter.acquire(ip).map_err(ServerError::TooManyRequests)?;
+    }
+    Ok(next.run(request).await)
+}
@@ -0,0 +1,45 @@
+use std::{fs, io, path::Path};
+
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::Response,
+};
+
+use crate::{disk::writes_files, ServerConfig, ServerError};
+
+// 實際嘗試在基礎目錄建立文件，判斷目前的程序能否寫入
+//
+// 權限位元無法反映唯讀掛載、ACL 或以 root 執行等情況，因此不用 `access(2)` 推測。
+pub fn is_writable(base: &Path) -> io::Result<bool> {
+    let probe = base.join(format!(".sfs-write-probe-{}", std::process::id()));
+    match fs::OpenOptions::new().write(true).create_new(true).open(&probe) {
+        Ok(_) => {
+            fs::remove_file(&probe)?;
+            Ok(true)
+        }
+        Err(e) if e.kind() == io::ErrorKind::PermissionDenied => Ok(false),
+        #[cfg(unix)]
+        Err(e) if e.raw_os_error() == Some(libc::EROFS) => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+// 會修改基礎目錄內容的請求，包括不占用空間的刪除
+fn modifies_files(request: &Request) -> bool {
+    writes_files(request)
+        || matches!(request.method().as_str(), "DELETE" | "PROPPATCH" | "LOCK" | "UNLOCK")
+            && !request.uri().path().starts_with("/_")
+}
+
+// 唯讀模式下拒絕所有寫入請求，不論用戶或 token 擁有什麼權限
+pub async fn refuse_writes(
+    State(config): State<ServerConfig>,
+    request: Request,
+    next: Next,
+) -> Result<Response, ServerError> {
+    if config.read_only && modifies_files(&request) {
+        return Err(ServerError::MethodNotAllowed("GET, HEAD".to_string()));
+    }
+    Ok(next.run(request).await)
+}
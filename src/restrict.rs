@@ -0,0 +1,243 @@
+use std::{cell::LazyCell, fs, io, path::Path};
+
+use globset::{Glob, GlobBuilder, GlobMatcher};
+use regex::Regex;
+
+// 禁止訪問的路徑規則
+//
+// 規則依寫法分成四種：
+// - 不含 `/` 的名稱或樣式（`.git`、`*.pem`）：比對每一層名稱，名稱完全符合的文件或目錄及其底下內容
+// - 以 `/` 結尾（`/.git/`、`node_modules/`）：任何一層名稱符合的目錄及其底下內容；
+//   同名的文件不受影響，只有路徑最後一層符合時才需要確認它是不是目錄
+// - 含 `/` 的樣式（`secret/**`、`/config.json`）：從根目錄比對整個相對路徑，
+//   符合的目錄底下內容也一併禁止
+// - 以 `re:` 開頭的正規表示式（`re:\.(bak|swp)$`）：比對整個相對路徑與每一層上層目錄的路徑，
+//   未以 `^`、`$` 錨定時路徑中任何位置符合即可
+//
+// 比對對象是正規化後的相對路徑（去除重複與開頭結尾的 `/`、`.` 與 `..`），而不是原始網址。
+enum Rule {
+    Component(GlobMatcher),
+    Directory(GlobMatcher),
+    Anchored(GlobMatcher),
+    Regex(Regex),
+}
+
+pub struct RestrictRules {
+    rules: Vec<Rule>,
+}
+
+impl RestrictRules {
+    pub fn new(patterns: &[String]) -> Result<Self, String> {
+        let mut rules = Vec::new();
+        for pattern in patterns.iter().map(|p| p.trim()).filter(|p| !p.is_empty()) {
+            let invalid = |e: &dyn std::fmt::Display| format!("無效的禁止訪問規則 {}: {}", pattern, e);
+
+            let rule = if let Some(regex) = pattern.strip_prefix("re:") {
+                Rule::Regex(Regex::new(regex).map_err(|e| invalid(&e))?)
+            } else if let Some(dir) = pattern.strip_suffix('/') {
+                Rule::Directory(Glob::new(dir.trim_start_matches('/')).map_err(|e| invalid(&e))?.compile_matcher())
+            } else if !pattern.contains('/') {
+                Rule::Component(Glob::new(pattern).map_err(|e| invalid(&e))?.compile_matcher())
+            } else {
+                let anchored = pattern.trim_start_matches('/');
+                // `secret/**` 也要擋下 `secret` 目錄本身的列表
+                if let Some(base) = anchored.strip_suffix("/**") {
+                    rules.push(Rule::Anchored(path_glob(base).map_err(|e| invalid(&e))?));
+                }
+                Rule::Anchored(path_glob(anchored).map_err(|e| invalid(&e))?)
+            };
+            rules.push(rule);
+        }
+        Ok(RestrictRules { rules })
+    }
+
+    // 路徑是否符合任何禁止規則
+    //
+    // `is_dir` 回報路徑本身是否為目錄，只有目錄規則符合最後一層名稱時才會呼叫。
+    pub fn is_restricted(&self, path: &str, is_dir: impl FnOnce() -> bool) -> bool {
+        let path = normalize(path);
+        let components: Vec<&str> = path.split('/').filter(|c| !c.is_empty()).collect();
+        let is_dir = LazyCell::new(is_dir);
+
+        self.rules.iter().any(|rule| match rule {
+            Rule::Component(glob) => components.iter().any(|c| glob.is_match(c)),
+            // 後面還有其他名稱時，這一層必定是目錄
+            Rule::Directory(glob) => components
+                .iter()
+                .enumerate()
+                .any(|(i, c)| glob.is_match(c) && (i + 1 < components.len() || *is_dir)),
+            Rule::Anchored(glob) => (1..=components.len())
+                .any(|n| glob.is_match(components[..n].join("/"))),
+            Rule::Regex(regex) => (1..=components.len())
+                .any(|n| regex.is_match(&components[..n].join("/"))),
+        })
+    }
+}
+
+// 以 `.` 開頭的隱藏文件與目錄的處理方式（--hidden）
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum HiddenPolicy {
+    // 不列出也不提供，與禁止訪問的路徑相同
+    Deny,
+    // 與一般文件相同
+    Show,
+    // 不列出，但知道網址時仍可下載，與 .sfignore 相同
+    Ignore,
+}
+
+impl HiddenPolicy {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "deny" => Some(HiddenPolicy::Deny),
+            "show" => Some(HiddenPolicy::Show),
+            "ignore" => Some(HiddenPolicy::Ignore),
+            _ => None,
+        }
+    }
+}
+
+// 符號連結的處理方式（--symlinks）
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SymlinkPolicy {
+    // 一律跟隨，即使指向基礎路徑之外
+    Follow,
+    // 路徑中有任何一層是符號連結時回應 403
+    Deny,
+    // 只跟隨解析後仍位於基礎路徑之內的符號連結
+    Confine,
+}
+
+impl SymlinkPolicy {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "follow" => Some(SymlinkPolicy::Follow),
+            "deny" => Some(SymlinkPolicy::Deny),
+            "confine" => Some(SymlinkPolicy::Confine),
+            _ => None,
+        }
+    }
+}
+
+// 基礎路徑底下的相對路徑是否經過符號連結；基礎路徑本身是符號連結不算
+pub fn crosses_symlink(base: &Path, rel: &str) -> io::Result<bool> {
+    let mut path = base.to_path_buf();
+    for component in rel.split(['/', '\\']).filter(|c| !c.is_empty() && *c != ".") {
+        path.push(component);
+        match fs::symlink_metadata(&path) {
+            Ok(metadata) if metadata.file_type().is_symlink() => return Ok(true),
+            Ok(_) => {}
+            // 路徑不存在（或中間一層是文件）時由呼叫端判斷
+            Err(e) if matches!(e.kind(), io::ErrorKind::NotFound | io::ErrorKind::NotADirectory) => return Ok(false),
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(false)
+}
+
+// 路徑中是否有任何一層以 `.` 開頭；`.well-known`（RFC 8615）用來公開資訊，不算隱藏
+pub fn is_hidden(path: &str) -> bool {
+    normalize(path)
+        .split('/')
+        .any(|c| c.starts_with('.') && c != ".well-known")
+}
+
+// 整個路徑的樣式，`*` 不跨越目錄，`**` 才會
+fn path_glob(pattern: &str) -> Result<GlobMatcher, globset::Error> {
+    Ok(GlobBuilder::new(pattern)
+        .literal_separator(true)
+        .build()?
+        .compile_matcher())
+}
+
+// 與 `normalize` 相同，但 `..` 超出根目錄時回傳 None，而不是停在根目錄
+//
+// 只處理字串，不存取文件系統，不存在的路徑也能判斷。
+pub fn normalize_within(path: &str) -> Option<String> {
+    let mut components: Vec<&str> = Vec::new();
+    for component in path.split(['/', '\\']) {
+        match component {
+            "" | "." => {}
+            ".." => {
+                components.pop()?;
+            }
+            c => components.push(c),
+        }
+    }
+    Some(components.join("/"))
+}
+
+// 將請求路徑正規化為不含 `.`、`..` 與多餘斜線的相對路徑
+pub fn normalize(path: &str) -> String {
+    let mut components: Vec<&str> = Vec::new();
+    for component in path.split(['/', '\\']) {
+        match component {
+            "" | "." => {}
+            ".." => {
+                components.pop();
+            }
+            c => components.push(c),
+        }
+    }
+    components.join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(patterns: &[&str]) -> RestrictRules {
+        RestrictRules::new(&patterns.iter().map(|p| p.to_string()).collect::<Vec<_>>()).unwrap()
+    }
+
+    #[test]
+    fn directory_rules_leave_files_with_the_same_name_alone() {
+        let rules = rules(&["node_modules/"]);
+        assert!(rules.is_restricted("node_modules", || true));
+        assert!(!rules.is_restricted("node_modules", || false));
+        assert!(!rules.is_restricted("docs/node_modules", || false));
+        // 底下還有內容時這一層必定是目錄，不必查看文件系統
+        assert!(rules.is_restricted("docs/node_modules/a.js", || panic!("不應查看文件系統")));
+        assert!(!rules.is_restricted("docs/a.js", || panic!("不應查看文件系統")));
+    }
+
+    #[test]
+    fn anchored_rules_match_from_the_root() {
+        let rules = rules(&["/.git/", "secret/**", "/config.json", "*.pem"]);
+        assert!(rules.is_restricted(".git/config", || false));
+        assert!(rules.is_restricted("secret", || true));
+        assert!(rules.is_restricted("secret/a/b.txt", || false));
+        assert!(!rules.is_restricted("public/secret/a.txt", || false));
+        assert!(rules.is_restricted("config.json", || false));
+        assert!(!rules.is_restricted("docs/config.json", || false));
+        assert!(rules.is_restricted("keys/server.pem", || false));
+        // 比對正規化後的路徑
+        assert!(rules.is_restricted("docs/../config.json", || false));
+        assert!(rules.is_restricted("//secret//x", || false));
+    }
+
+    #[test]
+    fn paths_are_normalized_lexically() {
+        assert_eq!(normalize_within("a/./b//c/"), Some("a/b/c".to_string()));
+        assert_eq!(normalize_within("a/b/../c"), Some("a/c".to_string()));
+        // 反斜線同樣視為分隔符號
+        assert_eq!(normalize_within("a\\..\\b"), Some("b".to_string()));
+        assert_eq!(normalize_within("a/.."), Some(String::new()));
+        assert_eq!(normalize_within(""), Some(String::new()));
+    }
+
+    #[test]
+    fn escaping_the_root_is_refused() {
+        for path in ["..", "../etc/passwd", "a/../../b", "a\\..\\..\\b", "./../a", "a/b/../../.."] {
+            assert_eq!(normalize_within(path), None, "{}", path);
+        }
+        // normalize 則停在根目錄
+        assert_eq!(normalize("../../etc/passwd"), "etc/passwd");
+    }
+
+    #[test]
+    fn dot_components_are_not_parent_references() {
+        // `...` 與 `..a` 是一般的名稱
+        assert_eq!(normalize_within("a/.../..a"), Some("a/.../..a".to_string()));
+        assert_eq!(normalize_within("%2e%2e/a"), Some("%2e%2e/a".to_string()));
+    }
+}
@@ -0,0 +1,352 @@
+use std::{
+    collections::HashSet,
+    fs,
+    io::{self, Read},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use axum::body::Body;
+use chrono::Utc;
+use futures_util::StreamExt;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::io::{AsyncSeekExt, AsyncWriteExt};
+use tracing::{info, warn};
+
+use crate::{upload::TEMP_PREFIX, ServerError};
+
+// 未完成的上傳保留多久
+const EXPIRY: Duration = Duration::from_secs(7 * 24 * 3600);
+
+// 每收到這麼多位元組就寫入磁碟並更新紀錄，連線中斷時最多需要重傳這麼多
+const CHECKPOINT_BYTES: u64 = 4 * 1024 * 1024;
+
+// `Content-Range` 標頭：`bytes <start>-<end>/<total>`，或只查詢進度的 `bytes */<total>`
+#[derive(Clone, Copy, Debug)]
+pub struct ContentRange {
+    pub range: Option<(u64, u64)>,
+    pub total: u64,
+}
+
+impl ContentRange {
+    pub fn parse(value: &str) -> Option<Self> {
+        let (range, total) = value.trim().strip_prefix("bytes ")?.split_once('/')?;
+        let total = total.trim().parse().ok()?;
+        let range = match range.trim() {
+            "*" => None,
+            range => {
+                let (start, end) = range.split_once('-')?;
+                let (start, end): (u64, u64) = (start.trim().parse().ok()?, end.trim().parse().ok()?);
+                if start > end || end >= total {
+                    return None;
+                }
+                Some((start, end))
+            }
+        };
+        Some(ContentRange { range, total })
+    }
+}
+
+// 一段已寫入磁碟的內容與其雜湊，重新啟動時以此驗證暫存文件
+#[derive(Serialize, Deserialize, Clone)]
+struct Chunk {
+    offset: u64,
+    len: u64,
+    sha256: String,
+}
+
+// 一個未完成的上傳
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    target: PathBuf,
+    temp: PathBuf,
+    total: u64,
+    // 已確認寫入的位元組數，即下一段內容的起點
+    offset: u64,
+    chunks: Vec<Chunk>,
+    // 最後一次收到內容的時間（Unix 秒）
+    updated: i64,
+}
+
+// 上傳的進度
+pub enum Progress {
+    // 尚未收齊，內容為已收到的位元組數
+    Incomplete(u64),
+    // 已收齊並取代目標文件，內容為文件大小
+    Complete(u64),
+}
+
+// 續傳紀錄（--upload-journal）
+//
+// 每個未完成的上傳在紀錄目錄中有一個 JSON 文件，記錄暫存文件、已寫入的位置與各段內容的
+// SHA-256。內容先寫入磁碟才更新紀錄，因此重新啟動後暫存文件中只保留紀錄過且雜湊相符的
+// 部分，用戶端以 `Content-Range: bytes */<總長度>` 查詢後從確切的位置繼續。
+pub struct UploadJournal {
+    dir: PathBuf,
+    // 正在接收內容的上傳，同一個上傳不能同時寫入
+    active: Mutex<HashSet<String>>,
+}
+
+// 離開時把上傳移出進行中的清單
+struct ActiveGuard<'a> {
+    journal: &'a UploadJournal,
+    key: String,
+}
+
+impl Drop for ActiveGuard<'_> {
+    fn drop(&mut self) {
+        self.journal.active.lock().unwrap().remove(&self.key);
+    }
+}
+
+impl UploadJournal {
+    pub fn open(dir: &Path) -> io::Result<Arc<Self>> {
+        fs::create_dir_all(dir)?;
+        let journal = Arc::new(UploadJournal {
+            dir: dir.to_path_buf(),
+            active: Mutex::new(HashSet::new()),
+        });
+        let resumed = journal.recover()?;
+        if resumed > 0 {
+            info!("有 {} 個未完成的上傳可以續傳", resumed);
+        }
+        Ok(journal)
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    // 同一個目標與總長度視為同一個上傳
+    fn key(target: &Path, total: u64) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(target.to_string_lossy().as_bytes());
+        hasher.update(format!("\0{}", total));
+        format!("{:x}", hasher.finalize())
+    }
+
+    fn entry_path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", key))
+    }
+
+    fn load(&self, key: &str) -> io::Result<Option<Entry>> {
+        match fs::read(self.entry_path(key)) {
+            Ok(data) => Ok(Some(serde_json::from_slice(&data).map_err(io::Error::other)?)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    // 先寫入暫存的紀錄再改名，中途當機也不會留下半份紀錄
+    fn save(&self, key: &str, entry: &Entry) -> io::Result<()> {
+        let path = self.entry_path(key);
+        let partial = path.with_extension("json.partial");
+        let mut file = fs::File::create(&partial)?;
+        io::Write::write_all(&mut file, &serde_json::to_vec(entry).map_err(io::Error::other)?)?;
+        file.sync_all()?;
+        fs::rename(&partial, &path)
+    }
+
+    fn discard(&self, key: &str, entry: &Entry) {
+        let _ = fs::remove_file(&entry.temp);
+        let _ = fs::remove_file(self.entry_path(key));
+    }
+
+    // 啟動時檢查所有紀錄：刪除過期與暫存文件已消失的上傳，暫存文件截斷到最後一段驗證通過的內容
+    fn recover(&self) -> io::Result<usize> {
+        let mut resumed = 0;
+        for dir_entry in fs::read_dir(&self.dir)? {
+            let path = dir_entry?.path();
+            let Some(key) = path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .and_then(|n| n.strip_suffix(".json"))
+                .map(str::to_string)
+            else {
+                // 寫到一半的紀錄
+                let _ = fs::remove_file(&path);
+                continue;
+            };
+            let mut entry = match self.load(&key) {
+                Ok(Some(entry)) => entry,
+                Ok(None) => continue,
+                Err(e) => {
+                    warn!("略過無法讀取的續傳紀錄 {}: {}", path.display(), e);
+                    continue;
+                }
+            };
+            if Utc::now().timestamp() - entry.updated > EXPIRY.as_secs() as i64 || !entry.temp.is_file() {
+                self.discard(&key, &entry);
+                continue;
+            }
+
+            let verified = verify(&entry.temp, &entry.chunks)?;
+            if verified < entry.chunks.len() {
+                warn!("{} 的暫存文件與紀錄不符，從第 {} 段重新接收", entry.target.display(), verified);
+            }
+            entry.chunks.truncate(verified);
+            entry.offset = entry.chunks.last().map_or(0, |c| c.offset + c.len);
+            fs::OpenOptions::new().write(true).open(&entry.temp)?.set_len(entry.offset)?;
+            self.save(&key, &entry)?;
+            resumed += 1;
+        }
+        Ok(resumed)
+    }
+
+    // 定期刪除過期的上傳
+    pub fn spawn_cleanup(self: Arc<Self>) {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(3600));
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                let keys: Vec<String> = match fs::read_dir(&self.dir) {
+                    Ok(entries) => entries
+                        .flatten()
+                        .filter_map(|e| e.file_name().to_str()?.strip_suffix(".json").map(str::to_string))
+                        .collect(),
+                    Err(_) => continue,
+                };
+                for key in keys {
+                    if self.active.lock().unwrap().contains(&key) {
+                        continue;
+                    }
+                    if let Ok(Some(entry)) = self.load(&key) {
+                        if Utc::now().timestamp() - entry.updated > EXPIRY.as_secs() as i64 {
+                            info!("刪除過期的未完成上傳: {}", entry.target.display());
+                            self.discard(&key, &entry);
+                        }
+                    }
+                }
+            }
+        });
+    }
+
+    // 接收一段內容到 `dir` 中的 `target`，收齊並通過 `check`（--clamd 掃描）後取代目標文件；
+    // 檢查失敗時捨棄已收到的內容
+    pub async fn receive(
+        &self,
+        dir: &Path,
+        target: &Path,
+        range: ContentRange,
+        body: Body,
+        check: impl AsyncFnOnce(&Path) -> Result<(), ServerError>,
+    ) -> Result<Progress, ServerError> {
+        let key = Self::key(target, range.total);
+        if !self.active.lock().unwrap().insert(key.clone()) {
+            return Err(ServerError::Conflict("此文件的上傳正在進行中".to_string()));
+        }
+        let _guard = ActiveGuard {
+            journal: self,
+            key: key.clone(),
+        };
+
+        let existing = self.load(&key)?;
+        let Some((start, end)) = range.range else {
+            return Ok(Progress::Incomplete(existing.map_or(0, |e| e.offset)));
+        };
+        let mut entry = match existing {
+            Some(entry) => entry,
+            None => {
+                let temp = dir.join(format!("{}{}", TEMP_PREFIX, &key[..16]));
+                tokio::fs::File::create(&temp).await?;
+                Entry {
+                    target: target.to_path_buf(),
+                    temp,
+                    total: range.total,
+                    offset: 0,
+                    chunks: Vec::new(),
+                    updated: Utc::now().timestamp(),
+                }
+            }
+        };
+        // 中間有缺口時不接收，讓用戶端從已收到的位置重送
+        if start > entry.offset || end < entry.offset {
+            return Ok(Progress::Incomplete(entry.offset));
+        }
+
+        let mut file = tokio::fs::OpenOptions::new().write(true).open(&entry.temp).await?;
+        file.seek(io::SeekFrom::Start(entry.offset)).await?;
+        // 與已收到的內容重疊的部分略過
+        let mut skip = entry.offset - start;
+        let mut remaining = end - entry.offset + 1;
+        let mut hasher = Sha256::new();
+        let mut pending = 0u64;
+        let mut stream = body.into_data_stream();
+        let mut failure = None;
+        while let Some(chunk) = stream.next().await {
+            let chunk = match chunk {
+                Ok(chunk) => chunk,
+                Err(e) => {
+                    failure = Some(ServerError::BadRequest(format!("讀取上傳內容失敗: {}", e)));
+                    break;
+                }
+            };
+            let data = &chunk[(skip.min(chunk.len() as u64) as usize)..];
+            skip -= skip.min(chunk.len() as u64);
+            if data.len() as u64 > remaining {
+                failure = Some(ServerError::BadRequest("內容超過 Content-Range 指定的範圍".to_string()));
+                break;
+            }
+            file.write_all(data).await?;
+            hasher.update(data);
+            pending += data.len() as u64;
+            remaining -= data.len() as u64;
+            if pending >= CHECKPOINT_BYTES {
+                file.sync_data().await?;
+                self.record(&key, &mut entry, pending, std::mem::take(&mut hasher))?;
+                pending = 0;
+            }
+        }
+        // 連線中斷前收到的內容也保留下來
+        if pending > 0 {
+            file.sync_data().await?;
+            self.record(&key, &mut entry, pending, hasher)?;
+        }
+        if let Some(failure) = failure {
+            return Err(failure);
+        }
+        if remaining > 0 {
+            return Err(ServerError::BadRequest("內容少於 Content-Range 指定的範圍".to_string()));
+        }
+        if entry.offset < entry.total {
+            return Ok(Progress::Incomplete(entry.offset));
+        }
+
+        file.sync_all().await?;
+        drop(file);
+        if let Err(e) = check(&entry.temp).await {
+            self.discard(&key, &entry);
+            return Err(e);
+        }
+        tokio::fs::rename(&entry.temp, target).await?;
+        let _ = fs::remove_file(self.entry_path(&key));
+        Ok(Progress::Complete(entry.total))
+    }
+
+    fn record(&self, key: &str, entry: &mut Entry, len: u64, hasher: Sha256) -> io::Result<()> {
+        entry.chunks.push(Chunk {
+            offset: entry.offset,
+            len,
+            sha256: format!("{:x}", hasher.finalize()),
+        });
+        entry.offset += len;
+        entry.updated = Utc::now().timestamp();
+        self.save(key, entry)
+    }
+}
+
+// 依序驗證暫存文件中的各段內容，回傳通過驗證的段數
+fn verify(path: &Path, chunks: &[Chunk]) -> io::Result<usize> {
+    let mut file = fs::File::open(path)?;
+    for (index, chunk) in chunks.iter().enumerate() {
+        let mut hasher = Sha256::new();
+        let copied = io::copy(&mut (&mut file).take(chunk.len), &mut hasher)?;
+        if copied != chunk.len || format!("{:x}", hasher.finalize()) != chunk.sha256 {
+            return Ok(index);
+        }
+    }
+    Ok(chunks.len())
+}
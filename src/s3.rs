@@ -0,0 +1,667 @@
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    net::SocketAddr,
+    sync::Arc,
+    time::UNIX_EPOCH,
+};
+
+use axum::{
+    body::Body,
+    extract::{ConnectInfo, Path, Query, Request, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use hmac::{Hmac, Mac};
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use tower::ServiceExt;
+
+use crate::{
+    catch_panic,
+    deadline::{self, Deadline},
+    file_etag, is_safe_path,
+    listener::ListenerRoutes,
+    metrics,
+    problem::Problem,
+    published, serve_static, transfer,
+    tree::walk_files,
+    webdav::escape,
+    ServerConfig, ServerError,
+};
+
+type HmacSha256 = Hmac<Sha256>;
+
+const NAMESPACE: &str = "http://s3.amazonaws.com/doc/2006-03-01/";
+
+// 一次列出的上限，與 S3 相同
+const MAX_KEYS: usize = 1000;
+
+// 簽章時間與伺服器時間的容許誤差
+const MAX_CLOCK_SKEW: i64 = 15 * 60;
+
+// 預先簽章網址的最長有效期限（7 天）
+const MAX_PRESIGNED_EXPIRY: i64 = 7 * 24 * 3600;
+
+// AWS 簽章的 URI 編碼：只保留 `A-Z a-z 0-9 - _ . ~`
+const AWS_ENCODE: &AsciiSet = &NON_ALPHANUMERIC.remove(b'-').remove(b'_').remove(b'.').remove(b'~');
+
+// `encoding-type=url` 時回應中的鍵以此編碼，保留 `/`
+const KEY_ENCODE: &AsciiSet = &AWS_ENCODE.remove(b'/');
+
+// S3 相容介面的設定（--s3-listen）
+pub struct S3Options {
+    pub bucket: String,
+    // 存取金鑰 ID 與私密金鑰；設定時要求 AWS Signature Version 4 簽章
+    pub credentials: Option<(String, String)>,
+}
+
+impl S3Options {
+    // 解析 `<存取金鑰 ID>:<私密金鑰>`
+    pub fn parse_credentials(value: &str) -> io::Result<(String, String)> {
+        match value.split_once(':') {
+            Some((key, secret)) if !key.is_empty() && !secret.is_empty() => {
+                Ok((key.to_string(), secret.to_string()))
+            }
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "S3 金鑰格式應為 <存取金鑰 ID>:<私密金鑰>",
+            )),
+        }
+    }
+
+    // 儲存貯體名稱的規則與 S3 相同，且必須能直接放在路徑中
+    pub fn valid_bucket(name: &str) -> bool {
+        (3..=63).contains(&name.len())
+            && name.bytes().all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'-' || b == b'.')
+            && name.starts_with(|c: char| c.is_ascii_alphanumeric())
+            && name.ends_with(|c: char| c.is_ascii_alphanumeric())
+    }
+}
+
+struct S3 {
+    config: ServerConfig,
+    options: S3Options,
+    // 取得物件時轉交的文件路由，沿用 Range、ETag 與條件式請求的處理
+    objects: Router,
+}
+
+// 唯讀的 S3 相容介面，以 path-style 提供 `GET /`、`GET /<bucket>?list-type=2` 與物件的 GET/HEAD
+//
+// 整個基礎目錄是一個儲存貯體，文件的相對路徑就是鍵。禁止訪問的文件無法取得，也與
+// .sfignore 排除的文件一樣不會列出；目錄本身不是物件。
+pub fn app(config: &ServerConfig, options: S3Options) -> Router {
+    let mut config = config.clone();
+    // S3 用戶端以簽章驗證，不使用帳號、token 與登入階段
+    config.users = None;
+    config.ldap = None;
+    config.tokens = None;
+    config.sessions = None;
+    #[cfg(feature = "pam")]
+    {
+        config.pam = None;
+    }
+    // 只提供文件內容：目錄、索引文件與 latest 連結都不是物件，內容也不即時壓縮
+    config.routes = ListenerRoutes {
+        files: true,
+        ..ListenerRoutes::default()
+    };
+    config.index_files = Arc::new(Vec::new());
+    config.latest = None;
+    config.suggest = false;
+    config.compress = None;
+    config.error_pages = None;
+    config.upload = None;
+    config.webdav = false;
+
+    let objects = Router::new()
+        .route("/*path", get(serve_static))
+        .with_state(config.clone());
+    let state = Arc::new(S3 {
+        config: config.clone(),
+        options,
+        objects,
+    });
+
+    Router::new()
+        .route("/", get(list_buckets))
+        .route("/:bucket", get(get_bucket).head(head_bucket))
+        .route("/:bucket/", get(get_bucket).head(head_bucket))
+        .route("/:bucket/*key", get(get_object))
+        .layer(middleware::from_fn_with_state(state.clone(), authenticate))
+        .layer(middleware::from_fn_with_state(config.clone(), deadline::enforce))
+        .layer(middleware::from_fn_with_state(config.clone(), transfer::track_transfer))
+        .layer(middleware::from_fn_with_state(config.clone(), catch_panic::catch))
+        .layer(middleware::from_fn(render_errors))
+        .layer(middleware::from_fn_with_state(config.clone(), metrics::track))
+        .with_state(state)
+}
+
+// S3 格式的錯誤
+struct S3Error {
+    status: StatusCode,
+    code: &'static str,
+    message: String,
+}
+
+impl S3Error {
+    fn new(status: StatusCode, code: &'static str, message: impl Into<String>) -> Self {
+        S3Error {
+            status,
+            code,
+            message: message.into(),
+        }
+    }
+
+    fn access_denied(message: impl Into<String>) -> Self {
+        S3Error::new(StatusCode::FORBIDDEN, "AccessDenied", message)
+    }
+
+    fn no_such_bucket(bucket: &str) -> Self {
+        S3Error::new(StatusCode::NOT_FOUND, "NoSuchBucket", format!("儲存貯體 {} 不存在", bucket))
+    }
+
+    // 依狀態碼對應 S3 的錯誤代碼
+    fn from_status(status: StatusCode, message: String) -> Self {
+        let code = match status {
+            StatusCode::NOT_FOUND => "NoSuchKey",
+            StatusCode::FORBIDDEN => "AccessDenied",
+            StatusCode::BAD_REQUEST => "InvalidArgument",
+            StatusCode::RANGE_NOT_SATISFIABLE => "InvalidRange",
+            StatusCode::METHOD_NOT_ALLOWED => "MethodNotAllowed",
+            StatusCode::SERVICE_UNAVAILABLE => "SlowDown",
+            _ => "InternalError",
+        };
+        S3Error::new(status, code, message)
+    }
+}
+
+impl From<ServerError> for S3Error {
+    fn from(e: ServerError) -> Self {
+        // 先經過 ServerError 的轉換，保留伺服器錯誤的紀錄
+        let response = e.into_response();
+        match response.extensions().get::<Problem>() {
+            Some(problem) => S3Error::from_status(problem.status, problem.detail.clone()),
+            None => S3Error::from_status(response.status(), String::new()),
+        }
+    }
+}
+
+impl IntoResponse for S3Error {
+    fn into_response(self) -> Response {
+        let xml = format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<Error><Code>{}</Code><Message>{}</Message></Error>\n",
+            self.code,
+            escape(&self.message)
+        );
+        (self.status, xml_headers(), xml).into_response()
+    }
+}
+
+fn xml_headers() -> [(header::HeaderName, HeaderValue); 1] {
+    [(header::CONTENT_TYPE, HeaderValue::from_static("application/xml"))]
+}
+
+// 把文件路由的錯誤回應（帶有 Problem）改寫為 S3 格式
+async fn render_errors(request: Request, next: Next) -> Response {
+    let response = next.run(request).await;
+    let Some(problem) = response.extensions().get::<Problem>().cloned() else {
+        return response;
+    };
+    let (parts, _) = response.into_parts();
+    let mut rendered = S3Error::from_status(problem.status, problem.detail).into_response();
+    // 保留 Content-Range、Retry-After 等標頭
+    for (name, value) in parts.headers {
+        if let Some(name) = name.filter(|n| n != header::CONTENT_TYPE && n != header::CONTENT_LENGTH) {
+            rendered.headers_mut().insert(name, value);
+        }
+    }
+    rendered
+}
+
+fn check_bucket(s3: &S3, bucket: &str) -> Result<(), S3Error> {
+    match bucket == s3.options.bucket {
+        true => Ok(()),
+        false => Err(S3Error::no_such_bucket(bucket)),
+    }
+}
+
+// `GET /`：ListBuckets
+async fn list_buckets(State(s3): State<Arc<S3>>) -> Response {
+    let created = tokio::fs::metadata(s3.config.base_path.as_str())
+        .await
+        .and_then(|m| m.modified())
+        .unwrap_or(UNIX_EPOCH);
+    let xml = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<ListAllMyBucketsResult xmlns=\"{}\">\
+         <Owner><ID>sfs</ID><DisplayName>sfs</DisplayName></Owner>\
+         <Buckets><Bucket><Name>{}</Name><CreationDate>{}</CreationDate></Bucket></Buckets>\
+         </ListAllMyBucketsResult>\n",
+        NAMESPACE,
+        escape(&s3.options.bucket),
+        timestamp(created.into())
+    );
+    (xml_headers(), xml).into_response()
+}
+
+// `HEAD /<bucket>`：HeadBucket
+async fn head_bucket(State(s3): State<Arc<S3>>, Path(bucket): Path<String>) -> Result<StatusCode, S3Error> {
+    check_bucket(&s3, &bucket)?;
+    Ok(StatusCode::OK)
+}
+
+#[derive(Deserialize, Default)]
+struct ListQuery {
+    #[serde(rename = "list-type")]
+    list_type: Option<String>,
+    prefix: Option<String>,
+    delimiter: Option<String>,
+    #[serde(rename = "max-keys")]
+    max_keys: Option<String>,
+    #[serde(rename = "continuation-token")]
+    continuation_token: Option<String>,
+    #[serde(rename = "start-after")]
+    start_after: Option<String>,
+    marker: Option<String>,
+    #[serde(rename = "encoding-type")]
+    encoding_type: Option<String>,
+    location: Option<String>,
+}
+
+// 列出的項目：物件，或以分隔字元合併的共同前綴
+enum Item {
+    Object { key: String, metadata: fs::Metadata },
+    Prefix(String),
+}
+
+impl Item {
+    fn key(&self) -> &str {
+        match self {
+            Item::Object { key, .. } => key,
+            Item::Prefix(prefix) => prefix,
+        }
+    }
+}
+
+// `GET /<bucket>`：ListObjectsV2（`list-type=2`）或 ListObjects，以及 GetBucketLocation
+async fn get_bucket(
+    State(s3): State<Arc<S3>>,
+    Path(bucket): Path<String>,
+    Query(query): Query<ListQuery>,
+) -> Result<Response, S3Error> {
+    check_bucket(&s3, &bucket)?;
+    if query.location.is_some() {
+        let xml = format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<LocationConstraint xmlns=\"{}\"/>\n",
+            NAMESPACE
+        );
+        return Ok((xml_headers(), xml).into_response());
+    }
+
+    let v2 = query.list_type.as_deref() == Some("2");
+    let max_keys = match &query.max_keys {
+        Some(value) => value
+            .parse::<usize>()
+            .map_err(|_| S3Error::new(StatusCode::BAD_REQUEST, "InvalidArgument", "max-keys 必須是非負整數"))?
+            .min(MAX_KEYS),
+        None => MAX_KEYS,
+    };
+    let prefix = query.prefix.clone().unwrap_or_default();
+    let delimiter = query.delimiter.clone().filter(|d| !d.is_empty());
+    let after = match v2 {
+        true => match &query.continuation_token {
+            Some(token) => Some(
+                STANDARD
+                    .decode(token)
+                    .ok()
+                    .and_then(|key| String::from_utf8(key).ok())
+                    .ok_or_else(|| S3Error::new(StatusCode::BAD_REQUEST, "InvalidArgument", "無效的 continuation-token"))?,
+            ),
+            None => query.start_after.clone(),
+        },
+        false => query.marker.clone(),
+    };
+
+    let config = s3.config.clone();
+    let (list_prefix, list_delimiter) = (prefix.clone(), delimiter.clone());
+    let items = tokio::task::spawn_blocking(move || list(&config, &list_prefix, list_delimiter.as_deref()))
+        .await
+        .map_err(|e| ServerError::Internal(e.to_string()))??;
+
+    let mut remaining = items.into_iter().filter(|item| after.as_ref().is_none_or(|after| item.key() > after.as_str()));
+    let page: Vec<Item> = remaining.by_ref().take(max_keys).collect();
+    let truncated = max_keys > 0 && remaining.next().is_some();
+
+    let url = query.encoding_type.as_deref() == Some("url");
+    let encode = |s: &str| match url {
+        true => utf8_percent_encode(s, KEY_ENCODE).to_string(),
+        false => escape(s),
+    };
+    let mut xml = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<ListBucketResult xmlns=\"{}\"><Name>{}</Name><Prefix>{}</Prefix>",
+        NAMESPACE,
+        escape(&bucket),
+        encode(&prefix)
+    );
+    if let Some(delimiter) = &delimiter {
+        xml.push_str(&format!("<Delimiter>{}</Delimiter>", encode(delimiter)));
+    }
+    xml.push_str(&format!("<MaxKeys>{}</MaxKeys><IsTruncated>{}</IsTruncated>", max_keys, truncated));
+    if url {
+        xml.push_str("<EncodingType>url</EncodingType>");
+    }
+    let last = page.last().map(|item| item.key().to_string());
+    if v2 {
+        xml.push_str(&format!("<KeyCount>{}</KeyCount>", page.len()));
+        if let Some(token) = &query.continuation_token {
+            xml.push_str(&format!("<ContinuationToken>{}</ContinuationToken>", escape(token)));
+        }
+        if let Some(start_after) = &query.start_after {
+            xml.push_str(&format!("<StartAfter>{}</StartAfter>", encode(start_after)));
+        }
+        if let (true, Some(last)) = (truncated, &last) {
+            xml.push_str(&format!("<NextContinuationToken>{}</NextContinuationToken>", STANDARD.encode(last)));
+        }
+    } else {
+        xml.push_str(&format!("<Marker>{}</Marker>", encode(query.marker.as_deref().unwrap_or(""))));
+        if let (true, Some(last)) = (truncated, &last) {
+            xml.push_str(&format!("<NextMarker>{}</NextMarker>", encode(last)));
+        }
+    }
+    for item in &page {
+        match item {
+            Item::Object { key, metadata } => {
+                let modified = metadata.modified().unwrap_or(UNIX_EPOCH);
+                xml.push_str(&format!(
+                    "<Contents><Key>{}</Key><LastModified>{}</LastModified><ETag>{}</ETag>\
+                     <Size>{}</Size><StorageClass>STANDARD</StorageClass></Contents>",
+                    encode(key),
+                    timestamp(modified.into()),
+                    escape(&file_etag(metadata)),
+                    metadata.len()
+                ));
+            }
+            Item::Prefix(prefix) => {
+                xml.push_str(&format!("<CommonPrefixes><Prefix>{}</Prefix></CommonPrefixes>", encode(prefix)));
+            }
+        }
+    }
+    xml.push_str("</ListBucketResult>\n");
+    Ok((xml_headers(), xml).into_response())
+}
+
+fn timestamp(time: DateTime<Utc>) -> String {
+    time.format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string()
+}
+
+// 依鍵排序列出前綴底下的物件
+//
+// 從前綴最後一個 `/` 之前的目錄開始走訪；分隔字元為 `/` 時只需讀取一層目錄。
+// 與封存檔相同，不跟隨符號連結。
+fn list(config: &ServerConfig, prefix: &str, delimiter: Option<&str>) -> Result<Vec<Item>, ServerError> {
+    let dir = prefix.rsplit_once('/').map_or("", |(dir, _)| dir);
+    let dir_path = match is_safe_path(dir, config) {
+        Ok(path) if path.fs_path.is_dir() => path.fs_path,
+        // 不存在或禁止訪問的前綴沒有任何物件
+        Ok(_) | Err(ServerError::NotFound(_)) | Err(ServerError::UnsafePath(_)) => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let allow = published(config);
+    let join = |name: &str| match dir.is_empty() {
+        true => name.to_string(),
+        false => format!("{}/{}", dir, name),
+    };
+
+    let mut items = BTreeMap::new();
+    if delimiter == Some("/") {
+        for entry in fs::read_dir(&dir_path)? {
+            let entry = entry?;
+            let key = join(&entry.file_name().to_string_lossy());
+            if !key.starts_with(prefix) || !allow(&key) {
+                continue;
+            }
+            let file_type = entry.file_type()?;
+            if file_type.is_dir() {
+                let prefix = format!("{}/", key);
+                items.insert(prefix.clone(), Item::Prefix(prefix));
+            } else if file_type.is_file() {
+                items.insert(key.clone(), Item::Object { key, metadata: entry.metadata()? });
+            }
+        }
+    } else {
+        let mut files = Vec::new();
+        walk_files(&dir_path, dir, &allow, &mut files)?;
+        for (key, _, metadata) in files {
+            let Some(rest) = key.strip_prefix(prefix) else {
+                continue;
+            };
+            match delimiter.and_then(|d| rest.find(d).map(|i| i + d.len())) {
+                Some(end) => {
+                    let common = format!("{}{}", prefix, &rest[..end]);
+                    items.insert(common.clone(), Item::Prefix(common));
+                }
+                None => {
+                    items.insert(key.clone(), Item::Object { key, metadata });
+                }
+            }
+        }
+    }
+    Ok(items.into_values().collect())
+}
+
+// `GET`/`HEAD /<bucket>/<key>`：交給文件路由處理
+async fn get_object(
+    State(s3): State<Arc<S3>>,
+    Path((bucket, _)): Path<(String, String)>,
+    request: Request,
+) -> Result<Response, S3Error> {
+    check_bucket(&s3, &bucket)?;
+    let (parts, body) = request.into_parts();
+    let raw = parts.uri.path();
+    let key = &raw[1 + bucket.len()..];
+    let uri = match parts.uri.query() {
+        Some(query) => format!("{}?{}", key, query),
+        None => key.to_string(),
+    };
+
+    // 重新建立請求，外層路由的路徑參數不能帶到文件路由
+    let mut forwarded = Request::builder()
+        .method(parts.method)
+        .uri(uri)
+        .version(parts.version)
+        .body(body)
+        .map_err(|e| ServerError::Internal(e.to_string()))?;
+    *forwarded.headers_mut() = parts.headers;
+    if let Some(deadline) = parts.extensions.get::<Deadline>() {
+        forwarded.extensions_mut().insert(*deadline);
+    }
+    if let Some(connect_info) = parts.extensions.get::<ConnectInfo<SocketAddr>>() {
+        forwarded.extensions_mut().insert(*connect_info);
+    }
+    Ok(s3.objects.clone().oneshot(forwarded).await.into_response())
+}
+
+// 設定金鑰時驗證 AWS Signature Version 4，支援 Authorization 標頭與預先簽章的網址
+async fn authenticate(State(s3): State<Arc<S3>>, request: Request, next: Next) -> Response {
+    let Some((access_key, secret)) = &s3.options.credentials else {
+        return next.run(request).await;
+    };
+    match verify(&request, access_key, secret) {
+        Ok(()) => next.run(request).await,
+        Err(e) => e.into_response(),
+    }
+}
+
+// 簽章的各個欄位
+struct Signature {
+    credential: String,
+    signed_headers: String,
+    signature: String,
+    amz_date: String,
+    payload_hash: String,
+    // 預先簽章網址的有效秒數
+    expires: Option<i64>,
+}
+
+fn verify(request: &Request<Body>, access_key: &str, secret: &str) -> Result<(), S3Error> {
+    let headers = request.headers();
+    let query: Vec<(String, String)> = request
+        .uri()
+        .query()
+        .unwrap_or("")
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            let decode = |s: &str| percent_decode_str(s).decode_utf8_lossy().to_string();
+            (decode(name), decode(value))
+        })
+        .collect();
+    let param = |name: &str| query.iter().find(|(n, _)| n == name).map(|(_, v)| v.clone());
+
+    let signature = match headers.get(header::AUTHORIZATION) {
+        Some(value) => from_header(value.to_str().unwrap_or(""), headers)?,
+        None if param("X-Amz-Algorithm").as_deref() == Some("AWS4-HMAC-SHA256") => {
+            let missing = || S3Error::access_denied("預先簽章的網址缺少參數");
+            Signature {
+                credential: param("X-Amz-Credential").ok_or_else(missing)?,
+                signed_headers: param("X-Amz-SignedHeaders").ok_or_else(missing)?,
+                signature: param("X-Amz-Signature").ok_or_else(missing)?,
+                amz_date: param("X-Amz-Date").ok_or_else(missing)?,
+                payload_hash: "UNSIGNED-PAYLOAD".to_string(),
+                expires: Some(
+                    param("X-Amz-Expires")
+                        .and_then(|v| v.parse().ok())
+                        .filter(|v| (1..=MAX_PRESIGNED_EXPIRY).contains(v))
+                        .ok_or_else(missing)?,
+                ),
+            }
+        }
+        None => return Err(S3Error::access_denied("需要 AWS Signature Version 4 簽章")),
+    };
+
+    // Credential 為 `<存取金鑰 ID>/<日期>/<區域>/<服務>/aws4_request`
+    let scope: Vec<&str> = signature.credential.split('/').collect();
+    if scope.len() != 5 || scope[4] != "aws4_request" {
+        return Err(S3Error::access_denied("無效的 Credential"));
+    }
+    if scope[0] != access_key {
+        return Err(S3Error::new(StatusCode::FORBIDDEN, "InvalidAccessKeyId", "存取金鑰 ID 不存在"));
+    }
+    let signed_at = NaiveDateTime::parse_from_str(&signature.amz_date, "%Y%m%dT%H%M%SZ")
+        .map_err(|_| S3Error::access_denied("無效的 X-Amz-Date"))?
+        .and_utc()
+        .timestamp();
+    if !signature.amz_date.starts_with(scope[1]) {
+        return Err(S3Error::access_denied("Credential 的日期與 X-Amz-Date 不符"));
+    }
+    let now = Utc::now().timestamp();
+    match signature.expires {
+        Some(expires) if now > signed_at + expires || signed_at > now + MAX_CLOCK_SKEW => {
+            return Err(S3Error::access_denied("預先簽章的網址已過期"));
+        }
+        None if (now - signed_at).abs() > MAX_CLOCK_SKEW => {
+            return Err(S3Error::new(StatusCode::FORBIDDEN, "RequestTimeTooSkewed", "請求時間與伺服器時間相差過多"));
+        }
+        _ => {}
+    }
+
+    let mut canonical_query: Vec<(String, String)> = query
+        .iter()
+        .filter(|(name, _)| name != "X-Amz-Signature")
+        .map(|(name, value)| (aws_encode(name), aws_encode(value)))
+        .collect();
+    canonical_query.sort();
+    let canonical_query: Vec<String> = canonical_query.iter().map(|(n, v)| format!("{}={}", n, v)).collect();
+
+    let mut canonical_headers = String::new();
+    for name in signature.signed_headers.split(';') {
+        let value = match name {
+            "host" => headers
+                .get(header::HOST)
+                .and_then(|v| v.to_str().ok())
+                .or_else(|| request.uri().authority().map(|a| a.as_str())),
+            name => headers.get(name).and_then(|v| v.to_str().ok()),
+        }
+        .ok_or_else(|| S3Error::access_denied(format!("缺少簽章的標頭 {}", name)))?;
+        let value = value.split_whitespace().collect::<Vec<_>>().join(" ");
+        canonical_headers.push_str(&format!("{}:{}\n", name, value));
+    }
+
+    let canonical_request = format!(
+        "{}\n{}\n{}\n{}\n{}\n{}",
+        request.method(),
+        request.uri().path(),
+        canonical_query.join("&"),
+        canonical_headers,
+        signature.signed_headers,
+        signature.payload_hash
+    );
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{}\n{}\n{:x}",
+        signature.amz_date,
+        scope[1..].join("/"),
+        Sha256::digest(canonical_request.as_bytes())
+    );
+
+    let mut key = format!("AWS4{}", secret).into_bytes();
+    for part in &scope[1..] {
+        key = hmac(&key, part.as_bytes());
+    }
+    let mut mac = HmacSha256::new_from_slice(&key).expect("HMAC 接受任意長度的金鑰");
+    mac.update(string_to_sign.as_bytes());
+    let provided = decode_hex(&signature.signature)
+        .ok_or_else(|| S3Error::new(StatusCode::FORBIDDEN, "SignatureDoesNotMatch", "簽章不符"))?;
+    mac.verify_slice(&provided)
+        .map_err(|_| S3Error::new(StatusCode::FORBIDDEN, "SignatureDoesNotMatch", "簽章不符"))
+}
+
+// 解析 `AWS4-HMAC-SHA256 Credential=..., SignedHeaders=..., Signature=...`
+fn from_header(value: &str, headers: &HeaderMap) -> Result<Signature, S3Error> {
+    let fields = value
+        .strip_prefix("AWS4-HMAC-SHA256 ")
+        .ok_or_else(|| S3Error::access_denied("只支援 AWS Signature Version 4 簽章"))?;
+    let field = |name: &str| {
+        fields.split(',').find_map(|field| {
+            let (n, v) = field.trim().split_once('=')?;
+            (n == name).then(|| v.to_string())
+        })
+    };
+    let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok()).map(str::to_string);
+    let missing = || S3Error::access_denied("Authorization 標頭缺少欄位");
+    Ok(Signature {
+        credential: field("Credential").ok_or_else(missing)?,
+        signed_headers: field("SignedHeaders").ok_or_else(missing)?,
+        signature: field("Signature").ok_or_else(missing)?,
+        amz_date: header("x-amz-date").ok_or_else(|| S3Error::access_denied("缺少 X-Amz-Date 標頭"))?,
+        // 沒有此標頭時視為空的內容
+        payload_hash: header("x-amz-content-sha256").unwrap_or_else(|| format!("{:x}", Sha256::digest(b""))),
+        expires: None,
+    })
+}
+
+fn aws_encode(s: &str) -> String {
+    utf8_percent_encode(s, AWS_ENCODE).to_string()
+}
+
+fn hmac(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC 接受任意長度的金鑰");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn decode_hex(s: &str) -> Option<Vec<u8>> {
+    if !s.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok())
+        .collect()
+}
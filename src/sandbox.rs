@@ -0,0 +1,97 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+// 解析主機名稱、驗證 TLS 憑證與換算本地時間時會讀取的系統文件
+#[cfg(target_os = "linux")]
+const SYSTEM_FILES: &[&str] = &[
+    "/etc/hosts",
+    "/etc/resolv.conf",
+    "/etc/nsswitch.conf",
+    "/etc/gai.conf",
+    "/etc/localtime",
+    "/etc/ssl",
+    "/etc/pki",
+    "/etc/ca-certificates",
+    "/usr/share/ca-certificates",
+    "/usr/share/zoneinfo",
+    // 目前開啟的文件數（/_admin/stats）
+    "/proc/self/fd",
+];
+
+// 升級時重新執行程式需要的動態連結函式庫
+#[cfg(target_os = "linux")]
+const LIBRARY_DIRS: &[&str] = &["/etc/ld.so.cache", "/lib", "/lib64", "/usr/lib", "/usr/lib64"];
+
+// 啟動完成後限制程序能存取的文件（Linux Landlock）
+//
+// 路徑檢查就算有漏洞，程序本身也無法開啟允許清單以外的文件。規則綁定在套用當下的
+// 目錄與文件上，之後才建立在清單外的路徑一律無法存取；限制會繼承給升級後的新程序。
+#[derive(Default)]
+pub struct Sandbox {
+    read: Vec<PathBuf>,
+    write: Vec<PathBuf>,
+    execute: Vec<PathBuf>,
+}
+
+impl Sandbox {
+    // 可讀取此路徑（目錄則含底下所有內容）
+    pub fn read(&mut self, path: impl AsRef<Path>) -> &mut Self {
+        self.read.push(path.as_ref().to_path_buf());
+        self
+    }
+
+    // 可讀寫、建立與刪除此路徑底下的內容
+    pub fn write(&mut self, path: impl AsRef<Path>) -> &mut Self {
+        self.write.push(path.as_ref().to_path_buf());
+        self
+    }
+
+    // 可執行此路徑底下的程式，用於升級時重新執行自己
+    pub fn execute(&mut self, path: impl AsRef<Path>) -> &mut Self {
+        self.execute.push(path.as_ref().to_path_buf());
+        self
+    }
+
+    #[cfg(target_os = "linux")]
+    pub fn enforce(&self) -> io::Result<()> {
+        use landlock::{
+            path_beneath_rules, Access, AccessFs, Ruleset, RulesetAttr, RulesetCreatedAttr, RulesetStatus, ABI,
+        };
+
+        let abi = ABI::V3;
+        let read = AccessFs::from_read(abi) & !AccessFs::Execute;
+        let status = Ruleset::default()
+            .handle_access(AccessFs::from_all(abi))
+            .and_then(|ruleset| ruleset.create())
+            .and_then(|ruleset| ruleset.add_rules(path_beneath_rules(SYSTEM_FILES, read)))
+            .and_then(|ruleset| ruleset.add_rules(path_beneath_rules(&self.read, read)))
+            .and_then(|ruleset| ruleset.add_rules(path_beneath_rules(&self.write, AccessFs::from_all(abi) & !AccessFs::Execute)))
+            .and_then(|ruleset| match self.execute.is_empty() {
+                true => Ok(ruleset),
+                false => ruleset
+                    .add_rules(path_beneath_rules(LIBRARY_DIRS, AccessFs::from_read(abi)))?
+                    .add_rules(path_beneath_rules(&self.execute, AccessFs::from_read(abi))),
+            })
+            .and_then(|ruleset| ruleset.restrict_self())
+            .map_err(io::Error::other)?;
+
+        match status.ruleset {
+            RulesetStatus::FullyEnforced => Ok(()),
+            RulesetStatus::PartiallyEnforced => {
+                tracing::warn!("核心的 Landlock 版本較舊，部分文件操作未受限制");
+                Ok(())
+            }
+            RulesetStatus::NotEnforced => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "核心不支援 Landlock，無法限制文件存取",
+            )),
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn enforce(&self) -> io::Result<()> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "此平台不支援 --sandbox"))
+    }
+}
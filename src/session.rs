@@ -0,0 +1,425 @@
+use std::{
+    collections::HashMap,
+    io,
+    net::SocketAddr,
+    sync::Mutex,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use axum::{
+    extract::{ConnectInfo, Query, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode, Uri},
+    response::{Html, IntoResponse, Response},
+    routing::{get, post},
+    Form, Router,
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use sha2::Sha256;
+
+use crate::{auth, csrf, html, ServerConfig, ServerError};
+
+// 登入階段 cookie 的名稱
+const COOKIE_NAME: &str = "sfs_session";
+
+// 一般登入的有效時間，cookie 本身在瀏覽器關閉時即失效
+const SESSION_TTL: Duration = Duration::from_secs(12 * 3600);
+
+// 勾選「記住我」時的有效時間
+const REMEMBER_TTL: Duration = Duration::from_secs(30 * 24 * 3600);
+
+type HmacSha256 = Hmac<Sha256>;
+
+// 簽發與驗證登入階段 cookie 的金鑰
+//
+// cookie 內容為 `base64url(用戶名稱).到期時間.簽發時間.HMAC`。
+// 未指定金鑰時每次啟動隨機產生，重新啟動後所有登入階段都會失效。
+//
+// 伺服器只記住每個用戶最後一次登出的時間，在那之前簽發的 cookie 一律無效，
+// 登出因此會結束該用戶所有裝置上的登入階段。這份紀錄只存在記憶體中，
+// 以固定的 --session-secret 重新啟動後，登出前簽發且尚未到期的 cookie 會恢復有效。
+pub struct SessionKeys {
+    secret: Vec<u8>,
+    // 透過 HTTPS 提供服務時 cookie 加上 Secure
+    secure: bool,
+    // 用戶名稱 → 最後一次登出的時間（Unix 毫秒），只有簽章有效的用戶才會加入
+    revoked: Mutex<HashMap<String, u128>>,
+}
+
+fn now_millis() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis()
+}
+
+impl SessionKeys {
+    pub fn new(secret: Option<&str>, secure: bool) -> io::Result<Self> {
+        let secret = match secret {
+            Some(secret) => secret.as_bytes().to_vec(),
+            None => {
+                let mut secret = vec![0u8; 32];
+                getrandom::fill(&mut secret).map_err(io::Error::other)?;
+                secret
+            }
+        };
+        Ok(SessionKeys {
+            secret,
+            secure,
+            revoked: Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub fn secure(&self) -> bool {
+        self.secure
+    }
+
+    fn attributes(&self) -> &'static str {
+        if self.secure {
+            "Path=/; HttpOnly; Secure; SameSite=Lax"
+        } else {
+            "Path=/; HttpOnly; SameSite=Lax"
+        }
+    }
+
+    fn sign(&self, payload: &str) -> String {
+        let mut mac = HmacSha256::new_from_slice(&self.secret).expect("HMAC 接受任意長度的金鑰");
+        mac.update(payload.as_bytes());
+        mac.finalize()
+            .into_bytes()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    }
+
+    // 產生登入成功後的 Set-Cookie 標頭值
+    fn issue(&self, name: &str, remember: bool) -> String {
+        let ttl = if remember { REMEMBER_TTL } else { SESSION_TTL };
+        let expires = (SystemTime::now() + ttl)
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let payload = format!("{}.{}.{}", URL_SAFE_NO_PAD.encode(name), expires, now_millis());
+        let value = format!("{}.{}", payload, self.sign(&payload));
+
+        let mut cookie = format!("{}={}; {}", COOKIE_NAME, value, self.attributes());
+        if remember {
+            cookie.push_str(&format!("; Max-Age={}", ttl.as_secs()));
+        }
+        cookie
+    }
+
+    // 驗證請求中的登入階段 cookie，回傳用戶名稱
+    pub fn verify(&self, headers: &HeaderMap) -> Option<String> {
+        let value = cookie_value(headers, COOKIE_NAME)?;
+        let (payload, signature) = value.rsplit_once('.')?;
+
+        let mut mac = HmacSha256::new_from_slice(&self.secret).ok()?;
+        mac.update(payload.as_bytes());
+        let signature = decode_hex(signature)?;
+        mac.verify_slice(&signature).ok()?;
+
+        let mut parts = payload.split('.');
+        let (name, expires, issued) = (parts.next()?, parts.next()?, parts.next()?);
+        let expires: u64 = expires.parse().ok()?;
+        let issued: u128 = issued.parse().ok()?;
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?.as_secs();
+        if now >= expires {
+            return None;
+        }
+
+        let name = String::from_utf8(URL_SAFE_NO_PAD.decode(name).ok()?).ok()?;
+        match self.revoked.lock().unwrap().get(&name) {
+            Some(&logout) if issued <= logout => None,
+            _ => Some(name),
+        }
+    }
+
+    // 撤銷用戶目前所有的登入階段
+    fn revoke(&self, name: &str) {
+        self.revoked.lock().unwrap().insert(name.to_string(), now_millis());
+    }
+}
+
+fn decode_hex(s: &str) -> Option<Vec<u8>> {
+    if !s.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+// 從 Cookie 標頭中取出指定名稱的值
+pub fn cookie_value<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value)
+}
+
+// 請求是否來自瀏覽器頁面瀏覽
+pub fn wants_html(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::ACCEPT)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.contains("text/html"))
+        .unwrap_or(false)
+}
+
+// 未登入的瀏覽器請求導向登入頁，登入後回到原本的路徑
+pub fn redirect_to_login(uri: &Uri) -> Response {
+    let next = uri.path_and_query().map(|p| p.as_str()).unwrap_or("/");
+    let location = format!("/_login?next={}", percent_encode(next));
+    redirect(&location, None)
+}
+
+fn redirect(location: &str, cookie: Option<String>) -> Response {
+    let mut response = StatusCode::SEE_OTHER.into_response();
+    let headers = response.headers_mut();
+    if let Ok(location) = HeaderValue::from_str(location) {
+        headers.insert(header::LOCATION, location);
+    }
+    if let Some(cookie) = cookie.and_then(|c| HeaderValue::from_str(&c).ok()) {
+        headers.insert(header::SET_COOKIE, cookie);
+    }
+    response
+}
+
+fn percent_encode(s: &str) -> String {
+    s.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+// 只允許導回本站的路徑，避免被利用成開放重新導向
+//
+// 瀏覽器解析網址時會略過 tab 與換行，`/\t/evil.example` 會變成 `//evil.example`，
+// 因此含有控制字元的路徑同樣不接受。
+fn safe_next(next: Option<&str>) -> &str {
+    match next {
+        Some(next)
+            if next.starts_with('/')
+                && !next.starts_with("//")
+                && !next.contains('\\')
+                && !next.chars().any(char::is_control) =>
+        {
+            next
+        }
+        _ => "/",
+    }
+}
+
+// 登入頁與登出路由，只有設定 --login-page 時才會掛載
+pub fn routes() -> Router<ServerConfig> {
+    Router::new()
+        .route("/_login", get(login_page).post(login))
+        .route("/_logout", post(logout))
+}
+
+#[derive(Deserialize)]
+struct LoginQuery {
+    next: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct LoginForm {
+    username: String,
+    password: String,
+    #[serde(default)]
+    remember: Option<String>,
+    #[serde(default)]
+    next: Option<String>,
+    #[serde(default)]
+    csrf_token: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct LogoutForm {
+    #[serde(default)]
+    csrf_token: Option<String>,
+}
+
+fn render_login(next: &str, csrf_token: &str, failed: bool) -> String {
+    let mut html = String::from("<!DOCTYPE html>\n<html>\n<head>\n");
+    html.push_str("<meta charset=\"utf-8\">\n<title>登入</title>\n");
+    html.push_str("<style>\n");
+    html.push_str("body { font-family: system-ui, -apple-system, sans-serif; padding: 2em; }\n");
+    html.push_str("form { max-width: 20em; }\n");
+    html.push_str("label { display: block; margin-top: 1em; }\n");
+    html.push_str("input[type=text], input[type=password] { width: 100%; padding: 6px; }\n");
+    html.push_str("button { margin-top: 1em; padding: 8px 16px; background-color: #4CAF50; color: white; border: none; }\n");
+    html.push_str(".error { color: #c62828; }\n");
+    html.push_str("</style>\n</head>\n<body>\n<h1>登入</h1>\n");
+    if failed {
+        html.push_str("<p class=\"error\">用戶名稱或密碼錯誤</p>\n");
+    }
+    html.push_str("<form method=\"post\" action=\"/_login\">\n");
+    html.push_str(&format!(
+        "<input type=\"hidden\" name=\"next\" value=\"{}\">\n",
+        html::escape(next)
+    ));
+    html.push_str(&format!(
+        "<input type=\"hidden\" name=\"{}\" value=\"{}\">\n",
+        csrf::FIELD_NAME, csrf_token
+    ));
+    html.push_str("<label>用戶名稱 <input type=\"text\" name=\"username\" autocomplete=\"username\" required autofocus></label>\n");
+    html.push_str("<label>密碼 <input type=\"password\" name=\"password\" autocomplete=\"current-password\" required></label>\n");
+    html.push_str("<label><input type=\"checkbox\" name=\"remember\" value=\"1\"> 記住我</label>\n");
+    html.push_str("<button type=\"submit\">登入</button>\n");
+    html.push_str("</form>\n</body>\n</html>");
+    html
+}
+
+// 顯示登入表單，並在需要時發出 CSRF token cookie
+async fn login_page(
+    State(config): State<ServerConfig>,
+    Query(query): Query<LoginQuery>,
+    headers: HeaderMap,
+) -> Result<Response, ServerError> {
+    let next = safe_next(query.next.as_deref());
+    let (token, new_cookie) = match csrf::current_token(&headers) {
+        Some(token) => (token.to_string(), None),
+        None => {
+            let token = csrf::new_token()
+                .ok_or_else(|| ServerError::Internal("無法產生 CSRF token".to_string()))?;
+            let secure = config.sessions.as_ref().is_some_and(|sessions| sessions.secure());
+            let cookie = csrf::cookie(&token, secure);
+            (token, Some(cookie))
+        }
+    };
+
+    let mut response = Html(render_login(next, &token, false)).into_response();
+    if let Some(cookie) = new_cookie.and_then(|c| HeaderValue::from_str(&c).ok()) {
+        response.headers_mut().insert(header::SET_COOKIE, cookie);
+    }
+    Ok(response)
+}
+
+// 驗證表單中的帳號密碼，成功時發出登入階段 cookie 並導回原本的路徑
+async fn login(
+    State(config): State<ServerConfig>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    uri: Uri,
+    headers: HeaderMap,
+    Form(form): Form<LoginForm>,
+) -> Result<Response, ServerError> {
+    let sessions = config
+        .sessions
+        .clone()
+        .ok_or_else(|| ServerError::NotFound("_login".to_string()))?;
+
+    // 防止其他網站以攻擊者的帳號替用戶登入
+    if csrf::is_cross_site(&headers) || !csrf::verify(&headers, &uri, form.csrf_token.as_deref()) {
+        return Err(ServerError::Forbidden("CSRF 驗證失敗".to_string()));
+    }
+
+    let next = safe_next(form.next.as_deref()).to_string();
+    let ip = connect_info.map(|ConnectInfo(addr)| addr.ip());
+    match auth::authenticate_password(&config, ip, form.username, form.password).await? {
+        Some(user) => {
+            let cookie = sessions.issue(&user.name, form.remember.is_some());
+            Ok(redirect(&next, Some(cookie)))
+        }
+        None => {
+            let token = csrf::current_token(&headers).unwrap_or_default();
+            let page = render_login(&next, token, true);
+            Ok((StatusCode::UNAUTHORIZED, Html(page)).into_response())
+        }
+    }
+}
+
+// 撤銷登入階段、清除 cookie 並回到登入頁
+async fn logout(
+    State(config): State<ServerConfig>,
+    uri: Uri,
+    headers: HeaderMap,
+    form: Option<Form<LogoutForm>>,
+) -> Result<Response, ServerError> {
+    // 以腳本登出時可改用標頭傳送 token，此時沒有表單內容
+    let field = form.as_ref().and_then(|Form(f)| f.csrf_token.as_deref());
+    if csrf::is_cross_site(&headers) || !csrf::verify(&headers, &uri, field) {
+        return Err(ServerError::Forbidden("CSRF 驗證失敗".to_string()));
+    }
+
+    let sessions = config
+        .sessions
+        .clone()
+        .ok_or_else(|| ServerError::NotFound("_logout".to_string()))?;
+
+    // 只清除 cookie 的話，被複製走的 cookie 在到期前仍然有效
+    if let Some(name) = sessions.verify(&headers) {
+        sessions.revoke(&name);
+    }
+
+    let cookie = format!("{}=; {}; Max-Age=0", COOKIE_NAME, sessions.attributes());
+    Ok(redirect("/_login", Some(cookie)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cookie_headers(set_cookie: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        let pair = set_cookie.split(';').next().unwrap();
+        headers.insert(header::COOKIE, HeaderValue::from_str(pair).unwrap());
+        headers
+    }
+
+    #[test]
+    fn only_local_paths_are_redirect_targets() {
+        assert_eq!(safe_next(Some("/docs/a.txt?x=1")), "/docs/a.txt?x=1");
+        assert_eq!(safe_next(Some("/")), "/");
+        for next in [
+            "https://evil.example/",
+            "//evil.example/",
+            "/\\evil.example/",
+            "\\\\evil.example",
+            "/\t/evil.example",
+            "/\n/evil.example",
+            "evil.example",
+            "",
+        ] {
+            assert_eq!(safe_next(Some(next)), "/", "{:?}", next);
+        }
+        assert_eq!(safe_next(None), "/");
+    }
+
+    #[test]
+    fn issued_cookies_verify_until_tampered_with() {
+        let keys = SessionKeys::new(Some("secret"), false).unwrap();
+        let cookie = keys.issue("alice", false);
+        assert!(cookie.contains("HttpOnly") && !cookie.contains("Secure") && !cookie.contains("Max-Age"));
+        assert_eq!(keys.verify(&cookie_headers(&cookie)).as_deref(), Some("alice"));
+
+        // 換掉用戶名稱後簽章不符
+        let forged = cookie.replacen(&URL_SAFE_NO_PAD.encode("alice"), &URL_SAFE_NO_PAD.encode("admin"), 1);
+        assert_eq!(keys.verify(&cookie_headers(&forged)), None);
+        // 其他金鑰簽發的 cookie 無效
+        let other = SessionKeys::new(Some("other"), false).unwrap();
+        assert_eq!(other.verify(&cookie_headers(&cookie)), None);
+    }
+
+    #[test]
+    fn logging_out_revokes_earlier_sessions() {
+        let keys = SessionKeys::new(None, true).unwrap();
+        let cookie = keys.issue("alice", true);
+        assert!(cookie.contains("Secure") && cookie.contains("Max-Age="));
+        let other = keys.issue("bob", false);
+        keys.revoke("alice");
+        assert_eq!(keys.verify(&cookie_headers(&cookie)), None);
+        assert_eq!(keys.verify(&cookie_headers(&other)).as_deref(), Some("bob"));
+    }
+}
@@ -0,0 +1,167 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+use globset::{GlobBuilder, GlobMatcher};
+use tracing::warn;
+
+use crate::restrict::normalize;
+
+pub const FILE_NAME: &str = ".sfignore";
+
+struct Rule {
+    glob: GlobMatcher,
+    // `!` 開頭，重新納入先前排除的路徑
+    negate: bool,
+    // `/` 結尾，只比對目錄
+    dir_only: bool,
+}
+
+// 一份 .sfignore 的規則，語法與 .gitignore 相同
+pub struct IgnoreRules {
+    base: PathBuf,
+    rules: Vec<Rule>,
+}
+
+impl IgnoreRules {
+    fn parse(base: &Path, content: &str) -> Self {
+        let mut rules = Vec::new();
+        for line in content.lines() {
+            let line = line.trim_end();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (negate, pattern) = match line.strip_prefix('!') {
+                Some(rest) => (true, rest),
+                None => (false, line.strip_prefix('\\').unwrap_or(line)),
+            };
+            let (dir_only, pattern) = match pattern.strip_suffix('/') {
+                Some(rest) => (true, rest),
+                None => (false, pattern),
+            };
+            // 含 `/` 的樣式從根目錄比對，其他樣式比對任何一層的名稱
+            let pattern = match pattern.contains('/') {
+                true => pattern.trim_start_matches('/').to_string(),
+                false => format!("**/{}", pattern),
+            };
+            match GlobBuilder::new(&pattern).literal_separator(true).build() {
+                Ok(glob) => rules.push(Rule {
+                    glob: glob.compile_matcher(),
+                    negate,
+                    dir_only,
+                }),
+                Err(e) => warn!("略過 {} 中無效的樣式 {}: {}", FILE_NAME, line, e),
+            }
+        }
+        IgnoreRules {
+            base: base.to_path_buf(),
+            rules,
+        }
+    }
+
+    // 最後一條符合的規則決定結果，沒有規則符合時為 None
+    fn decide(&self, path: &str, is_dir: &dyn Fn() -> bool) -> Option<bool> {
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| rule.glob.is_match(path) && (!rule.dir_only || is_dir()))
+            .map(|rule| !rule.negate)
+    }
+
+    // 相對於 .sfignore 所在目錄的路徑是否被排除；與 git 相同，被排除的目錄底下無法再重新納入
+    pub fn is_ignored(&self, path: &str) -> bool {
+        if self.rules.is_empty() {
+            return false;
+        }
+        let path = normalize(path);
+        let components: Vec<&str> = path.split('/').filter(|c| !c.is_empty()).collect();
+        for n in 1..components.len() {
+            if self.decide(&components[..n].join("/"), &|| true) == Some(true) {
+                return true;
+            }
+        }
+        self.decide(&path, &|| self.base.join(&path).is_dir()) == Some(true)
+    }
+}
+
+struct Loaded {
+    modified: Option<SystemTime>,
+    rules: Arc<IgnoreRules>,
+}
+
+// 基礎目錄根部的 .sfignore，排除不想出現在列表、文件清單與封存檔中的產生物
+//
+// 與禁止訪問的規則不同，被排除的文件仍可直接下載。文件有變動時自動重新載入。
+pub struct IgnoreFile {
+    path: PathBuf,
+    // 有自己根目錄的用戶看到的路徑需要加上此前綴
+    prefix: String,
+    loaded: Arc<Mutex<Loaded>>,
+}
+
+impl IgnoreFile {
+    pub fn open(base: &Path) -> Self {
+        let file = IgnoreFile {
+            path: base.join(FILE_NAME),
+            prefix: String::new(),
+            loaded: Arc::new(Mutex::new(Loaded {
+                modified: None,
+                rules: Arc::new(IgnoreRules::parse(base, "")),
+            })),
+        };
+        file.reload();
+        file
+    }
+
+    // 同一份規則，但路徑相對於基礎目錄底下的 `root`
+    pub fn scoped(&self, root: &str) -> Self {
+        IgnoreFile {
+            path: self.path.clone(),
+            prefix: join(&self.prefix, root),
+            loaded: self.loaded.clone(),
+        }
+    }
+
+    fn file_modified(&self) -> Option<SystemTime> {
+        fs::metadata(&self.path).and_then(|m| m.modified()).ok()
+    }
+
+    fn reload(&self) {
+        let modified = self.file_modified();
+        let base = self.path.parent().unwrap_or(Path::new(""));
+        let content = match fs::read_to_string(&self.path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e) => {
+                warn!("無法讀取 {}: {}", self.path.display(), e);
+                String::new()
+            }
+        };
+        *self.loaded.lock().unwrap() = Loaded {
+            modified,
+            rules: Arc::new(IgnoreRules::parse(base, &content)),
+        };
+    }
+
+    // 取得目前的規則，回傳的函式以相對路徑判斷是否被排除
+    //
+    // 走訪整個目錄樹時先取得一次，避免每個路徑都檢查 .sfignore 是否變動。
+    pub fn matcher(&self) -> impl Fn(&str) -> bool + Send + Sync + 'static {
+        if self.file_modified() != self.loaded.lock().unwrap().modified {
+            self.reload();
+        }
+        let rules = self.loaded.lock().unwrap().rules.clone();
+        let prefix = self.prefix.clone();
+        move |path| rules.is_ignored(&join(&prefix, path))
+    }
+}
+
+fn join(prefix: &str, path: &str) -> String {
+    match prefix.is_empty() {
+        true => path.to_string(),
+        false => format!("{}/{}", prefix, path),
+    }
+}
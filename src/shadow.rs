@@ -0,0 +1,111 @@
+use std::{net::SocketAddr, sync::Arc, time::Duration};
+
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{header, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+use tokio::sync::Semaphore;
+use tracing::debug;
+
+use crate::ServerConfig;
+
+// 同時進行中的鏡像請求上限，目標變慢時多出的請求直接略過，不會在記憶體中累積
+const MAX_IN_FLIGHT: usize = 64;
+
+// 鏡像請求的逾時
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+// 不轉送的逐跳標頭；Host 依目標網址重新產生，原本的值放在 X-Forwarded-Host
+const HOP_BY_HOP: &[&str] = &[
+    "connection",
+    "keep-alive",
+    "proxy-connection",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+    "host",
+    "content-length",
+];
+
+// 將部分請求的複本送到另一個網址（--shadow、--shadow-percent）
+//
+// 複本只包含方法、路徑、查詢字串與標頭，在背景送出，回應直接丟棄，不影響原本的請求。
+// 只鏡像 GET、HEAD 等安全的方法，替換中的後端不會收到寫入。
+pub struct Shadow {
+    client: reqwest::Client,
+    // 不含結尾斜線
+    target: String,
+    percent: u8,
+    slots: Arc<Semaphore>,
+}
+
+impl Shadow {
+    pub fn new(target: &str, percent: u8) -> reqwest::Result<Self> {
+        Ok(Shadow {
+            client: reqwest::Client::builder().timeout(TIMEOUT).build()?,
+            target: target.trim_end_matches('/').to_string(),
+            percent,
+            slots: Arc::new(Semaphore::new(MAX_IN_FLIGHT)),
+        })
+    }
+
+    // 依比例抽樣
+    fn sampled(&self) -> bool {
+        self.percent >= 100 || getrandom::u32().is_ok_and(|n| n % 100 < u32::from(self.percent))
+    }
+
+    // 在背景送出請求的複本
+    fn send(&self, request: &Request) {
+        let Ok(permit) = self.slots.clone().try_acquire_owned() else {
+            debug!("鏡像請求過多，略過 {}", request.uri());
+            return;
+        };
+        let path = request.uri().path_and_query().map_or("/", |p| p.as_str());
+        let url = format!("{}{}", self.target, path);
+
+        let mut headers = request.headers().clone();
+        let host = headers
+            .get(header::HOST)
+            .cloned()
+            .or_else(|| request.uri().authority().and_then(|a| HeaderValue::from_str(a.as_str()).ok()));
+        for name in HOP_BY_HOP {
+            headers.remove(*name);
+        }
+        if let Some(host) = host {
+            headers.insert("x-forwarded-host", host);
+        }
+        if let Some(ConnectInfo(addr)) = request.extensions().get::<ConnectInfo<SocketAddr>>() {
+            let forwarded = match headers.get("x-forwarded-for").and_then(|v| v.to_str().ok()) {
+                Some(previous) => format!("{}, {}", previous, addr.ip()),
+                None => addr.ip().to_string(),
+            };
+            if let Ok(value) = HeaderValue::from_str(&forwarded) {
+                headers.insert("x-forwarded-for", value);
+            }
+        }
+
+        let shadow = self.client.request(request.method().clone(), &url).headers(headers);
+        tokio::spawn(async move {
+            match shadow.send().await {
+                Ok(response) => debug!("鏡像請求 {} 回應 {}", url, response.status()),
+                Err(e) => debug!("鏡像請求 {} 失敗: {}", url, e),
+            }
+            drop(permit);
+        });
+    }
+}
+
+// 抽樣鏡像請求，之後照常處理
+pub async fn mirror(State(config): State<ServerConfig>, request: Request, next: Next) -> Response {
+    if let Some(shadow) = &config.shadow {
+        // 管理 API 的請求帶有管理 token，不送到其他地方
+        let admin = request.uri().path().starts_with("/_admin/");
+        if request.method().is_safe() && !admin && shadow.sampled() {
+            shadow.send(&request);
+        }
+    }
+    next.run(request).await
+}
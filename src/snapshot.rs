@@ -0,0 +1,134 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
+};
+
+use chrono::Utc;
+use cron::Schedule;
+use tracing::{error, info};
+
+use crate::{
+    archive::{self, ArchiveFormat},
+    published, ServerConfig,
+};
+
+// 一個定期產生快照的目錄
+pub struct SnapshotSpec {
+    // 相對於基礎目錄，根目錄為空字串
+    pub dir: String,
+    schedule: Schedule,
+}
+
+impl SnapshotSpec {
+    // 格式為 `<目錄>=<排程>`，排程為含秒數的 cron 運算式，例如 `releases=0 0 3 * * *`
+    pub fn parse(spec: &str) -> io::Result<Self> {
+        let invalid = |reason: String| io::Error::new(io::ErrorKind::InvalidInput, reason);
+        let (dir, schedule) = spec
+            .split_once('=')
+            .ok_or_else(|| invalid(format!("快照設定 {} 應為 <目錄>=<排程>", spec)))?;
+        let schedule = Schedule::from_str(schedule.trim())
+            .map_err(|e| invalid(format!("無效的快照排程 {}: {}", schedule, e)))?;
+        Ok(SnapshotSpec {
+            dir: dir.trim().trim_matches('/').to_string(),
+            schedule,
+        })
+    }
+}
+
+// 依排程預先產生目錄的封存檔
+//
+// 大型目錄樹的「下載全部」直接提供快取中的檔案，不必在請求時產生。快照先寫入暫存檔，
+// 完成後才改名取代舊檔，產生期間下載的仍是上一份完整的快照。
+pub struct Snapshots {
+    cache: PathBuf,
+    formats: Vec<ArchiveFormat>,
+    specs: Vec<SnapshotSpec>,
+}
+
+impl Snapshots {
+    pub fn new(cache: PathBuf, formats: Vec<ArchiveFormat>, specs: Vec<SnapshotSpec>) -> Self {
+        Snapshots { cache, formats, specs }
+    }
+
+    pub fn cache_dir(&self) -> &Path {
+        &self.cache
+    }
+
+    fn file_path(&self, dir: &str, format: ArchiveFormat) -> PathBuf {
+        let name = if dir.is_empty() { "_root".to_string() } else { dir.replace('/', "%2F") };
+        self.cache.join(format!("{}.{}", name, format.name()))
+    }
+
+    // 已產生的快照，目錄未設定快照或尚未產生時回傳 None
+    pub fn get(&self, dir: &str, format: ArchiveFormat) -> Option<PathBuf> {
+        let dir = dir.trim_matches('/');
+        if !self.formats.contains(&format) || !self.specs.iter().any(|s| s.dir == dir) {
+            return None;
+        }
+        let path = self.file_path(dir, format);
+        path.is_file().then_some(path)
+    }
+
+    // 每個目錄各自依排程產生快照；啟動時缺少的快照立即產生
+    pub fn spawn(self: Arc<Self>, config: ServerConfig) {
+        for index in 0..self.specs.len() {
+            let snapshots = self.clone();
+            let config = config.clone();
+            tokio::spawn(async move {
+                let spec = &snapshots.specs[index];
+                let mut missing = snapshots
+                    .formats
+                    .iter()
+                    .any(|&format| !snapshots.file_path(&spec.dir, format).is_file());
+                loop {
+                    if !missing {
+                        let Some(next) = spec.schedule.upcoming(Utc).next() else {
+                            break;
+                        };
+                        tokio::time::sleep((next - Utc::now()).to_std().unwrap_or_default()).await;
+                    }
+                    missing = false;
+
+                    for &format in &snapshots.formats {
+                        let (snapshots, config) = (snapshots.clone(), config.clone());
+                        let result = tokio::task::spawn_blocking(move || {
+                            snapshots.build(&snapshots.specs[index].dir, format, &config)
+                        })
+                        .await;
+                        match result {
+                            Ok(Ok(bytes)) => info!("已產生 /{} 的 {} 快照 ({} 位元組)", spec.dir, format.name(), bytes),
+                            Ok(Err(e)) => error!("產生 /{} 的 {} 快照失敗: {}", spec.dir, format.name(), e),
+                            Err(e) => error!("產生 /{} 的 {} 快照失敗: {}", spec.dir, format.name(), e),
+                        }
+                    }
+                }
+            });
+        }
+    }
+
+    // 產生一份快照，禁止訪問與被 .sfignore 排除的文件不會放入
+    fn build(&self, dir: &str, format: ArchiveFormat, config: &ServerConfig) -> io::Result<u64> {
+        let files = archive::collect(
+            &Path::new(config.base_path.as_str()).join(dir),
+            dir,
+            &published(config),
+        )?;
+
+        let path = self.file_path(dir, format);
+        let mut partial = path.clone().into_os_string();
+        partial.push(".partial");
+        let partial = PathBuf::from(partial);
+        let result = fs::File::create(&partial)
+            .and_then(|file| archive::write(format, &files, io::BufWriter::new(file)))
+            .and_then(|out| out.into_inner().map_err(|e| e.into_error()))
+            .and_then(|file| file.sync_all());
+        if let Err(e) = result {
+            let _ = fs::remove_file(&partial);
+            return Err(e);
+        }
+        fs::rename(&partial, &path)?;
+        Ok(fs::metadata(&path)?.len())
+    }
+}
@@ -0,0 +1,35 @@
+use std::io::{self, SeekFrom};
+
+use tokio::{
+    fs::File,
+    io::{AsyncReadExt, AsyncSeekExt},
+};
+
+// 判斷內容類型時讀取的開頭位元組數
+const SNIFF_LEN: usize = 8192;
+
+// 依文件開頭的特徵位元組判斷內容類型，讀取後將位置移回開頭
+//
+// 只用於沒有副檔名的文件（例如以雜湊命名的產出物）。無法辨識的二進位內容回傳 None，
+// 沿用 application/octet-stream；有效的 UTF-8 且不含 NUL 的內容視為純文字。
+pub async fn sniff(file: &mut File) -> io::Result<Option<String>> {
+    let mut buf = Vec::with_capacity(SNIFF_LEN);
+    (&mut *file).take(SNIFF_LEN as u64).read_to_end(&mut buf).await?;
+    file.seek(SeekFrom::Start(0)).await?;
+
+    if let Some(kind) = infer::get(&buf) {
+        return Ok(Some(kind.mime_type().to_string()));
+    }
+    if !buf.is_empty() && !buf.contains(&0) && looks_like_utf8(&buf) {
+        return Ok(Some("text/plain; charset=utf-8".to_string()));
+    }
+    Ok(None)
+}
+
+// 開頭的內容可能截斷在多位元組字元中間，結尾不完整的字元不算錯誤
+fn looks_like_utf8(buf: &[u8]) -> bool {
+    match std::str::from_utf8(buf) {
+        Ok(_) => true,
+        Err(e) => e.error_len().is_none() && buf.len() == SNIFF_LEN,
+    }
+}
@@ -0,0 +1,61 @@
+use std::{fs, path::Path};
+
+// 最多回傳的建議數量
+const MAX_SUGGESTIONS: usize = 5;
+
+// 計算兩個字串的編輯距離
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.iter().enumerate() {
+        let mut curr = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let cost = if ca == cb { 0 } else { 1 };
+            curr[j + 1] = (prev[j] + cost).min(prev[j + 1] + 1).min(curr[j] + 1);
+        }
+        prev = curr;
+    }
+
+    prev[b.len()]
+}
+
+// 從目錄中找出與請求名稱相近的項目，依相似度排序
+pub fn close_matches(dir: &Path, name: &str) -> Vec<String> {
+    let wanted = name.to_lowercase();
+    if wanted.is_empty() {
+        return Vec::new();
+    }
+    let threshold = (wanted.chars().count() / 4).max(2);
+
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+
+    let mut matches: Vec<(usize, String)> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let file_name = entry.file_name().to_string_lossy().to_string();
+            let candidate = file_name.to_lowercase();
+
+            // 前綴相符的項目視為最接近，例如缺少副檔名或版本號
+            let is_prefix = wanted.len() >= 3
+                && (candidate.starts_with(&wanted) || wanted.starts_with(&candidate));
+            let distance = edit_distance(&wanted, &candidate);
+
+            if is_prefix {
+                Some((0, file_name))
+            } else if distance <= threshold {
+                Some((distance, file_name))
+            } else {
+                None
+            }
+        })
+        .collect();
+
+    matches.sort();
+    matches.truncate(MAX_SUGGESTIONS);
+    matches.into_iter().map(|(_, name)| name).collect()
+}
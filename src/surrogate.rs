@@ -0,0 +1,84 @@
+use axum::http::{header::HeaderName, HeaderValue};
+use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS};
+
+// 快取標籤中需要編碼的字元：分隔符號與百分比符號本身，非 ASCII 字元一律編碼
+const KEY_ENCODE: &AsciiSet = &CONTROLS.add(b' ').add(b',').add(b'%').add(b'"');
+
+// 送出快取標籤的標頭
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum KeyHeader {
+    // Fastly 等使用的 `Surrogate-Key`，以空白分隔
+    SurrogateKey,
+    // Cloudflare 等使用的 `Cache-Tag`，以逗號分隔
+    CacheTag,
+}
+
+impl KeyHeader {
+    pub const NAMES: &'static [&'static str] = &["surrogate-key", "cache-tag"];
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "surrogate-key" => Some(KeyHeader::SurrogateKey),
+            "cache-tag" => Some(KeyHeader::CacheTag),
+            _ => None,
+        }
+    }
+}
+
+// 給 CDN 的快取標頭（--surrogate-control、--surrogate-keys）
+//
+// 文件回應的標籤是每一層上層目錄加上文件本身，例如 `/docs/v1/guide.html` 的標籤為
+// `/`、`/docs/`、`/docs/v1/` 與 `/docs/v1/guide.html`，重新產生某個目錄後依標籤清除即可
+// 清掉其下所有文件，不必逐一指定網址。
+#[derive(Clone)]
+pub struct SurrogateOptions {
+    // 只給 CDN 的快取指示，CDN 會在轉送前移除，不影響瀏覽器的 Cache-Control
+    pub control: Option<HeaderValue>,
+    pub keys: Option<KeyHeader>,
+}
+
+impl SurrogateOptions {
+    // 相對於基礎路徑的文件路徑對應的標頭
+    pub fn headers(&self, path: &str) -> Vec<(HeaderName, HeaderValue)> {
+        let mut headers = Vec::new();
+        if let Some(control) = &self.control {
+            headers.push((HeaderName::from_static("surrogate-control"), control.clone()));
+        }
+        if let Some(kind) = self.keys {
+            let (name, separator) = match kind {
+                KeyHeader::SurrogateKey => ("surrogate-key", " "),
+                KeyHeader::CacheTag => ("cache-tag", ","),
+            };
+            let value = keys(path).join(separator);
+            if let Ok(value) = HeaderValue::from_str(&value) {
+                headers.push((HeaderName::from_static(name), value));
+            }
+        }
+        headers
+    }
+}
+
+// 路徑的所有上層目錄與路徑本身
+pub fn keys(path: &str) -> Vec<String> {
+    let components: Vec<&str> = path.split('/').filter(|c| !c.is_empty()).collect();
+    (0..=components.len())
+        .map(|n| match n == components.len() {
+            true => tag(path),
+            false => tag(&format!("{}/", components[..n].join("/"))),
+        })
+        .collect()
+}
+
+// 單一路徑的標籤，以 `/` 結尾的路徑代表目錄
+pub fn tag(path: &str) -> String {
+    let encoded: Vec<String> = path
+        .split('/')
+        .filter(|c| !c.is_empty())
+        .map(|c| utf8_percent_encode(c, KEY_ENCODE).to_string())
+        .collect();
+    let mut tag = format!("/{}", encoded.join("/"));
+    if path.ends_with('/') && !encoded.is_empty() {
+        tag.push('/');
+    }
+    tag
+}
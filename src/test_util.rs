@@ -0,0 +1,114 @@
+use std::{
+    fs, io,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+};
+
+use tokio::{net::TcpListener, task::JoinHandle};
+
+use crate::{build_app, cli, listener::ListenerRoutes, server_config};
+
+// 測試用的伺服器（`test-util` 功能）
+//
+// 在暫存目錄上以指定的命令列參數啟動伺服器，監聽 127.0.0.1 的隨機埠，
+// 讓整合測試以實際的 HTTP 請求檢查行為。只建立 `--base` 的網站，
+// 不處理 TLS、管理位址、虛擬主機等需要另外監聽或讀取設定檔的參數。
+// 結束時停止伺服器並刪除暫存目錄。
+pub struct TestServer {
+    addr: SocketAddr,
+    base_url: String,
+    dir: PathBuf,
+    task: JoinHandle<()>,
+}
+
+impl TestServer {
+    // 以空的暫存目錄啟動，例如 `TestServer::start(&["--upload"])`
+    pub async fn start(args: &[&str]) -> Result<TestServer, Box<dyn std::error::Error>> {
+        TestServer::with_files(&[], args).await
+    }
+
+    // 先在暫存目錄建立文件再啟動，路徑相對於基礎路徑，需要時建立上層目錄
+    pub async fn with_files(
+        files: &[(&str, &[u8])],
+        args: &[&str],
+    ) -> Result<TestServer, Box<dyn std::error::Error>> {
+        let dir = temp_dir()?;
+        let server = async {
+            for (path, contents) in files {
+                write_file(&dir, path, contents)?;
+            }
+            let base = dir.to_string_lossy().into_owned();
+            let matches = cli().try_get_matches_from(["sfs", "--base", &base].iter().chain(args))?;
+            let config = server_config(&matches, None)?;
+            let app = build_app(&config, ListenerRoutes::all());
+
+            let listener = TcpListener::bind("127.0.0.1:0").await?;
+            let addr = listener.local_addr()?;
+            let task = tokio::spawn(async move {
+                let _ = axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await;
+            });
+            Ok::<_, Box<dyn std::error::Error>>((addr, task))
+        };
+        match server.await {
+            Ok((addr, task)) => Ok(TestServer {
+                addr,
+                base_url: format!("http://{}", addr),
+                dir,
+                task,
+            }),
+            Err(e) => {
+                let _ = fs::remove_dir_all(&dir);
+                Err(e)
+            }
+        }
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    // 不含結尾斜線的網址，例如 `http://127.0.0.1:40123`
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    // 路徑對應的完整網址，`path` 應以 `/` 開頭
+    pub fn url(&self, path: &str) -> String {
+        format!("{}{}", self.base_url, path)
+    }
+
+    // 伺服器的基礎路徑
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    // 啟動後新增或改寫基礎路徑中的文件
+    pub fn write(&self, path: &str, contents: impl AsRef<[u8]>) -> io::Result<()> {
+        write_file(&self.dir, path, contents.as_ref())
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        self.task.abort();
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+// 系統暫存目錄下隨機命名的新目錄
+fn temp_dir() -> io::Result<PathBuf> {
+    let mut bytes = [0u8; 8];
+    getrandom::fill(&mut bytes).map_err(io::Error::other)?;
+    let name: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    let dir = std::env::temp_dir().join(format!("sfs-test-{}", name));
+    fs::create_dir(&dir)?;
+    Ok(dir)
+}
+
+fn write_file(dir: &Path, path: &str, contents: &[u8]) -> io::Result<()> {
+    let path = dir.join(path.trim_start_matches('/'));
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, contents)
+}
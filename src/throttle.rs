@@ -0,0 +1,167 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    net::IpAddr,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use tracing::warn;
+
+// 第一次鎖定的時間，之後每次失敗加倍
+const BASE_LOCKOUT: Duration = Duration::from_secs(1);
+
+// 鎖定時間上限
+const MAX_LOCKOUT: Duration = Duration::from_secs(15 * 60);
+
+// 超過此時間沒有再失敗就忘記失敗紀錄
+const FORGET_AFTER: Duration = Duration::from_secs(3600);
+
+// 紀錄數量超過此值時清理過期的紀錄
+const PRUNE_THRESHOLD: usize = 10_000;
+
+// 紀錄數量的硬上限，清理後仍然額滿時不再加入新的紀錄（已有的紀錄照常計算與鎖定）
+const MAX_ENTRIES: usize = 100_000;
+
+// 來源 IP 的失敗次數是單一帳號的幾倍才鎖定整個 IP，
+// 讓共用 NAT 後的用戶打錯密碼時不會立刻連累其他人
+const IP_FACTOR: u32 = 4;
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+enum Key {
+    // 來源 IP，不論嘗試哪個用戶
+    Ip(IpAddr),
+    // 同一來源對同一用戶的嘗試，其他來源不受影響，避免有人故意鎖住別人的帳號
+    Account(Option<IpAddr>, String),
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Key::Ip(ip) => write!(f, "ip:{}", ip),
+            Key::Account(Some(ip), name) => write!(f, "user:{}@{}", name, ip),
+            Key::Account(None, name) => write!(f, "user:{}", name),
+        }
+    }
+}
+
+struct Failures {
+    count: u32,
+    last: Instant,
+    locked_until: Option<Instant>,
+}
+
+// 驗證失敗次數追蹤，分別依來源 IP 以及（來源 IP, 用戶）計算
+//
+// 連續失敗超過允許次數後進入暫時鎖定，鎖定時間隨失敗次數指數增加，
+// 鎖定期間即使密碼正確也會被拒絕。驗證成功後清除該 IP 與用戶的紀錄。
+// 只有已知的用戶才建立用戶紀錄，亂猜的用戶名稱只計入來源 IP。
+pub struct LoginThrottle {
+    max_failures: u32,
+    entries: Mutex<HashMap<Key, Failures>>,
+}
+
+impl LoginThrottle {
+    // `max_failures` 為 0 時停用
+    pub fn new(max_failures: u32) -> Self {
+        LoginThrottle {
+            max_failures,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn keys(ip: Option<IpAddr>, name: Option<&str>) -> Vec<Key> {
+        let mut keys = Vec::with_capacity(2);
+        if let Some(ip) = ip {
+            keys.push(Key::Ip(ip));
+        }
+        if let Some(name) = name {
+            keys.push(Key::Account(ip, name.to_string()));
+        }
+        keys
+    }
+
+    fn limit(&self, key: &Key) -> u32 {
+        match key {
+            Key::Ip(_) => self.max_failures.saturating_mul(IP_FACTOR),
+            Key::Account(..) => self.max_failures,
+        }
+    }
+
+    // 目前是否鎖定中，回傳剩餘的鎖定時間
+    pub fn check(&self, ip: Option<IpAddr>, name: Option<&str>) -> Option<Duration> {
+        if self.max_failures == 0 {
+            return None;
+        }
+
+        let now = Instant::now();
+        let entries = self.entries.lock().unwrap();
+        Self::keys(ip, name)
+            .iter()
+            .filter_map(|key| entries.get(key)?.locked_until)
+            .filter(|until| *until > now)
+            .map(|until| until - now)
+            .max()
+    }
+
+    // 紀錄一次驗證失敗並寫入稽核日誌
+    //
+    // `known` 表示用戶名稱確實存在；不存在的名稱不建立用戶紀錄，以免任意名稱塞滿紀錄。
+    pub fn record_failure(&self, ip: Option<IpAddr>, name: Option<&str>, known: bool) {
+        let ip_label = ip.map(|ip| ip.to_string()).unwrap_or_else(|| "-".to_string());
+        warn!(target: "audit", "驗證失敗: 用戶 {} 來源 {}", name.unwrap_or("-"), ip_label);
+
+        if self.max_failures == 0 {
+            return;
+        }
+
+        let now = Instant::now();
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() > PRUNE_THRESHOLD {
+            entries.retain(|_, f| now.duration_since(f.last) < FORGET_AFTER);
+        }
+
+        for key in Self::keys(ip, name.filter(|_| known)) {
+            if !entries.contains_key(&key) && entries.len() >= MAX_ENTRIES {
+                warn!(target: "audit", "驗證失敗紀錄已達上限 {} 筆，不再追蹤 {}", MAX_ENTRIES, key);
+                continue;
+            }
+            let limit = self.limit(&key);
+            let failures = entries.entry(key.clone()).or_insert(Failures {
+                count: 0,
+                last: now,
+                locked_until: None,
+            });
+            if now.duration_since(failures.last) >= FORGET_AFTER {
+                failures.count = 0;
+            }
+            failures.count += 1;
+            failures.last = now;
+
+            if failures.count >= limit {
+                let exponent = (failures.count - limit).min(16);
+                let lockout = (BASE_LOCKOUT * 2u32.pow(exponent)).min(MAX_LOCKOUT);
+                failures.locked_until = Some(now + lockout);
+                warn!(
+                    target: "audit",
+                    "{} 連續驗證失敗 {} 次，鎖定 {} 秒",
+                    key,
+                    failures.count,
+                    lockout.as_secs()
+                );
+            }
+        }
+    }
+
+    // 驗證成功，清除相關的失敗紀錄
+    pub fn record_success(&self, ip: Option<IpAddr>, name: Option<&str>) {
+        if self.max_failures == 0 {
+            return;
+        }
+
+        let mut entries = self.entries.lock().unwrap();
+        for key in Self::keys(ip, name) {
+            entries.remove(&key);
+        }
+    }
+}
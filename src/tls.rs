@@ -0,0 +1,40 @@
+use std::{io, net::SocketAddr, path::Path};
+
+use axum::{
+    extract::connect_info::IntoMakeServiceWithConnectInfo,
+    Router,
+};
+use axum_server::{tls_rustls::RustlsConfig, Handle};
+use tokio::net::TcpListener;
+
+use crate::{metrics::CountConnections, upgrade::Shutdown};
+
+// 讀取 PEM 格式的憑證鏈與私鑰，ALPN 同時提供 HTTP/2 與 HTTP/1.1
+pub async fn load(cert: &Path, key: &Path) -> io::Result<RustlsConfig> {
+    RustlsConfig::from_pem_file(cert, key).await.map_err(|e| {
+        io::Error::new(
+            e.kind(),
+            format!("無法載入 TLS 憑證 {} 或私鑰 {}: {}", cert.display(), key.display(), e),
+        )
+    })
+}
+
+// 以 HTTPS 在既有的監聽 socket 上提供服務，收到停止通知後等待現有連線結束
+pub async fn serve(
+    listener: TcpListener,
+    config: RustlsConfig,
+    app: CountConnections<IntoMakeServiceWithConnectInfo<Router, SocketAddr>>,
+    shutdown: Shutdown,
+) -> io::Result<()> {
+    let handle = Handle::new();
+    let stopper = handle.clone();
+    tokio::spawn(async move {
+        shutdown.wait().await;
+        stopper.graceful_shutdown(None);
+    });
+
+    axum_server::from_tcp_rustls(listener.into_std()?, config)?
+        .handle(handle)
+        .serve(app)
+        .await
+}
@@ -0,0 +1,176 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+use axum::http::{header, HeaderMap};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::auth::{Permission, User};
+
+// token 的前綴，方便在日誌或程式碼掃描中辨識
+const TOKEN_PREFIX: &str = "sfs_";
+
+// 可指派給 token 的權限範圍
+pub const SCOPES: [&str; 2] = ["read", "upload"];
+
+// 已發行的 API token，文件中只保存 token 的 SHA-256
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ApiToken {
+    pub id: String,
+    hash: String,
+    pub scopes: Vec<String>,
+    // 限制只能存取此路徑前綴底下的內容，空字串代表不限制
+    #[serde(default)]
+    pub path: String,
+    pub created: String,
+}
+
+impl ApiToken {
+    fn permissions(&self) -> Vec<Permission> {
+        let mut permissions = Vec::new();
+        for scope in &self.scopes {
+            match scope.as_str() {
+                "read" => permissions.extend([Permission::Read, Permission::List]),
+                "upload" => permissions.push(Permission::Write),
+                _ => {}
+            }
+        }
+        permissions
+    }
+}
+
+// 已載入的 token 與文件修改時間
+struct Loaded {
+    modified: Option<SystemTime>,
+    tokens: Vec<ApiToken>,
+}
+
+// API token 儲存檔（JSON），文件變動時自動重新載入，讓 CLI 建立或撤銷的 token 立即生效
+pub struct TokenStore {
+    path: PathBuf,
+    loaded: Mutex<Loaded>,
+}
+
+impl TokenStore {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let store = TokenStore {
+            path: path.to_path_buf(),
+            loaded: Mutex::new(Loaded {
+                modified: None,
+                tokens: Vec::new(),
+            }),
+        };
+        store.reload()?;
+        Ok(store)
+    }
+
+    fn file_modified(&self) -> Option<SystemTime> {
+        fs::metadata(&self.path).and_then(|m| m.modified()).ok()
+    }
+
+    fn reload(&self) -> io::Result<()> {
+        let modified = self.file_modified();
+        let tokens = match fs::read_to_string(&self.path) {
+            Ok(content) if !content.trim().is_empty() => {
+                serde_json::from_str(&content).map_err(io::Error::other)?
+            }
+            Ok(_) => Vec::new(),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e),
+        };
+
+        *self.loaded.lock().unwrap() = Loaded { modified, tokens };
+        Ok(())
+    }
+
+    // 取得目前的 token 清單，文件有變動時先重新載入
+    pub fn list(&self) -> io::Result<Vec<ApiToken>> {
+        if self.file_modified() != self.loaded.lock().unwrap().modified {
+            self.reload()?;
+        }
+        Ok(self.loaded.lock().unwrap().tokens.clone())
+    }
+
+    fn save(&self, tokens: Vec<ApiToken>) -> io::Result<()> {
+        let json = serde_json::to_string_pretty(&tokens).map_err(io::Error::other)?;
+        fs::write(&self.path, json)?;
+        *self.loaded.lock().unwrap() = Loaded {
+            modified: self.file_modified(),
+            tokens,
+        };
+        Ok(())
+    }
+
+    // 建立新的 token，回傳只會顯示這一次的 token 內容
+    pub fn create(&self, id: &str, scopes: Vec<String>, path: &str) -> io::Result<String> {
+        let invalid = |reason: String| io::Error::new(io::ErrorKind::InvalidInput, reason);
+
+        if id.is_empty() {
+            return Err(invalid("token 名稱不可為空".to_string()));
+        }
+        if let Some(scope) = scopes.iter().find(|s| !SCOPES.contains(&s.as_str())) {
+            return Err(invalid(format!("未知的權限範圍: {}", scope)));
+        }
+
+        let mut tokens = self.list()?;
+        if tokens.iter().any(|t| t.id == id) {
+            return Err(invalid(format!("token {} 已存在", id)));
+        }
+
+        let mut secret = [0u8; 32];
+        getrandom::fill(&mut secret).map_err(io::Error::other)?;
+        let token: String = secret.iter().map(|b| format!("{:02x}", b)).collect();
+        let token = format!("{}{}", TOKEN_PREFIX, token);
+
+        tokens.push(ApiToken {
+            id: id.to_string(),
+            hash: hash_token(&token),
+            scopes,
+            path: path.trim_matches('/').to_string(),
+            created: Utc::now().to_rfc3339(),
+        });
+        self.save(tokens)?;
+
+        Ok(token)
+    }
+
+    // 撤銷 token，回傳是否有找到
+    pub fn revoke(&self, id: &str) -> io::Result<bool> {
+        let mut tokens = self.list()?;
+        let before = tokens.len();
+        tokens.retain(|t| t.id != id);
+        if tokens.len() == before {
+            return Ok(false);
+        }
+        self.save(tokens)?;
+        Ok(true)
+    }
+
+    // 以 Authorization: Bearer 標頭驗證 token
+    pub fn authenticate(&self, headers: &HeaderMap) -> Option<Arc<User>> {
+        let value = headers.get(header::AUTHORIZATION)?.to_str().ok()?;
+        let token = value.strip_prefix("Bearer ")?;
+        if !token.starts_with(TOKEN_PREFIX) {
+            return None;
+        }
+
+        let hash = hash_token(token);
+        let tokens = self.list().ok()?;
+        let found = tokens.iter().find(|t| t.hash == hash)?;
+
+        Some(Arc::new(User::token(
+            format!("token:{}", found.id),
+            found.permissions(),
+            found.path.clone(),
+        )))
+    }
+}
+
+fn hash_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
@@ -0,0 +1,402 @@
+use std::{
+    io,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    task::{Context, Poll},
+    time::Instant,
+};
+
+use axum::{
+    body::{Body, Bytes, HttpBody},
+    extract::{Request, State},
+    http::header,
+    middleware::Next,
+    response::Response,
+};
+use futures_util::{stream, Stream};
+use http_body::{Frame, SizeHint};
+use serde::Serialize;
+use tokio::{fs::File, io::AsyncReadExt, runtime::Handle, sync::mpsc};
+use tokio_util::io::ReaderStream;
+use tracing::{debug, info, warn};
+
+use crate::{
+    access_log::{AccessEntry, AccessLog},
+    auth::User,
+    deadline::Deadline,
+    webhook::{WebhookEvent, Webhooks},
+    ServerConfig,
+};
+
+// 記憶體內容切分成區塊傳送的大小
+const CHUNK_SIZE: usize = 64 * 1024;
+
+// 將記憶體中的內容切成區塊串流
+pub fn chunked(content: Bytes) -> impl Stream<Item = std::io::Result<Bytes>> + Unpin {
+    let len = content.len();
+    stream::iter(
+        (0..len)
+            .step_by(CHUNK_SIZE)
+            .map(move |start| Ok(content.slice(start..(start + CHUNK_SIZE).min(len)))),
+    )
+}
+
+// 從磁碟逐塊讀取文件的串流，記憶體用量與文件大小無關
+//
+// 只讀取開啟時的 `len` 位元組：傳送期間文件變長時，送出的內容才不會超過 Content-Length。
+pub fn file(file: File, len: u64) -> impl Stream<Item = io::Result<Bytes>> + Unpin {
+    ReaderStream::with_capacity(file.take(len), CHUNK_SIZE)
+}
+
+// HEAD 請求的回應主體：不讀取也不產生內容，長度不明的回應也不會被補上 `Content-Length: 0`
+pub fn omitted() -> Body {
+    Body::from_stream(stream::empty::<io::Result<Bytes>>())
+}
+
+// 產生內容時最多暫存的區塊數，用戶端讀取較慢時產生端會在此等待
+const GENERATED_BUFFERED_CHUNKS: usize = 4;
+
+// 產生內容的寫入端，累積到一個區塊大小後送出
+//
+// 用戶端中斷連線或請求超過期限後 `is_closed` 會變為 true，產生端應盡早停止。
+pub struct ChunkWriter {
+    buf: Vec<u8>,
+    tx: mpsc::Sender<Bytes>,
+    closed: bool,
+    deadline: Deadline,
+    runtime: Handle,
+}
+
+impl ChunkWriter {
+    pub fn push_str(&mut self, s: &str) {
+        self.push_bytes(s.as_bytes());
+    }
+
+    pub fn push_bytes(&mut self, bytes: &[u8]) {
+        if self.is_closed() {
+            return;
+        }
+        self.buf.extend_from_slice(bytes);
+        if self.buf.len() >= CHUNK_SIZE {
+            self.send_buffered();
+        }
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.closed || self.deadline.expired()
+    }
+
+    fn send_buffered(&mut self) {
+        if self.buf.is_empty() || self.closed {
+            return;
+        }
+        let chunk = Bytes::from(std::mem::take(&mut self.buf));
+        let sent = match self.deadline.remaining() {
+            // 用戶端停止讀取時最多等到請求期限
+            Some(remaining) => self
+                .runtime
+                .block_on(tokio::time::timeout(remaining, self.tx.send(chunk)))
+                .is_ok_and(|sent| sent.is_ok()),
+            None => self.tx.blocking_send(chunk).is_ok(),
+        };
+        if !sent {
+            self.closed = true;
+        }
+    }
+}
+
+impl io::Write for ChunkWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.closed {
+            return Err(io::Error::from(io::ErrorKind::BrokenPipe));
+        }
+        self.deadline.check()?;
+        self.push_bytes(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.send_buffered();
+        Ok(())
+    }
+}
+
+// 在阻塞執行緒中產生回應內容，邊產生邊以區塊串流送出
+//
+// 目錄列表、封存檔等產生的內容都透過此函式輸出，不需要在記憶體中組出完整內容。
+// 通道容量有限，用戶端讀取較慢時產生端會暫停（背壓），但不會等過請求期限。
+// 產生端回傳錯誤或超過期限時串流以錯誤結束，用戶端會看到連線中斷而不是不完整但看似成功的回應。
+pub fn generated<F>(deadline: Deadline, produce: F) -> impl Stream<Item = io::Result<Bytes>>
+where
+    F: FnOnce(&mut ChunkWriter) -> io::Result<()> + Send + 'static,
+{
+    let (tx, rx) = mpsc::channel(GENERATED_BUFFERED_CHUNKS);
+    let outcome: Arc<Mutex<Option<io::Result<()>>>> = Arc::default();
+    let producer_outcome = outcome.clone();
+    let runtime = Handle::current();
+    tokio::task::spawn_blocking(move || {
+        let mut writer = ChunkWriter {
+            buf: Vec::with_capacity(CHUNK_SIZE),
+            tx,
+            closed: false,
+            deadline,
+            runtime,
+        };
+        let mut result = produce(&mut writer);
+        if result.is_ok() {
+            writer.send_buffered();
+        }
+        if deadline.expired() {
+            warn!("產生內容超過請求期限，已停止");
+            result = deadline.check();
+        } else if writer.closed {
+            debug!("用戶端已中斷連線，停止產生內容");
+        }
+        // 先記下結果再關閉通道，讀取端看到通道關閉時結果一定已經存在
+        *producer_outcome.lock().unwrap() = Some(result);
+        drop(writer);
+    });
+
+    stream::unfold(Some(rx), move |rx| {
+        let outcome = outcome.clone();
+        async move {
+            let mut rx = rx?;
+            match rx.recv().await {
+                Some(chunk) => Some((Ok(chunk), Some(rx))),
+                None => match outcome.lock().unwrap().take() {
+                    Some(Ok(())) => None,
+                    Some(Err(e)) => Some((Err(e), None)),
+                    None => Some((Err(io::Error::other("產生內容時發生錯誤")), None)),
+                },
+            }
+        }
+    })
+}
+
+// 追蹤回應主體傳送進度的串流包裝，完整送出後呼叫回呼函式
+//
+// 已知長度的回應在送滿 `len` 位元組後，hyper 不一定會再輪詢到串流結束，
+// 因此送出的位元組數達到長度時也視為完成。
+pub struct TrackedStream<S> {
+    inner: S,
+    len: u64,
+    sent: u64,
+    on_complete: Option<Box<dyn FnOnce(u64) + Send>>,
+}
+
+impl<S> TrackedStream<S> {
+    pub fn new(inner: S, len: u64, on_complete: impl FnOnce(u64) + Send + 'static) -> Self {
+        TrackedStream {
+            inner,
+            len,
+            sent: 0,
+            on_complete: Some(Box::new(on_complete)),
+        }
+    }
+
+    fn complete(&mut self) {
+        if let Some(on_complete) = self.on_complete.take() {
+            on_complete(self.sent);
+        }
+    }
+}
+
+impl<S, E> Stream for TrackedStream<S>
+where
+    S: Stream<Item = Result<Bytes, E>> + Unpin,
+{
+    type Item = Result<Bytes, E>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let poll = Pin::new(&mut self.inner).poll_next(cx);
+        match &poll {
+            Poll::Ready(Some(Ok(chunk))) => {
+                self.sent += chunk.len() as u64;
+                if self.sent >= self.len {
+                    self.complete();
+                }
+            }
+            Poll::Ready(None) => self.complete(),
+            _ => {}
+        }
+        poll
+    }
+}
+
+// 全部回應的傳輸統計
+#[derive(Default, Serialize)]
+pub struct TransferStats {
+    pub completed: AtomicU64,
+    pub aborted: AtomicU64,
+    pub bytes_sent: AtomicU64,
+}
+
+impl TransferStats {
+    fn record(&self, bytes: u64, completed: bool) {
+        let counter = if completed { &self.completed } else { &self.aborted };
+        counter.fetch_add(1, Ordering::Relaxed);
+        self.bytes_sent.fetch_add(bytes, Ordering::Relaxed);
+    }
+}
+
+// 單一回應的傳輸結果
+pub struct TransferRecord {
+    pub method: String,
+    pub path: String,
+    pub status: u16,
+    pub bytes: u64,
+    pub duration_ms: u128,
+    pub completed: bool,
+}
+
+// 計算實際送出位元組數的回應主體
+//
+// 主體送完（或送滿已知長度）時記錄為完成；用戶端中途斷線時 hyper 會直接丟棄主體，
+// 此時在 Drop 中記錄為中斷。
+struct MeteredBody {
+    inner: Body,
+    sent: u64,
+    // Content-Length 標頭的值，hyper 送滿此長度後不一定會再輪詢主體
+    expected: Option<u64>,
+    started: Instant,
+    record: Option<(TransferRecord, Arc<TransferStats>)>,
+    // 設定時在用戶端中斷連線後發送通知
+    abort_hook: Option<Arc<Webhooks>>,
+    // 設定時在傳送結束後寫入存取紀錄
+    access: Option<(AccessEntry, Arc<AccessLog>)>,
+}
+
+impl MeteredBody {
+    fn fully_sent(&self) -> bool {
+        self.inner.is_end_stream() || self.expected.is_some_and(|len| self.sent >= len)
+    }
+
+    fn finish(&mut self, completed: bool) {
+        if let Some((mut record, stats)) = self.record.take() {
+            record.bytes = self.sent;
+            record.duration_ms = self.started.elapsed().as_millis();
+            record.completed = completed;
+            stats.record(record.bytes, completed);
+            log_transfer(&record);
+            if let Some((entry, log)) = self.access.take() {
+                entry.finish(&log, record.status, record.bytes);
+            }
+
+            if !completed {
+                if let Some(webhooks) = &self.abort_hook {
+                    webhooks.notify(WebhookEvent::TransferAborted {
+                        path: record.path.trim_start_matches('/').to_string(),
+                        bytes: record.bytes,
+                    });
+                }
+            }
+        }
+    }
+}
+
+impl HttpBody for MeteredBody {
+    type Data = Bytes;
+    type Error = axum::Error;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, axum::Error>>> {
+        let poll = Pin::new(&mut self.inner).poll_frame(cx);
+        match &poll {
+            Poll::Ready(Some(Ok(frame))) => {
+                if let Some(data) = frame.data_ref() {
+                    self.sent += data.len() as u64;
+                }
+                if self.fully_sent() {
+                    self.finish(true);
+                }
+            }
+            Poll::Ready(Some(Err(_))) => self.finish(false),
+            Poll::Ready(None) => self.finish(true),
+            Poll::Pending => {}
+        }
+        poll
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+impl Drop for MeteredBody {
+    fn drop(&mut self) {
+        // 沒有內容的回應（HEAD、304）不會被輪詢，不視為中斷
+        let head = matches!(&self.record, Some((record, _)) if record.method == "HEAD");
+        let completed = head || self.fully_sent();
+        self.finish(completed);
+    }
+}
+
+fn log_transfer(record: &TransferRecord) {
+    let path = record.path.trim_start_matches('/');
+    if record.completed {
+        info!(
+            target: "transfer",
+            "{} /{} {} {} 位元組 {} ms 完成",
+            record.method, path, record.status, record.bytes, record.duration_ms
+        );
+    } else {
+        warn!(
+            target: "transfer",
+            "{} /{} {} {} 位元組 {} ms 用戶端中斷",
+            record.method, path, record.status, record.bytes, record.duration_ms
+        );
+    }
+}
+
+// 記錄每個回應實際送出的位元組數與傳輸時間
+pub async fn track_transfer(
+    State(config): State<ServerConfig>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let started = Instant::now();
+    let method = request.method().to_string();
+    let path = request.uri().path().to_string();
+    let mut access = config.access_log.clone().map(|log| (AccessEntry::new(&request), log));
+
+    let response = next.run(request).await;
+    let status = response.status().as_u16();
+    if let (Some((entry, _)), Some(user)) = (&mut access, response.extensions().get::<Arc<User>>()) {
+        entry.set_user(&user.name);
+    }
+    let expected = response
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse().ok());
+    response.map(|body| {
+        Body::new(MeteredBody {
+            inner: body,
+            sent: 0,
+            expected,
+            started,
+            record: Some((
+                TransferRecord {
+                    method,
+                    path,
+                    status,
+                    bytes: 0,
+                    duration_ms: 0,
+                    completed: false,
+                },
+                config.transfer_stats.clone(),
+            )),
+            abort_hook: config.webhooks.clone().filter(|w| w.notify_aborts),
+            access,
+        })
+    })
+}
@@ -0,0 +1,36 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+// 遞迴收集目錄下的所有文件，不跟隨符號連結以避免循環
+//
+// `allow` 以相對路徑判斷是否納入，被排除的目錄不會再往下走訪。
+pub fn walk_files(
+    dir: &Path,
+    prefix: &str,
+    allow: &dyn Fn(&str) -> bool,
+    out: &mut Vec<(String, PathBuf, fs::Metadata)>,
+) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        let rel = if prefix.is_empty() {
+            name
+        } else {
+            format!("{}/{}", prefix, name)
+        };
+
+        if !allow(&rel) {
+            continue;
+        }
+
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            walk_files(&entry.path(), &rel, allow, out)?;
+        } else if file_type.is_file() {
+            out.push((rel, entry.path(), entry.metadata()?));
+        }
+    }
+    Ok(())
+}
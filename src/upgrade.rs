@@ -0,0 +1,136 @@
+use std::io;
+
+use tokio::{net::TcpListener, sync::watch};
+
+// 新程序從此環境變數得知繼承的監聽 socket，依序為公開位址、管理位址與 ACME 驗證用的 HTTP 位址
+const LISTEN_FDS_ENV: &str = "SFS_LISTEN_FDS";
+
+// 新程序啟動後觀察的時間，期間內結束代表啟動失敗，舊程序繼續服務
+#[cfg(unix)]
+const STARTUP_GRACE: std::time::Duration = std::time::Duration::from_secs(2);
+
+// 升級完成的通知，收到後舊程序停止接受新連線並等待現有連線結束
+#[derive(Clone)]
+pub struct Shutdown(watch::Receiver<bool>);
+
+impl Shutdown {
+    pub async fn wait(mut self) {
+        let _ = self.0.wait_for(|done| *done).await;
+    }
+}
+
+// 取得監聽 socket：由舊程序傳入時直接沿用，否則綁定新的位址
+//
+// `index` 是此 socket 在 SFS_LISTEN_FDS 中的位置。
+pub async fn listen(addr: &str, index: usize) -> io::Result<TcpListener> {
+    #[cfg(unix)]
+    if let Some(listener) = inherited(index)? {
+        tracing::info!("沿用舊程序的監聽 socket: {}", listener.local_addr()?);
+        return Ok(listener);
+    }
+    #[cfg(not(unix))]
+    let _ = index;
+
+    TcpListener::bind(addr).await
+}
+
+#[cfg(unix)]
+fn inherited(index: usize) -> io::Result<Option<TcpListener>> {
+    use std::os::fd::{FromRawFd, RawFd};
+
+    let Ok(fds) = std::env::var(LISTEN_FDS_ENV) else {
+        return Ok(None);
+    };
+    let Some(fd) = fds.split(',').nth(index).and_then(|fd| fd.parse::<RawFd>().ok()) else {
+        return Ok(None);
+    };
+
+    let listener = unsafe {
+        // 恢復 FD_CLOEXEC，避免之後啟動的其他子程序也繼承此 socket
+        let flags = libc::fcntl(fd, libc::F_GETFD);
+        if flags < 0 || libc::fcntl(fd, libc::F_SETFD, flags | libc::FD_CLOEXEC) < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        std::net::TcpListener::from_raw_fd(fd)
+    };
+    listener.set_nonblocking(true)?;
+    TcpListener::from_std(listener).map(Some)
+}
+
+// 收到 SIGUSR2 時以相同參數啟動新的執行檔並交出監聽 socket
+//
+// 新程序啟動後舊程序停止接受連線，處理完進行中的請求後結束，兩者共用同一個 socket，
+// 升級期間不會拒絕任何連線。啟動失敗時舊程序繼續服務。
+//
+// `listeners` 的位置與 `listen` 的 `index` 對應，未使用的位址傳入 None。
+#[cfg(unix)]
+pub fn on_upgrade_signal(listeners: Vec<Option<&TcpListener>>) -> io::Result<Shutdown> {
+    use std::os::fd::AsRawFd;
+    use tokio::signal::unix::{signal, SignalKind};
+    use tracing::{error, info};
+
+    let fds: Vec<Option<i32>> = listeners.iter().map(|l| l.map(|l| l.as_raw_fd())).collect();
+    let mut signals = signal(SignalKind::user_defined2())?;
+    let (tx, rx) = watch::channel(false);
+
+    tokio::spawn(async move {
+        while signals.recv().await.is_some() {
+            info!("收到 SIGUSR2，啟動新的執行檔");
+            let mut child = match spawn_successor(&fds) {
+                Ok(child) => child,
+                Err(e) => {
+                    error!("無法啟動新的執行檔: {}", e);
+                    continue;
+                }
+            };
+
+            tokio::time::sleep(STARTUP_GRACE).await;
+            match child.try_wait() {
+                Ok(None) => {
+                    info!("新程序 {} 已啟動，停止接受新連線", child.id());
+                    let _ = tx.send(true);
+                    break;
+                }
+                Ok(Some(status)) => error!("新程序啟動失敗 ({})，繼續以目前的程序服務", status),
+                Err(e) => error!("無法確認新程序狀態: {}", e),
+            }
+        }
+    });
+
+    Ok(Shutdown(rx))
+}
+
+#[cfg(not(unix))]
+pub fn on_upgrade_signal(_listeners: Vec<Option<&TcpListener>>) -> io::Result<Shutdown> {
+    // 保留傳送端，讓等待永遠不會結束
+    let (tx, rx) = watch::channel(false);
+    std::mem::forget(tx);
+    Ok(Shutdown(rx))
+}
+
+#[cfg(unix)]
+fn spawn_successor(fds: &[Option<i32>]) -> io::Result<std::process::Child> {
+    // 監聽 socket 預設帶有 FD_CLOEXEC，必須清除才能被新程序繼承
+    for &fd in fds.iter().flatten() {
+        unsafe {
+            let flags = libc::fcntl(fd, libc::F_GETFD);
+            if flags < 0 || libc::fcntl(fd, libc::F_SETFD, flags & !libc::FD_CLOEXEC) < 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+    }
+
+    // 以原本的啟動路徑執行，而不是 current_exe()：執行檔被替換後後者會指向已刪除的舊檔
+    let mut args = std::env::args_os();
+    let program = args.next().ok_or_else(|| io::Error::other("無法取得執行檔路徑"))?;
+    // 未使用的位置留空，其他 socket 的位置才不會錯開
+    let fds = fds
+        .iter()
+        .map(|fd| fd.map(|fd| fd.to_string()).unwrap_or_default())
+        .collect::<Vec<_>>()
+        .join(",");
+    std::process::Command::new(program)
+        .args(args)
+        .env(LISTEN_FDS_ENV, fds)
+        .spawn()
+}
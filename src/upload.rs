@@ -0,0 +1,735 @@
+use std::{
+    io,
+    path::{Path as FsPath, PathBuf},
+    sync::Arc,
+};
+
+use axum::{
+    body::{Body, BodyDataStream, Bytes},
+    extract::{Extension, Path, Request, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Redirect, Response},
+    Json,
+};
+use futures_util::StreamExt;
+use serde_json::json;
+use tokio::io::AsyncWriteExt;
+use tracing::{info, warn};
+
+use crate::{
+    auth::{Permission, User},
+    check_restricted,
+    blocking,
+    clamd::{Clamd, Verdict},
+    holding::{self, Holding, Item},
+    hook::{self, UploadHook, Uploaded},
+    is_dir, safe_path,
+    resume::{ContentRange, Progress},
+    ServerConfig, ServerError,
+};
+
+// 上傳中的暫存文件名稱開頭，這些文件一律禁止訪問
+pub const TEMP_PREFIX: &str = ".sfs-upload-";
+
+// 上傳設定（--upload）
+#[derive(Clone)]
+pub struct UploadOptions {
+    // 單一文件的大小上限；multipart 請求的整個內容也不能超過此大小
+    pub max_size: u64,
+    // 允許上傳的文件類型（--upload-types），未設定時不限制
+    pub types: Option<Arc<UploadTypes>>,
+    pub policy: UploadPolicy,
+    // 上傳完成後執行的命令（--on-upload）
+    pub on_upload: Option<Arc<UploadHook>>,
+    // 進入目錄前掃描上傳的文件（--clamd）
+    pub clamd: Option<Arc<Clamd>>,
+    // 等待管理員核准的上傳（--moderate-uploads）
+    pub quarantine: Option<Arc<Holding>>,
+}
+
+// 已存在的文件如何處理（--upload-policy）
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum UploadPolicy {
+    // 上傳取代同名文件，WebDAV 可以刪除與移動
+    Overwrite,
+    // 已存在的文件不能被取代或刪除；同名的上傳改存成 `name-1.ext`、`name-2.ext`……
+    AppendOnly,
+}
+
+impl UploadPolicy {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "overwrite" => Some(UploadPolicy::Overwrite),
+            "append-only" => Some(UploadPolicy::AppendOnly),
+            _ => None,
+        }
+    }
+}
+
+// 是否僅能新增文件（--upload-policy append-only）
+pub fn append_only(config: &ServerConfig) -> bool {
+    config.upload.as_ref().is_some_and(|options| options.policy == UploadPolicy::AppendOnly)
+}
+
+// 僅能新增文件時拒絕會取代或移除既有文件的操作
+pub fn check_append_only(action: &str, rel: &str, config: &ServerConfig) -> Result<(), ServerError> {
+    match append_only(config) {
+        true => Err(ServerError::Forbidden(format!("僅能新增文件，不能{} /{}", action, rel))),
+        false => Ok(()),
+    }
+}
+
+// 允許上傳的副檔名與 MIME 類型
+//
+// 項目含 `/` 時為 MIME 類型（`image/*`、`application/pdf`），依文件名稱推測的類型比對，
+// 不採信用戶端宣告的 Content-Type；其他項目為副檔名（`.zip` 或 `zip`），不分大小寫。
+pub struct UploadTypes {
+    extensions: Vec<String>,
+    mime_types: Vec<String>,
+}
+
+impl UploadTypes {
+    pub fn parse(list: &str) -> Result<Self, String> {
+        let mut types = UploadTypes {
+            extensions: Vec::new(),
+            mime_types: Vec::new(),
+        };
+        for entry in list.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let entry = entry.to_ascii_lowercase();
+            match entry.split_once('/') {
+                Some((kind, subtype)) => {
+                    if kind.is_empty() || subtype.is_empty() || subtype.contains('/') {
+                        return Err(format!("無效的 MIME 類型: {}", entry));
+                    }
+                    types.mime_types.push(entry);
+                }
+                None => types.extensions.push(entry.trim_start_matches('.').to_string()),
+            }
+        }
+        if types.extensions.is_empty() && types.mime_types.is_empty() {
+            return Err("--upload-types 至少需要一個副檔名或 MIME 類型".to_string());
+        }
+        Ok(types)
+    }
+
+    pub fn allows(&self, name: &str) -> bool {
+        let extension = name.rsplit_once('.').map(|(_, ext)| ext.to_ascii_lowercase());
+        if extension.as_ref().is_some_and(|ext| self.extensions.contains(ext)) {
+            return true;
+        }
+        mime_guess::from_path(name).iter().any(|mime| {
+            let mime = mime.essence_str().to_ascii_lowercase();
+            self.mime_types.iter().any(|allowed| match allowed.strip_suffix("/*") {
+                Some(kind) => mime.split_once('/').is_some_and(|(k, _)| k == kind),
+                None => *allowed == mime,
+            })
+        })
+    }
+}
+
+// 檢查上傳的文件名稱是否為允許的類型（--upload-types）
+pub fn check_type(name: &str, config: &ServerConfig) -> Result<(), ServerError> {
+    match config.upload.as_ref().and_then(|options| options.types.as_ref()) {
+        Some(types) if !types.allows(name) => Err(ServerError::UnsupportedMediaType(name.to_string())),
+        _ => Ok(()),
+    }
+}
+
+// 用戶端提供的文件名稱（multipart 的 filename）整理成可用的名稱
+//
+// 舊版瀏覽器會送出完整路徑，只取最後的名稱；去掉控制字元與開頭的 `.`，
+// 避免上傳成隱藏文件。整理後為空時回傳 None。
+fn sanitize_filename(filename: &str) -> Option<String> {
+    let name = filename.rsplit(['/', '\\']).next().unwrap_or("");
+    let name: String = name.chars().filter(|c| !c.is_control()).collect();
+    let name = name.trim().trim_start_matches('.');
+    (!name.is_empty()).then(|| name.to_string())
+}
+
+// 寫入中的暫存文件，與目標放在同一個目錄，完成後以 rename 取代目標
+//
+// 寫入失敗、超過大小上限或用戶端中斷時暫存文件隨著被捨棄而刪除，目標不會只寫了一半。
+struct TempFile {
+    path: PathBuf,
+    file: Option<tokio::fs::File>,
+    written: u64,
+}
+
+impl TempFile {
+    async fn create(dir: &FsPath) -> io::Result<Self> {
+        let mut random = [0u8; 8];
+        getrandom::fill(&mut random).map_err(io::Error::other)?;
+        let name: String = random.iter().map(|b| format!("{:02x}", b)).collect();
+        let path = dir.join(format!("{}{}", TEMP_PREFIX, name));
+        let file = tokio::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+            .await?;
+        Ok(TempFile {
+            path,
+            file: Some(file),
+            written: 0,
+        })
+    }
+
+    // `used` 是同一個請求中先前的文件已用掉的大小
+    async fn write(&mut self, data: &[u8], used: u64, limit: u64) -> Result<(), ServerError> {
+        self.written += data.len() as u64;
+        if used + self.written > limit {
+            return Err(ServerError::PayloadTooLarge(limit));
+        }
+        let file = self.file.as_mut().expect("暫存文件已完成");
+        file.write_all(data).await.map_err(ServerError::Filesystem)
+    }
+
+    // 寫入磁碟並關閉，之後可以掃描內容
+    async fn sync(&mut self) -> io::Result<()> {
+        if let Some(mut file) = self.file.take() {
+            file.flush().await?;
+            file.sync_all().await?;
+        }
+        Ok(())
+    }
+
+    // 寫入磁碟後改名為目標文件
+    async fn commit(mut self, target: &FsPath) -> io::Result<u64> {
+        self.sync().await?;
+        tokio::fs::rename(&self.path, target).await?;
+        // 已改名，不需要刪除
+        self.path = PathBuf::new();
+        Ok(self.written)
+    }
+
+    // 移入審核區，核准後才放到目標位置
+    async fn hold(mut self, quarantine: &Arc<Holding>, target: &Target, user: Option<&User>) -> Result<Item, ServerError> {
+        self.sync().await?;
+        let path = holding::tree_rel(user, &target.rel);
+        let (holding, temp, user) = (quarantine.clone(), self.path.clone(), user.map(|user| user.name.clone()));
+        let item = blocking(move || holding.keep(&temp, &path, user.as_deref())).await??;
+        self.path = PathBuf::new();
+        info!("已收到待審核的上傳 /{} ({} 位元組，編號 {})", item.path, item.size, item.id);
+        Ok(item)
+    }
+
+    // 寫入磁碟後以目標名稱或第一個未被使用的 `name-N.ext` 建立文件，不會取代既有文件
+    //
+    // 以 hard link 建立新名稱，名稱已存在時失敗而不是取代，同時上傳同名文件也不會互相覆蓋。
+    async fn commit_new(mut self, target: &mut Target) -> Result<u64, ServerError> {
+        self.sync().await?;
+
+        let (dir, name) = target.rel.rsplit_once('/').unwrap_or(("", &target.rel));
+        let (dir, name) = (dir.to_string(), name.to_string());
+        for n in 0..MAX_SUFFIX {
+            let candidate = numbered(&name, n);
+            let path = target.dir.join(&candidate);
+            match tokio::fs::hard_link(&self.path, &path).await {
+                Ok(()) => {
+                    target.rel = match dir.is_empty() {
+                        true => candidate,
+                        false => format!("{}/{}", dir, candidate),
+                    };
+                    target.file = path;
+                    // 暫存名稱由 Drop 刪除
+                    return Ok(self.written);
+                }
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(ServerError::Filesystem(e)),
+            }
+        }
+        Err(ServerError::Conflict(format!("/{} 的同名文件過多", target.rel)))
+    }
+}
+
+// 僅能新增文件時，同名文件最多嘗試的編號
+const MAX_SUFFIX: u32 = 10_000;
+
+// 第 `n` 個候選名稱：`report.pdf`、`report-1.pdf`、`report-2.pdf`……；開頭的 `.` 不算副檔名
+fn numbered(name: &str, n: u32) -> String {
+    if n == 0 {
+        return name.to_string();
+    }
+    match name.rfind('.').filter(|&dot| dot > 0) {
+        Some(dot) => format!("{}-{}{}", &name[..dot], n, &name[dot..]),
+        None => format!("{}-{}", name, n),
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        if !self.path.as_os_str().is_empty() {
+            // 在非同步執行緒上不做阻塞的文件操作，執行環境已關閉時才直接刪除
+            let path = std::mem::take(&mut self.path);
+            match tokio::runtime::Handle::try_current() {
+                Ok(runtime) => {
+                    runtime.spawn_blocking(move || std::fs::remove_file(path));
+                }
+                Err(_) => {
+                    let _ = std::fs::remove_file(path);
+                }
+            }
+        }
+    }
+}
+
+// 寫入目標：相對路徑、所在目錄與目標的實際路徑
+pub struct Target {
+    pub rel: String,
+    pub dir: PathBuf,
+    pub file: PathBuf,
+}
+
+// 驗證寫入目標：名稱不含路徑分隔字元，所在目錄存在且位於基礎目錄內
+//
+// 目標本身可以不存在；是否允許目標為目錄由呼叫端決定。
+pub async fn resolve_target(path: &str, config: &ServerConfig) -> Result<Target, ServerError> {
+    let path = path.trim_matches('/');
+    let (dir, name) = path.rsplit_once('/').unwrap_or(("", path));
+    if name.is_empty() || name == "." || name == ".." || name.contains('\\') {
+        return Err(ServerError::BadRequest(format!("無效的文件名稱: {}", name)));
+    }
+    // 換行等控制字元會讓名稱在列表、日誌與標頭中被誤讀
+    if path.chars().any(char::is_control) {
+        return Err(ServerError::BadRequest(format!("名稱含有控制字元: {}", path.escape_debug())));
+    }
+
+    // 上層目錄以 safe_path 正規化（並對應實際大小寫），權限檢查使用的相對路徑也由此組成，
+    // 不能直接使用網址中的 `a/../b`
+    let parent = safe_path(dir, config).await?;
+    if !is_dir(&parent.fs_path).await {
+        return Err(ServerError::NotFound(dir.to_string()));
+    }
+    let rel = match parent.rel.is_empty() {
+        true => name.to_string(),
+        false => format!("{}/{}", parent.rel, name),
+    };
+    check_restricted(&rel, config)?;
+    Ok(Target {
+        rel,
+        file: parent.fs_path.join(name),
+        dir: parent.fs_path,
+    })
+}
+
+// 上傳的目標必須是允許類型的文件
+async fn file_target(path: &str, config: &ServerConfig) -> Result<Target, ServerError> {
+    let target = resolve_target(path, config).await?;
+    check_type(&target.rel, config)?;
+    if tokio::fs::symlink_metadata(&target.file).await.is_ok_and(|m| m.is_dir()) {
+        return Err(ServerError::Forbidden(format!("/{} 是目錄", target.rel)));
+    }
+    Ok(target)
+}
+
+// 登入的用戶需要目標路徑的指定權限
+pub fn check_permission(
+    user: Option<&User>,
+    rel: &str,
+    permission: Permission,
+    config: &ServerConfig,
+) -> Result<(), ServerError> {
+    let Some(user) = user else {
+        return Ok(());
+    };
+    let tree_path = format!("{}/{}", user.root, rel);
+    if user.can_access(config.access_rules.as_deref(), &tree_path, permission) {
+        return Ok(());
+    }
+    info!("用戶 {} 缺少 {:?} 權限: /{}", user.name, permission, rel);
+    Err(ServerError::UnsafePath(rel.to_string()))
+}
+
+// 宣告的內容長度超過上限時直接拒絕，不必等到讀完
+fn check_length(headers: &HeaderMap, limit: u64) -> Result<(), ServerError> {
+    let length = headers
+        .get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok());
+    match length {
+        Some(length) if length > limit => Err(ServerError::PayloadTooLarge(limit)),
+        _ => Ok(()),
+    }
+}
+
+// 單一請求可寫入的上限：大小上限與剩餘容量（--quota）中較小者
+async fn write_limit(options: &UploadOptions, config: &ServerConfig) -> Result<u64, ServerError> {
+    match &config.quota {
+        Some(quota) => Ok(options.max_size.min(quota.remaining().await?)),
+        None => Ok(options.max_size),
+    }
+}
+
+// 不帶請求內容的寫入（WebDAV 的 COPY 與 MOVE）與上傳使用同樣的容量檢查：
+// 已用完時回應 507，要寫入的 `bytes` 超過剩餘容量時也是
+pub async fn check_quota(bytes: u64, config: &ServerConfig) -> Result<(), ServerError> {
+    match &config.quota {
+        Some(quota) if bytes > quota.remaining().await? => Err(ServerError::InsufficientStorage),
+        _ => Ok(()),
+    }
+}
+
+// 寫入完成後讓快取與名稱索引重新讀取，並忘記先前查詢時記住的「不存在」
+pub fn invalidate(target: &Target, config: &ServerConfig) {
+    config.file_cache.flush(Some(&target.file));
+    config.name_index.flush(Some(&target.dir));
+    if let Some(not_found) = &config.not_found {
+        // 與 safe_path 記錄時的寫法相同：設定的基礎路徑加上相對路徑
+        not_found.forget(&FsPath::new(config.base_path.as_str()).join(&target.rel));
+    }
+}
+
+// 有設定 --clamd 時，在暫存文件改名進入目錄前掃描；含有病毒時回應 422 並記錄到稽核日誌
+async fn scan(options: &UploadOptions, temp: &FsPath, target: &Target, user: Option<&User>) -> Result<(), ServerError> {
+    let Some(clamd) = &options.clamd else {
+        return Ok(());
+    };
+    match clamd.scan(temp).await? {
+        Verdict::Clean => Ok(()),
+        Verdict::Infected(name) => {
+            let user = user.map_or("-", |user| user.name.as_str());
+            warn!(target: "audit", "拒絕含有病毒的上傳 /{}: {} 用戶 {}", target.rel, name, user);
+            Err(ServerError::Infected(name))
+        }
+    }
+}
+
+// 有設定 --on-upload 時，在背景處理剛上傳（或剛核准）的文件
+pub fn run_hook(options: &UploadOptions, target: &Target, bytes: u64, config: &ServerConfig) {
+    if let Some(hook) = &options.on_upload {
+        hook.spawn(Uploaded {
+            file: hook::absolute(&target.file),
+            url: format!("/{}", config.url_path(&target.rel)),
+            bytes,
+        });
+    }
+}
+
+fn read_error(e: axum::Error) -> ServerError {
+    ServerError::BadRequest(format!("讀取上傳內容失敗: {}", e))
+}
+
+// `PUT /<路徑>`：以請求內容建立或取代文件
+pub async fn put(
+    State(config): State<ServerConfig>,
+    user: Option<Extension<Arc<User>>>,
+    path: Option<Path<String>>,
+    request: Request,
+) -> Result<Response, ServerError> {
+    let options = config.upload.clone().ok_or_else(|| ServerError::MethodNotAllowed("GET, HEAD".to_string()))?;
+    let path = path.map(|Path(p)| p).unwrap_or_default();
+    let user = user.map(|Extension(user)| user);
+    let config = match &user {
+        Some(user) => config.scoped_to(user),
+        None => config,
+    };
+    let mut target = file_target(&path, &config).await?;
+    check_permission(user.as_deref(), &target.rel, Permission::Write, &config)?;
+    let limit = write_limit(&options, &config).await?;
+    check_length(request.headers(), limit)?;
+    let append_only = options.policy == UploadPolicy::AppendOnly;
+
+    let existed = target.file.exists();
+    let created = |target: &Target, bytes: u64| {
+        invalidate(target, &config);
+        if let Some(quota) = &config.quota {
+            quota.record(bytes);
+        }
+        info!("已上傳 /{} ({} 位元組)", target.rel, bytes);
+        run_hook(&options, target, bytes, &config);
+        match existed && !append_only {
+            true => StatusCode::NO_CONTENT.into_response(),
+            false => (StatusCode::CREATED, [(header::LOCATION, format!("/{}", config.url_path(&target.rel)))]).into_response(),
+        }
+    };
+
+    // 有 Content-Range 時是分段上傳的一段，或是以 `bytes */<總長度>` 查詢進度
+    if let Some(value) = request.headers().get(header::CONTENT_RANGE) {
+        let journal = config
+            .upload_journal
+            .clone()
+            .ok_or_else(|| ServerError::BadRequest("未啟用 --upload-journal，無法分段上傳".to_string()))?;
+        let range = value
+            .to_str()
+            .ok()
+            .and_then(ContentRange::parse)
+            .ok_or_else(|| ServerError::BadRequest("無效的 Content-Range".to_string()))?;
+        // 分段上傳收齊後直接放到目標位置，不經過審核區
+        if options.quarantine.is_some() {
+            return Err(ServerError::BadRequest("上傳需要審核時無法分段上傳".to_string()));
+        }
+        // 分段上傳以目標路徑記錄進度，無法中途改名，僅能新增文件時必須換一個名稱
+        if append_only && existed {
+            return Err(ServerError::Conflict(format!("/{} 已存在，分段上傳請使用新的名稱", target.rel)));
+        }
+        if range.total > limit {
+            return Err(ServerError::PayloadTooLarge(limit));
+        }
+        let check = async |temp: &FsPath| scan(&options, temp, &target, user.as_deref()).await;
+        return match journal.receive(&target.dir, &target.file, range, request.into_body(), check).await? {
+            // 與常見的續傳協定相同，以 308 與 Range 告知已收到的範圍
+            Progress::Incomplete(0) => Ok(StatusCode::PERMANENT_REDIRECT.into_response()),
+            Progress::Incomplete(offset) => Ok((
+                StatusCode::PERMANENT_REDIRECT,
+                [(header::RANGE, format!("bytes=0-{}", offset - 1))],
+            )
+                .into_response()),
+            Progress::Complete(bytes) => Ok(created(&target, bytes)),
+        };
+    }
+
+    let mut temp = TempFile::create(&target.dir).await?;
+    let mut body = request.into_body().into_data_stream();
+    while let Some(chunk) = body.next().await {
+        temp.write(&chunk.map_err(read_error)?, 0, limit).await?;
+    }
+    temp.sync().await?;
+    scan(&options, &temp.path, &target, user.as_deref()).await?;
+    if let Some(quarantine) = &options.quarantine {
+        let item = temp.hold(quarantine, &target, user.as_deref()).await?;
+        if let Some(quota) = &config.quota {
+            quota.record(item.size);
+        }
+        return Ok((StatusCode::ACCEPTED, Json(json!({ "pending": item }))).into_response());
+    }
+    let bytes = match append_only {
+        true => temp.commit_new(&mut target).await?,
+        false => temp.commit(&target.file).await?,
+    };
+    Ok(created(&target, bytes))
+}
+
+// `POST /<目錄>/`：以 multipart/form-data 上傳一或多個文件到目錄
+//
+// 瀏覽器表單送出後轉址回目錄列表，其他用戶端取得上傳結果的 JSON。
+pub async fn post(
+    State(config): State<ServerConfig>,
+    user: Option<Extension<Arc<User>>>,
+    path: Option<Path<String>>,
+    request: Request,
+) -> Result<Response, ServerError> {
+    let options = config.upload.clone().ok_or_else(|| ServerError::MethodNotAllowed("GET, HEAD".to_string()))?;
+    let dir = path.map(|Path(p)| p).unwrap_or_default();
+    let user = user.map(|Extension(user)| user);
+    let config = match &user {
+        Some(user) => config.scoped_to(user),
+        None => config,
+    };
+    let headers = request.headers().clone();
+    let limit = write_limit(&options, &config).await?;
+    check_length(&headers, limit)?;
+    let boundary = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(boundary)
+        .ok_or_else(|| ServerError::BadRequest("需要 multipart/form-data 內容".to_string()))?;
+
+    let uri_path = request.uri().path().to_string();
+    let mut form = Multipart::new(request.into_body(), &boundary);
+    let mut uploaded = Vec::new();
+    let mut pending = Vec::new();
+    let mut total = 0;
+    while let Some(filename) = form.next_part().await? {
+        // 不是文件的欄位（例如 CSRF token）直接略過
+        let Some(filename) = filename else {
+            continue;
+        };
+        let Some(name) = sanitize_filename(&filename) else {
+            continue;
+        };
+        let mut target = file_target(&format!("{}/{}", dir, name), &config).await?;
+        check_permission(user.as_deref(), &target.rel, Permission::Write, &config)?;
+
+        let mut temp = TempFile::create(&target.dir).await?;
+        while let Some(chunk) = form.chunk().await? {
+            temp.write(&chunk, total, limit).await?;
+        }
+        temp.sync().await?;
+        scan(&options, &temp.path, &target, user.as_deref()).await?;
+        if let Some(quarantine) = &options.quarantine {
+            let item = temp.hold(quarantine, &target, user.as_deref()).await?;
+            total += item.size;
+            if let Some(quota) = &config.quota {
+                quota.record(item.size);
+            }
+            pending.push(item);
+            continue;
+        }
+        let bytes = match options.policy {
+            UploadPolicy::AppendOnly => temp.commit_new(&mut target).await?,
+            UploadPolicy::Overwrite => temp.commit(&target.file).await?,
+        };
+        total += bytes;
+        invalidate(&target, &config);
+        if let Some(quota) = &config.quota {
+            quota.record(bytes);
+        }
+        info!("已上傳 /{} ({} 位元組)", target.rel, bytes);
+        run_hook(&options, &target, bytes, &config);
+        uploaded.push(json!({ "path": format!("/{}", config.url_path(&target.rel)), "size": bytes }));
+    }
+    if uploaded.is_empty() && pending.is_empty() {
+        return Err(ServerError::BadRequest("請求中沒有文件".to_string()));
+    }
+
+    if crate::session::wants_html(&headers) {
+        return Ok(Redirect::to(&uri_path).into_response());
+    }
+    // 全部等待審核時回應 202，`pending` 列出審核區的編號
+    let status = match uploaded.is_empty() {
+        true => StatusCode::ACCEPTED,
+        false => StatusCode::CREATED,
+    };
+    Ok((status, Json(json!({ "files": uploaded, "pending": pending }))).into_response())
+}
+
+// 從 Content-Type 取得 multipart 的分界字串
+fn boundary(content_type: &str) -> Option<String> {
+    let mut params = content_type.split(';');
+    if !params.next()?.trim().eq_ignore_ascii_case("multipart/form-data") {
+        return None;
+    }
+    params.find_map(|param| {
+        let (name, value) = param.split_once('=')?;
+        name.trim()
+            .eq_ignore_ascii_case("boundary")
+            .then(|| value.trim().trim_matches('"').to_string())
+            .filter(|b| !b.is_empty() && b.len() <= 70)
+    })
+}
+
+// 串流解析 multipart/form-data，文件內容不會整個讀入記憶體
+struct Multipart {
+    body: BodyDataStream,
+    buffer: Vec<u8>,
+    // `\r\n--<分界字串>`
+    delimiter: Vec<u8>,
+    in_part: bool,
+    done: bool,
+}
+
+// 標頭區塊的大小上限
+const MAX_HEADER_SIZE: usize = 16 * 1024;
+
+impl Multipart {
+    fn new(body: Body, boundary: &str) -> Self {
+        Multipart {
+            body: body.into_data_stream(),
+            // 第一個分界線前面沒有換行，先補上讓所有分界線的寫法一致
+            buffer: b"\r\n".to_vec(),
+            delimiter: format!("\r\n--{}", boundary).into_bytes(),
+            in_part: false,
+            done: false,
+        }
+    }
+
+    // 讀入更多內容，請求已結束時回傳 false
+    async fn fill(&mut self) -> Result<bool, ServerError> {
+        match self.body.next().await {
+            Some(chunk) => {
+                self.buffer.extend_from_slice(&chunk.map_err(read_error)?);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn fill_or_fail(&mut self) -> Result<(), ServerError> {
+        match self.fill().await? {
+            true => Ok(()),
+            false => Err(ServerError::BadRequest("multipart 內容不完整".to_string())),
+        }
+    }
+
+    // 移到下一個部分並解析其標頭，回傳文件名稱（不是文件的欄位為 `Some(None)`），沒有更多部分時為 None
+    async fn next_part(&mut self) -> Result<Option<Option<String>>, ServerError> {
+        while self.in_part {
+            self.chunk().await?;
+        }
+        if self.done {
+            return Ok(None);
+        }
+
+        loop {
+            if let Some(pos) = find(&self.buffer, &self.delimiter) {
+                self.buffer.drain(..pos + self.delimiter.len());
+                break;
+            }
+            // 分界線之前的內容沒有意義，只保留可能是分界線開頭的部分
+            let keep = self.delimiter.len().min(self.buffer.len());
+            self.buffer.drain(..self.buffer.len() - keep);
+            self.fill_or_fail().await?;
+        }
+        while self.buffer.len() < 2 {
+            self.fill_or_fail().await?;
+        }
+        if self.buffer.starts_with(b"--") {
+            self.done = true;
+            return Ok(None);
+        }
+
+        let end = loop {
+            if let Some(pos) = find(&self.buffer, b"\r\n\r\n") {
+                break pos;
+            }
+            if self.buffer.len() > MAX_HEADER_SIZE {
+                return Err(ServerError::BadRequest("multipart 標頭過長".to_string()));
+            }
+            self.fill_or_fail().await?;
+        };
+        let headers = String::from_utf8_lossy(&self.buffer[..end]).to_string();
+        self.buffer.drain(..end + 4);
+        self.in_part = true;
+
+        let filename = headers
+            .split("\r\n")
+            .filter_map(|line| line.split_once(':'))
+            .find(|(name, _)| name.trim().eq_ignore_ascii_case("content-disposition"))
+            .and_then(|(_, value)| {
+                value.split(';').find_map(|param| {
+                    let (name, value) = param.split_once('=')?;
+                    (name.trim() == "filename").then(|| value.trim().trim_matches('"').to_string())
+                })
+            });
+        Ok(Some(filename))
+    }
+
+    // 讀取目前部分的下一段內容，部分結束時回傳 None
+    async fn chunk(&mut self) -> Result<Option<Bytes>, ServerError> {
+        if !self.in_part {
+            return Ok(None);
+        }
+        loop {
+            if let Some(pos) = find(&self.buffer, &self.delimiter) {
+                // 分界線留給 next_part 處理
+                let data: Vec<u8> = self.buffer.drain(..pos).collect();
+                self.in_part = false;
+                return Ok((!data.is_empty()).then(|| Bytes::from(data)));
+            }
+            // 結尾可能是被切開的分界線，先保留
+            let keep = self.delimiter.len() - 1;
+            if self.buffer.len() > keep {
+                let data: Vec<u8> = self.buffer.drain(..self.buffer.len() - keep).collect();
+                return Ok(Some(Bytes::from(data)));
+            }
+            self.fill_or_fail().await?;
+        }
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|window| window == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numbered_names_keep_the_extension() {
+        assert_eq!(numbered("report.pdf", 0), "report.pdf");
+        assert_eq!(numbered("report.pdf", 2), "report-2.pdf");
+        assert_eq!(numbered("archive.tar.gz", 1), "archive.tar-1.gz");
+        assert_eq!(numbered("README", 1), "README-1");
+        assert_eq!(numbered(".env", 1), ".env-1");
+    }
+}
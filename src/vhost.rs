@@ -0,0 +1,120 @@
+use std::{path::PathBuf, sync::Arc};
+
+use axum::{
+    extract::{Request, State},
+    http::header,
+    response::Response,
+    Router,
+};
+use percent_encoding::percent_decode_str;
+use tower::ServiceExt;
+
+// 設定檔中 `[[vhost]]`（或 `[[tenant]]`）的一個網站
+pub struct VirtualHost {
+    // 主機名稱，`*.example.com` 比對所有子網域（不含 example.com 本身）
+    pub names: Vec<String>,
+    // 以網址前綴劃分時的第一層路徑名稱，與 `names` 擇一
+    pub prefix: Option<String>,
+    pub base: PathBuf,
+    // 未指定時沿用最上層的設定
+    pub index: Option<String>,
+    pub restricted_files: Option<String>,
+    // 網站自己的用戶檔，設定後只接受其中的用戶，不使用最上層的驗證設定
+    pub users: Option<PathBuf>,
+    pub max_upload_size: Option<u64>,
+    pub quota: Option<u64>,
+    pub rate_limit: Option<u32>,
+    // 沒有任何網站符合時改由此網站處理，而不是最上層的基礎路徑
+    pub default: bool,
+}
+
+impl VirtualHost {
+    // 記錄與錯誤訊息中代表此網站的名稱
+    pub fn label(&self) -> String {
+        match &self.prefix {
+            Some(prefix) => format!("/{}/", prefix),
+            None => self.names.join(", "),
+        }
+    }
+
+    fn matches(&self, host: &str) -> bool {
+        self.names.iter().any(|name| match name.strip_prefix("*.") {
+            Some(suffix) => host
+                .strip_suffix(suffix)
+                .is_some_and(|sub| sub.len() > 1 && sub.ends_with('.')),
+            None => name == host,
+        })
+    }
+}
+
+// 依網址前綴或 `Host` 分派到各網站的路由，未符合的請求交給預設網站
+//
+// 網址前綴優先：`/team-a/...` 一律由該網站處理，不論 Host 為何。
+pub struct VirtualHosts {
+    hosts: Vec<(VirtualHost, Router)>,
+    default: Router,
+}
+
+impl VirtualHosts {
+    // `fallback` 是最上層設定的路由，沒有網站設定 `default = true` 時作為預設網站
+    pub fn new(hosts: Vec<(VirtualHost, Router)>, fallback: Router) -> Self {
+        let default = hosts
+            .iter()
+            .find(|(host, _)| host.default)
+            .map_or(fallback, |(_, app)| app.clone());
+        VirtualHosts { hosts, default }
+    }
+
+    // 包成只有 fallback 的路由，外層不做路由比對，請求原封不動交給各網站
+    pub fn into_router(self) -> Router {
+        Router::new().fallback(dispatch).with_state(Arc::new(self))
+    }
+
+    fn route(&self, host: Option<&str>, path: &str) -> &Router {
+        let first = path.trim_start_matches('/').split('/').next().unwrap_or_default();
+        let first = percent_decode_str(first).decode_utf8_lossy();
+        if let Some((_, app)) = self
+            .hosts
+            .iter()
+            .find(|(vhost, _)| vhost.prefix.as_deref() == Some(first.as_ref()))
+        {
+            return app;
+        }
+        host.map(normalize)
+            .and_then(|host| {
+                self.hosts
+                    .iter()
+                    .find(|(vhost, _)| vhost.matches(&host))
+                    .map(|(_, app)| app)
+            })
+            .unwrap_or(&self.default)
+    }
+}
+
+// 去掉連接埠與結尾的點，名稱不分大小寫
+fn normalize(host: &str) -> String {
+    let host = match host.strip_prefix('[') {
+        // IPv6 位址，例如 `[::1]:8080`
+        Some(rest) => rest.split(']').next().unwrap_or(rest),
+        None => host.rsplit_once(':').map_or(host, |(name, _)| name),
+    };
+    host.trim_end_matches('.').to_ascii_lowercase()
+}
+
+async fn dispatch(State(hosts): State<Arc<VirtualHosts>>, request: Request) -> Response {
+    // HTTP/2 的主機名稱在 `:authority`，HTTP/1.1 則在 Host 標頭
+    let host = request
+        .uri()
+        .host()
+        .map(str::to_string)
+        .or_else(|| {
+            request
+                .headers()
+                .get(header::HOST)
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string)
+        });
+
+    let app = hosts.route(host.as_deref(), request.uri().path()).clone();
+    app.oneshot(request).await.unwrap_or_else(|e| match e {})
+}
@@ -0,0 +1,72 @@
+use std::{
+    collections::BTreeSet,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use tokio::sync::{broadcast, mpsc};
+use tracing::warn;
+
+// 合併短時間內連續發生的事件，避免大量寫入時重複觸發
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+// 監看基礎目錄的文件變動，並將變動的路徑分批廣播給訂閱者
+pub struct FsWatcher {
+    _watcher: RecommendedWatcher,
+    changes: broadcast::Sender<Arc<Vec<PathBuf>>>,
+}
+
+impl FsWatcher {
+    pub fn start(base: &Path) -> notify::Result<Self> {
+        let (event_tx, mut event_rx) = mpsc::unbounded_channel();
+
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+            match event {
+                // 讀取文件產生的存取事件不代表內容變動
+                Ok(event) if event.kind.is_access() => {}
+                Ok(event) => {
+                    let _ = event_tx.send(event.paths);
+                }
+                Err(e) => warn!("文件監看錯誤: {}", e),
+            }
+        })?;
+        watcher.watch(base, RecursiveMode::Recursive)?;
+
+        let (changes, _) = broadcast::channel(16);
+        let sender = changes.clone();
+
+        tokio::spawn(async move {
+            while let Some(paths) = event_rx.recv().await {
+                let mut batch: BTreeSet<PathBuf> = paths.into_iter().collect();
+
+                // 收集防抖時間內的其他事件
+                let deadline = tokio::time::sleep(DEBOUNCE);
+                tokio::pin!(deadline);
+                loop {
+                    tokio::select! {
+                        _ = &mut deadline => break,
+                        more = event_rx.recv() => match more {
+                            Some(paths) => batch.extend(paths),
+                            None => break,
+                        },
+                    }
+                }
+
+                // 沒有訂閱者時發送失敗是正常情況
+                let _ = sender.send(Arc::new(batch.into_iter().collect()));
+            }
+        });
+
+        Ok(FsWatcher {
+            _watcher: watcher,
+            changes,
+        })
+    }
+
+    // 訂閱變動通知，每則訊息為一批已變動的絕對路徑
+    pub fn subscribe(&self) -> broadcast::Receiver<Arc<Vec<PathBuf>>> {
+        self.changes.subscribe()
+    }
+}
@@ -0,0 +1,443 @@
+use std::{
+    fmt::Write as _,
+    fs, io,
+    path::{Path as FsPath, PathBuf},
+    sync::Arc,
+};
+
+use axum::{
+    extract::{Extension, Path, Request, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+use percent_encoding::{percent_decode_str, utf8_percent_encode};
+use tracing::info;
+
+use crate::{
+    auth::{Permission, User},
+    blocking, check_restricted, file_etag, holding, http_date, is_dir, safe_path, SafePath,
+    listing::HREF,
+    upload::{self, check_permission, resolve_target, Target},
+    ServerConfig, ServerError,
+};
+
+// 唯讀時允許的方法
+const READ_METHODS: &str = "GET, HEAD, OPTIONS, PROPFIND";
+// 啟用 --upload 後允許的方法
+const WRITE_METHODS: &str = "GET, HEAD, OPTIONS, PROPFIND, PROPPATCH, PUT, POST, DELETE, MKCOL, COPY, MOVE";
+
+// WebDAV class 1（--webdav）
+//
+// 不是標準 HTTP 的方法由 `/*path` 路由的 fallback 交給此處。讀取只需要 PROPFIND；
+// 修改內容的方法與上傳相同需要 --upload，並沿用上傳的路徑驗證與權限檢查。
+// 不支援 LOCK，因此 macOS Finder 會以唯讀方式掛載。
+pub async fn handle(
+    State(config): State<ServerConfig>,
+    user: Option<Extension<Arc<User>>>,
+    path: Option<Path<String>>,
+    request: Request,
+) -> Result<Response, ServerError> {
+    let path = path.map(|Path(p)| p).unwrap_or_default();
+    let user = user.map(|Extension(user)| user);
+    let config = match &user {
+        Some(user) => config.scoped_to(user),
+        None => config,
+    };
+    let rel = path.trim_matches('/').to_string();
+    let user = user.as_deref();
+    let headers = request.headers();
+    let writable = config.upload.is_some();
+
+    match request.method().as_str() {
+        "OPTIONS" => Ok(options(writable)),
+        "PROPFIND" => propfind(&rel, headers, user, &config).await,
+        "PROPPATCH" if writable => proppatch(&rel, &config).await,
+        "MKCOL" if writable => mkcol(&rel, headers, user, &config).await,
+        "COPY" if writable => copy_or_move(&rel, headers, false, user, &config).await,
+        "MOVE" if writable => copy_or_move(&rel, headers, true, user, &config).await,
+        "DELETE" if writable => delete(&rel, user, &config).await,
+        _ => Err(ServerError::MethodNotAllowed(allowed(writable).to_string())),
+    }
+}
+
+fn allowed(writable: bool) -> &'static str {
+    match writable {
+        true => WRITE_METHODS,
+        false => READ_METHODS,
+    }
+}
+
+fn options(writable: bool) -> Response {
+    (
+        StatusCode::OK,
+        [
+            ("dav", "1"),
+            (header::ALLOW.as_str(), allowed(writable)),
+            // Windows 的 Web 資料夾以此判斷可用 WebDAV 編輯
+            ("ms-author-via", "DAV"),
+        ],
+    )
+        .into_response()
+}
+
+// `PROPFIND`：回傳資源與（Depth: 1 時）其下一層項目的所有屬性
+//
+// 請求內容一律視為 allprop。不支援 `Depth: infinity`，以 1 處理，避免一個請求走訪整個目錄樹。
+async fn propfind(
+    rel: &str,
+    headers: &HeaderMap,
+    user: Option<&User>,
+    config: &ServerConfig,
+) -> Result<Response, ServerError> {
+    // 權限以正規化、對應實際大小寫後的路徑判斷
+    let SafePath { fs_path, rel } = safe_path(rel, config).await?;
+    let rel = rel.as_str();
+    let metadata = tokio::fs::metadata(&fs_path).await.map_err(ServerError::Filesystem)?;
+    let permission = if metadata.is_dir() { Permission::List } else { Permission::Read };
+    check_permission(user, rel, permission, config)?;
+    let depth = headers.get("depth").and_then(|v| v.to_str().ok()).map(str::trim);
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<D:multistatus xmlns:D=\"DAV:\">\n");
+    write_response(&mut xml, rel, &metadata, config);
+    if metadata.is_dir() && depth != Some("0") {
+        // 走訪目錄在阻塞執行緒上進行，很大的目錄不會拖住其他連線
+        let (rel, config) = (rel.to_string(), config.clone());
+        xml = blocking(move || {
+            let ignored = config.ignored();
+            for entry in fs::read_dir(&fs_path)? {
+                let entry = entry?;
+                let name = entry.file_name().to_string_lossy().to_string();
+                let child = if rel.is_empty() { name } else { format!("{}/{}", rel, name) };
+                // 與目錄列表相同：不列出 .sfignore 排除的項目，必要時也不列出禁止訪問的項目
+                if ignored(&child) || (config.hide_restricted && check_restricted(&child, &config).is_err()) {
+                    continue;
+                }
+                // 失效的符號連結等無法取得資訊的項目直接略過
+                let Ok(metadata) = fs::metadata(entry.path()) else {
+                    continue;
+                };
+                write_response(&mut xml, &child, &metadata, &config);
+            }
+            Ok::<_, io::Error>(xml)
+        })
+        .await?
+        .map_err(ServerError::Filesystem)?;
+    }
+    xml.push_str("</D:multistatus>\n");
+    Ok(multistatus(xml))
+}
+
+fn write_response(xml: &mut String, rel: &str, metadata: &fs::Metadata, config: &ServerConfig) {
+    let is_dir = metadata.is_dir();
+    let href = format!(
+        "/{}{}",
+        utf8_percent_encode(&config.url_path(rel), HREF),
+        if is_dir && !rel.is_empty() { "/" } else { "" }
+    );
+    let name = rel.rsplit('/').next().unwrap_or_default();
+
+    let _ = write!(xml, "<D:response><D:href>{}</D:href><D:propstat><D:prop>", escape(&href));
+    let _ = write!(xml, "<D:displayname>{}</D:displayname>", escape(name));
+    if let Ok(modified) = metadata.modified() {
+        let _ = write!(xml, "<D:getlastmodified>{}</D:getlastmodified>", http_date::format(modified));
+    }
+    if is_dir {
+        xml.push_str("<D:resourcetype><D:collection/></D:resourcetype>");
+    } else {
+        let content_type = mime_guess::from_path(name).first_or_octet_stream();
+        xml.push_str("<D:resourcetype/>");
+        let _ = write!(xml, "<D:getcontentlength>{}</D:getcontentlength>", metadata.len());
+        let _ = write!(xml, "<D:getcontenttype>{}</D:getcontenttype>", escape(content_type.as_ref()));
+        let _ = write!(xml, "<D:getetag>{}</D:getetag>", escape(&file_etag(metadata)));
+    }
+    xml.push_str("</D:prop><D:status>HTTP/1.1 200 OK</D:status></D:propstat></D:response>\n");
+}
+
+fn multistatus(xml: String) -> Response {
+    (
+        StatusCode::MULTI_STATUS,
+        [(header::CONTENT_TYPE, HeaderValue::from_static("application/xml; charset=utf-8"))],
+        xml,
+    )
+        .into_response()
+}
+
+// XML 文字與屬性值的跳脫
+pub fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+// `PROPPATCH`：屬性一律由文件系統決定，不接受修改
+//
+// 部分用戶端上傳後會嘗試設定修改時間，回傳 207 與 403 讓它們繼續而不是整個操作失敗。
+async fn proppatch(rel: &str, config: &ServerConfig) -> Result<Response, ServerError> {
+    let SafePath { fs_path, rel } = safe_path(rel, config).await?;
+    let rel = rel.as_str();
+    let collection = !rel.is_empty() && is_dir(&fs_path).await;
+    let href = format!("/{}{}", utf8_percent_encode(&config.url_path(rel), HREF), if collection { "/" } else { "" });
+    let xml = format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<D:multistatus xmlns:D=\"DAV:\">\n\
+         <D:response><D:href>{}</D:href><D:propstat><D:prop/>\
+         <D:status>HTTP/1.1 403 Forbidden</D:status></D:propstat></D:response>\n</D:multistatus>\n",
+        escape(&href)
+    );
+    Ok(multistatus(xml))
+}
+
+// 寫入目標的上層目錄不存在時，WebDAV 規定回傳 409 而不是 404
+async fn resolve_new(rel: &str, config: &ServerConfig) -> Result<Target, ServerError> {
+    if rel.is_empty() {
+        return Err(ServerError::Forbidden("不能取代根目錄".to_string()));
+    }
+    resolve_target(rel, config).await.map_err(|e| match e {
+        ServerError::NotFound(dir) => ServerError::Conflict(format!("上層目錄 /{} 不存在", dir)),
+        e => e,
+    })
+}
+
+// `MKCOL`：建立目錄，上層目錄必須已存在
+async fn mkcol(rel: &str, headers: &HeaderMap, user: Option<&User>, config: &ServerConfig) -> Result<Response, ServerError> {
+    let has_body = headers
+        .get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.trim() != "0")
+        || headers.contains_key(header::TRANSFER_ENCODING);
+    if has_body {
+        return Ok(StatusCode::UNSUPPORTED_MEDIA_TYPE.into_response());
+    }
+    let target = resolve_new(rel, config).await?;
+    check_permission(user, &target.rel, Permission::Write, config)?;
+    // 目錄規則（`node_modules/`）在建立前還無法從文件系統判斷
+    if config.restricted.is_restricted(&target.rel, || true) {
+        return Err(ServerError::UnsafePath(target.rel));
+    }
+    if tokio::fs::symlink_metadata(&target.file).await.is_ok() {
+        return Err(ServerError::MethodNotAllowed(WRITE_METHODS.to_string()));
+    }
+    tokio::fs::create_dir(&target.file).await?;
+    upload::invalidate(&target, config);
+    info!("已建立目錄 /{}", target.rel);
+    Ok((StatusCode::CREATED, [(header::LOCATION, format!("/{}/", config.url_path(&target.rel)))]).into_response())
+}
+
+// 從 `Destination` 標頭取得目標路徑，可以是完整 URL 或絕對路徑
+fn destination(headers: &HeaderMap, config: &ServerConfig) -> Result<String, ServerError> {
+    let value = headers
+        .get("destination")
+        .and_then(|v| v.to_str().ok())
+        .ok_or_else(|| ServerError::BadRequest("缺少 Destination 標頭".to_string()))?;
+    let path = match value.split_once("://") {
+        Some((_, rest)) => rest.find('/').map_or("/", |i| &rest[i..]),
+        None => value,
+    };
+    let path = path.split(['?', '#']).next().unwrap_or_default();
+    let decoded = percent_decode_str(path).decode_utf8_lossy();
+    // `..` 不做正規化，直接拒絕
+    if decoded.split('/').any(|c| c == "..") {
+        return Err(ServerError::UnsafePath(decoded.to_string()));
+    }
+    let path = decoded.split('/').filter(|c| !c.is_empty() && *c != ".").collect::<Vec<_>>().join("/");
+    // 以網址前綴劃分的網站，目標也必須在同一個網站內
+    if config.mount.is_empty() {
+        return Ok(path);
+    }
+    match path.strip_prefix(config.mount.as_str()) {
+        Some("") => Ok(String::new()),
+        Some(rest) if rest.starts_with('/') => Ok(rest[1..].to_string()),
+        _ => Err(ServerError::Forbidden(format!("目標 /{} 不在此網站內", path))),
+    }
+}
+
+// 目錄底下是否有禁止訪問的項目；複製、移動或刪除這種目錄會繞過禁止規則
+fn contains_restricted(dir: &FsPath, rel: &str, config: &ServerConfig) -> io::Result<bool> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let child = format!("{}/{}", rel, entry.file_name().to_string_lossy());
+        if check_restricted(&child, config).is_err() {
+            return Ok(true);
+        }
+        if entry.file_type()?.is_dir() && contains_restricted(&entry.path(), &child, config)? {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+// 目錄不能含有禁止訪問的項目
+async fn check_tree(path: &FsPath, rel: &str, config: &ServerConfig) -> Result<(), ServerError> {
+    if !tokio::fs::symlink_metadata(path).await.is_ok_and(|m| m.is_dir()) {
+        return Ok(());
+    }
+    let (dir, tree_rel, config) = (path.to_path_buf(), rel.to_string(), config.clone());
+    if blocking(move || contains_restricted(&dir, &tree_rel, &config)).await?? {
+        return Err(ServerError::Forbidden(format!("/{} 含有禁止訪問的項目", rel)));
+    }
+    Ok(())
+}
+
+// 刪除或取代前有 --trash-days 時移到 .trash，之後可由管理 API 還原；否則直接刪除
+async fn discard(path: &FsPath, rel: &str, user: Option<&User>, config: &ServerConfig) -> Result<(), ServerError> {
+    let Some(trash) = config.trash.clone() else {
+        return Ok(remove(path).await?);
+    };
+    let (source, tree_rel, name) = (path.to_path_buf(), holding::tree_rel(user, rel), user.map(|user| user.name.clone()));
+    let item = blocking(move || trash.keep(&source, &tree_rel, name.as_deref())).await??;
+    info!("已將 /{} 移到 .trash (編號 {})", item.path, item.id);
+    Ok(())
+}
+
+// 刪除文件或整個目錄，不跟隨符號連結
+async fn remove(path: &FsPath) -> io::Result<()> {
+    match tokio::fs::symlink_metadata(path).await?.is_dir() {
+        true => tokio::fs::remove_dir_all(path).await,
+        false => tokio::fs::remove_file(path).await,
+    }
+}
+
+// `COPY` 與 `MOVE`：複製或移動到 `Destination`，`Overwrite: F` 時不取代已存在的目標
+async fn copy_or_move(
+    rel: &str,
+    headers: &HeaderMap,
+    is_move: bool,
+    user: Option<&User>,
+    config: &ServerConfig,
+) -> Result<Response, ServerError> {
+    if rel.is_empty() {
+        return Err(ServerError::Forbidden("不能移動或複製根目錄".to_string()));
+    }
+    // 權限以正規化、對應實際大小寫後的路徑判斷
+    let SafePath { fs_path: source, rel } = safe_path(rel, config).await?;
+    let rel = rel.as_str();
+    check_permission(user, rel, if is_move { Permission::Delete } else { Permission::Read }, config)?;
+    if is_move {
+        upload::check_append_only("移動", rel, config)?;
+    }
+    check_tree(&source, rel, config).await?;
+
+    let target = resolve_new(&destination(headers, config)?, config).await?;
+    check_permission(user, &target.rel, Permission::Write, config)?;
+    // 改名後的文件也要符合 --upload-types，目錄內的名稱不變所以不必逐一檢查；
+    // 目錄則不能改成目錄規則禁止的名稱
+    if !is_dir(&source).await {
+        upload::check_type(&target.rel, config)?;
+    } else if config.restricted.is_restricted(&target.rel, || true) {
+        return Err(ServerError::UnsafePath(target.rel));
+    }
+    if target.rel == rel || target.rel.starts_with(&format!("{}/", rel)) {
+        return Err(ServerError::Forbidden("目標位於來源之內".to_string()));
+    }
+    let exists = tokio::fs::symlink_metadata(&target.file).await.is_ok();
+    if exists {
+        // 僅能新增文件時與 `Overwrite: F` 相同，不取代既有的目標
+        let overwrite = headers.get("overwrite").and_then(|v| v.to_str().ok()).map(str::trim);
+        if overwrite.is_some_and(|v| v.eq_ignore_ascii_case("F")) || upload::append_only(config) {
+            return Ok(StatusCode::PRECONDITION_FAILED.into_response());
+        }
+    }
+    // 只有 `Depth: 0` 時只複製目錄本身
+    let shallow = headers.get("depth").and_then(|v| v.to_str().ok()).is_some_and(|v| v.trim() == "0");
+    // 移動不增加用量，但與上傳相同，容量已用完時拒絕；複製則要放得下整個來源。
+    // 取代的目標稍後才刪除，這裡不扣除它的大小
+    let bytes = match is_move {
+        true => 0,
+        false => {
+            let from = source.clone();
+            blocking(move || tree_size(&from, shallow)).await??
+        }
+    };
+    upload::check_quota(bytes, config).await?;
+    if exists {
+        check_permission(user, &target.rel, Permission::Delete, config)?;
+        check_tree(&target.file, &target.rel, config).await?;
+        discard(&target.file, &target.rel, user, config).await?;
+    }
+
+    if is_move {
+        tokio::fs::rename(&source, &target.file).await?;
+        config.file_cache.flush(Some(&source));
+        config.name_index.flush(source.parent());
+        info!("已移動 /{} 到 /{}", rel, target.rel);
+    } else {
+        let (from, to) = (source.clone(), target.file.clone());
+        blocking(move || copy_tree(&from, &to, shallow)).await??;
+        if let Some(quota) = &config.quota {
+            quota.record(bytes);
+        }
+        info!("已複製 /{} 到 /{}", rel, target.rel);
+    }
+    upload::invalidate(&target, config);
+
+    match exists {
+        true => Ok(StatusCode::NO_CONTENT.into_response()),
+        false => Ok((StatusCode::CREATED, [(header::LOCATION, format!("/{}", config.url_path(&target.rel)))]).into_response()),
+    }
+}
+
+// copy_tree 會寫入的總位元組數，同樣不跟隨符號連結
+fn tree_size(path: &FsPath, shallow: bool) -> io::Result<u64> {
+    let metadata = fs::symlink_metadata(path)?;
+    if metadata.file_type().is_symlink() {
+        return Ok(0);
+    }
+    if !metadata.is_dir() {
+        return Ok(metadata.len());
+    }
+    if shallow {
+        return Ok(0);
+    }
+    let mut total = 0;
+    for entry in fs::read_dir(path)? {
+        total += tree_size(&entry?.path(), false)?;
+    }
+    Ok(total)
+}
+
+// 遞迴複製，不跟隨符號連結；文件先寫入暫存名稱再改名，中斷時不會留下半個文件
+fn copy_tree(from: &FsPath, to: &FsPath, shallow: bool) -> io::Result<()> {
+    let metadata = fs::symlink_metadata(from)?;
+    if metadata.file_type().is_symlink() {
+        return Ok(());
+    }
+    if !metadata.is_dir() {
+        let temp: PathBuf = to.with_file_name(format!(
+            "{}{}",
+            upload::TEMP_PREFIX,
+            to.file_name().unwrap_or_default().to_string_lossy()
+        ));
+        fs::copy(from, &temp)?;
+        return fs::rename(&temp, to).inspect_err(|_| {
+            let _ = fs::remove_file(&temp);
+        });
+    }
+    fs::create_dir(to)?;
+    if shallow {
+        return Ok(());
+    }
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        copy_tree(&entry.path(), &to.join(entry.file_name()), false)?;
+    }
+    Ok(())
+}
+
+// `DELETE`：刪除文件或整個目錄
+async fn delete(rel: &str, user: Option<&User>, config: &ServerConfig) -> Result<Response, ServerError> {
+    if rel.is_empty() {
+        return Err(ServerError::Forbidden("不能刪除根目錄".to_string()));
+    }
+    // 權限以正規化、對應實際大小寫後的路徑判斷
+    let SafePath { fs_path: path, rel } = safe_path(rel, config).await?;
+    let rel = rel.as_str();
+    check_permission(user, rel, Permission::Delete, config)?;
+    upload::check_append_only("刪除", rel, config)?;
+    check_tree(&path, rel, config).await?;
+    discard(&path, rel, user, config).await?;
+    config.file_cache.flush(Some(&path));
+    config.name_index.flush(path.parent());
+    info!("已刪除 /{}", rel);
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
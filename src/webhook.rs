@@ -0,0 +1,152 @@
+use std::{sync::Arc, time::Duration};
+
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use serde_json::json;
+use sha2::Sha256;
+use tracing::{error, warn};
+
+use crate::surrogate;
+
+// 失敗時的最大重試次數
+const MAX_ATTEMPTS: u32 = 3;
+
+// 會觸發 webhook 的事件
+pub enum WebhookEvent {
+    DownloadCompleted { path: String, bytes: u64 },
+    // 用戶端在回應送完前中斷連線，`bytes` 為中斷前已送出的位元組數
+    TransferAborted { path: String, bytes: u64 },
+    // 磁碟剩餘空間低於門檻，`path` 為基礎目錄，`bytes` 為剩餘位元組數
+    DiskLow { path: String, bytes: u64 },
+    // 透過 /_admin/purge 清除了路徑的快取，只送給 --purge-webhook
+    CachePurged { path: String },
+}
+
+impl WebhookEvent {
+    fn name(&self) -> &'static str {
+        match self {
+            WebhookEvent::DownloadCompleted { .. } => "download.completed",
+            WebhookEvent::TransferAborted { .. } => "transfer.aborted",
+            WebhookEvent::DiskLow { .. } => "disk.low",
+            WebhookEvent::CachePurged { .. } => "cache.purged",
+        }
+    }
+
+    fn path(&self) -> &str {
+        match self {
+            WebhookEvent::DownloadCompleted { path, .. }
+            | WebhookEvent::TransferAborted { path, .. }
+            | WebhookEvent::DiskLow { path, .. }
+            | WebhookEvent::CachePurged { path } => path,
+        }
+    }
+
+    fn bytes(&self) -> u64 {
+        match self {
+            WebhookEvent::DownloadCompleted { bytes, .. }
+            | WebhookEvent::TransferAborted { bytes, .. }
+            | WebhookEvent::DiskLow { bytes, .. } => *bytes,
+            WebhookEvent::CachePurged { .. } => 0,
+        }
+    }
+}
+
+// Webhook 通知設定與發送
+pub struct Webhooks {
+    pub client: reqwest::Client,
+    pub urls: Vec<String>,
+    pub template: Option<String>,
+    pub secret: Option<String>,
+    // 每個請求附加的標頭，例如 CDN API 的授權標頭
+    pub headers: Vec<(String, String)>,
+    pub download_threshold: Option<u64>,
+    pub notify_aborts: bool,
+}
+
+impl Webhooks {
+    // 下載大小是否達到通知門檻
+    pub fn wants_download(&self, bytes: u64) -> bool {
+        self.download_threshold.is_some_and(|threshold| bytes >= threshold)
+    }
+
+    // 在背景發送事件通知，不影響請求處理
+    pub fn notify(self: &Arc<Self>, event: WebhookEvent) {
+        let body = self.render(&event);
+        for url in &self.urls {
+            let webhooks = self.clone();
+            let url = url.clone();
+            let body = body.clone();
+            tokio::spawn(async move { webhooks.deliver(&url, body).await });
+        }
+    }
+
+    // 產生通知內容，未設定樣板時使用預設 JSON
+    fn render(&self, event: &WebhookEvent) -> String {
+        let timestamp = Utc::now().to_rfc3339();
+
+        match &self.template {
+            Some(template) => {
+                // 佔位符的值以 JSON 字串跳脫，讓樣板可以直接放在引號內
+                let escape = |value: &str| {
+                    let quoted = json!(value).to_string();
+                    quoted[1..quoted.len() - 1].to_string()
+                };
+                template
+                    .replace("{event}", event.name())
+                    .replace("{path}", &escape(event.path()))
+                    .replace("{bytes}", &event.bytes().to_string())
+                    .replace("{tag}", &escape(&surrogate::tag(event.path())))
+                    .replace("{timestamp}", &timestamp)
+            }
+            None => {
+                let mut body = json!({
+                    "event": event.name(),
+                    "path": event.path(),
+                    "bytes": event.bytes(),
+                    "timestamp": timestamp,
+                });
+                // 清除快取的通知附上對應的快取標籤，可直接轉給 CDN 的依標籤清除 API
+                if let WebhookEvent::CachePurged { path } = event {
+                    body["tag"] = json!(surrogate::tag(path));
+                }
+                body.to_string()
+            }
+        }
+    }
+
+    // 發送通知，失敗時以指數退避重試
+    async fn deliver(&self, url: &str, body: String) {
+        let signature = self.secret.as_ref().map(|secret| {
+            let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+                .expect("HMAC 可接受任意長度的金鑰");
+            mac.update(body.as_bytes());
+            format!("sha256={:x}", mac.finalize().into_bytes())
+        });
+
+        for attempt in 1..=MAX_ATTEMPTS {
+            let mut request = self
+                .client
+                .post(url)
+                .header("Content-Type", "application/json")
+                .body(body.clone());
+            if let Some(signature) = &signature {
+                request = request.header("X-Webhook-Signature", signature);
+            }
+            for (name, value) in &self.headers {
+                request = request.header(name, value);
+            }
+
+            match request.send().await {
+                Ok(response) if response.status().is_success() => return,
+                Ok(response) => warn!("Webhook {} 回應 {} (第 {} 次)", url, response.status(), attempt),
+                Err(e) => warn!("Webhook {} 發送失敗: {} (第 {} 次)", url, e, attempt),
+            }
+
+            if attempt < MAX_ATTEMPTS {
+                tokio::time::sleep(Duration::from_secs(1 << attempt)).await;
+            }
+        }
+
+        error!("Webhook {} 重試 {} 次後仍失敗", url, MAX_ATTEMPTS);
+    }
+}
@@ -0,0 +1,84 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use static_file_server::test_util::TestServer;
+
+mod common;
+
+use common::{raw_get_with, raw_request, users_file, OutsideFile};
+
+const FILES: &[(&str, &[u8])] = &[("public/a.txt", b"public"), ("private/s.txt", b"secret")];
+
+// alice 在 private 底下只能列出目錄，不能讀取或上傳
+fn acl_file(name: &str) -> OutsideFile {
+    OutsideFile::new(&format!("acl-{}", name), "alice /private list\n")
+}
+
+fn basic() -> String {
+    format!("Basic {}", STANDARD.encode("alice:pw"))
+}
+
+#[tokio::test]
+async fn acl_rules_check_the_normalized_path() {
+    let (users, acl) = (users_file("alice", "pw"), acl_file("dotdot"));
+    let server = TestServer::with_files(FILES, &["--users", users.path(), "--acl", acl.path()]).await.unwrap();
+    let basic = basic();
+    let auth = [("Authorization", basic.as_str())];
+
+    let (status, _) = raw_get_with(server.addr(), "/public/a.txt", &auth).await;
+    assert_eq!(status, 200);
+    for target in [
+        "/private/s.txt",
+        "/public/../private/s.txt",
+        "/public/%2e%2e/private/s.txt",
+        "/public/%2E%2E/private/s.txt",
+        "/public//.//..//private/s.txt",
+    ] {
+        let (status, response) = raw_get_with(server.addr(), target, &auth).await;
+        assert_eq!(status, 403, "{}", target);
+        assert!(!response.contains("secret"), "{}: {}", target, response);
+    }
+}
+
+#[tokio::test]
+async fn acl_rules_ignore_case_when_paths_do() {
+    let (users, acl) = (users_file("alice", "pw"), acl_file("case"));
+    let args = ["--users", users.path(), "--acl", acl.path(), "--case-insensitive"];
+    let server = TestServer::with_files(FILES, &args).await.unwrap();
+    let basic = basic();
+    let auth = [("Authorization", basic.as_str())];
+
+    for target in ["/PRIVATE/s.txt", "/Private/S.TXT", "/public/../PRIVATE/s.txt"] {
+        let (status, response) = raw_get_with(server.addr(), target, &auth).await;
+        assert_eq!(status, 403, "{}", target);
+        assert!(!response.contains("secret"), "{}: {}", target, response);
+    }
+    let (status, _) = raw_get_with(server.addr(), "/PUBLIC/A.txt", &auth).await;
+    assert_eq!(status, 200);
+}
+
+#[tokio::test]
+async fn upload_targets_are_normalized_before_acl_checks() {
+    let (users, acl) = (users_file("alice", "pw"), acl_file("upload"));
+    let args = ["--users", users.path(), "--acl", acl.path(), "--upload", "--webdav", "-i"];
+    let server = TestServer::with_files(FILES, &args).await.unwrap();
+    let basic = basic();
+    let auth = [("Authorization", basic.as_str())];
+
+    for target in ["/public/%2e%2e/private/new.txt", "/PRIVATE/new.txt"] {
+        let (status, _) = raw_request(server.addr(), "PUT", target, &auth, "x").await;
+        assert_eq!(status, 403, "{}", target);
+    }
+    assert!(!server.dir().join("private/new.txt").exists());
+
+    // WebDAV 的來源路徑同樣先正規化再檢查
+    let destination = [("Authorization", basic.as_str()), ("Destination", "/public/copy.txt")];
+    let (status, _) = raw_request(server.addr(), "COPY", "/public/%2e%2e/private/s.txt", &destination, "").await;
+    assert_eq!(status, 403);
+    let (status, _) = raw_request(server.addr(), "DELETE", "/PRIVATE/s.txt", &auth, "").await;
+    assert_eq!(status, 403);
+    assert!(server.dir().join("private/s.txt").exists());
+    assert!(!server.dir().join("public/copy.txt").exists());
+
+    let (status, _) = raw_request(server.addr(), "PUT", "/PUBLIC/new.txt", &auth, "x").await;
+    assert_eq!(status, 201);
+    assert!(server.dir().join("public/new.txt").exists());
+}
@@ -0,0 +1,88 @@
+use reqwest::{header, redirect::Policy, Client, StatusCode};
+use static_file_server::test_util::TestServer;
+
+mod common;
+
+use common::{set_cookie, users_file};
+
+async fn get_as(client: &Client, url: &str, name: &str, password: &str) -> StatusCode {
+    client.get(url).basic_auth(name, Some(password)).send().await.unwrap().status()
+}
+
+#[tokio::test]
+async fn repeated_failures_lock_the_account_for_that_ip() {
+    let users = users_file("throttle", "right");
+    let server = TestServer::with_files(&[("a.txt", b"a")], &["--users", users.path(), "--auth-max-failures", "2"])
+        .await
+        .unwrap();
+    let client = Client::new();
+    let url = server.url("/a.txt");
+
+    assert_eq!(get_as(&client, &url, "throttle", "wrong").await, StatusCode::UNAUTHORIZED);
+    assert_eq!(get_as(&client, &url, "throttle", "wrong").await, StatusCode::UNAUTHORIZED);
+
+    // 鎖定期間即使密碼正確也拒絕
+    let response = client.get(&url).basic_auth("throttle", Some("right")).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert!(response.headers().contains_key(header::RETRY_AFTER));
+}
+
+#[tokio::test]
+async fn unknown_names_do_not_lock_real_users() {
+    let users = users_file("known", "right");
+    let server = TestServer::with_files(&[("a.txt", b"a")], &["--users", users.path(), "--auth-max-failures", "2"])
+        .await
+        .unwrap();
+    let client = Client::new();
+    let url = server.url("/a.txt");
+
+    // 不存在的名稱只計入來源 IP，IP 的上限是帳號的四倍
+    for i in 0..3 {
+        let status = get_as(&client, &url, &format!("nobody{}", i), "wrong").await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+    assert_eq!(get_as(&client, &url, "known", "right").await, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn logout_revokes_the_session_cookie() {
+    let users = users_file("session", "right");
+    let server = TestServer::with_files(&[("a.txt", b"a")], &["--users", users.path(), "--login-page"])
+        .await
+        .unwrap();
+    let client = Client::builder().redirect(Policy::none()).build().unwrap();
+
+    let page = client.get(server.url("/_login")).send().await.unwrap();
+    let csrf = set_cookie(&page, "sfs_csrf").unwrap();
+    // 未啟用 TLS 時 cookie 不能標示 Secure，否則瀏覽器不會送回
+    let raw = page.headers()[header::SET_COOKIE].to_str().unwrap().to_string();
+    assert!(!raw.contains("Secure"), "{}", raw);
+
+    let login = client
+        .post(server.url("/_login"))
+        .header(header::COOKIE, format!("sfs_csrf={}", csrf))
+        .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+        .body(format!("username=session&password=right&csrf_token={}", csrf))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(login.status(), StatusCode::SEE_OTHER);
+    let session = set_cookie(&login, "sfs_session").unwrap();
+    let cookies = format!("sfs_csrf={}; sfs_session={}", csrf, session);
+
+    let response = client.get(server.url("/a.txt")).header(header::COOKIE, &cookies).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let logout = client
+        .post(server.url("/_logout"))
+        .header(header::COOKIE, &cookies)
+        .header("x-csrf-token", &csrf)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(logout.status(), StatusCode::SEE_OTHER);
+
+    // 保留下來的舊 cookie 不再有效
+    let response = client.get(server.url("/a.txt")).header(header::COOKIE, &cookies).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
@@ -0,0 +1,86 @@
+// 整合測試共用的輔助函式
+#![allow(dead_code)]
+
+use std::{fs, net::SocketAddr, path::PathBuf};
+
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+
+// 不經過網址正規化直接送出請求，回傳狀態碼與完整回應
+//
+// reqwest 會先把 `..`、`%2e%2e` 解析掉，路徑穿越的測試必須送出原始的請求行。
+pub async fn raw_get(addr: SocketAddr, target: &str) -> (u16, String) {
+    raw_get_with(addr, target, &[]).await
+}
+
+// 與 raw_get 相同，另外附上標頭
+pub async fn raw_get_with(addr: SocketAddr, target: &str, headers: &[(&str, &str)]) -> (u16, String) {
+    raw_request(addr, "GET", target, headers, "").await
+}
+
+// 以任意方法送出原始請求，附上標頭與內容
+pub async fn raw_request(
+    addr: SocketAddr,
+    method: &str,
+    target: &str,
+    headers: &[(&str, &str)],
+    body: &str,
+) -> (u16, String) {
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    let mut request = format!("{} {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n", method, target);
+    for (name, value) in headers {
+        request.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    request.push_str(&format!("Content-Length: {}\r\n\r\n{}", body.len(), body));
+    stream.write_all(request.as_bytes()).await.unwrap();
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).await.unwrap();
+    let response = String::from_utf8_lossy(&response).into_owned();
+    let status = response
+        .split(' ')
+        .nth(1)
+        .and_then(|code| code.parse().ok())
+        .unwrap_or(0);
+    (status, response)
+}
+
+// 基礎路徑之外的暫存文件，例如帳號檔，測試結束時刪除
+pub struct OutsideFile(pub PathBuf);
+
+impl OutsideFile {
+    pub fn new(name: &str, contents: &str) -> OutsideFile {
+        let path = std::env::temp_dir().join(format!("sfs-test-{}-{}", std::process::id(), name));
+        fs::write(&path, contents).unwrap();
+        OutsideFile(path)
+    }
+
+    pub fn path(&self) -> &str {
+        self.0.to_str().unwrap()
+    }
+}
+
+impl Drop for OutsideFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+// 只有一個用戶的帳號檔，bcrypt 成本取最低讓測試快一點
+pub fn users_file(name: &str, password: &str) -> OutsideFile {
+    let hash = bcrypt::hash(password, 4).unwrap();
+    OutsideFile::new(&format!("users-{}", name), &format!("{}:{}::read,list,write\n", name, hash))
+}
+
+// 回應中指定名稱的 Set-Cookie 值（不含屬性）
+pub fn set_cookie(response: &reqwest::Response, name: &str) -> Option<String> {
+    response
+        .headers()
+        .get_all(reqwest::header::SET_COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .filter_map(|v| v.split(';').next()?.split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value.to_string())
+}
@@ -0,0 +1,81 @@
+use std::io::Read;
+
+use reqwest::{header, Client};
+use static_file_server::test_util::TestServer;
+
+const TEXT: &str = "static file server compression test\n";
+
+async fn fetch(server: &TestServer, accept: &str) -> reqwest::Response {
+    Client::new()
+        .get(server.url("/a.txt"))
+        .header(header::ACCEPT_ENCODING, accept)
+        .send()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn brotli_on_the_fly() {
+    let body = TEXT.repeat(200);
+    let server = TestServer::with_files(&[("a.txt", body.as_bytes())], &["--compress"]).await.unwrap();
+
+    let response = fetch(&server, "br").await;
+    assert_eq!(response.headers()[header::CONTENT_ENCODING], "br");
+    let compressed = response.bytes().await.unwrap();
+    assert!(compressed.len() < body.len());
+    let mut decoded = String::new();
+    brotli::Decompressor::new(&compressed[..], 4096).read_to_string(&mut decoded).unwrap();
+    assert_eq!(decoded, body);
+
+    // 同樣可接受時偏好 zstd
+    let response = fetch(&server, "gzip, br, zstd").await;
+    assert_eq!(response.headers()[header::CONTENT_ENCODING], "zstd");
+}
+
+#[tokio::test]
+async fn refusing_everything_is_not_acceptable() {
+    let server = TestServer::with_files(&[("a.txt", TEXT.as_bytes())], &["--compress"]).await.unwrap();
+    let response = fetch(&server, "identity;q=0, deflate").await;
+    assert_eq!(response.status(), 406);
+    let response = fetch(&server, "gzip;q=0.2, identity").await;
+    assert!(!response.headers().contains_key(header::CONTENT_ENCODING));
+}
+
+#[tokio::test]
+async fn compressed_listings_are_reused_until_the_directory_changes() {
+    let server = TestServer::with_files(&[("docs/a.txt", TEXT.as_bytes())], &["--compress"]).await.unwrap();
+    let listing = || async {
+        let response = Client::new()
+            .get(server.url("/docs/"))
+            .header(header::ACCEPT_ENCODING, "zstd")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.headers()[header::CONTENT_ENCODING], "zstd");
+        let etag = response.headers()[header::ETAG].to_str().unwrap().to_string();
+        let cached = response.headers().contains_key(header::CONTENT_LENGTH);
+        let html = String::from_utf8(zstd::decode_all(&response.bytes().await.unwrap()[..]).unwrap()).unwrap();
+        (etag, cached, html)
+    };
+
+    // 第一次即時壓縮，完成後在背景保存
+    let (etag, cached, html) = listing().await;
+    assert!(!cached);
+    assert!(html.contains("a.txt"));
+    let mut again = listing().await;
+    for _ in 0..100 {
+        if again.1 {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        again = listing().await;
+    }
+    assert_eq!(again, (etag.clone(), true, html));
+
+    // 目錄變動後 ETag 不同，不會送出舊的列表
+    server.write("docs/b.txt", "b").unwrap();
+    let (changed, cached, html) = listing().await;
+    assert_ne!(changed, etag);
+    assert!(!cached);
+    assert!(html.contains("b.txt"));
+}
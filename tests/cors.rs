@@ -0,0 +1,36 @@
+use reqwest::{header, Client};
+use static_file_server::test_util::TestServer;
+
+#[tokio::test]
+async fn credentials_need_an_explicit_origin_list() {
+    assert!(TestServer::start(&["--cors", "*", "--cors-credentials"]).await.is_err());
+}
+
+#[tokio::test]
+async fn listed_origins_get_credentials() {
+    let server = TestServer::with_files(&[("a.json", b"{}")], &["--cors", "https://app.example", "--cors-credentials"])
+        .await
+        .unwrap();
+    let client = Client::new();
+
+    let response = client.get(server.url("/a.json")).header(header::ORIGIN, "https://app.example").send().await.unwrap();
+    let headers = response.headers();
+    assert_eq!(headers[header::ACCESS_CONTROL_ALLOW_ORIGIN], "https://app.example");
+    assert_eq!(headers[header::ACCESS_CONTROL_ALLOW_CREDENTIALS], "true");
+
+    let response = client.get(server.url("/a.json")).header(header::ORIGIN, "https://evil.example").send().await.unwrap();
+    assert!(!response.headers().contains_key(header::ACCESS_CONTROL_ALLOW_ORIGIN));
+}
+
+#[tokio::test]
+async fn wildcard_never_echoes_the_origin() {
+    let server = TestServer::with_files(&[("a.json", b"{}")], &["--cors", "*"]).await.unwrap();
+    let response = Client::new()
+        .get(server.url("/a.json"))
+        .header(header::ORIGIN, "https://evil.example")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.headers()[header::ACCESS_CONTROL_ALLOW_ORIGIN], "*");
+    assert!(!response.headers().contains_key(header::ACCESS_CONTROL_ALLOW_CREDENTIALS));
+}
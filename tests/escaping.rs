@@ -0,0 +1,56 @@
+use reqwest::header::ACCEPT;
+use static_file_server::test_util::TestServer;
+
+mod common;
+
+const XSS_NAME: &str = "<img src=x onerror=alert(1)>.txt";
+
+async fn html(url: String) -> (u16, String) {
+    let response = reqwest::Client::new()
+        .get(url)
+        .header(ACCEPT, "text/html")
+        .send()
+        .await
+        .unwrap();
+    (response.status().as_u16(), response.text().await.unwrap())
+}
+
+#[tokio::test]
+async fn listing_escapes_names() {
+    for args in [&[][..], &["--plain"][..]] {
+        let server = TestServer::with_files(&[(XSS_NAME, b"x")], args).await.unwrap();
+        let (status, body) = html(server.url("/")).await;
+        assert_eq!(status, 200);
+        assert!(!body.contains("<img"), "{:?}: {}", args, body);
+        assert!(body.contains("&lt;img src=x onerror=alert(1)&gt;.txt"), "{:?}: {}", args, body);
+    }
+}
+
+#[tokio::test]
+async fn listing_encodes_links() {
+    let server = TestServer::with_files(&[("a b#c?.txt", b"x"), ("q\"uote.txt", b"x")], &[]).await.unwrap();
+    let (_, body) = html(server.url("/")).await;
+    assert!(body.contains("href=\"/a%20b%23c%3F.txt\""), "{}", body);
+    assert!(!body.contains("q\"uote"), "{}", body);
+
+    let response = reqwest::get(server.url("/a%20b%23c%3F.txt")).await.unwrap();
+    assert_eq!(response.status(), 200);
+}
+
+#[tokio::test]
+async fn not_found_page_escapes_path() {
+    let server = TestServer::start(&[]).await.unwrap();
+    let (status, body) = html(server.url("/%3Cscript%3Ealert(1)%3C/script%3E")).await;
+    assert_eq!(status, 404);
+    assert!(!body.contains("<script>"), "{}", body);
+    assert!(body.contains("&lt;script&gt;"), "{}", body);
+}
+
+#[tokio::test]
+async fn suggestions_are_escaped() {
+    let server = TestServer::with_files(&[(XSS_NAME, b"x")], &["--suggest"]).await.unwrap();
+    let (status, body) = html(server.url("/%3Cimg%20src=x%20onerror=alert(1)%3E.tx")).await;
+    assert_eq!(status, 404);
+    assert!(!body.contains("<img"), "{}", body);
+    assert!(body.contains("href=\"/%3Cimg%20src=x%20onerror=alert(1)%3E.txt\""), "{}", body);
+}
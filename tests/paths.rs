@@ -0,0 +1,80 @@
+use static_file_server::test_util::TestServer;
+
+mod common;
+
+use common::raw_get;
+
+#[tokio::test]
+async fn traversal_stays_inside_base() {
+    let server = TestServer::with_files(&[("a.txt", b"inside")], &[]).await.unwrap();
+    for target in [
+        "/../../../../../../etc/passwd",
+        "/%2e%2e/%2e%2e/%2e%2e/%2e%2e/etc/passwd",
+        "/..%2f..%2f..%2f..%2fetc%2fpasswd",
+        "/..%5c..%5c..%5c..%5cetc%5cpasswd",
+        "/a.txt/../../../../etc/passwd",
+    ] {
+        let (status, response) = raw_get(server.addr(), target).await;
+        assert_ne!(status, 200, "{}", target);
+        assert!(!response.contains("root:"), "{}: {}", target, response);
+    }
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn symlinks_out_of_base_are_refused() {
+    let server = TestServer::start(&[]).await.unwrap();
+    std::os::unix::fs::symlink("/etc", server.dir().join("etc")).unwrap();
+    let response = reqwest::get(server.url("/etc/passwd")).await.unwrap();
+    assert_eq!(response.status(), 403);
+}
+
+#[tokio::test]
+async fn default_rules_and_hidden_files() {
+    let files: &[(&str, &[u8])] = &[
+        (".env", b"SECRET=1"),
+        (".git/config", b"[core]"),
+        ("Cargo.toml", b"[package]"),
+        (".gitignore", b"target"),
+        ("sub/.env.local", b"SECRET=2"),
+        ("public.txt", b"ok"),
+    ];
+    let server = TestServer::with_files(files, &["--hidden", "show"]).await.unwrap();
+    for path in ["/.env", "/.git/config", "/.git/", "/Cargo.toml", "/sub/.env.local", "/sub/../.env"] {
+        let (status, _) = raw_get(server.addr(), path).await;
+        assert_eq!(status, 403, "{}", path);
+    }
+    for path in ["/.gitignore", "/public.txt"] {
+        let response = reqwest::get(server.url(path)).await.unwrap();
+        assert_eq!(response.status(), 200, "{}", path);
+    }
+
+    // 列表中也不出現禁止訪問的項目
+    let listing = reqwest::get(server.url("/")).await.unwrap().text().await.unwrap();
+    assert!(!listing.contains("Cargo.toml"), "{}", listing);
+    assert!(listing.contains(".gitignore"), "{}", listing);
+}
+
+#[tokio::test]
+async fn directory_rules_leave_files_alone() {
+    let files: &[(&str, &[u8])] = &[("cache/a.txt", b"a"), ("docs/cache", b"file"), ("docs/x/cache/b.txt", b"b")];
+    let server = TestServer::with_files(files, &["--restricted-files", "cache/"]).await.unwrap();
+    for path in ["/cache/a.txt", "/cache/", "/cache", "/docs/x/cache/b.txt"] {
+        let response = reqwest::get(server.url(path)).await.unwrap();
+        assert_eq!(response.status(), 403, "{}", path);
+    }
+    let response = reqwest::get(server.url("/docs/cache")).await.unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.text().await.unwrap(), "file");
+}
+
+#[tokio::test]
+async fn case_insensitive_lookup_keeps_rules() {
+    // 請求的大小寫與規則不同，但對應到的實際名稱符合規則
+    let files: &[(&str, &[u8])] = &[("secret/key.pem", b"k"), ("Docs/Readme.txt", b"r")];
+    let server = TestServer::with_files(files, &["-i", "--restricted-files", "secret/**"]).await.unwrap();
+    let response = reqwest::get(server.url("/docs/readme.TXT")).await.unwrap();
+    assert_eq!(response.status(), 200);
+    let response = reqwest::get(server.url("/SECRET/key.pem")).await.unwrap();
+    assert_eq!(response.status(), 403);
+}
@@ -0,0 +1,58 @@
+use sha2::{Digest, Sha256};
+use static_file_server::test_util::TestServer;
+
+mod common;
+
+use common::{raw_get_with, OutsideFile};
+
+const TOKEN: &str = "sfs_0123456789abcdef0123456789abcdef";
+
+// 只有一個 read token 的 token 檔，範圍限制在 `path` 底下
+fn tokens_file(name: &str, path: &str) -> OutsideFile {
+    let hash = format!("{:x}", Sha256::digest(TOKEN.as_bytes()));
+    let json = format!(
+        r#"[{{"id": "ci", "hash": "{}", "scopes": ["read"], "path": "{}", "created": "2026-01-01T00:00:00Z"}}]"#,
+        hash, path
+    );
+    OutsideFile::new(&format!("tokens-{}", name), &json)
+}
+
+const FILES: &[(&str, &[u8])] = &[("releases/a.txt", b"release"), ("private/s.txt", b"secret")];
+
+#[tokio::test]
+async fn token_scope_covers_its_prefix_only() {
+    let tokens = tokens_file("prefix", "releases");
+    let server = TestServer::with_files(FILES, &["--tokens", tokens.path()]).await.unwrap();
+    let bearer = format!("Bearer {}", TOKEN);
+    let auth = [("Authorization", bearer.as_str())];
+
+    let (status, response) = raw_get_with(server.addr(), "/releases/a.txt", &auth).await;
+    assert_eq!(status, 200);
+    assert!(response.ends_with("release"));
+    let (status, _) = raw_get_with(server.addr(), "/private/s.txt", &auth).await;
+    assert_eq!(status, 403);
+}
+
+#[tokio::test]
+async fn token_scope_checks_the_normalized_path() {
+    let tokens = tokens_file("dotdot", "releases");
+    let server = TestServer::with_files(FILES, &["--tokens", tokens.path()]).await.unwrap();
+    let bearer = format!("Bearer {}", TOKEN);
+    let auth = [("Authorization", bearer.as_str())];
+
+    for target in [
+        "/releases/../private/s.txt",
+        "/releases/%2e%2e/private/s.txt",
+        "/releases/%2E%2E/private/s.txt",
+        "/releases/./../private/s.txt",
+        "/releases//..//private/s.txt",
+    ] {
+        let (status, response) = raw_get_with(server.addr(), target, &auth).await;
+        assert_eq!(status, 403, "{}", target);
+        assert!(!response.contains("secret"), "{}: {}", target, response);
+    }
+
+    // 正規化後仍在範圍內的路徑照常提供
+    let (status, _) = raw_get_with(server.addr(), "/private/../releases/a.txt", &auth).await;
+    assert_eq!(status, 200);
+}
@@ -0,0 +1,342 @@
+use reqwest::{Client, StatusCode};
+use static_file_server::test_util::TestServer;
+
+mod common;
+
+use common::raw_get;
+
+async fn put(client: &Client, url: String, body: &'static str) -> StatusCode {
+    client.put(url).body(body).send().await.unwrap().status()
+}
+
+#[tokio::test]
+async fn names_with_control_characters_are_refused() {
+    let server = TestServer::start(&["--upload"]).await.unwrap();
+    let client = Client::new();
+    for name in ["a%0Ab.txt", "a%0Db.txt", "a%00b.txt", "a%1Bb.txt"] {
+        assert_eq!(put(&client, server.url(&format!("/{}", name)), "x").await, StatusCode::BAD_REQUEST, "{}", name);
+    }
+    assert_eq!(put(&client, server.url("/plain.txt"), "x").await, StatusCode::CREATED);
+}
+
+#[tokio::test]
+async fn upload_types_limit_what_can_be_stored() {
+    let server = TestServer::start(&["--upload", "--webdav", "--upload-types", "zip,image/*"]).await.unwrap();
+    let client = Client::new();
+    assert_eq!(put(&client, server.url("/a.zip"), "x").await, StatusCode::CREATED);
+    assert_eq!(put(&client, server.url("/b.PNG"), "x").await, StatusCode::CREATED);
+    assert_eq!(put(&client, server.url("/c.html"), "x").await, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    assert!(!server.dir().join("c.html").exists());
+
+    // 改名也不能繞過
+    let response = client
+        .request(reqwest::Method::from_bytes(b"MOVE").unwrap(), server.url("/a.zip"))
+        .header("destination", "/a.html")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    assert!(server.dir().join("a.zip").exists());
+}
+
+#[tokio::test]
+async fn form_file_names_are_sanitized() {
+    let server = TestServer::start(&["--upload"]).await.unwrap();
+    let body = "--XX\r\nContent-Disposition: form-data; name=\"f\"; filename=\"..\\\\..\\\\.evil.txt\"\r\n\r\nhello\r\n--XX--\r\n";
+    let response = Client::new()
+        .post(server.url("/"))
+        .header("content-type", "multipart/form-data; boundary=XX")
+        .body(body)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+    assert_eq!(std::fs::read_to_string(server.dir().join("evil.txt")).unwrap(), "hello");
+}
+
+#[tokio::test]
+async fn uploads_clear_the_not_found_cache() {
+    let server = TestServer::start(&["--upload", "--not-found-cache", "60"]).await.unwrap();
+    let client = Client::new();
+    let (status, _) = raw_get(server.addr(), "/new.txt").await;
+    assert_eq!(status, 404);
+    assert_eq!(put(&client, server.url("/new.txt"), "fresh").await, StatusCode::CREATED);
+    let response = client.get(server.url("/new.txt")).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.text().await.unwrap(), "fresh");
+}
+
+#[tokio::test]
+async fn webdav_copies_respect_the_quota() {
+    let server = TestServer::start(&["--upload", "--webdav", "--quota", "100"]).await.unwrap();
+    server.write("big/a.bin", [0u8; 40]).unwrap();
+    server.write("big/b.bin", [0u8; 40]).unwrap();
+    let client = Client::new();
+    let copy = |destination: &'static str| {
+        client
+            .request(reqwest::Method::from_bytes(b"COPY").unwrap(), server.url("/big"))
+            .header("destination", destination)
+            .send()
+    };
+
+    // 已用 80 位元組，再複製 80 位元組會超過上限
+    assert_eq!(copy("/again").await.unwrap().status(), StatusCode::INSUFFICIENT_STORAGE);
+    assert!(!server.dir().join("again").exists());
+
+    // 只複製目錄本身不佔用容量
+    let response = client
+        .request(reqwest::Method::from_bytes(b"COPY").unwrap(), server.url("/big"))
+        .header("destination", "/empty")
+        .header("depth", "0")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+}
+
+#[tokio::test]
+async fn append_only_uploads_never_replace_files() {
+    let server = TestServer::start(&["--upload", "--webdav", "--upload-policy", "append-only"]).await.unwrap();
+    server.write("r.txt", "original").unwrap();
+    let client = Client::new();
+    let dav = |method: &str, path: &str| {
+        client.request(reqwest::Method::from_bytes(method.as_bytes()).unwrap(), server.url(path))
+    };
+
+    for expected in ["/r-1.txt", "/r-2.txt"] {
+        let response = client.put(server.url("/r.txt")).body("new").send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_eq!(response.headers()["location"], expected);
+    }
+    let body = "--XX\r\nContent-Disposition: form-data; name=\"f\"; filename=\"r.txt\"\r\n\r\nform\r\n--XX--\r\n";
+    let response = client
+        .post(server.url("/"))
+        .header("content-type", "multipart/form-data; boundary=XX")
+        .body(body)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+    assert_eq!(std::fs::read_to_string(server.dir().join("r-3.txt")).unwrap(), "form");
+
+    assert_eq!(dav("DELETE", "/r.txt").send().await.unwrap().status(), StatusCode::FORBIDDEN);
+    let moved = dav("MOVE", "/r.txt").header("destination", "/m.txt").send().await.unwrap();
+    assert_eq!(moved.status(), StatusCode::FORBIDDEN);
+    let copied = dav("COPY", "/r.txt").header("destination", "/r-1.txt").send().await.unwrap();
+    assert_eq!(copied.status(), StatusCode::PRECONDITION_FAILED);
+    assert_eq!(std::fs::read_to_string(server.dir().join("r.txt")).unwrap(), "original");
+    assert_eq!(std::fs::read_to_string(server.dir().join("r-1.txt")).unwrap(), "new");
+
+    // 複製到新的名稱仍然可以
+    let copied = dav("COPY", "/r.txt").header("destination", "/c.txt").send().await.unwrap();
+    assert_eq!(copied.status(), StatusCode::CREATED);
+}
+
+// 等待背景命令完成
+async fn wait_for(path: &std::path::Path) -> bool {
+    for _ in 0..50 {
+        if path.exists() {
+            return true;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    false
+}
+
+#[tokio::test]
+async fn on_upload_runs_the_command_with_the_file_path() {
+    let command = r#"sh -c 'printf "%s %s" "$2" "$3" > "$1.done"' sh {path} {url} {bytes}"#;
+    let server = TestServer::start(&["--upload", "--on-upload", command]).await.unwrap();
+    let client = Client::new();
+    assert_eq!(put(&client, server.url("/a%20b;c.txt"), "hello").await, StatusCode::CREATED);
+
+    let done = server.dir().join("a b;c.txt.done");
+    assert!(wait_for(&done).await);
+    assert_eq!(std::fs::read_to_string(done).unwrap(), "/a b;c.txt 5");
+}
+
+#[tokio::test]
+async fn on_upload_commands_are_killed_after_the_timeout() {
+    let command = r#"sh -c 'touch "$1.started"; sleep 2; touch "$1.late"' sh {path}"#;
+    let args = ["--upload", "--on-upload", command, "--on-upload-timeout", "1"];
+    let server = TestServer::start(&args).await.unwrap();
+    assert_eq!(put(&Client::new(), server.url("/slow.txt"), "x").await, StatusCode::CREATED);
+
+    assert!(wait_for(&server.dir().join("slow.txt.started")).await);
+    tokio::time::sleep(std::time::Duration::from_secs(3)).await;
+    assert!(!server.dir().join("slow.txt.late").exists());
+}
+
+// 模擬 clamd：內容含有 `EICAR` 時回報病毒，回傳監聽的位址
+async fn fake_clamd() -> String {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    tokio::spawn(async move {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            let mut command = [0u8; 10];
+            stream.read_exact(&mut command).await.unwrap();
+            assert_eq!(&command, b"zINSTREAM\0");
+            let mut content = Vec::new();
+            loop {
+                let len = stream.read_u32().await.unwrap() as usize;
+                if len == 0 {
+                    break;
+                }
+                let start = content.len();
+                content.resize(start + len, 0);
+                stream.read_exact(&mut content[start..]).await.unwrap();
+            }
+            let reply: &[u8] = match content.windows(5).any(|w| w == b"EICAR") {
+                true => b"stream: Eicar-Test-Signature FOUND\0",
+                false => b"stream: OK\0",
+            };
+            stream.write_all(reply).await.unwrap();
+        }
+    });
+    addr
+}
+
+#[tokio::test]
+async fn clamd_rejects_infected_uploads() {
+    let clamd = fake_clamd().await;
+    let server = TestServer::start(&["--upload", "--clamd", &clamd]).await.unwrap();
+    let client = Client::new();
+
+    let response = client.put(server.url("/bad.txt")).body("X5O EICAR test").send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    assert!(response.text().await.unwrap().contains("Eicar-Test-Signature"));
+    assert_eq!(put(&client, server.url("/good.txt"), "hello").await, StatusCode::CREATED);
+
+    // 暫存文件也已刪除
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    let names: Vec<_> = std::fs::read_dir(server.dir()).unwrap().map(|e| e.unwrap().file_name()).collect();
+    assert_eq!(names, ["good.txt"]);
+}
+
+#[tokio::test]
+async fn uploads_fail_closed_when_clamd_is_unreachable() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    drop(listener);
+    let server = TestServer::start(&["--upload", "--clamd", &addr]).await.unwrap();
+    assert_eq!(put(&Client::new(), server.url("/a.txt"), "hello").await, StatusCode::INTERNAL_SERVER_ERROR);
+    assert!(!server.dir().join("a.txt").exists());
+}
+
+async fn json(response: reqwest::Response) -> serde_json::Value {
+    serde_json::from_str(&response.text().await.unwrap()).unwrap()
+}
+
+#[tokio::test]
+async fn moderated_uploads_wait_for_approval() {
+    let args = ["--upload", "--moderate-uploads", "--admin-token", "admin-secret"];
+    let server = TestServer::start(&args).await.unwrap();
+    let client = Client::new();
+    let admin = |request: reqwest::RequestBuilder| request.bearer_auth("admin-secret").send();
+
+    let response = client.put(server.url("/a.txt")).body("hello").send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::ACCEPTED);
+    let id = json(response).await["pending"]["id"].as_str().unwrap().to_string();
+    assert_eq!(client.get(server.url("/a.txt")).send().await.unwrap().status(), StatusCode::NOT_FOUND);
+    let listing = client.get(server.url("/")).send().await.unwrap().text().await.unwrap();
+    assert!(!listing.contains("a.txt") && !listing.contains("quarantine"));
+    let (status, _) = raw_get(server.addr(), &format!("/.sfs-quarantine/{}", id)).await;
+    assert_eq!(status, 403);
+
+    // 只有管理員能查看與核准
+    let pending = client.get(server.url("/_admin/quarantine")).send().await.unwrap();
+    assert_eq!(pending.status(), StatusCode::UNAUTHORIZED);
+    let pending = json(admin(client.get(server.url("/_admin/quarantine"))).await.unwrap()).await;
+    assert_eq!(pending["uploads"][0]["path"], "a.txt");
+    let approved = admin(client.post(server.url(&format!("/_admin/quarantine/{}/approve", id)))).await.unwrap();
+    assert_eq!(approved.status(), StatusCode::OK);
+    assert_eq!(client.get(server.url("/a.txt")).send().await.unwrap().text().await.unwrap(), "hello");
+
+    // 拒絕的上傳直接刪除
+    let response = client.put(server.url("/b.txt")).body("spam").send().await.unwrap();
+    let id = json(response).await["pending"]["id"].as_str().unwrap().to_string();
+    let rejected = admin(client.delete(server.url(&format!("/_admin/quarantine/{}", id)))).await.unwrap();
+    assert_eq!(rejected.status(), StatusCode::NO_CONTENT);
+    let pending = json(admin(client.get(server.url("/_admin/quarantine"))).await.unwrap()).await;
+    assert_eq!(pending["uploads"], serde_json::json!([]));
+    assert!(!server.dir().join("b.txt").exists());
+}
+
+// 管理 API 列出的垃圾桶項目
+async fn trash_items(server: &TestServer) -> Vec<serde_json::Value> {
+    let response = Client::new()
+        .get(server.url("/_admin/trash"))
+        .bearer_auth("admin-secret")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = json(response).await;
+    assert_eq!(body["retention_days"], 7);
+    body["items"].as_array().unwrap().clone()
+}
+
+#[tokio::test]
+async fn webdav_deletes_go_to_the_trash() {
+    let args = ["--upload", "--webdav", "--trash-days", "7", "--admin-token", "admin-secret", "--hidden", "show"];
+    let server = TestServer::start(&args).await.unwrap();
+    server.write("docs/a.txt", "first").unwrap();
+    let client = Client::new();
+    let dav = |method: &str, path: &str| {
+        client.request(reqwest::Method::from_bytes(method.as_bytes()).unwrap(), server.url(path))
+    };
+    let restore = |id: &str| {
+        client
+            .post(server.url(&format!("/_admin/trash/{}/restore", id)))
+            .bearer_auth("admin-secret")
+            .send()
+    };
+
+    assert_eq!(dav("DELETE", "/docs/a.txt").send().await.unwrap().status(), StatusCode::NO_CONTENT);
+    assert!(!server.dir().join("docs/a.txt").exists());
+    // 即使顯示隱藏文件，垃圾桶也不能瀏覽
+    let listing = client.get(server.url("/")).send().await.unwrap().text().await.unwrap();
+    assert!(!listing.contains(".trash"));
+    assert_eq!(raw_get(server.addr(), "/.trash/").await.0, 403);
+
+    let items = trash_items(&server).await;
+    assert_eq!(items.len(), 1);
+    assert_eq!((items[0]["path"].as_str(), items[0]["size"].as_u64()), (Some("docs/a.txt"), Some(5)));
+    assert!(items[0]["time"].is_string());
+    assert_eq!(restore(items[0]["id"].as_str().unwrap()).await.unwrap().status(), StatusCode::OK);
+    assert_eq!(std::fs::read_to_string(server.dir().join("docs/a.txt")).unwrap(), "first");
+
+    // 整個目錄也能還原；原本的位置又有文件時不取代
+    assert_eq!(dav("DELETE", "/docs").send().await.unwrap().status(), StatusCode::NO_CONTENT);
+    server.write("docs/new.txt", "new").unwrap();
+    let items = trash_items(&server).await;
+    let id = items[0]["id"].as_str().unwrap();
+    assert_eq!(restore(id).await.unwrap().status(), StatusCode::CONFLICT);
+    std::fs::remove_dir_all(server.dir().join("docs")).unwrap();
+    assert_eq!(restore(id).await.unwrap().status(), StatusCode::OK);
+    assert_eq!(std::fs::read_to_string(server.dir().join("docs/a.txt")).unwrap(), "first");
+
+    // COPY 取代的目標同樣先移到垃圾桶
+    server.write("b.txt", "second").unwrap();
+    let copied = dav("COPY", "/b.txt").header("destination", "/docs/a.txt").send().await.unwrap();
+    assert_eq!(copied.status(), StatusCode::NO_CONTENT);
+    let items = trash_items(&server).await;
+    assert_eq!(items.len(), 1);
+    assert_eq!(items[0]["path"], "docs/a.txt");
+
+    // 永久刪除後不能再還原
+    let id = items[0]["id"].as_str().unwrap();
+    let purged = client
+        .delete(server.url(&format!("/_admin/trash/{}", id)))
+        .bearer_auth("admin-secret")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(purged.status(), StatusCode::NO_CONTENT);
+    assert!(trash_items(&server).await.is_empty());
+    assert_eq!(restore(id).await.unwrap().status(), StatusCode::NOT_FOUND);
+    let anonymous = client.get(server.url("/_admin/trash")).send().await.unwrap();
+    assert_eq!(anonymous.status(), StatusCode::UNAUTHORIZED);
+}